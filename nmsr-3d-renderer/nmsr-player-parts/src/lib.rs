@@ -2,6 +2,8 @@
 
 pub mod model;
 pub mod parts;
+pub mod skin;
+pub mod skin_regions;
 pub mod types;
 
 pub use strum::IntoEnumIterator;
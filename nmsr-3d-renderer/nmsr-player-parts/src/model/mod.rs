@@ -1,3 +1,6 @@
+use glam::Vec3;
+use strum::{Display, EnumString};
+
 use crate::types::{PlayerBodyPartType, PlayerPartTextureType};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -16,6 +19,59 @@ impl PlayerModel {
     }
 }
 
+/// The body proportions to use when building a player model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum PlayerBodyProportions {
+    /// The regular proportions of a player.
+    #[default]
+    Adult,
+    /// Child proportions, with a bigger head and a shorter body and limbs, like baby mobs.
+    #[strum(serialize = "child", serialize = "baby")]
+    Child,
+}
+
+impl PlayerBodyProportions {
+    /// Returns the transform to apply to the given body part as a (scale, pivot, offset) tuple.
+    /// The part is scaled around the pivot and then moved by the offset.
+    ///
+    /// Returns [None] if the body part should be left as-is.
+    pub fn get_part_transform(&self, body_part: PlayerBodyPartType) -> Option<(f32, Vec3, Vec3)> {
+        match self {
+            PlayerBodyProportions::Adult => None,
+            PlayerBodyProportions::Child => {
+                if body_part.get_non_layer_part() == PlayerBodyPartType::Head {
+                    // Shrink the head around the neck, and then move it down to sit on the shorter body.
+                    Some((0.75, [0.0, 24.0, 0.0].into(), [0.0, -12.0, 0.0].into()))
+                } else {
+                    // Shrink the body and limbs towards the ground.
+                    Some((0.5, Vec3::ZERO, Vec3::ZERO))
+                }
+            }
+        }
+    }
+
+    /// Transforms a position relative to the given body part into the position it ends up in.
+    pub fn transform_position(&self, body_part: PlayerBodyPartType, position: Vec3) -> Vec3 {
+        self.get_part_transform(body_part)
+            .map_or(position, |(scale, pivot, offset)| {
+                pivot + (position - pivot) * scale + offset
+            })
+    }
+
+    /// Returns the scale applied to the given body part.
+    pub fn get_part_scale(&self, body_part: PlayerBodyPartType) -> f32 {
+        self.get_part_transform(body_part)
+            .map_or(1.0, |(scale, _, _)| scale)
+    }
+
+    /// Returns the height of the player model, from the feet to the top of the head.
+    pub fn get_height(&self) -> f32 {
+        self.transform_position(PlayerBodyPartType::Head, [0.0, 32.0, 0.0].into())
+            .y
+    }
+}
+
 pub trait ArmorMaterial {
     fn get_texture_type(slot: PlayerArmorSlot) -> Option<PlayerPartTextureType> {
         None
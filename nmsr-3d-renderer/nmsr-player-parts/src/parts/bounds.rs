@@ -0,0 +1,73 @@
+use glam::{BVec3, Mat4, Vec3};
+
+use super::part::Part;
+
+/// An axis-aligned box around one or more parts, in the space the model is drawn in (once rotations are applied).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PartBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl PartBounds {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the smallest box containing all the given points, if there are any.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds: Option<Self>, point| {
+            Some(match bounds {
+                Some(bounds) => Self::new(bounds.min.min(point), bounds.max.max(point)),
+                None => Self::new(point, point),
+            })
+        })
+    }
+
+    /// Returns the smallest box containing all the given parts, if there are any.
+    pub fn from_parts<'a>(parts: impl IntoIterator<Item = &'a Part>) -> Option<Self> {
+        parts
+            .into_iter()
+            .map(Part::get_bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Returns the eight corners of the box.
+    pub fn corners(&self) -> [Vec3; 8] {
+        std::array::from_fn(|i| {
+            Vec3::select(
+                BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                self.max,
+                self.min,
+            )
+        })
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Returns the box around this one once it's transformed, like when a model is placed in a scene.
+    pub fn transformed(&self, transform: Mat4) -> Self {
+        Self::from_points(self.corners().map(|c| transform.transform_point3(c)))
+            .unwrap_or(*self)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Whether the boxes overlap, like when placing props around a model without them colliding.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+}
@@ -1,5 +1,7 @@
+pub mod bounds;
 pub mod part;
 pub mod provider;
+pub mod skeleton;
 pub mod uv;
 #[cfg(feature = "part_tracker")]
 pub mod tracking;
@@ -1,8 +1,10 @@
+use super::bounds::PartBounds;
 use super::provider::minecraft::compute_base_part;
+use super::skeleton::Bone;
 use crate::parts::part::Part::{Cube, Quad};
 use crate::parts::uv::{CubeFaceUvs, FaceUv};
 use crate::types::{PlayerBodyPartType, PlayerPartTextureType};
-use glam::{Mat4, Quat, Vec3};
+use glam::{Mat4, Quat, Vec3, Vec4};
 
 #[cfg(feature = "part_tracker")]
 use super::tracking::PartTrackingData;
@@ -68,6 +70,10 @@ pub enum Part {
         rotation_matrix: Mat4,
         face_uvs: CubeFaceUvs,
         texture: PlayerPartTextureType,
+        /// An RGBA color the part's texture is multiplied by, if any.
+        tint: Option<Vec4>,
+        /// The bone of the skeleton the part moves with, if any.
+        bone: Option<Bone>,
         #[cfg(feature = "part_tracker")]
         part_tracking_data: PartTrackingData,
     },
@@ -79,6 +85,14 @@ pub enum Part {
         face_uv: FaceUv,
         normal: Vec3,
         texture: PlayerPartTextureType,
+        /// An RGBA color the part's texture is multiplied by, if any.
+        tint: Option<Vec4>,
+        /// The bone of the skeleton the part moves with, if any.
+        bone: Option<Bone>,
+        /// Whether the quad is also rendered (and lit) from behind, with its texture mirrored.
+        double_sided: bool,
+        /// The UVs shown on the back of a double-sided quad instead of its mirrored texture, if any.
+        back_face_uv: Option<FaceUv>,
         #[cfg(feature = "part_tracker")]
         part_tracking_data: PartTrackingData,
     },
@@ -109,6 +123,8 @@ impl Part {
             rotation_matrix: Mat4::IDENTITY,
             face_uvs: uvs,
             texture,
+            tint: None,
+            bone: None,
             #[cfg(feature = "part_tracker")]
             part_tracking_data: PartTrackingData::new(name),
         }
@@ -129,6 +145,10 @@ impl Part {
             face_uv: uvs,
             normal,
             texture,
+            tint: None,
+            bone: None,
+            double_sided: false,
+            back_face_uv: None,
             #[cfg(feature = "part_tracker")]
             part_tracking_data: PartTrackingData::new(name),
         }
@@ -245,6 +265,53 @@ impl Part {
         *self.rotation_matrix_mut() = model_transform * prev_rotation;
     }
 
+    /// Scales the part around the given pivot, and then moves it by the given offset.
+    ///
+    /// Any rotation previously applied to the part is preserved.
+    pub fn scale_around(
+        &mut self,
+        scale: f32,
+        pivot: MinecraftPosition,
+        offset: MinecraftPosition,
+    ) {
+        let transform = Mat4::from_translation(pivot + offset)
+            * Mat4::from_scale(Vec3::splat(scale))
+            * Mat4::from_translation(-pivot);
+
+        *self.position_mut() = transform.transform_point3(self.get_position());
+        *self.size_mut() *= scale;
+        *self.rotation_matrix_mut() = transform * self.get_rotation_matrix() * transform.inverse();
+
+        #[cfg(feature = "part_tracker")]
+        if let Some((_, anchor)) = self.last_rotation_mut() {
+            anchor.rotation_anchor = transform.transform_point3(anchor.rotation_anchor);
+            anchor.translation_anchor *= scale;
+        }
+    }
+
+    /// Applies the given transform on top of the part's current one, in model space.
+    pub fn apply_transform(&mut self, transform: Mat4) {
+        *self.rotation_matrix_mut() = transform * self.get_rotation_matrix();
+    }
+
+    /// Returns the corners of the part, once its rotations are applied.
+    ///
+    /// Quads are flat, so some of their corners are the same.
+    pub fn get_corners(&self) -> [MinecraftPosition; 8] {
+        let position = self.get_position();
+        let transform = self.get_rotation_matrix();
+
+        PartBounds::new(position, position + self.get_size())
+            .corners()
+            .map(|corner| transform.transform_point3(corner))
+    }
+
+    /// Returns the box around the part, once its rotations are applied.
+    pub fn get_bounds(&self) -> PartBounds {
+        PartBounds::from_points(self.get_corners())
+            .expect("Parts always have corners")
+    }
+
     pub fn get_size(&self) -> MinecraftPosition {
         match self {
             Cube { size, .. } => *size,
@@ -291,6 +358,50 @@ impl Part {
         }
     }
 
+    pub fn get_tint(&self) -> Option<Vec4> {
+        match self {
+            Cube { tint, .. } => *tint,
+            Quad { tint, .. } => *tint,
+        }
+    }
+
+    /// Sets the RGBA color the part's texture is multiplied by when rendered, e.g. for dyeing
+    /// leather armor, or rendering a translucent preview of the part.
+    pub fn set_tint(&mut self, tint: Option<Vec4>) {
+        match self {
+            Cube { tint: ref mut t, .. } => *t = tint,
+            Quad { tint: ref mut t, .. } => *t = tint,
+        }
+    }
+
+    pub fn with_tint(mut self, tint: Vec4) -> Self {
+        self.set_tint(Some(tint));
+
+        self
+    }
+
+    pub fn get_bone(&self) -> Option<Bone> {
+        match self {
+            Cube { bone, .. } => *bone,
+            Quad { bone, .. } => *bone,
+        }
+    }
+
+    /// Attaches the part to the given bone of the skeleton, so it follows it when the player is posed.
+    /// Parts that aren't attached to any bone, like the shadow, stay where they are.
+    pub fn set_bone(&mut self, bone: Option<Bone>) {
+        match self {
+            Cube { bone: ref mut b, .. } => *b = bone,
+            Quad { bone: ref mut b, .. } => *b = bone,
+        }
+    }
+
+    pub fn attached_to(mut self, bone: Bone) -> Self {
+        self.set_bone(Some(bone));
+
+        self
+    }
+
     pub fn get_face_uv(&self) -> FaceUv {
         match self {
             Cube { face_uvs, .. } => unimplemented!("Cannot get face UV on a cube"),
@@ -336,6 +447,52 @@ impl Part {
         }
     }
 
+    pub fn is_double_sided(&self) -> bool {
+        match self {
+            Cube { .. } => false,
+            Quad { double_sided, .. } => *double_sided,
+        }
+    }
+
+    /// Sets whether the quad can be seen from behind, for thin parts like capes or ears.
+    ///
+    /// Cubes already have a face on every side, so this does nothing on them.
+    pub fn set_double_sided(&mut self, double_sided: bool) {
+        match self {
+            Cube { .. } => {}
+            Quad {
+                double_sided: ref mut d,
+                ..
+            } => *d = double_sided,
+        }
+    }
+
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.set_double_sided(double_sided);
+
+        self
+    }
+
+    pub fn get_back_face_uv(&self) -> Option<FaceUv> {
+        match self {
+            Cube { .. } => None,
+            Quad { back_face_uv, .. } => *back_face_uv,
+        }
+    }
+
+    /// Sets the UVs shown on the back of the quad when it's double-sided, e.g. for ears with a back texture.
+    ///
+    /// Like [`Part::set_double_sided`], this does nothing on cubes.
+    pub fn set_back_face_uv(&mut self, back_face_uv: Option<FaceUv>) {
+        match self {
+            Cube { .. } => {}
+            Quad {
+                back_face_uv: ref mut b,
+                ..
+            } => *b = back_face_uv,
+        }
+    }
+
     #[cfg(feature = "part_tracker")]
     pub fn part_tracking_data(&self) -> &PartTrackingData {
         match self {
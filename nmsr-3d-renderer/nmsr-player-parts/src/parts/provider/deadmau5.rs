@@ -0,0 +1,72 @@
+use crate::model::ArmorMaterial;
+use crate::parts::part::Part;
+use crate::parts::provider::{PartsProvider, PlayerPartProviderContext};
+use crate::parts::skeleton::Bone;
+use crate::parts::uv::box_uv;
+use crate::types::PlayerBodyPartType::{self, Head};
+use crate::types::PlayerPartTextureType;
+
+/// The ears only deadmau5 gets in-game, rendered as two flat discs on the sides of the head.
+/// Their texture is drawn in an otherwise unused region of the skin, next to the head's.
+pub struct Deadmau5Ears;
+
+impl Deadmau5Ears {
+    /// The position of the front of the ears on the skin.
+    pub const UV: (u16, u16) = (25, 1);
+    /// The size of the ears on the skin, in pixels.
+    pub const SIZE: [u32; 3] = [6, 6, 1];
+    /// Like in-game, the ears are scaled up from their texture.
+    pub const SCALE: f32 = 4.0 / 3.0;
+    /// How far from the center of the head each ear is, in pixels.
+    pub const OFFSET_X: i32 = 6;
+    /// How far above the neck the bottom of the ears is, in pixels.
+    pub const OFFSET_Y: i32 = 6;
+
+    fn create_part(left: bool) -> Part {
+        let [width, _, depth] = Self::SIZE;
+        let (width, depth) = (width as i32, depth as i32);
+
+        let center_x = if left {
+            -Self::OFFSET_X
+        } else {
+            Self::OFFSET_X
+        };
+
+        // The ears are anchored to the neck, like the head.
+        let anchor = [center_x as f32, (24 + Self::OFFSET_Y) as f32, 0.0];
+
+        let (uv_x, uv_y) = Self::UV;
+        let mut part = Part::new_cube(
+            PlayerPartTextureType::Skin,
+            [center_x - width / 2, 24 + Self::OFFSET_Y, -depth],
+            Self::SIZE,
+            box_uv(uv_x, uv_y, Self::SIZE.map(|size| size as u16)),
+            #[cfg(feature = "part_tracker")]
+            Some(if left { "Left Ear" } else { "Right Ear" }.to_string()),
+        );
+
+        part.scale_around(Self::SCALE, anchor.into(), [0.0; 3].into());
+
+        part.attached_to(Bone::Head)
+    }
+}
+
+#[derive(Default)]
+pub struct Deadmau5EarsPlayerPartsProvider;
+
+impl<M: ArmorMaterial> PartsProvider<M> for Deadmau5EarsPlayerPartsProvider {
+    fn get_parts(
+        &self,
+        context: &PlayerPartProviderContext<M>,
+        body_part: PlayerBodyPartType,
+    ) -> Vec<Part> {
+        if !context.has_deadmau5_ears || body_part != Head {
+            return vec![];
+        }
+
+        vec![
+            Deadmau5Ears::create_part(true),
+            Deadmau5Ears::create_part(false),
+        ]
+    }
+}
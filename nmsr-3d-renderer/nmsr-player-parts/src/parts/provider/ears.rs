@@ -5,6 +5,7 @@ use crate::{
     model::ArmorMaterial,
     parts::{
         part::{Part, PartAnchorInfo},
+        skeleton::Bone,
         uv::{uv_from_pos_and_size, FaceUv},
     },
     types::{PlayerBodyPartType, PlayerBodyPartType::*, PlayerPartTextureType},
@@ -19,7 +20,6 @@ use ears_rs::features::{
     EarsFeatures,
 };
 use glam::Vec3;
-use itertools::Itertools;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -172,7 +172,6 @@ struct EarsPlayerBodyPartDefinition {
     enabled: fn(&EarsFeatures) -> bool,
     vertical_quad: bool,
     double_sided: bool,
-    is_back: bool,
     name: &'static str,
     part_count: Option<u32>,
     reset_rotation_stack: bool,
@@ -198,7 +197,6 @@ impl Default for EarsPlayerBodyPartDefinition {
             double_sided: true,
             name: "",
             part_count: None,
-            is_back: false,
             reset_rotation_stack: false,
         }
     }
@@ -889,29 +887,7 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                 let processed_parts = parts
                     .into_iter()
                     .chain(dynamic_parts)
-                    .filter(|p| (p.enabled)(&features))
-                    .flat_map(|p| {
-                        if p.double_sided {
-                            let mut back = p.clone();
-                            back.normal *= -1.0;
-
-                            back.uv = p.back_uv.unwrap_or(p.uv);
-                            back.cw = p.back_cw.unwrap_or(back.cw);
-
-                            if back.cw {
-                                back.vertical_flip ^= true;
-                            } else {
-                                back.horizontal_flip ^= true;
-                            }
-
-                            back.is_back = true;
-
-                            vec![p, back]
-                        } else {
-                            vec![p]
-                        }
-                    })
-                    .sorted_by_key(|p| p.is_back);
+                    .filter(|p| (p.enabled)(&features));
 
                 let mut last_pos = Vec3::ZERO;
                 for part_definition in processed_parts {
@@ -937,12 +913,6 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         [size[0] as u32, 0, size[1] as u32]
                     };
 
-                    let normal_offset = if part_definition.double_sided {
-                        part_definition.normal * 0.01
-                    } else {
-                        Vec3::ZERO
-                    };
-
                     #[cfg(feature = "part_tracker")]
                     let mut name = String::from(part_definition.name);
 
@@ -951,10 +921,6 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         if let Some(count) = part_definition.part_count {
                             name.push_str(&count.to_string());
                         }
-
-                        if part_definition.is_back {
-                            name.push_str("Back");
-                        }
                     }
 
                     let mut part_quad = Part::new_quad(
@@ -967,6 +933,21 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         Some(name.clone()),
                     );
 
+                    if part_definition.double_sided {
+                        // The back of the quad has its own texture, or the front one the right way round
+                        let back_cw = part_definition.back_cw.unwrap_or(part_definition.cw);
+                        let back_uvs = process_uvs(
+                            part_definition.back_uv.unwrap_or(part_definition.uv),
+                            part_definition.horizontal_flip ^ !back_cw,
+                            part_definition.vertical_flip ^ back_cw,
+                            back_cw,
+                            part_definition.vertical_quad,
+                        );
+
+                        part_quad.set_double_sided(true);
+                        part_quad.set_back_face_uv(Some(back_uvs));
+                    }
+
                     #[cfg(feature = "part_tracker")]
                     {
                         part_quad.push_groups(
@@ -1007,12 +988,6 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         part_quad.rotate(part_definition.rot.into(), Some(anchor));
                     }
 
-                    let new_offset = part_quad
-                        .get_rotation_matrix()
-                        .transform_vector3(normal_offset);
-
-                    *part_quad.position_mut() += new_offset;
-
                     #[cfg(feature = "markers")]
                     markers.push(Marker::new(format!("{name} (Pos [f32; 3])"), pos.into()));
 
@@ -1031,7 +1006,7 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         [pos[0], pos[1], pos[2] - size[1] as f32]
                     };
 
-                    let old = Vec3::from(old_point) - normal_offset;
+                    let old = Vec3::from(old_point);
 
                     last_pos = part_quad.get_rotation_matrix().transform_point3(old);
 
@@ -1050,7 +1025,7 @@ impl<M: ArmorMaterial> PartsProvider<M> for EarsPlayerPartsProvider {
                         part_quad.add_markers(markers.drain(..).as_slice());
                     }
 
-                    result.push(part_quad);
+                    result.push(part_quad.attached_to(Bone::for_body_part(body_part)));
                 }
             }
 
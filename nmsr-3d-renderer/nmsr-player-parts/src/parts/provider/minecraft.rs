@@ -5,6 +5,7 @@ use glam::Vec3;
 use crate::model::{ArmorMaterial, PlayerArmorSlot, PlayerArmorSlots};
 use crate::parts::part::{Part, PartAnchorInfo};
 use crate::parts::provider::{PartsProvider, PlayerPartProviderContext};
+use crate::parts::skeleton::Bone;
 use crate::parts::uv::uv_from_pos_and_size;
 use crate::types::PlayerBodyPartType::*;
 use crate::types::{PlayerBodyPartType, PlayerPartTextureType};
@@ -65,7 +66,9 @@ impl<M: ArmorMaterial> PartsProvider<M> for MinecraftPlayerPartsProvider<M> {
 
         let non_layer_body_part_type = body_part.get_non_layer_part();
 
-        let part = compute_base_part(non_layer_body_part_type, context.model.is_slim_arms());
+        let bone = Bone::for_body_part(non_layer_body_part_type);
+        let part = compute_base_part(non_layer_body_part_type, context.model.is_slim_arms())
+            .attached_to(bone);
 
         if body_part.is_layer() || body_part.is_hat_layer() {
             let expand_offset = get_layer_expand_offset(non_layer_body_part_type);
@@ -120,6 +123,7 @@ impl<M: ArmorMaterial> PartsProvider<M> for MinecraftPlayerPartsProvider<M> {
                         }
 
                         armor_part.set_texture(texture);
+                        armor_part.set_bone(Some(bone));
                         result.push(armor_part);
                     }
                 }
@@ -238,7 +242,7 @@ fn append_cape_part(result: &mut Vec<Part>) {
         )),
     );
 
-    result.push(cape);
+    result.push(cape.attached_to(Bone::Cape));
 }
 
 fn expand_player_body_part(
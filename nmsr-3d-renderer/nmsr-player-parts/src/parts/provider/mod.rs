@@ -1,19 +1,25 @@
+use self::deadmau5::Deadmau5EarsPlayerPartsProvider;
 #[cfg(feature = "ears")]
 use self::ears::EarsPlayerPartsProvider;
 use self::minecraft::{perform_arm_part_rotation, MinecraftPlayerPartsProvider};
-use crate::model::{ArmorMaterial, PlayerArmorSlots, PlayerModel};
+use self::shoulder_buddies::{ShoulderBuddies, ShoulderBuddiesPlayerPartsProvider};
+use crate::model::{ArmorMaterial, PlayerArmorSlots, PlayerBodyProportions, PlayerModel};
 use crate::parts::part::Part;
-use crate::types::PlayerBodyPartType;
+use crate::types::{PlayerBodyPartType, PlayerPartTextureType};
 #[cfg(feature = "ears")]
 use ears_rs::features::EarsFeatures;
 
+pub mod deadmau5;
 #[cfg(feature = "ears")]
 pub mod ears;
 pub mod minecraft;
+pub mod shoulder_buddies;
 
 #[derive(Copy, Clone)]
 pub enum PlayerPartsProvider {
     Minecraft,
+    ShoulderBuddies,
+    Deadmau5Ears,
     #[cfg(feature = "ears")]
     Ears,
 }
@@ -32,6 +38,10 @@ where
     pub shadow_y_pos: Option<f32>,
     pub shadow_is_square: bool,
     pub armor_slots: Option<PlayerArmorSlots<M>>,
+    pub proportions: PlayerBodyProportions,
+    pub shoulder_buddies: Option<ShoulderBuddies>,
+    /// Whether to render the ears deadmau5 has in-game.
+    pub has_deadmau5_ears: bool,
     #[cfg(feature = "ears")]
     pub ears_features: Option<EarsFeatures>,
 }
@@ -58,6 +68,10 @@ impl<M: ArmorMaterial> PartsProvider<M> for PlayerPartsProvider {
             Self::Minecraft => {
                 MinecraftPlayerPartsProvider::default().get_parts(context, body_part)
             }
+            Self::ShoulderBuddies => {
+                ShoulderBuddiesPlayerPartsProvider.get_parts(context, body_part)
+            }
+            Self::Deadmau5Ears => Deadmau5EarsPlayerPartsProvider.get_parts(context, body_part),
             #[cfg(feature = "ears")]
             Self::Ears => EARS_PLAYER_PARTS_PROVIDER
                 .get_or_init(EarsPlayerPartsProvider::default)
@@ -75,6 +89,16 @@ impl<M: ArmorMaterial> PartsProvider<M> for PlayerPartsProvider {
             }
         }
 
+        if let Some((scale, pivot, offset)) = context.proportions.get_part_transform(body_part) {
+            // The shadow stays on the ground, regardless of the proportions.
+            for part in parts
+                .iter_mut()
+                .filter(|p| p.get_texture() != PlayerPartTextureType::Shadow)
+            {
+                part.scale_around(scale, pivot, offset);
+            }
+        }
+
         parts
     }
 }
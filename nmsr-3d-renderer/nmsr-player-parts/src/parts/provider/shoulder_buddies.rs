@@ -0,0 +1,151 @@
+use crate::model::ArmorMaterial;
+use crate::parts::part::Part;
+use crate::parts::provider::{PartsProvider, PlayerPartProviderContext};
+use crate::parts::skeleton::Bone;
+use crate::parts::uv::{uv_from_pos_and_size, CubeFaceUvs};
+use crate::types::PlayerBodyPartType::{self, LeftArm, RightArm};
+use crate::types::PlayerPartTextureType;
+
+/// Shoulder buddies are small pets some community skins draw in the unused regions of the hat layer.
+/// When present, they are rendered as small cubes sitting on top of the player's shoulders.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ShoulderBuddies {
+    /// Whether there's a buddy on the left shoulder.
+    pub left: bool,
+    /// Whether there's a buddy on the right shoulder.
+    pub right: bool,
+    /// The size of each buddy, in pixels.
+    pub size: u32,
+    /// How high above the shoulder each buddy sits, in pixels.
+    pub offset: i32,
+}
+
+impl ShoulderBuddies {
+    pub const DEFAULT_SIZE: u32 = 4;
+
+    /// The unused hat layer region used to texture the buddy on the left shoulder.
+    pub const LEFT_UV: (u16, u16) = (56, 0);
+    /// The unused hat layer region used to texture the buddy on the right shoulder.
+    pub const RIGHT_UV: (u16, u16) = (32, 0);
+    /// The size of the regions used to texture the buddies.
+    pub const UV_SIZE: u16 = 8;
+
+    /// Detects shoulder buddies from the RGBA pixels of a skin.
+    /// A buddy is present when its texture region has any non-transparent pixel.
+    ///
+    /// Legacy (64x32) skins never have buddies, the game ignores their hat layer when it's opaque so the unused
+    /// regions are often left filled in.
+    pub fn detect(
+        skin_rgba: &[u8],
+        skin_width: u32,
+        skin_height: u32,
+        size: u32,
+        offset: i32,
+    ) -> Option<Self> {
+        if skin_height < skin_width {
+            return None;
+        }
+
+        // Support HD skins by working out how many texture pixels a skin pixel has.
+        let scale = (skin_width / 64).max(1) as usize;
+        let skin_width = skin_width as usize;
+
+        let is_region_used = |(x, y): (u16, u16)| {
+            let region = Self::UV_SIZE as usize * scale;
+            let (x, y) = (x as usize * scale, y as usize * scale);
+
+            (y..y + region).any(|row| {
+                (x..x + region).any(|column| {
+                    let alpha = (row * skin_width + column) * 4 + 3;
+                    skin_rgba.get(alpha).is_some_and(|&alpha| alpha != 0)
+                })
+            })
+        };
+
+        let left = is_region_used(Self::LEFT_UV);
+        let right = is_region_used(Self::RIGHT_UV);
+
+        (left || right).then_some(Self {
+            left,
+            right,
+            size,
+            offset,
+        })
+    }
+
+    fn create_part(&self, body_part: PlayerBodyPartType, is_slim_arms: bool) -> Part {
+        let (uv_x, uv_y) = if body_part == LeftArm {
+            Self::LEFT_UV
+        } else {
+            Self::RIGHT_UV
+        };
+
+        // Center the buddy on top of the arm.
+        let arm_width = if is_slim_arms { 3 } else { 4 };
+        let arm_x = if body_part == LeftArm {
+            -4 - arm_width
+        } else {
+            4
+        };
+
+        let size = self.size as i32;
+        let position = [arm_x + (arm_width - size) / 2, 24 + self.offset, -size / 2];
+
+        let face = uv_from_pos_and_size(uv_x, uv_y, Self::UV_SIZE, Self::UV_SIZE);
+        let uvs = CubeFaceUvs {
+            north: face,
+            south: face,
+            east: face,
+            west: face,
+            up: face,
+            down: face,
+        };
+
+        Part::new_cube(
+            PlayerPartTextureType::Skin,
+            position,
+            [self.size; 3],
+            uvs,
+            #[cfg(feature = "part_tracker")]
+            Some(
+                if body_part == LeftArm {
+                    "Left Shoulder Buddy"
+                } else {
+                    "Right Shoulder Buddy"
+                }
+                .to_string(),
+            ),
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct ShoulderBuddiesPlayerPartsProvider;
+
+impl<M: ArmorMaterial> PartsProvider<M> for ShoulderBuddiesPlayerPartsProvider {
+    fn get_parts(
+        &self,
+        context: &PlayerPartProviderContext<M>,
+        body_part: PlayerBodyPartType,
+    ) -> Vec<Part> {
+        // Buddies are drawn on the hat layer, so they're only shown alongside the other layers.
+        let Some(buddies) = context.shoulder_buddies.filter(|_| context.has_layers) else {
+            return vec![];
+        };
+
+        let has_buddy = match body_part {
+            LeftArm => buddies.left,
+            RightArm => buddies.right,
+            _ => false,
+        };
+
+        if !has_buddy {
+            return vec![];
+        }
+
+        // Buddies sit on the shoulders, so they move with the arm underneath them.
+        vec![buddies
+            .create_part(body_part, context.model.is_slim_arms())
+            .attached_to(Bone::for_body_part(body_part))]
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use strum::{Display, EnumIter, EnumString};
+
+use super::part::{MinecraftPosition, Part};
+use crate::model::PlayerBodyProportions;
+use crate::types::PlayerBodyPartType;
+
+/// A bone of the player skeleton. Part providers attach the parts they create to one of these,
+/// see [`Part::attached_to`].
+///
+/// Bones are laid out in a hierarchy, so moving a bone moves every bone attached to it:
+/// the head, arms and cape hang off the torso, while the torso and legs are the roots.
+#[derive(Debug, Copy, Clone, EnumIter, EnumString, Display, Eq, PartialEq, Hash)]
+#[strum(serialize_all = "snake_case")]
+pub enum Bone {
+    Head,
+    Torso,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+    Cape,
+}
+
+impl Bone {
+    /// Returns the bone this bone is attached to, if any.
+    pub fn parent(&self) -> Option<Self> {
+        match self {
+            Self::Head | Self::LeftArm | Self::RightArm | Self::Cape => Some(Self::Torso),
+            Self::Torso | Self::LeftLeg | Self::RightLeg => None,
+        }
+    }
+
+    /// Returns the joint this bone rotates around (the neck, shoulders, hips or the top of the cape),
+    /// before any body proportions are applied.
+    pub fn get_pivot(&self) -> MinecraftPosition {
+        match self {
+            Self::Head => [0.0, 24.0, 0.0],
+            Self::Torso => [0.0, 12.0, 0.0],
+            Self::LeftArm => [-5.0, 22.0, 0.0],
+            Self::RightArm => [5.0, 22.0, 0.0],
+            Self::LeftLeg => [-2.0, 12.0, 0.0],
+            Self::RightLeg => [2.0, 12.0, 0.0],
+            Self::Cape => [0.0, 24.0, 2.0],
+        }
+        .into()
+    }
+
+    /// Returns the body part whose proportions this bone follows.
+    pub fn get_body_part(&self) -> PlayerBodyPartType {
+        match self {
+            Self::Head => PlayerBodyPartType::Head,
+            Self::Torso | Self::Cape => PlayerBodyPartType::Body,
+            Self::LeftArm => PlayerBodyPartType::LeftArm,
+            Self::RightArm => PlayerBodyPartType::RightArm,
+            Self::LeftLeg => PlayerBodyPartType::LeftLeg,
+            Self::RightLeg => PlayerBodyPartType::RightLeg,
+        }
+    }
+
+    /// Returns the bone the given body part (or its layer) is attached to.
+    pub fn for_body_part(body_part: PlayerBodyPartType) -> Self {
+        match body_part.get_non_layer_part() {
+            PlayerBodyPartType::Head => Self::Head,
+            PlayerBodyPartType::LeftArm => Self::LeftArm,
+            PlayerBodyPartType::RightArm => Self::RightArm,
+            PlayerBodyPartType::LeftLeg => Self::LeftLeg,
+            PlayerBodyPartType::RightLeg => Self::RightLeg,
+            _ => Self::Torso,
+        }
+    }
+}
+
+/// A rotation and translation of a bone, relative to its resting pose.
+///
+/// The rotation is in degrees and follows the same conventions as [`Part::rotate`].
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BoneTransform {
+    pub rotation: Vec3,
+    pub translation: Vec3,
+}
+
+impl BoneTransform {
+    pub fn new(rotation: Vec3, translation: Vec3) -> Self {
+        Self {
+            rotation,
+            translation,
+        }
+    }
+
+    pub fn from_rotation(rotation: Vec3) -> Self {
+        Self::new(rotation, Vec3::ZERO)
+    }
+
+    pub fn lerp(&self, other: &Self, amount: f32) -> Self {
+        Self {
+            rotation: self.rotation.lerp(other.rotation, amount),
+            translation: self.translation.lerp(other.translation, amount),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.rotation == Vec3::ZERO && self.translation == Vec3::ZERO
+    }
+
+    /// Returns the matrix of this transform, rotating around the given pivot.
+    pub fn to_matrix(&self, pivot: MinecraftPosition) -> Mat4 {
+        let rotation = Mat4::from_quat(Quat::from_euler(
+            EulerRot::YXZ,
+            self.rotation.y.to_radians(),
+            -self.rotation.x.to_radians(),
+            -self.rotation.z.to_radians(),
+        ));
+
+        Mat4::from_translation(self.translation + pivot)
+            * rotation
+            * Mat4::from_translation(-pivot)
+    }
+}
+
+/// A pose of the player skeleton, made of the transform of each bone relative to its parent.
+///
+/// Bones without a transform are left in their resting pose.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    transforms: HashMap<Bone, BoneTransform>,
+    proportions: PlayerBodyProportions,
+}
+
+impl Skeleton {
+    pub fn new(proportions: PlayerBodyProportions) -> Self {
+        Self {
+            transforms: HashMap::new(),
+            proportions,
+        }
+    }
+
+    pub fn proportions(&self) -> PlayerBodyProportions {
+        self.proportions
+    }
+
+    pub fn set_proportions(&mut self, proportions: PlayerBodyProportions) {
+        self.proportions = proportions;
+    }
+
+    pub fn get_transform(&self, bone: Bone) -> BoneTransform {
+        self.transforms.get(&bone).copied().unwrap_or_default()
+    }
+
+    pub fn set_transform(&mut self, bone: Bone, transform: BoneTransform) {
+        self.transforms.insert(bone, transform);
+    }
+
+    pub fn with_transform(mut self, bone: Bone, transform: BoneTransform) -> Self {
+        self.set_transform(bone, transform);
+        self
+    }
+
+    pub fn set_rotation(&mut self, bone: Bone, rotation: Vec3) {
+        self.transforms.entry(bone).or_default().rotation = rotation;
+    }
+
+    pub fn set_translation(&mut self, bone: Bone, translation: Vec3) {
+        self.transforms.entry(bone).or_default().translation = translation;
+    }
+
+    /// Puts every bone back in its resting pose.
+    pub fn reset(&mut self) {
+        self.transforms.clear();
+    }
+
+    pub fn is_rest_pose(&self) -> bool {
+        self.transforms.values().all(BoneTransform::is_identity)
+    }
+
+    /// Returns the joint the given bone rotates around, with the body proportions applied.
+    pub fn get_pivot(&self, bone: Bone) -> MinecraftPosition {
+        self.proportions
+            .transform_position(bone.get_body_part(), bone.get_pivot())
+    }
+
+    /// Returns the transform of the given bone in model space, including the transforms of its parents.
+    pub fn get_world_transform(&self, bone: Bone) -> Mat4 {
+        let local = self.get_transform(bone).to_matrix(self.get_pivot(bone));
+
+        match bone.parent() {
+            Some(parent) => self.get_world_transform(parent) * local,
+            None => local,
+        }
+    }
+
+    /// Moves a part from its resting pose into this pose, following the bone it is attached to.
+    ///
+    /// Parts that aren't attached to any bone are left untouched.
+    pub fn apply_to_part(&self, part: &mut Part) {
+        let Some(bone) = part.get_bone() else {
+            return;
+        };
+
+        let transform = self.get_world_transform(bone);
+
+        if transform != Mat4::IDENTITY {
+            part.apply_transform(transform);
+        }
+    }
+}
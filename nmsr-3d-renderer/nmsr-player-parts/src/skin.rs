@@ -0,0 +1,136 @@
+//! Utilities for converting skins into the format the renderer expects, the same way the game does it.
+
+use image::{Pixel, RgbaImage};
+
+use crate::skin_regions::{self, skin_scale, SkinRegion};
+
+/// The regions copied by the game when upgrading a legacy skin: source position, offset, size and whether to mirror
+/// them horizontally. Legacy skins only have the right arm and leg, so the left ones are mirrored from them.
+#[rustfmt::skip]
+const LEGACY_UPGRADE_COPIES: [(u32, u32, i32, i32, u32, u32); 12] = [
+    // Right leg -> left leg
+    (4, 16, 16, 32, 4, 4),
+    (8, 16, 16, 32, 4, 4),
+    (0, 20, 24, 32, 4, 12),
+    (4, 20, 16, 32, 4, 12),
+    (8, 20, 8, 32, 4, 12),
+    (12, 20, 16, 32, 4, 12),
+    // Right arm -> left arm
+    (44, 16, -8, 32, 4, 4),
+    (48, 16, -8, 32, 4, 4),
+    (40, 20, 0, 32, 4, 12),
+    (44, 20, -8, 32, 4, 12),
+    (48, 20, -16, 32, 4, 12),
+    (52, 20, -8, 32, 4, 12),
+];
+
+/// The regions of a modern skin that contain the base layer of each body part.
+pub const BASE_LAYER_REGIONS: [SkinRegion; 6] = [
+    skin_regions::HEAD.bounds(),
+    skin_regions::BODY.bounds(),
+    skin_regions::RIGHT_ARM.bounds(),
+    skin_regions::LEFT_ARM.bounds(),
+    skin_regions::RIGHT_LEG.bounds(),
+    skin_regions::LEFT_LEG.bounds(),
+];
+
+/// The regions of a modern skin that contain the second layer of each body part.
+pub const SECOND_LAYER_REGIONS: [SkinRegion; 6] = [
+    skin_regions::HAT.bounds(),
+    skin_regions::JACKET.bounds(),
+    skin_regions::RIGHT_SLEEVE.bounds(),
+    skin_regions::LEFT_SLEEVE.bounds(),
+    skin_regions::RIGHT_PANTS.bounds(),
+    skin_regions::LEFT_PANTS.bounds(),
+];
+
+/// Whether the skin is in the legacy (64x32) format, used before Minecraft 1.8.
+pub fn is_legacy_skin(skin: &RgbaImage) -> bool {
+    skin.width() == skin.height() * 2
+}
+
+/// Upgrades a legacy (64x32) skin to the modern (64x64) format, with the hat layer's background stripped.
+/// Skins already in the modern format are returned as-is.
+pub fn upgrade_legacy_skin(mut skin: RgbaImage) -> RgbaImage {
+    if !is_legacy_skin(&skin) {
+        return skin;
+    }
+
+    strip_opaque_hat_background(&mut skin);
+
+    let scale = skin_scale(&skin);
+    let mut upgraded = RgbaImage::new(skin.width(), skin.width());
+
+    for (x, y, pixel) in skin.enumerate_pixels() {
+        upgraded.put_pixel(x, y, *pixel);
+    }
+
+    for (x, y, offset_x, offset_y, width, height) in LEGACY_UPGRADE_COPIES {
+        let (x, y, width, height) = (x * scale, y * scale, width * scale, height * scale);
+        let (offset_x, offset_y) = (offset_x * scale as i32, offset_y * scale as i32);
+
+        for dy in 0..height {
+            for dx in 0..width {
+                let pixel = *skin.get_pixel(x + dx, y + dy);
+
+                // The copied region is mirrored horizontally.
+                let target_x = (x + width - 1 - dx).saturating_add_signed(offset_x);
+                let target_y = (y + dy).saturating_add_signed(offset_y);
+
+                upgraded.put_pixel(target_x, target_y, pixel);
+            }
+        }
+    }
+
+    upgraded
+}
+
+/// Makes the hat layer of a legacy skin fully transparent if it doesn't have any transparent pixels.
+///
+/// Many old skins were drawn with an opaque background behind the hat layer, which would otherwise cover the head.
+/// Just like the game, the hat layer is only kept if at least one of its pixels is transparent.
+pub fn strip_opaque_hat_background(skin: &mut RgbaImage) {
+    if !skin_regions::HAT.bounds().is_inside(skin) {
+        return;
+    }
+
+    let SkinRegion {
+        x,
+        y,
+        width,
+        height,
+    } = skin_regions::HAT.bounds().for_skin(skin);
+
+    let region = || (y..y + height).flat_map(move |py| (x..x + width).map(move |px| (px, py)));
+
+    if region().any(|(px, py)| skin.get_pixel(px, py)[3] < 128) {
+        return;
+    }
+
+    for (px, py) in region() {
+        skin.get_pixel_mut(px, py)[3] = 0;
+    }
+}
+
+/// Draws an overlay on top of the given regions of a modern skin, blending it using the overlay's transparency.
+///
+/// The overlay is expected to be the same size as the skin, anything outside of either of them is ignored.
+pub fn composite_overlay(skin: &mut RgbaImage, overlay: &RgbaImage, regions: &[SkinRegion]) {
+    let max_x = skin.width().min(overlay.width());
+    let max_y = skin.height().min(overlay.height());
+
+    for region in regions {
+        let SkinRegion {
+            x,
+            y,
+            width,
+            height,
+        } = region.for_skin(skin);
+
+        for py in y..(y + height).min(max_y) {
+            for px in x..(x + width).min(max_x) {
+                skin.get_pixel_mut(px, py).blend(overlay.get_pixel(px, py));
+            }
+        }
+    }
+}
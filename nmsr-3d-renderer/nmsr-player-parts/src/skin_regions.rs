@@ -0,0 +1,181 @@
+//! Named regions of a Minecraft skin texture.
+//!
+//! Every region is given in the coordinates of a regular 64x64 skin, and is scaled up automatically when working
+//! with HD skins. Only the arms differ between the classic and slim layouts, the slim ones being a pixel thinner.
+
+use image::{imageops, RgbaImage};
+use thiserror::Error;
+
+use crate::types::PlayerBodyPartType;
+
+/// The width of a regular skin, in pixels.
+pub const SKIN_WIDTH: u32 = 64;
+
+/// Returns the scale of the skin compared to a regular one, for HD skins.
+pub fn skin_scale(skin: &RgbaImage) -> u32 {
+    (skin.width() / SKIN_WIDTH).max(1)
+}
+
+/// The error returned when an image doesn't fit the region of a skin it's meant to replace.
+#[derive(Debug, Error)]
+#[error("Skin region {0:?} doesn't fit the skin or a {1}x{2} image")]
+pub struct SkinRegionMismatch(pub SkinRegion, pub u32, pub u32);
+
+/// A rectangular region of a skin texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkinRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SkinRegion {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns this region scaled up by the given factor, for HD skins.
+    pub const fn scaled(self, scale: u32) -> Self {
+        Self::new(
+            self.x * scale,
+            self.y * scale,
+            self.width * scale,
+            self.height * scale,
+        )
+    }
+
+    /// Returns this region in the coordinates of the given skin, taking its resolution into account.
+    pub fn for_skin(self, skin: &RgbaImage) -> Self {
+        self.scaled(skin_scale(skin))
+    }
+
+    /// Whether this region is fully inside the given skin.
+    /// Legacy (64x32) skins don't have the regions in their bottom half, for example.
+    pub fn is_inside(self, skin: &RgbaImage) -> bool {
+        let region = self.for_skin(skin);
+
+        region.x + region.width <= skin.width() && region.y + region.height <= skin.height()
+    }
+
+    /// Copies this region of the skin into a new image, or returns `None` if the skin doesn't have it.
+    pub fn extract(self, skin: &RgbaImage) -> Option<RgbaImage> {
+        if !self.is_inside(skin) {
+            return None;
+        }
+
+        let region = self.for_skin(skin);
+
+        Some(imageops::crop_imm(skin, region.x, region.y, region.width, region.height).to_image())
+    }
+
+    /// Replaces this region of the skin with the given image, which has to be the same size as the region.
+    pub fn replace(self, skin: &mut RgbaImage, image: &RgbaImage) -> Result<(), SkinRegionMismatch> {
+        let region = self.for_skin(skin);
+
+        if !self.is_inside(skin) || image.dimensions() != (region.width, region.height) {
+            return Err(SkinRegionMismatch(self, image.width(), image.height()));
+        }
+
+        imageops::replace(skin, image, region.x.into(), region.y.into());
+
+        Ok(())
+    }
+}
+
+/// The regions of each face of a body part, laid out the way the game does it for cubes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CubeSkinRegions {
+    pub top: SkinRegion,
+    pub bottom: SkinRegion,
+    pub right: SkinRegion,
+    pub front: SkinRegion,
+    pub left: SkinRegion,
+    pub back: SkinRegion,
+}
+
+impl CubeSkinRegions {
+    /// Lays out the faces of a cube of the given size (width, height and depth), starting at the given position.
+    pub const fn new(x: u32, y: u32, size: [u32; 3]) -> Self {
+        let [width, height, depth] = size;
+
+        Self {
+            top: SkinRegion::new(x + depth, y, width, depth),
+            bottom: SkinRegion::new(x + depth + width, y, width, depth),
+            right: SkinRegion::new(x, y + depth, depth, height),
+            front: SkinRegion::new(x + depth, y + depth, width, height),
+            left: SkinRegion::new(x + depth + width, y + depth, depth, height),
+            back: SkinRegion::new(x + depth * 2 + width, y + depth, width, height),
+        }
+    }
+
+    /// The faces of the cube, in the order top, bottom, right, front, left and back.
+    pub const fn faces(&self) -> [SkinRegion; 6] {
+        [
+            self.top,
+            self.bottom,
+            self.right,
+            self.front,
+            self.left,
+            self.back,
+        ]
+    }
+
+    /// The smallest region containing every face of the cube.
+    pub const fn bounds(&self) -> SkinRegion {
+        SkinRegion::new(
+            self.right.x,
+            self.top.y,
+            self.back.x + self.back.width - self.right.x,
+            self.front.y + self.front.height - self.top.y,
+        )
+    }
+}
+
+pub const HEAD: CubeSkinRegions = CubeSkinRegions::new(0, 0, [8, 8, 8]);
+pub const HAT: CubeSkinRegions = CubeSkinRegions::new(32, 0, [8, 8, 8]);
+
+pub const BODY: CubeSkinRegions = CubeSkinRegions::new(16, 16, [8, 12, 4]);
+pub const JACKET: CubeSkinRegions = CubeSkinRegions::new(16, 32, [8, 12, 4]);
+
+pub const RIGHT_ARM: CubeSkinRegions = CubeSkinRegions::new(40, 16, [4, 12, 4]);
+pub const RIGHT_SLEEVE: CubeSkinRegions = CubeSkinRegions::new(40, 32, [4, 12, 4]);
+pub const LEFT_ARM: CubeSkinRegions = CubeSkinRegions::new(32, 48, [4, 12, 4]);
+pub const LEFT_SLEEVE: CubeSkinRegions = CubeSkinRegions::new(48, 48, [4, 12, 4]);
+
+pub const SLIM_RIGHT_ARM: CubeSkinRegions = CubeSkinRegions::new(40, 16, [3, 12, 4]);
+pub const SLIM_RIGHT_SLEEVE: CubeSkinRegions = CubeSkinRegions::new(40, 32, [3, 12, 4]);
+pub const SLIM_LEFT_ARM: CubeSkinRegions = CubeSkinRegions::new(32, 48, [3, 12, 4]);
+pub const SLIM_LEFT_SLEEVE: CubeSkinRegions = CubeSkinRegions::new(48, 48, [3, 12, 4]);
+
+pub const RIGHT_LEG: CubeSkinRegions = CubeSkinRegions::new(0, 16, [4, 12, 4]);
+pub const RIGHT_PANTS: CubeSkinRegions = CubeSkinRegions::new(0, 32, [4, 12, 4]);
+pub const LEFT_LEG: CubeSkinRegions = CubeSkinRegions::new(16, 48, [4, 12, 4]);
+pub const LEFT_PANTS: CubeSkinRegions = CubeSkinRegions::new(0, 48, [4, 12, 4]);
+
+/// Returns the skin regions of a body part, in either the classic or the slim layout.
+pub const fn body_part_regions(part: PlayerBodyPartType, slim: bool) -> CubeSkinRegions {
+    match (part, slim) {
+        (PlayerBodyPartType::Head, _) => HEAD,
+        (PlayerBodyPartType::HeadLayer, _) => HAT,
+        (PlayerBodyPartType::Body, _) => BODY,
+        (PlayerBodyPartType::BodyLayer, _) => JACKET,
+        (PlayerBodyPartType::RightArm, false) => RIGHT_ARM,
+        (PlayerBodyPartType::RightArm, true) => SLIM_RIGHT_ARM,
+        (PlayerBodyPartType::RightArmLayer, false) => RIGHT_SLEEVE,
+        (PlayerBodyPartType::RightArmLayer, true) => SLIM_RIGHT_SLEEVE,
+        (PlayerBodyPartType::LeftArm, false) => LEFT_ARM,
+        (PlayerBodyPartType::LeftArm, true) => SLIM_LEFT_ARM,
+        (PlayerBodyPartType::LeftArmLayer, false) => LEFT_SLEEVE,
+        (PlayerBodyPartType::LeftArmLayer, true) => SLIM_LEFT_SLEEVE,
+        (PlayerBodyPartType::RightLeg, _) => RIGHT_LEG,
+        (PlayerBodyPartType::RightLegLayer, _) => RIGHT_PANTS,
+        (PlayerBodyPartType::LeftLeg, _) => LEFT_LEG,
+        (PlayerBodyPartType::LeftLegLayer, _) => LEFT_PANTS,
+    }
+}
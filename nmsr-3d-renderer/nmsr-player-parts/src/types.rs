@@ -1,6 +1,7 @@
-use strum::{Display, EnumIter, IntoStaticStr};
+use strum::{Display, EnumIter, EnumString, IntoStaticStr};
 
-#[derive(Debug, Copy, Clone, EnumIter, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, EnumIter, EnumString, Display, Eq, PartialEq, Hash)]
+#[strum(serialize_all = "snake_case")]
 pub enum PlayerBodyPartType {
     // Normal body parts
     Head,
@@ -1,4 +1,6 @@
-use nmsr_player_parts::types::PlayerPartTextureType;
+#[cfg(feature = "pipeline")]
+use crate::high_level::pipeline::scene::TextureRegion;
+use nmsr_player_parts::{skin_regions::SkinRegionMismatch, types::PlayerPartTextureType};
 use thiserror::Error;
 use tokio::sync::oneshot::error::RecvError;
 
@@ -19,6 +21,38 @@ pub enum NMSRRenderingError {
     SceneContextTexturesNotInitialized,
     #[error("SceneContext Texture not set: {0}")]
     SceneContextTextureNotSet(PlayerPartTextureType),
+    #[error("The scene doesn't have an entity at index {0}")]
+    SceneEntityNotFound(usize),
+    #[cfg(feature = "pipeline")]
+    #[error("Texture region {0:?} is outside of the {1} texture")]
+    TextureRegionOutOfBounds(TextureRegion, PlayerPartTextureType),
+    #[cfg(feature = "pipeline")]
+    #[error("Texture region {0:?} needs {1} bytes of pixel data, but {2} bytes were given")]
+    TextureRegionSizeMismatch(TextureRegion, usize, usize),
+    #[cfg(feature = "pipeline")]
+    #[error("A sample count of {0} isn't supported by the adapter")]
+    UnsupportedSampleCount(u32),
+    #[cfg(feature = "pipeline")]
+    #[error("The GPU didn't finish the render before its deadline")]
+    RenderTimedOut,
+    #[cfg(feature = "pipeline")]
+    #[error("Scenes can't be rendered to {0:?} textures on this adapter")]
+    UnsupportedOutputFormat(wgpu::TextureFormat),
+    #[cfg(feature = "pipeline")]
+    #[error("The variants of the {0} texture need to all be the same size, and there needs to be at least one")]
+    TextureVariantsMismatch(PlayerPartTextureType),
+    #[cfg(feature = "pipeline")]
+    #[error("Depth readback isn't enabled for this scene, or it hasn't been rendered yet")]
+    DepthReadbackNotEnabled,
+    #[cfg(feature = "pipeline")]
+    #[error("The output of a render doesn't fit its {0}x{1} viewport")]
+    OutputSizeMismatch(u32, u32),
+    #[cfg(feature = "pipeline")]
+    #[error("Renders can't be read back as floats from the {0:?} output format")]
+    UnsupportedFloatOutputFormat(wgpu::TextureFormat),
+    #[cfg(feature = "exr")]
+    #[error("Unable to encode the render as EXR: {0}")]
+    ExrEncodeError(image::ImageError),
     #[cfg(feature = "pipeline")]
     #[error("Buffer Async error: {0}")]
     BufferAsyncError(#[from] wgpu::BufferAsyncError),
@@ -26,6 +60,8 @@ pub enum NMSRRenderingError {
     RecvError(#[from] RecvError),
     #[error("Unable to convert image from raw bytes: {0}")]
     ImageFromRawError(image::ImageError),
+    #[error("{0}")]
+    SkinRegionMismatch(#[from] SkinRegionMismatch),
     #[cfg(feature = "pipeline")]
     #[error("Pool error: {0}")]
     PoolError(#[from] deadpool::managed::PoolError<Box<Self>>),
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use nmsr_player_parts::parts::skeleton::{Bone, BoneTransform, Skeleton};
+
+/// How a value moves from one keyframe to the next.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Holds the value of the previous keyframe until the next one is reached.
+    Step,
+}
+
+impl Easing {
+    /// Maps the progress between two keyframes (from 0 to 1) to how far the value has moved.
+    pub fn apply(&self, progress: f32) -> f32 {
+        let t = progress.clamp(0.0, 1.0);
+
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Self::Step => {
+                if t < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// A keyframe of a value that can be interpolated, like a bone's transform or a camera pose.
+pub(crate) trait Keyframed {
+    type Value;
+
+    /// The time of this keyframe, in seconds.
+    fn time(&self) -> f32;
+
+    /// The easing used to get from the previous keyframe to this one.
+    fn easing(&self) -> Easing;
+
+    fn value(&self) -> Self::Value;
+
+    /// Interpolates from the value of this keyframe (at 0) to the value of the next one (at 1).
+    fn lerp(&self, next: &Self, t: f32) -> Self::Value;
+}
+
+/// Wraps the time around the duration of looping animations, or clamps it to the duration of the others.
+pub(crate) fn playback_time(time: f32, duration: f32, looping: bool) -> f32 {
+    if looping && duration > 0.0 {
+        time.rem_euclid(duration)
+    } else {
+        time.clamp(0.0, duration.max(0.0))
+    }
+}
+
+/// Inserts a keyframe after the ones at or before its time, keeping the keyframes sorted by time.
+pub(crate) fn insert_keyframe<K: Keyframed>(keyframes: &mut Vec<K>, keyframe: K) {
+    let index = keyframes.partition_point(|k| k.time() <= keyframe.time());
+    keyframes.insert(index, keyframe);
+}
+
+/// Computes the value at the given time from keyframes sorted by time, if there are any.
+///
+/// Before the first keyframe and after the last one, their value is held.
+pub(crate) fn sample_keyframes<K: Keyframed>(keyframes: &[K], time: f32) -> Option<K::Value> {
+    let next_index = keyframes.partition_point(|k| k.time() <= time);
+
+    match (
+        next_index.checked_sub(1).and_then(|i| keyframes.get(i)),
+        keyframes.get(next_index),
+    ) {
+        (Some(previous), Some(next)) => {
+            let span = next.time() - previous.time();
+            let progress = if span > 0.0 {
+                (time - previous.time()) / span
+            } else {
+                1.0
+            };
+
+            Some(previous.lerp(next, next.easing().apply(progress)))
+        }
+        (Some(only), None) | (None, Some(only)) => Some(only.value()),
+        (None, None) => None,
+    }
+}
+
+/// A bone's transform at a given point in time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe {
+    /// The time of this keyframe, in seconds.
+    pub time: f32,
+    pub transform: BoneTransform,
+    /// The easing used to get from the previous keyframe to this one.
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, transform: BoneTransform, easing: Easing) -> Self {
+        Self {
+            time,
+            transform,
+            easing,
+        }
+    }
+}
+
+impl Keyframed for Keyframe {
+    type Value = BoneTransform;
+
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    fn easing(&self) -> Easing {
+        self.easing
+    }
+
+    fn value(&self) -> BoneTransform {
+        self.transform
+    }
+
+    fn lerp(&self, next: &Self, t: f32) -> BoneTransform {
+        self.transform.lerp(&next.transform, t)
+    }
+}
+
+/// A set of keyframed transforms for the bones of a player, like a walk cycle or an emote.
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+    duration: f32,
+    looping: bool,
+    tracks: HashMap<Bone, Vec<Keyframe>>,
+}
+
+impl Animation {
+    pub fn new(duration: f32, looping: bool) -> Self {
+        Self {
+            duration,
+            looping,
+            tracks: HashMap::new(),
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Adds a keyframe for the given bone, keeping its track sorted by time.
+    pub fn add_keyframe(&mut self, bone: Bone, keyframe: Keyframe) {
+        insert_keyframe(self.tracks.entry(bone).or_default(), keyframe);
+    }
+
+    pub fn with_keyframe(mut self, bone: Bone, keyframe: Keyframe) -> Self {
+        self.add_keyframe(bone, keyframe);
+        self
+    }
+
+    /// Computes the transform of every animated bone at the given time, in seconds.
+    ///
+    /// Looping animations wrap around their duration, while the others hold their last frame.
+    pub fn sample(&self, time: f32) -> HashMap<Bone, BoneTransform> {
+        let time = playback_time(time, self.duration, self.looping);
+
+        self.tracks
+            .iter()
+            .filter_map(|(bone, track)| {
+                sample_keyframes(track, time).map(|transform| (*bone, transform))
+            })
+            .collect()
+    }
+
+    /// Poses the animated bones of the skeleton at the given time, in seconds.
+    ///
+    /// Bones this animation doesn't have keyframes for are left untouched.
+    pub fn apply_frame(&self, skeleton: &mut Skeleton, time: f32) {
+        for (bone, transform) in self.sample(time) {
+            skeleton.set_transform(bone, transform);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn walk_cycle() -> Animation {
+        Animation::new(1.0, true)
+            .with_keyframe(
+                Bone::LeftLeg,
+                Keyframe::new(0.0, BoneTransform::from_rotation(Vec3::X * -30.0), Easing::Linear),
+            )
+            .with_keyframe(
+                Bone::LeftLeg,
+                Keyframe::new(0.5, BoneTransform::from_rotation(Vec3::X * 30.0), Easing::Linear),
+            )
+            .with_keyframe(
+                Bone::LeftLeg,
+                Keyframe::new(1.0, BoneTransform::from_rotation(Vec3::X * -30.0), Easing::Linear),
+            )
+    }
+
+    #[test]
+    fn interpolates_between_keyframes() {
+        let sample = walk_cycle().sample(0.25);
+
+        assert_eq!(sample[&Bone::LeftLeg].rotation, Vec3::ZERO);
+    }
+
+    #[test]
+    fn looping_animations_wrap_around() {
+        let animation = walk_cycle();
+
+        assert_eq!(animation.sample(1.5), animation.sample(0.5));
+    }
+
+    #[test]
+    fn non_looping_animations_hold_their_last_frame() {
+        let animation = Animation::new(1.0, false).with_keyframe(
+            Bone::Head,
+            Keyframe::new(0.5, BoneTransform::from_rotation(Vec3::Y * 45.0), Easing::Step),
+        );
+
+        assert_eq!(animation.sample(2.0)[&Bone::Head].rotation, Vec3::Y * 45.0);
+    }
+
+    #[test]
+    fn easing_stays_within_bounds() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+    }
+}
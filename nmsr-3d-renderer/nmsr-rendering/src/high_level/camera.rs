@@ -1,4 +1,5 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec3, Vec4};
+use nmsr_player_parts::parts::bounds::PartBounds;
 use std::mem;
 
 use crate::high_level::utils::{
@@ -268,6 +269,70 @@ impl Camera {
     camera_inner_getters_setters!(get_position(), position, x, y, z);
     camera_inner_getters_setters!(get_look_at(), look_at, x, y, z);
 
+    /// Returns where the camera is, whether it's placed absolutely or orbiting a point.
+    pub fn get_world_position(&self) -> Vec3 {
+        self.position_parameters
+            .to_absolute(self.rotation.yaw, self.rotation.pitch)
+            .get_position()
+            .unwrap_or(Vec3::ZERO)
+    }
+
+    /// Orbits the camera around the center of a bounding box, far enough for the whole box to be in view
+    /// (from the camera's current rotation), with `margin` extra room around it (like 0.1 for 10%).
+    ///
+    /// Perspective cameras are moved closer or further away, while orthographic ones are zoomed instead.
+    pub fn frame_bounds(&mut self, bounds: PartBounds, margin: f32) {
+        let center = bounds.center();
+
+        let look = look_from_yaw_pitch(self.rotation.yaw, self.rotation.pitch);
+        let right = look.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let up = right.cross(look);
+
+        // The corners of the box, relative to its center, in the camera's own axes
+        let corners = bounds.corners().map(|corner| {
+            let offset = corner - center;
+
+            Vec3::new(offset.dot(right).abs(), offset.dot(up).abs(), offset.dot(look))
+        });
+
+        let aspect_ratio = self.get_aspect_ratio();
+        let scale = 1.0 + margin.max(0.0);
+
+        let distance = match &mut self.projection {
+            ProjectionParameters::Perspective { fov } => {
+                let tan_vertical = (fov.to_radians() / 2.0).tan() / scale;
+                let tan_horizontal = tan_vertical * aspect_ratio;
+
+                // Each corner needs to be far enough away for its offset to fit in the field of view
+                corners
+                    .iter()
+                    .map(|corner| (corner.x / tan_horizontal).max(corner.y / tan_vertical) - corner.z)
+                    .fold(0.0, f32::max)
+            }
+            ProjectionParameters::Orthographic { aspect } => {
+                *aspect = corners
+                    .iter()
+                    .map(|corner| corner.y.max(corner.x / aspect_ratio))
+                    .fold(0.0, f32::max)
+                    * scale;
+
+                // Orthographic cameras see as much from any distance, as long as the box is in front of them
+                bounds.size().length()
+            }
+        };
+
+        self.position_parameters = CameraPositionParameters::Orbital {
+            look_at: center,
+            distance,
+        };
+        self.dirty = true;
+    }
+
+    /// Returns whether any of a bounding box might be in view of the camera.
+    pub fn can_see(&mut self, bounds: PartBounds) -> bool {
+        bounds_in_frustum(self.get_view_projection_matrix(), bounds)
+    }
+
     pub fn get_view_projection_matrix(&mut self) -> Mat4 {
         if self.dirty {
             self.cached_view_projection_matrix = self.compute_view_projection_matrix()
@@ -309,3 +374,85 @@ impl Camera {
         projection * view_position
     }
 }
+
+/// Returns whether any of a bounding box might be in view of a view-projection matrix,
+/// i.e. whether it isn't entirely on the outer side of one of the planes of the frustum.
+///
+/// Boxes just outside the corners of the frustum count as in view, which only costs drawing them for nothing.
+pub(crate) fn bounds_in_frustum(view_projection: Mat4, bounds: PartBounds) -> bool {
+    let corners = bounds
+        .corners()
+        .map(|corner| view_projection * corner.extend(1.0));
+    let all_outside = |outside: fn(&Vec4) -> bool| corners.iter().all(outside);
+
+    !(all_outside(|c| c.x < -c.w)
+        || all_outside(|c| c.x > c.w)
+        || all_outside(|c| c.y < -c.w)
+        || all_outside(|c| c.y > c.w)
+        || all_outside(|c| c.z < 0.0)
+        || all_outside(|c| c.z > c.w))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use nmsr_player_parts::parts::bounds::PartBounds;
+
+    use super::{Camera, CameraRotation, ProjectionParameters};
+    use crate::high_level::pipeline::scene::Size;
+
+    #[test]
+    fn framed_bounds_are_in_view() {
+        let bounds = PartBounds::new(Vec3::new(-8.0, 0.0, -4.0), Vec3::new(8.0, 40.0, 4.0));
+
+        let mut camera = Camera::new_orbital(
+            Vec3::ZERO,
+            1.0,
+            CameraRotation {
+                yaw: 20.0,
+                pitch: 10.0,
+                roll: 0.0,
+            },
+            ProjectionParameters::Perspective { fov: 45.0 },
+            Some(Size {
+                width: 512,
+                height: 869,
+            }),
+        );
+
+        camera.frame_bounds(bounds, 0.1);
+        let view_projection = camera.get_view_projection_matrix();
+
+        for corner in bounds.corners() {
+            let projected = view_projection.project_point3(corner);
+
+            assert!(projected.x.abs() <= 1.0 && projected.y.abs() <= 1.0, "{:?}", projected);
+        }
+    }
+
+    #[test]
+    fn bounds_out_of_view_are_culled() {
+        let mut camera = Camera::new_orbital(
+            Vec3::ZERO,
+            20.0,
+            CameraRotation {
+                yaw: 0.0,
+                pitch: 0.0,
+                roll: 0.0,
+            },
+            ProjectionParameters::Perspective { fov: 45.0 },
+            Some(Size {
+                width: 512,
+                height: 512,
+            }),
+        );
+
+        let cube = |center: Vec3| PartBounds::new(center - Vec3::ONE, center + Vec3::ONE);
+        let behind = camera.get_world_position() * 2.0;
+
+        assert!(camera.can_see(cube(Vec3::ZERO)));
+        assert!(!camera.can_see(cube(Vec3::new(100.0, 0.0, 0.0))));
+        assert!(!camera.can_see(cube(Vec3::new(0.0, -100.0, 0.0))));
+        assert!(!camera.can_see(cube(behind)));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::high_level::{
+    animation::{insert_keyframe, playback_time, sample_keyframes, Easing, Keyframed},
+    camera::{Camera, CameraPositionParameters, CameraRotation},
+};
+
+/// Where a camera is, where it's looking, and how wide it sees, at one point of a [`CameraTrack`].
+#[derive(Debug, Copy, Clone)]
+pub struct CameraPose {
+    pub position: CameraPositionParameters,
+    pub rotation: CameraRotation,
+    /// The field of view of perspective cameras (in degrees), or [`None`] to keep the camera's.
+    pub fov: Option<f32>,
+}
+
+impl CameraPose {
+    pub fn new(
+        position: CameraPositionParameters,
+        rotation: CameraRotation,
+        fov: Option<f32>,
+    ) -> Self {
+        Self {
+            position,
+            rotation,
+            fov,
+        }
+    }
+
+    /// Interpolates between two poses.
+    ///
+    /// Rotations aren't wrapped around, so going from a yaw of 0 to 360 makes a full turn.
+    /// Orbital poses keep orbiting their (interpolated) look at point, while an absolute and an orbital pose
+    /// are interpolated between the absolute positions of both.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+
+        let rotation = CameraRotation {
+            yaw: lerp(self.rotation.yaw, other.rotation.yaw),
+            pitch: lerp(self.rotation.pitch, other.rotation.pitch),
+            roll: lerp(self.rotation.roll, other.rotation.roll),
+        };
+
+        let position = match (self.position, other.position) {
+            (
+                CameraPositionParameters::Orbital { look_at, distance },
+                CameraPositionParameters::Orbital {
+                    look_at: other_look_at,
+                    distance: other_distance,
+                },
+            ) => CameraPositionParameters::Orbital {
+                look_at: look_at.lerp(other_look_at, t),
+                distance: lerp(distance, other_distance),
+            },
+            (position, other_position) => {
+                let absolute = |position: CameraPositionParameters, rotation: CameraRotation| {
+                    position
+                        .to_absolute(rotation.yaw, rotation.pitch)
+                        .get_position()
+                        .unwrap_or_default()
+                };
+
+                CameraPositionParameters::Absolute(
+                    absolute(position, self.rotation)
+                        .lerp(absolute(other_position, other.rotation), t),
+                )
+            }
+        };
+
+        let fov = match (self.fov, other.fov) {
+            (Some(fov), Some(other_fov)) => Some(lerp(fov, other_fov)),
+            (fov, other_fov) => other_fov.or(fov),
+        };
+
+        Self {
+            position,
+            rotation,
+            fov,
+        }
+    }
+
+    /// Moves the camera to this pose.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_position_parameters(self.position);
+        camera.set_rotation(self.rotation);
+
+        if let Some(fov) = self.fov {
+            camera.set_fov(fov);
+        }
+    }
+}
+
+/// A camera pose at a given point in time.
+#[derive(Debug, Copy, Clone)]
+pub struct CameraKeyframe {
+    /// The time of this keyframe, in seconds.
+    pub time: f32,
+    pub pose: CameraPose,
+    /// The easing used to get from the previous keyframe to this one.
+    pub easing: Easing,
+}
+
+impl CameraKeyframe {
+    pub fn new(time: f32, pose: CameraPose, easing: Easing) -> Self {
+        Self { time, pose, easing }
+    }
+}
+
+impl Keyframed for CameraKeyframe {
+    type Value = CameraPose;
+
+    fn time(&self) -> f32 {
+        self.time
+    }
+
+    fn easing(&self) -> Easing {
+        self.easing
+    }
+
+    fn value(&self) -> CameraPose {
+        self.pose
+    }
+
+    fn lerp(&self, next: &Self, t: f32) -> CameraPose {
+        self.pose.lerp(&next.pose, t)
+    }
+}
+
+/// A keyframed path for the camera to follow, like a turntable or a fly-around.
+#[derive(Debug, Clone, Default)]
+pub struct CameraTrack {
+    duration: f32,
+    looping: bool,
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraTrack {
+    pub fn new(duration: f32, looping: bool) -> Self {
+        Self {
+            duration,
+            looping,
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    /// Adds a keyframe, keeping the track sorted by time.
+    pub fn add_keyframe(&mut self, keyframe: CameraKeyframe) {
+        insert_keyframe(&mut self.keyframes, keyframe);
+    }
+
+    pub fn with_keyframe(mut self, keyframe: CameraKeyframe) -> Self {
+        self.add_keyframe(keyframe);
+        self
+    }
+
+    /// Computes the pose of the camera at the given time, in seconds, if the track has any keyframes.
+    ///
+    /// Looping tracks wrap around their duration, while the others hold their last pose.
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let time = playback_time(time, self.duration, self.looping);
+
+        sample_keyframes(&self.keyframes, time)
+    }
+
+    /// The times of `frame_count` frames spread evenly along the track.
+    ///
+    /// Looping tracks leave out the end, since it's the same as the start,
+    /// while the others end on their last pose.
+    pub fn frame_times(&self, frame_count: usize) -> impl Iterator<Item = f32> + '_ {
+        let steps = if self.looping {
+            frame_count
+        } else {
+            frame_count.saturating_sub(1)
+        }
+        .max(1);
+
+        (0..frame_count).map(move |frame| self.duration * frame as f32 / steps as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    fn pose(yaw: f32, distance: f32) -> CameraPose {
+        CameraPose::new(
+            CameraPositionParameters::Orbital {
+                look_at: Vec3::ZERO,
+                distance,
+            },
+            CameraRotation {
+                yaw,
+                pitch: 0.0,
+                roll: 0.0,
+            },
+            None,
+        )
+    }
+
+    fn turntable() -> CameraTrack {
+        CameraTrack::new(4.0, true)
+            .with_keyframe(CameraKeyframe::new(0.0, pose(0.0, 40.0), Easing::Linear))
+            .with_keyframe(CameraKeyframe::new(4.0, pose(360.0, 40.0), Easing::Linear))
+    }
+
+    #[test]
+    fn rotations_are_not_wrapped() {
+        let sampled = turntable().sample(1.0).unwrap();
+
+        assert_eq!(sampled.rotation.yaw, 90.0);
+        assert_eq!(sampled.position.get_distance(), Some(40.0));
+    }
+
+    #[test]
+    fn looping_frames_leave_out_the_end() {
+        let times = turntable().frame_times(4).collect::<Vec<_>>();
+
+        assert_eq!(times, vec![0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn non_looping_frames_end_on_the_last_pose() {
+        let track = CameraTrack::new(2.0, false)
+            .with_keyframe(CameraKeyframe::new(0.0, pose(0.0, 20.0), Easing::Linear))
+            .with_keyframe(CameraKeyframe::new(2.0, pose(0.0, 40.0), Easing::EaseInOut));
+
+        let times = track.frame_times(3).collect::<Vec<_>>();
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+
+        let last = track.sample(2.0).unwrap();
+        assert_eq!(last.position.get_distance(), Some(40.0));
+    }
+}
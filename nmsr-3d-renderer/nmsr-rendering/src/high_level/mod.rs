@@ -1,6 +1,9 @@
+pub mod animation;
 #[cfg(feature = "pipeline")]
 pub mod camera;
 #[cfg(feature = "pipeline")]
+pub mod camera_track;
+#[cfg(feature = "pipeline")]
 pub mod pipeline;
 pub mod utils;
 
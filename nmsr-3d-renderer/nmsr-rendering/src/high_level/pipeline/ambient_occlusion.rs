@@ -0,0 +1,166 @@
+use std::{borrow::Cow, mem};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, BufferSize, ColorTargetState, ColorWrites, Device, FragmentState,
+    MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, SamplerBindingType, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+/// How the creases between parts (and between parts and their layers) are darkened,
+/// when screen-space ambient occlusion is enabled on a scene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AmbientOcclusionSettings {
+    /// How many samples are taken around each pixel. More samples give smoother, but slower, occlusion.
+    pub sample_count: u32,
+    /// How far away (in pixels of the skin) surfaces can be to occlude each other.
+    pub radius: f32,
+    /// How dark the occluded creases get.
+    pub intensity: f32,
+    /// How far a surface needs to stick out before it occludes, to keep flat surfaces from occluding themselves.
+    pub bias: f32,
+}
+
+impl Default for AmbientOcclusionSettings {
+    fn default() -> Self {
+        Self {
+            sample_count: 16,
+            radius: 1.5,
+            intensity: 0.75,
+            bias: 0.05,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub(crate) struct AmbientOcclusionParameters {
+    view_projection: Mat4,
+    inverse_view_projection: Mat4,
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    sample_count: u32,
+}
+
+impl AmbientOcclusionParameters {
+    pub(crate) fn new(settings: &AmbientOcclusionSettings, view_projection: Mat4) -> Self {
+        Self {
+            view_projection,
+            inverse_view_projection: view_projection.inverse(),
+            radius: settings.radius,
+            intensity: settings.intensity,
+            bias: settings.bias,
+            sample_count: settings.sample_count,
+        }
+    }
+}
+
+/// The pipeline of the ambient occlusion pass, which reads the depth of the scene on top of its colors.
+#[derive(Debug)]
+pub(crate) struct AmbientOcclusionPipeline {
+    pub(crate) bind_group_layout: BindGroupLayout,
+    pub(crate) pipeline: RenderPipeline,
+}
+
+impl AmbientOcclusionPipeline {
+    pub(crate) fn new(device: &Device, texture_format: TextureFormat, sample_count: u32) -> Self {
+        let multisampled = sample_count > 1;
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            mem::size_of::<AmbientOcclusionParameters>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ambient Occlusion Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // The depth texture is multisampled along with the scene, which changes how the shader has to read it.
+        let depth_texture_type = if multisampled {
+            "texture_depth_multisampled_2d"
+        } else {
+            "texture_depth_2d"
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Ambient Occlusion Shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                include_str!("ambient_occlusion.wgsl")
+                    .replace("DEPTH_TEXTURE_TYPE", depth_texture_type),
+            )),
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Ambient Occlusion Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: texture_format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
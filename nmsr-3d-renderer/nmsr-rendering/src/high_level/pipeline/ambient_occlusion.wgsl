@@ -0,0 +1,113 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+struct AmbientOcclusionParameters {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    sample_count: u32,
+}
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var source_sampler: sampler;
+
+@group(0)
+@binding(2)
+var depth: DEPTH_TEXTURE_TYPE;
+
+@group(0)
+@binding(3)
+var<uniform> parameters: AmbientOcclusionParameters;
+
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    result.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    result.tex_coord = uv;
+    return result;
+}
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth));
+    return textureLoad(depth, clamp(pixel, vec2<i32>(0), size - vec2<i32>(1)), 0);
+}
+
+// Turns a pixel and its depth back into the position it was rendered from.
+fn reconstruct_position(pixel: vec2<i32>, pixel_depth: f32) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(depth));
+    let uv = (vec2<f32>(pixel) + vec2<f32>(0.5)) / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, pixel_depth, 1.0);
+    let position = parameters.inverse_view_projection * ndc;
+    return position.xyz / position.w;
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(source, source_sampler, vertex.tex_coord, 0.0);
+
+    let size = vec2<f32>(textureDimensions(depth));
+    let pixel = vec2<i32>(vertex.tex_coord * size);
+    let center_depth = load_depth(pixel);
+
+    // Nothing was drawn here, so there's nothing to occlude.
+    if (center_depth >= 1.0) {
+        return color;
+    }
+
+    let position = reconstruct_position(pixel, center_depth);
+    let right = reconstruct_position(pixel + vec2<i32>(1, 0), load_depth(pixel + vec2<i32>(1, 0)));
+    let down = reconstruct_position(pixel + vec2<i32>(0, 1), load_depth(pixel + vec2<i32>(0, 1)));
+    var normal = normalize(cross(down - position, right - position));
+
+    // Make sure the normal faces the camera, whichever way the neighbouring pixels were picked.
+    let toward_camera = reconstruct_position(pixel, 0.0) - position;
+    if (dot(normal, toward_camera) < 0.0) {
+        normal = -normal;
+    }
+
+    // Work out how big the radius is on screen at this depth.
+    let center_clip = parameters.view_projection * vec4<f32>(position, 1.0);
+    let screen_right = normalize(right - position);
+    let edge_clip = parameters.view_projection * vec4<f32>(position + screen_right * parameters.radius, 1.0);
+    let radius_pixels = max(length((edge_clip.xy / edge_clip.w - center_clip.xy / center_clip.w) * size * 0.5), 1.0);
+
+    var occlusion: f32 = 0.0;
+    let sample_count = max(parameters.sample_count, 1u);
+
+    for (var i = 0u; i < sample_count; i++) {
+        // Spread the samples over a disc in a spiral, so they cover it evenly.
+        let progress = (f32(i) + 0.5) / f32(sample_count);
+        let angle = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(angle), sin(angle)) * progress * radius_pixels;
+
+        let sample_pixel = pixel + vec2<i32>(offset);
+        let sample_depth = load_depth(sample_pixel);
+
+        if (sample_depth >= 1.0) {
+            continue;
+        }
+
+        let difference = reconstruct_position(sample_pixel, sample_depth) - position;
+        let distance_squared = dot(difference, difference);
+
+        if (distance_squared < parameters.radius * parameters.radius) {
+            occlusion += max(dot(difference, normal) - parameters.bias, 0.0) / (distance_squared + 0.01);
+        }
+    }
+
+    let ambient_occlusion = clamp(1.0 - parameters.intensity * occlusion / f32(sample_count), 0.0, 1.0);
+
+    return vec4<f32>(color.rgb * ambient_occlusion, color.a);
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+use nmsr_player_parts::types::PlayerPartTextureType;
+use wgpu::{
+    CommandEncoderDescriptor, Extent3d, ImageCopyTexture, Origin3d, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureUsages, TextureView,
+};
+
+use super::{scene::TextureRegion, textures::SceneTexture, GraphicsContext};
+
+/// Several textures of a scene packed into a single one, so that their parts can all be drawn with one bind group.
+#[derive(Debug)]
+pub(crate) struct TextureAtlas {
+    texture: SceneTexture,
+    regions: HashMap<PlayerPartTextureType, TextureRegion>,
+}
+
+impl TextureAtlas {
+    /// Packs the given textures into a new atlas.
+    ///
+    /// Returns [`None`] when there's nothing to gain from an atlas (less than two textures),
+    /// or when the textures don't fit in a single texture on this device.
+    pub(crate) fn new<'a>(
+        graphics_context: &GraphicsContext,
+        textures: impl Iterator<Item = (&'a PlayerPartTextureType, &'a SceneTexture)>,
+    ) -> Option<Self> {
+        let textures = textures.collect::<Vec<_>>();
+
+        if textures.len() < 2 {
+            return None;
+        }
+
+        let sizes = textures
+            .iter()
+            .map(|(_, t)| (t.texture.width(), t.texture.height()))
+            .collect::<Vec<_>>();
+
+        let ((width, height), origins) = pack(&sizes);
+
+        let max_dimension = graphics_context.device.limits().max_texture_dimension_2d;
+        if width > max_dimension || height > max_dimension {
+            return None;
+        }
+
+        // All the scene textures are uploaded in the same format, so they can be copied over as-is.
+        let texture = graphics_context.device.create_texture(&TextureDescriptor {
+            label: Some("Texture Atlas"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: textures[0].1.texture.format(),
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+
+        let regions = textures
+            .iter()
+            .zip(origins.into_iter().zip(sizes))
+            .map(|((texture_type, _), ((x, y), (width, height)))| {
+                let region = TextureRegion {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+
+                (**texture_type, region)
+            })
+            .collect();
+
+        let atlas = Self {
+            texture: SceneTexture { texture, view },
+            regions,
+        };
+
+        for (texture_type, source) in textures {
+            let (width, height) = (source.texture.width(), source.texture.height());
+
+            atlas.copy_region(
+                graphics_context,
+                *texture_type,
+                source,
+                TextureRegion {
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                },
+            );
+        }
+
+        Some(atlas)
+    }
+
+    pub(crate) fn view(&self) -> &TextureView {
+        &self.texture.view
+    }
+
+    pub(crate) fn contains(&self, texture_type: PlayerPartTextureType) -> bool {
+        self.regions.contains_key(&texture_type)
+    }
+
+    /// The offset and scale that move uv coordinates of the given texture into its place in the atlas.
+    pub(crate) fn get_uv_transform(
+        &self,
+        texture_type: PlayerPartTextureType,
+    ) -> Option<(Vec2, Vec2)> {
+        let region = self.regions.get(&texture_type)?;
+        let size = Vec2::new(
+            self.texture.texture.width() as f32,
+            self.texture.texture.height() as f32,
+        );
+
+        let offset = Vec2::new(region.x as f32, region.y as f32) / size;
+        let scale = Vec2::new(region.width as f32, region.height as f32) / size;
+
+        Some((offset, scale))
+    }
+
+    /// Copies a region of one of the textures of the atlas over to its place in the atlas,
+    /// to keep it up to date when the texture changes.
+    pub(crate) fn copy_region(
+        &self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        source: &SceneTexture,
+        region: TextureRegion,
+    ) {
+        let Some(destination) = self.regions.get(&texture_type) else {
+            return;
+        };
+
+        let mut encoder =
+            graphics_context
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Texture Atlas copy"),
+                });
+
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &source.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: &self.texture.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: destination.x + region.x,
+                    y: destination.y + region.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// Packs rectangles of the given sizes into shelves, tallest first.
+///
+/// Returns the size of the atlas and the origin of every rectangle, in the same order as the sizes.
+fn pack(sizes: &[(u32, u32)]) -> ((u32, u32), Vec<(u32, u32)>) {
+    let total_area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    let widest = sizes.iter().map(|&(w, _)| w).max().unwrap_or(0);
+
+    // Aim for a roughly square atlas, in steps of the widest texture so that the usual
+    // 64 pixel wide textures fill their shelves.
+    let columns = ((total_area as f64).sqrt() / widest.max(1) as f64)
+        .ceil()
+        .max(1.0) as u32;
+    let width = widest * columns;
+
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut origins = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+    for i in order {
+        let (w, h) = sizes[i];
+
+        if x + w > width {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+
+        origins[i] = (x, y);
+        x += w;
+        shelf_height = shelf_height.max(h);
+    }
+
+    ((width, y + shelf_height), origins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pack;
+
+    #[test]
+    fn packed_rectangles_do_not_overlap() {
+        let sizes = [(64, 32), (64, 64), (64, 32), (64, 64), (22, 17)];
+        let ((width, height), origins) = pack(&sizes);
+
+        let rects = sizes
+            .iter()
+            .zip(&origins)
+            .map(|(&(w, h), &(x, y))| (x, y, x + w, y + h))
+            .collect::<Vec<_>>();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.2 <= width && a.3 <= height, "{a:?} is out of the atlas");
+
+            for b in &rects[i + 1..] {
+                let overlaps = a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3;
+                assert!(!overlaps, "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn skin_and_cape_share_a_square_atlas() {
+        let ((width, height), origins) = pack(&[(64, 64), (64, 32), (64, 32)]);
+
+        assert_eq!((width, height), (128, 96));
+        assert_eq!(origins, vec![(0, 0), (64, 0), (0, 64)]);
+    }
+}
@@ -0,0 +1,310 @@
+use std::{borrow::Cow, mem};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use image::RgbaImage;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferSize, BufferUsages, Color, ColorTargetState, ColorWrites, Device,
+    FilterMode, FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState,
+    RenderPass, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat,
+    TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use super::{textures::SceneTexture, ColorSpace, GraphicsContext, SceneContext};
+
+/// What is drawn behind the player.
+#[derive(Debug, Clone, Default)]
+pub enum SceneBackground {
+    /// Nothing, the background is left transparent.
+    #[default]
+    Transparent,
+    /// A solid RGBA color.
+    Color([u8; 4]),
+    /// A solid RGB key color (like a green screen), for compositing in tools that don't support alpha.
+    ///
+    /// The key color is filled in when the output is copied, so the render keeps its coverage,
+    /// which [`Scene::copy_coverage_mask`](super::scene::Scene::copy_coverage_mask) can read back separately.
+    ChromaKey([u8; 3]),
+    /// An image stretched over the whole render, like a billboard behind the player.
+    Image(RgbaImage),
+    /// An equirectangular panorama surrounding the player, which moves along with the camera
+    /// (like the Minecraft main menu panorama).
+    Panorama(RgbaImage),
+}
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+struct BackgroundInformation {
+    inverse_view_projection: Mat4,
+}
+
+/// The pipelines used to draw image and panorama backgrounds, shared by all the scenes of a [`GraphicsContext`].
+#[derive(Debug)]
+pub struct BackgroundPipelines {
+    bind_group_layout: BindGroupLayout,
+    image_sampler: Sampler,
+    panorama_sampler: Sampler,
+    image_pipeline: RenderPipeline,
+    panorama_pipeline: RenderPipeline,
+}
+
+impl BackgroundPipelines {
+    pub(crate) fn new(device: &Device, texture_format: TextureFormat, sample_count: u32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Background Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            mem::size_of::<BackgroundInformation>() as u64
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Background Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("background.wgsl"))),
+        });
+
+        // The background is drawn into the same (possibly multisampled) texture as the parts.
+        let create_pipeline = |label, entry_point| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format: texture_format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+        };
+
+        let create_sampler = |label, address_mode_u| {
+            device.create_sampler(&SamplerDescriptor {
+                label: Some(label),
+                address_mode_u,
+                address_mode_v: AddressMode::ClampToEdge,
+                address_mode_w: AddressMode::ClampToEdge,
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                ..Default::default()
+            })
+        };
+
+        Self {
+            image_pipeline: create_pipeline("Background Image Pipeline", "fs_image"),
+            panorama_pipeline: create_pipeline("Background Panorama Pipeline", "fs_panorama"),
+            image_sampler: create_sampler("Background Image Sampler", AddressMode::ClampToEdge),
+            // Panoramas wrap around horizontally
+            panorama_sampler: create_sampler("Background Panorama Sampler", AddressMode::Repeat),
+            bind_group_layout,
+        }
+    }
+}
+
+/// A [`SceneBackground`] with its image (if any) uploaded, ready to be drawn.
+#[derive(Debug, Default)]
+pub(crate) enum PreparedBackground {
+    #[default]
+    Transparent,
+    Color(Color),
+    ChromaKey([u8; 3]),
+    Textured {
+        is_panorama: bool,
+        // Kept alive for as long as the bind group uses it
+        _texture: SceneTexture,
+        information_buffer: Buffer,
+        bind_group: BindGroup,
+    },
+}
+
+impl PreparedBackground {
+    pub(crate) fn new(graphics_context: &GraphicsContext, background: &SceneBackground) -> Self {
+        let (image, is_panorama) = match background {
+            SceneBackground::Transparent => return Self::Transparent,
+            SceneBackground::ChromaKey(color) => return Self::ChromaKey(*color),
+            SceneBackground::Color(color) => {
+                return Self::Color(Self::convert_color(graphics_context, *color))
+            }
+            SceneBackground::Image(image) => (image, false),
+            SceneBackground::Panorama(image) => (image, true),
+        };
+
+        let device = &graphics_context.device;
+        let pipelines = graphics_context.get_background_pipelines();
+
+        let texture = SceneContext::upload_texture(graphics_context, image, Some("Background"));
+
+        let information_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Background Information Buffer"),
+            contents: bytemuck::cast_slice(&[BackgroundInformation {
+                inverse_view_projection: Mat4::IDENTITY,
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let sampler = if is_panorama {
+            &pipelines.panorama_sampler
+        } else {
+            &pipelines.image_sampler
+        };
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Background Bind Group"),
+            layout: &pipelines.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: information_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self::Textured {
+            is_panorama,
+            _texture: texture,
+            information_buffer,
+            bind_group,
+        }
+    }
+
+    /// Converts an RGBA color to the premultiplied (and, when lighting in linear space, linear) color
+    /// the render is cleared with.
+    fn convert_color(graphics_context: &GraphicsContext, [r, g, b, a]: [u8; 4]) -> Color {
+        let is_linear = graphics_context.color_space == ColorSpace::Linear;
+        let alpha = a as f64 / 255.0;
+
+        let convert = |channel: u8| {
+            let channel = channel as f64 / 255.0;
+            let channel = if !is_linear {
+                channel
+            } else if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            };
+
+            channel * alpha
+        };
+
+        Color {
+            r: convert(r),
+            g: convert(g),
+            b: convert(b),
+            a: alpha,
+        }
+    }
+
+    /// Whether nothing is drawn behind the player, which is also the case for chroma keys until the output is copied.
+    pub(crate) fn is_transparent(&self) -> bool {
+        matches!(self, Self::Transparent | Self::ChromaKey(_))
+    }
+
+    /// The key color filled in behind the player when the output is copied, if any.
+    pub(crate) fn chroma_key(&self) -> Option<[u8; 3]> {
+        match self {
+            Self::ChromaKey(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    /// The color the render is cleared with before the background is drawn.
+    pub(crate) fn clear_color(&self) -> Color {
+        match self {
+            Self::Color(color) => *color,
+            _ => Color::TRANSPARENT,
+        }
+    }
+
+    /// Updates the background to match the camera the scene is about to be rendered with.
+    pub(crate) fn prepare(&self, graphics_context: &GraphicsContext, view_projection: Mat4) {
+        if let Self::Textured {
+            information_buffer, ..
+        } = self
+        {
+            graphics_context.queue.write_buffer(
+                information_buffer,
+                0,
+                bytemuck::cast_slice(&[BackgroundInformation {
+                    inverse_view_projection: view_projection.inverse(),
+                }]),
+            );
+        }
+    }
+
+    pub(crate) fn draw<'a>(&'a self, pipelines: &'a BackgroundPipelines, rpass: &mut RenderPass<'a>) {
+        if let Self::Textured {
+            is_panorama,
+            bind_group,
+            ..
+        } = self
+        {
+            let pipeline = if *is_panorama {
+                &pipelines.panorama_pipeline
+            } else {
+                &pipelines.image_pipeline
+            };
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
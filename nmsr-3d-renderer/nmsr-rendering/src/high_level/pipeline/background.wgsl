@@ -0,0 +1,54 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+struct BackgroundInformation {
+    inverse_view_projection: mat4x4<f32>,
+}
+
+@group(0)
+@binding(0)
+var background: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var background_sampler: sampler;
+
+@group(0)
+@binding(2)
+var<uniform> information: BackgroundInformation;
+
+const PI: f32 = 3.14159265;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    result.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    result.tex_coord = uv;
+    return result;
+}
+
+// Stretches the image over the whole render.
+@fragment
+fn fs_image(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSampleLevel(background, background_sampler, vertex.tex_coord, 0.0);
+}
+
+// Looks up the direction the camera is looking at through this pixel in an equirectangular panorama.
+@fragment
+fn fs_panorama(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let ndc = vec2<f32>(vertex.tex_coord.x * 2.0 - 1.0, 1.0 - vertex.tex_coord.y * 2.0);
+
+    let near = information.inverse_view_projection * vec4<f32>(ndc, 0.0, 1.0);
+    let far = information.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w - near.xyz / near.w);
+
+    let uv = vec2<f32>(
+        atan2(direction.x, -direction.z) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+
+    return textureSampleLevel(background, background_sampler, uv, 0.0);
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+use wgpu::{TextureFormat, TextureUsages};
+
+use super::{GraphicsContext, MultiSamplingStrategy};
+
+/// The sample counts we're willing to use for multisampling.
+const CANDIDATE_SAMPLE_COUNTS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// The texture formats we're able to render to and read back from.
+const CANDIDATE_OUTPUT_FORMATS: [TextureFormat; 5] = [
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::Rgba16Float,
+];
+
+/// A description of what the renderer is able to do on the current adapter.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphicsContextCapabilities {
+    /// The graphics backend in use (e.g. vulkan, metal, dx12).
+    pub backend: &'static str,
+    /// Information about the adapter used for rendering.
+    pub adapter: AdapterCapabilities,
+    /// The preferred backends that were skipped because none of their adapters fit, in order.
+    pub skipped_backends: Vec<&'static str>,
+    /// The maximum width and height of a texture, which also limits the size of a render.
+    pub max_texture_size: u32,
+    /// The MSAA sample counts supported by the output texture format.
+    pub supported_sample_counts: Vec<u32>,
+    /// The MSAA sample count in use.
+    pub sample_count: u32,
+    /// Whether SMAA is in use.
+    pub smaa: bool,
+    /// The texture formats that can be used for the output of a render.
+    pub supported_output_formats: Vec<String>,
+    /// The texture format in use for the output of a render.
+    pub output_format: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdapterCapabilities {
+    pub name: String,
+    pub vendor: u32,
+    pub device: u32,
+    pub device_type: String,
+    pub driver: String,
+    pub driver_info: String,
+}
+
+impl GraphicsContext {
+    pub fn capabilities(&self) -> GraphicsContextCapabilities {
+        let info = self.adapter.get_info();
+
+        let sample_flags = self
+            .adapter
+            .get_texture_format_features(self.texture_format)
+            .flags;
+
+        let supported_sample_counts = CANDIDATE_SAMPLE_COUNTS
+            .into_iter()
+            .filter(|&count| sample_flags.sample_count_supported(count))
+            .collect();
+
+        let supported_output_formats = CANDIDATE_OUTPUT_FORMATS
+            .into_iter()
+            .filter(|&format| {
+                let usages = self
+                    .adapter
+                    .get_texture_format_features(format)
+                    .allowed_usages;
+                usages.contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC)
+            })
+            .map(|format| format!("{format:?}"))
+            .collect();
+
+        GraphicsContextCapabilities {
+            backend: info.backend.to_str(),
+            skipped_backends: self
+                .skipped_backends
+                .iter()
+                .map(|backend| backend.to_str())
+                .collect(),
+            adapter: AdapterCapabilities {
+                name: info.name,
+                vendor: info.vendor,
+                device: info.device,
+                device_type: format!("{:?}", info.device_type),
+                driver: info.driver,
+                driver_info: info.driver_info,
+            },
+            max_texture_size: self.device.limits().max_texture_dimension_2d,
+            supported_sample_counts,
+            sample_count: self.multisampling_strategy.get_msaa_sample_count(),
+            smaa: !matches!(self.multisampling_strategy, MultiSamplingStrategy::MSAA(_)),
+            supported_output_formats,
+            output_format: format!("{:?}", self.texture_format),
+        }
+    }
+}
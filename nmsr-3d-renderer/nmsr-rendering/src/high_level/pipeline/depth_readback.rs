@@ -0,0 +1,298 @@
+use std::{borrow::Cow, mem};
+
+use bytemuck::{Pod, Zeroable};
+use tracing::trace_span;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferSize, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CommandEncoderDescriptor, Extent3d, FragmentState, LoadOp, MultisampleState, Operations,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StoreOp, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDimension,
+    VertexState,
+};
+
+use super::{
+    scene::Size,
+    textures::{create_texture, BufferDimensions, SceneTexture},
+    GraphicsContext,
+};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::utils::buffer::read_buffer,
+};
+
+/// The format depth is read back in, one 32-bit float per pixel.
+const DEPTH_READBACK_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+struct DepthReadbackParameters {
+    supersampling: u32,
+    _padding: [u32; 3],
+}
+
+/// Copies the depth of renders into a buffer, for it to be read back along with the colors.
+///
+/// The depth texture itself can't be copied when it's multisampled or supersampled,
+/// so it's first resolved into a single float per output pixel.
+#[derive(Debug, Default)]
+pub(crate) struct DepthReadback {
+    resources: Option<DepthReadbackResources>,
+}
+
+#[derive(Debug)]
+struct DepthReadbackResources {
+    output_size: Size,
+    viewport_size: Size,
+    multisampled: bool,
+    bind_group_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    texture: SceneTexture,
+    buffer: Buffer,
+    dimensions: BufferDimensions,
+}
+
+impl DepthReadback {
+    /// Makes sure the resources needed to read back depth exist, and match the render.
+    /// They're dropped when depth isn't read back anymore.
+    pub(crate) fn prepare(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        enabled: bool,
+        output_size: Size,
+        viewport_size: Size,
+        multisampled: bool,
+    ) {
+        if !enabled {
+            self.resources = None;
+            return;
+        }
+
+        if self.resources.as_ref().is_some_and(|r| {
+            r.output_size == output_size
+                && r.viewport_size == viewport_size
+                && r.multisampled == multisampled
+        }) {
+            return;
+        }
+
+        let _guard = trace_span!("create_depth_readback").entered();
+        let device = &graphics_context.device;
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Depth Readback Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            mem::size_of::<DepthReadbackParameters>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Depth Readback Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let depth_texture_type = if multisampled {
+            "texture_depth_multisampled_2d"
+        } else {
+            "texture_depth_2d"
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Depth Readback Shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                include_str!("depth_readback.wgsl")
+                    .replace("DEPTH_TEXTURE_TYPE", depth_texture_type),
+            )),
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Depth Readback Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: DEPTH_READBACK_FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        let texture = create_texture(
+            graphics_context,
+            output_size.width,
+            output_size.height,
+            DEPTH_READBACK_FORMAT,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            Some("Depth Readback Texture"),
+            1,
+        );
+
+        let dimensions = BufferDimensions::new(
+            viewport_size.width as usize,
+            viewport_size.height as usize,
+            mem::size_of::<f32>(),
+        );
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: dimensions.size(),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        self.resources = Some(DepthReadbackResources {
+            output_size,
+            viewport_size,
+            multisampled,
+            bind_group_layout,
+            pipeline,
+            texture,
+            buffer,
+            dimensions,
+        });
+    }
+
+    /// Resolves the depth of the render and copies it into the readback buffer, if depth is being read back.
+    pub(crate) fn resolve(
+        &self,
+        graphics_context: &GraphicsContext,
+        depth: &TextureView,
+        supersampling: u32,
+    ) {
+        let Some(resources) = &self.resources else {
+            return;
+        };
+
+        let device = &graphics_context.device;
+
+        let parameters_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Depth Readback Parameters Buffer"),
+            contents: bytemuck::cast_slice(&[DepthReadbackParameters {
+                supersampling,
+                _padding: [0; 3],
+            }]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Depth Readback Bind Group"),
+            layout: &resources.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(depth),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Depth Readback"),
+        });
+
+        {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Depth Readback pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &resources.texture.view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::WHITE),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            rpass.set_pipeline(&resources.pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &resources.texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &resources.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(resources.dimensions.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: resources.viewport_size.width,
+                height: resources.viewport_size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Reads back the depth of the last render, row by row.
+    pub(crate) async fn read(&self, graphics_context: &GraphicsContext) -> Result<Vec<f32>> {
+        let resources = self
+            .resources
+            .as_ref()
+            .ok_or(NMSRRenderingError::DepthReadbackNotEnabled)?;
+
+        let bytes = read_buffer(
+            &graphics_context.device,
+            &resources.buffer,
+            &resources.dimensions,
+            false,
+        )
+        .await?;
+
+        Ok(bytes
+            .chunks_exact(mem::size_of::<f32>())
+            .map(|chunk| f32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect())
+    }
+}
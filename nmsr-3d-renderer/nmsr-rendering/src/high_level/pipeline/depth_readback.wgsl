@@ -0,0 +1,34 @@
+struct DepthReadbackParameters {
+    supersampling: u32,
+}
+
+@group(0)
+@binding(0)
+var depth: DEPTH_TEXTURE_TYPE;
+
+@group(0)
+@binding(1)
+var<uniform> parameters: DepthReadbackParameters;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Keeps the nearest depth of the render pixels that make up this output pixel.
+// Multisampled depth is read from its first sample, since samples can't be averaged meaningfully.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let factor = max(parameters.supersampling, 1u);
+    let origin = vec2<i32>(vec2<u32>(position.xy) * factor);
+
+    var nearest: f32 = 1.0;
+    for (var y = 0; y < i32(factor); y++) {
+        for (var x = 0; x < i32(factor); x++) {
+            nearest = min(nearest, textureLoad(depth, origin + vec2<i32>(x, y), 0));
+        }
+    }
+
+    return vec4<f32>(nearest, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use glam::Mat4;
+use nmsr_player_parts::{parts::part::Part, types::PlayerPartTextureType};
+
+use super::textures::SceneTexture;
+
+/// A model drawn in a scene alongside its player, like another player in a group shot, or a prop.
+///
+/// Entities have their own parts and textures, and are placed in the scene with their own transform.
+#[derive(Debug)]
+pub struct SceneEntity {
+    pub(crate) parts: Vec<Part>,
+    pub(crate) textures: HashMap<PlayerPartTextureType, SceneTexture>,
+    /// Where the entity is placed in the scene, applied on top of the parts' own transforms.
+    pub transform: Mat4,
+}
+
+impl SceneEntity {
+    pub(crate) fn new(parts: Vec<Part>, transform: Mat4) -> Self {
+        Self {
+            parts,
+            textures: HashMap::new(),
+            transform,
+        }
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn has_texture(&self, texture_type: PlayerPartTextureType) -> bool {
+        self.textures.contains_key(&texture_type)
+    }
+}
@@ -1,28 +1,50 @@
-use std::{borrow::Cow, env, mem, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env, mem,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+#[cfg(feature = "deadlines")]
+use std::time::Instant;
 
 use deadpool::managed::{Object, Pool};
 use smaa::SmaaMode;
+#[cfg(feature = "deadlines")]
+use tokio::sync::oneshot::channel;
+use tracing::{info, trace_span, warn};
 use wgpu::{
     vertex_attr_array, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
     BindingType, BufferAddress, BufferBindingType, BufferSize, ColorTargetState, ColorWrites,
-    CompareFunction, DepthStencilState, FragmentState, FrontFace, MultisampleState,
-    PipelineLayoutDescriptor, PresentMode, PrimitiveState, RenderPipeline,
-    RenderPipelineDescriptor, SamplerBindingType, ShaderModuleDescriptor, ShaderStages,
-    TextureSampleType, TextureViewDimension, VertexBufferLayout, VertexState,
+    CompareFunction, DepthStencilState, DeviceType, FragmentState, FrontFace, MultisampleState,
+    PipelineLayout, PipelineLayoutDescriptor, PresentMode, PrimitiveState, PrimitiveTopology,
+    RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, SamplerBindingType,
+    ShaderModule, ShaderModuleDescriptor, ShaderStages, TextureFormatFeatureFlags,
+    TextureSampleType, TextureUsages, TextureViewDimension, VertexAttribute, VertexBufferLayout,
+    VertexState,
 };
 pub use wgpu::{
-    Adapter, Backends, BlendState, Device, Features, Instance, Queue, ShaderSource, Surface,
-    SurfaceConfiguration, TextureFormat, Limits,
+    Adapter, Backend, Backends, BlendState, Device, Features, Instance, Limits, PowerPreference,
+    Queue, ShaderSource, Surface, SurfaceConfiguration, TextureFormat,
 };
 
+#[cfg(feature = "deadlines")]
+use crate::high_level::utils::buffer::wait_for_work;
 use crate::{
     errors::{NMSRRenderingError, Result},
     low_level::primitives::vertex::Vertex,
 };
 
 use super::{
+    background::BackgroundPipelines,
+    instancing::InstanceInformation,
+    materials::{DefaultMaterialMaps, MaterialInformation},
     pools::SceneContextPoolManager,
+    post_processing::PostProcessingPipelines,
     scene::{Size, SunInformation},
+    shader_hooks::SceneShader,
+    shadows::ShadowInformation,
+    textures::is_readable_output_format,
 };
 
 #[derive(Debug)]
@@ -33,13 +55,65 @@ pub struct GraphicsContext {
     pub surface: Option<Surface>,
     pub surface_config: Result<Option<SurfaceConfiguration>>,
     pub texture_format: TextureFormat,
+    /// The color space scenes are lit and blended in, which decides whether the texture format is sRGB.
+    pub color_space: ColorSpace,
     pub adapter: Adapter,
+    /// The preferred backends that were skipped (in order) because none of their adapters fit.
+    pub skipped_backends: Vec<Backend>,
 
-    pub pipeline: RenderPipeline,
+    /// The pipelines for the default sample count and format.
+    default_pipelines: Arc<OutputPipelines>,
+    /// The depth-only pipeline used to render shadow maps, created on first use.
+    shadow_pipeline: OnceLock<RenderPipeline>,
     pub layouts: GraphicsContextLayouts,
+    /// The normal and specular maps used for textures that don't have their own.
+    pub default_material_maps: DefaultMaterialMaps,
+    /// The shader and blend state of the main pipeline, to create it again for other sample counts.
+    shader: ShaderModule,
+    blend_state: Option<BlendState>,
+    /// The pipelines for the sample counts and formats scenes asked for instead of the default ones,
+    /// created on first use.
+    output_pipelines: RwLock<HashMap<(u32, TextureFormat), Arc<OutputPipelines>>>,
+    /// The pipelines drawing scenes as wireframes, per sample count and format, created on first use.
+    wireframe_pipelines: RwLock<HashMap<(u32, TextureFormat), Arc<RenderPipeline>>>,
+    /// The pipelines of the custom shaders scenes asked for, per sample count and format, created on first use.
+    scene_shader_pipelines: RwLock<HashMap<(SceneShader, u32, TextureFormat), Arc<RenderPipeline>>>,
     pub multisampling_strategy: MultiSamplingStrategy,
 }
 
+/// The color space the lighting and blending of a render happen in.
+///
+/// Both give the same colors for unlit parts, but lighting darkens and brightens them differently.
+/// Renders always come out sRGB-encoded, whichever color space is used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colors are used as they're stored in textures, in sRGB space, like the 2D renderer and the game do.
+    Srgb,
+    /// Textures are decoded to linear space, and the output is encoded back to sRGB.
+    Linear,
+}
+
+impl ColorSpace {
+    /// The color space a texture format is rendered in by default.
+    pub fn of_format(format: TextureFormat) -> Self {
+        if format.is_srgb() {
+            Self::Linear
+        } else {
+            Self::Srgb
+        }
+    }
+
+    /// The variant of a texture format that renders in this color space.
+    ///
+    /// Formats without an sRGB variant (like float formats) are kept as-is.
+    pub fn apply_to_format(&self, format: TextureFormat) -> TextureFormat {
+        match self {
+            Self::Srgb => format.remove_srgb_suffix(),
+            Self::Linear => format.add_srgb_suffix(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum MultiSamplingStrategy {
     MSAA(u32),
@@ -69,6 +143,9 @@ pub struct GraphicsContextLayouts {
     pub skin_sampler_bind_group_layout: BindGroupLayout,
     pub pipeline_layout: wgpu::PipelineLayout,
     pub sun_bind_group_layout: BindGroupLayout,
+    pub shadow_bind_group_layout: BindGroupLayout,
+    pub light_transform_bind_group_layout: BindGroupLayout,
+    pub shadow_pipeline_layout: PipelineLayout,
 }
 
 #[derive(Debug)]
@@ -90,17 +167,415 @@ impl GraphicsContextPools {
 
 impl GraphicsContext {
     pub fn get_pipeline(&self) -> &RenderPipeline {
-        &self.pipeline
+        &self.default_pipelines.pipeline
+    }
+
+    /// Returns the depth-only pipeline used to render shadow maps, creating it if needed.
+    pub fn get_shadow_pipeline(&self) -> &RenderPipeline {
+        self.shadow_pipeline.get_or_init(|| {
+            let _guard = trace_span!("create_shadow_pipeline").entered();
+
+            Self::create_shadow_pipeline(&self.device, &self.layouts.shadow_pipeline_layout)
+        })
+    }
+
+    /// Returns the background pipelines for the default sample count and format, creating them if needed.
+    pub(crate) fn get_background_pipelines(&self) -> &BackgroundPipelines {
+        self.default_pipelines.get_background(&self.device)
     }
+
+    /// Waits for the GPU to finish the work submitted so far (like renders that aren't read back),
+    /// giving up with [`NMSRRenderingError::RenderTimedOut`] if it isn't done by the deadline.
+    ///
+    /// Deadlines are ignored in browsers, which have no way to give up on the GPU.
+    #[cfg(feature = "deadlines")]
+    pub async fn wait_for_submitted_work_until(&self, deadline: Instant) -> Result<()> {
+        let (tx, rx) = channel();
+        self.queue.on_submitted_work_done(move || {
+            // Nobody is listening anymore if the caller gave up on waiting.
+            let _ = tx.send(());
+        });
+
+        wait_for_work(&self.device, rx, Some(deadline)).await
+    }
+
+    /// Returns whether scenes can be drawn with the given number of samples per pixel on this adapter.
+    pub fn is_sample_count_supported(&self, sample_count: u32) -> bool {
+        self.is_sample_count_supported_with(self.texture_format, sample_count)
+    }
+
+    /// Returns whether scenes can be drawn into the given output format with the given number of samples per pixel.
+    pub fn is_sample_count_supported_with(&self, format: TextureFormat, sample_count: u32) -> bool {
+        [format, Self::DEPTH_TEXTURE_FORMAT].iter().all(|&format| {
+            self.adapter
+                .get_texture_format_features(format)
+                .flags
+                .sample_count_supported(sample_count)
+        })
+    }
+
+    /// Returns whether scenes can be drawn into (and read back from) the given output format on this adapter.
+    ///
+    /// Post-processing samples and blends the output, so the format needs to be filterable and blendable too.
+    pub fn is_output_format_supported(&self, format: TextureFormat) -> bool {
+        let features = self.adapter.get_texture_format_features(format);
+
+        is_readable_output_format(format)
+            && features.allowed_usages.contains(
+                TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_SRC,
+            )
+            && features.flags.contains(
+                TextureFormatFeatureFlags::FILTERABLE | TextureFormatFeatureFlags::BLENDABLE,
+            )
+    }
+
+    /// Returns the pipelines for drawing scenes with the given sample count into the given format,
+    /// creating them if needed.
+    pub(crate) fn get_output_pipelines(
+        &self,
+        sample_count: u32,
+        format: TextureFormat,
+    ) -> Result<Arc<OutputPipelines>> {
+        if sample_count == self.multisampling_strategy.get_msaa_sample_count()
+            && format == self.texture_format
+        {
+            return Ok(self.default_pipelines.clone());
+        }
+
+        if format != self.texture_format && !self.is_output_format_supported(format) {
+            return Err(NMSRRenderingError::UnsupportedOutputFormat(format));
+        }
+
+        if !self.is_sample_count_supported_with(format, sample_count) {
+            return Err(NMSRRenderingError::UnsupportedSampleCount(sample_count));
+        }
+
+        let key = (sample_count, format);
+
+        if let Some(pipelines) = self
+            .output_pipelines
+            .read()
+            .expect("Output pipelines lock poisoned")
+            .get(&key)
+        {
+            return Ok(pipelines.clone());
+        }
+
+        let _guard = trace_span!("create_output_pipelines", sample_count, ?format).entered();
+
+        let pipeline = Self::create_scene_pipeline(
+            &self.device,
+            &self.layouts.pipeline_layout,
+            &self.shader,
+            format,
+            self.blend_state,
+            sample_count,
+            PrimitiveTopology::TriangleList,
+            "fs_main",
+        );
+        let pipelines = Arc::new(OutputPipelines::new(pipeline, format, sample_count));
+
+        self.output_pipelines
+            .write()
+            .expect("Output pipelines lock poisoned")
+            .insert(key, pipelines.clone());
+
+        Ok(pipelines)
+    }
+
+    /// Returns the pipeline drawing scenes with the given sample count and format as wireframes,
+    /// creating it if needed.
+    pub(crate) fn get_wireframe_pipeline(
+        &self,
+        sample_count: u32,
+        format: TextureFormat,
+    ) -> Arc<RenderPipeline> {
+        let key = (sample_count, format);
+
+        if let Some(pipeline) = self
+            .wireframe_pipelines
+            .read()
+            .expect("Wireframe pipelines lock poisoned")
+            .get(&key)
+        {
+            return pipeline.clone();
+        }
+
+        let _guard = trace_span!("create_wireframe_pipeline", sample_count).entered();
+
+        // Wireframes draw the edges of the triangles as lines, which works on every backend (unlike polygon modes).
+        let pipeline = Arc::new(Self::create_scene_pipeline(
+            &self.device,
+            &self.layouts.pipeline_layout,
+            &self.shader,
+            format,
+            self.blend_state,
+            sample_count,
+            PrimitiveTopology::LineList,
+            "fs_wireframe",
+        ));
+
+        self.wireframe_pipelines
+            .write()
+            .expect("Wireframe pipelines lock poisoned")
+            .insert(key, pipeline.clone());
+
+        pipeline
+    }
+
+    /// Returns the pipeline drawing scenes with a custom shader, the given sample count and format,
+    /// creating it if needed.
+    ///
+    /// Custom shaders are always based on the built-in scene shader, even if the context was created with another one.
+    pub(crate) fn get_scene_shader_pipeline(
+        &self,
+        shader: &SceneShader,
+        sample_count: u32,
+        format: TextureFormat,
+    ) -> Arc<RenderPipeline> {
+        let key = (shader.clone(), sample_count, format);
+
+        if let Some(pipeline) = self
+            .scene_shader_pipelines
+            .read()
+            .expect("Scene shader pipelines lock poisoned")
+            .get(&key)
+        {
+            return pipeline.clone();
+        }
+
+        let _guard = trace_span!("create_scene_shader_pipeline", sample_count).entered();
+
+        let module = self.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Custom Scene Shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(shader.source())),
+        });
+
+        let pipeline = Arc::new(Self::create_scene_pipeline(
+            &self.device,
+            &self.layouts.pipeline_layout,
+            &module,
+            format,
+            self.blend_state,
+            sample_count,
+            PrimitiveTopology::TriangleList,
+            shader.fragment_entry_point(),
+        ));
+
+        self.scene_shader_pipelines
+            .write()
+            .expect("Scene shader pipelines lock poisoned")
+            .insert(key, pipeline.clone());
+
+        pipeline
+    }
+}
+
+/// The pipelines that depend on how many samples a scene is drawn with and the format it's drawn into.
+///
+/// Only the scene pipeline is created up front. The background and post-processing pipelines are created
+/// the first time a scene needs them, and reused by every scene drawn the same way afterwards.
+#[derive(Debug)]
+pub(crate) struct OutputPipelines {
+    pub(crate) pipeline: RenderPipeline,
+    format: TextureFormat,
+    sample_count: u32,
+    background: OnceLock<BackgroundPipelines>,
+    post_processing: OnceLock<PostProcessingPipelines>,
 }
 
+impl OutputPipelines {
+    fn new(pipeline: RenderPipeline, format: TextureFormat, sample_count: u32) -> Self {
+        Self {
+            pipeline,
+            format,
+            sample_count,
+            background: OnceLock::new(),
+            post_processing: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn get_background(&self, device: &Device) -> &BackgroundPipelines {
+        self.background.get_or_init(|| {
+            let _guard = trace_span!("create_background_pipelines", self.sample_count).entered();
+
+            BackgroundPipelines::new(device, self.format, self.sample_count)
+        })
+    }
+
+    pub(crate) fn get_post_processing(&self, device: &Device) -> &PostProcessingPipelines {
+        self.post_processing.get_or_init(|| {
+            let _guard =
+                trace_span!("create_post_processing_pipelines", self.sample_count).entered();
+
+            PostProcessingPipelines::new(device, self.format, self.sample_count)
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 pub type ServiceProvider<'a> = dyn FnOnce(&Instance) -> Option<Surface> + 'a + Send;
 
+/// Surfaces are made from canvases in browsers, which can't be sent across threads.
+#[cfg(target_arch = "wasm32")]
+pub type ServiceProvider<'a> = dyn FnOnce(&Instance) -> Option<Surface> + 'a;
+
+/// How to pick the adapter to render with, for machines with more than one.
+///
+/// The `WGPU_ADAPTER_NAME` and `WGPU_POWER_PREF` environment variables take precedence over this.
+/// In browsers, only the power preference and fallback adapter settings are used.
+#[derive(Debug, Clone, Default)]
+pub struct AdapterSelector {
+    /// The backends to look for adapters in, in order, falling back to the next one when none of a backend's
+    /// adapters fit (e.g. Vulkan, then DX12, then GL). When set, the context only uses these backends.
+    pub backend_preference: Vec<Backend>,
+    /// Picks the first adapter whose name contains this, case-insensitively.
+    pub name: Option<String>,
+    /// Picks the adapter at this index, amongst the available adapters (that match the name, if given).
+    pub index: Option<usize>,
+    /// The kind of adapter to prefer when neither a name nor an index is given.
+    pub power_preference: PowerPreference,
+    /// Whether to only pick a fallback (software) adapter.
+    pub force_fallback_adapter: bool,
+}
+
+impl AdapterSelector {
+    /// Only uses OpenGL, for machines whose drivers don't support anything more recent (e.g. old servers).
+    pub fn gl_only() -> Self {
+        Self::default().with_backend_preference([Backend::Gl])
+    }
+
+    pub fn with_backend_preference(mut self, backends: impl IntoIterator<Item = Backend>) -> Self {
+        self.backend_preference = backends.into_iter().collect();
+        self
+    }
+
+    /// The backends the context needs to be able to use, if there's a preference.
+    fn preferred_backends(&self) -> Option<Backends> {
+        (!self.backend_preference.is_empty()).then(|| {
+            self.backend_preference
+                .iter()
+                .fold(Backends::empty(), |backends, &backend| {
+                    backends | backend.into()
+                })
+        })
+    }
+
+    /// Picks an adapter, along with the preferred backends that were skipped to get to it.
+    async fn select(
+        &self,
+        instance: &Instance,
+        backends: Backends,
+        surface: Option<&Surface>,
+    ) -> Option<(Adapter, Vec<Backend>)> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(adapter) = wgpu::util::initialize_adapter_from_env(instance, surface) {
+            return Some((adapter, Vec::new()));
+        }
+
+        // Browsers don't let adapters be listed, so they can only be asked for one.
+        if cfg!(target_arch = "wasm32")
+            || (self.name.is_none() && self.index.is_none() && self.backend_preference.is_empty())
+        {
+            return instance
+                .request_adapter(&RequestAdapterOptions {
+                    power_preference: wgpu::util::power_preference_from_env()
+                        .unwrap_or(self.power_preference),
+                    force_fallback_adapter: self.force_fallback_adapter,
+                    compatible_surface: surface,
+                })
+                .await
+                .map(|adapter| (adapter, Vec::new()));
+        }
+
+        if self.backend_preference.is_empty() {
+            return self
+                .select_from_list(instance, backends, surface)
+                .map(|adapter| (adapter, Vec::new()));
+        }
+
+        let mut skipped_backends = Vec::new();
+
+        for &backend in &self.backend_preference {
+            if let Some(adapter) = self.select_from_list(instance, backend.into(), surface) {
+                return Some((adapter, skipped_backends));
+            }
+
+            skipped_backends.push(backend);
+        }
+
+        None
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn select_from_list(
+        &self,
+        _instance: &Instance,
+        _backends: Backends,
+        _surface: Option<&Surface>,
+    ) -> Option<Adapter> {
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn select_from_list(
+        &self,
+        instance: &Instance,
+        backends: Backends,
+        surface: Option<&Surface>,
+    ) -> Option<Adapter> {
+        let name = self.name.as_deref().map(str::to_lowercase);
+
+        let mut adapters = instance
+            .enumerate_adapters(backends)
+            .filter(|adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface)))
+            .filter(|adapter| {
+                let info = adapter.get_info();
+
+                let matches_name = name
+                    .as_ref()
+                    .is_none_or(|name| info.name.to_lowercase().contains(name));
+
+                matches_name
+                    && (!self.force_fallback_adapter || info.device_type == DeviceType::Cpu)
+            });
+
+        match self.index {
+            Some(index) => adapters.nth(index),
+            // Like when asking for an adapter, go with the kind of adapter that's preferred
+            None => adapters
+                .min_by_key(|adapter| self.power_preference_rank(adapter.get_info().device_type)),
+        }
+    }
+
+    /// How well a kind of adapter matches the power preference, lower being better.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn power_preference_rank(&self, device_type: DeviceType) -> u8 {
+        let power_preference =
+            wgpu::util::power_preference_from_env().unwrap_or(self.power_preference);
+
+        match (power_preference, device_type) {
+            (PowerPreference::HighPerformance, DeviceType::DiscreteGpu)
+            | (PowerPreference::LowPower, DeviceType::IntegratedGpu) => 0,
+            (_, DeviceType::DiscreteGpu | DeviceType::IntegratedGpu) => 1,
+            (_, DeviceType::VirtualGpu | DeviceType::Other) => 2,
+            (_, DeviceType::Cpu) => 3,
+        }
+    }
+}
+
 pub struct GraphicsContextDescriptor<'a> {
     pub backends: Option<Backends>,
+    pub adapter: AdapterSelector,
     pub surface_provider: Box<ServiceProvider<'a>>,
     pub default_size: (u32, u32),
     pub texture_format: Option<TextureFormat>,
+    /// The color space to render in, or [`None`] to go with the texture format (or the surface's).
+    ///
+    /// Setting it keeps colors consistent across backends, whose surfaces may or may not be sRGB.
+    pub color_space: Option<ColorSpace>,
     pub features: Features,
     pub limits: Option<Limits>,
     pub blend_state: Option<BlendState>,
@@ -149,6 +624,128 @@ impl GraphicsContext {
     pub const DEFAULT_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
     pub const DEPTH_TEXTURE_FORMAT: TextureFormat = TextureFormat::Depth32Float;
 
+    const VERTEX_ATTRIBUTES: [VertexAttribute; 5] = vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x3,
+        3 => Float32x4,
+        4 => Float32x4
+    ];
+
+    /// The transform (as four columns) and texture variant of each instance.
+    const INSTANCE_ATTRIBUTES: [VertexAttribute; 5] = vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+        9 => Uint32
+    ];
+
+    fn vertex_buffer_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<Vertex>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::VERTEX_ATTRIBUTES,
+        }
+    }
+
+    fn instance_buffer_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: mem::size_of::<InstanceInformation>() as BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::INSTANCE_ATTRIBUTES,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_scene_pipeline(
+        device: &Device,
+        pipeline_layout: &PipelineLayout,
+        shader: &ShaderModule,
+        texture_format: TextureFormat,
+        blend: Option<BlendState>,
+        sample_count: u32,
+        topology: PrimitiveTopology,
+        fragment_entry_point: &str,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(pipeline_layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[Self::vertex_buffer_layout(), Self::instance_buffer_layout()],
+            },
+            primitive: PrimitiveState {
+                topology,
+                cull_mode: None,
+                front_face: FrontFace::Cw,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Self::DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState {
+                count: sample_count,
+                alpha_to_coverage_enabled: false,
+                ..Default::default()
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: fragment_entry_point,
+                targets: &[Some(ColorTargetState {
+                    format: texture_format,
+                    blend,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+    }
+
+    fn create_shadow_pipeline(
+        device: &Device,
+        shadow_pipeline_layout: &PipelineLayout,
+    ) -> RenderPipeline {
+        let shadow_shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Shadow Map Shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shadow.wgsl"))),
+        });
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Shadow Map Pipeline"),
+            layout: Some(shadow_pipeline_layout),
+            vertex: VertexState {
+                module: &shadow_shader,
+                entry_point: "vs_main",
+                buffers: &[Self::vertex_buffer_layout(), Self::instance_buffer_layout()],
+            },
+            primitive: PrimitiveState {
+                cull_mode: None,
+                front_face: FrontFace::Cw,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Self::DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shadow_shader,
+                entry_point: "fs_main",
+                targets: &[],
+            }),
+            multiview: None,
+        })
+    }
+
     pub async fn new(descriptor: GraphicsContextDescriptor<'_>) -> Result<Self> {
         Self::new_with_shader(
             descriptor,
@@ -163,6 +760,7 @@ impl GraphicsContext {
         shader: ShaderSource<'_>,
     ) -> Result<Self> {
         let backends = wgpu::util::backend_bits_from_env()
+            .or(descriptor.adapter.preferred_backends())
             .or(descriptor.backends)
             .ok_or(NMSRRenderingError::NoBackendFound)?;
 
@@ -176,17 +774,38 @@ impl GraphicsContext {
 
         let mut surface = (descriptor.surface_provider)(&instance);
 
-        let adapter =
-            wgpu::util::initialize_adapter_from_env_or_default(&instance, surface.as_ref())
-                .await
-                .ok_or(NMSRRenderingError::NoAdapterFound)?;
+        let (adapter, skipped_backends) = descriptor
+            .adapter
+            .select(&instance, backends, surface.as_ref())
+            .await
+            .ok_or(NMSRRenderingError::NoAdapterFound)?;
+
+        if !skipped_backends.is_empty() {
+            warn!(
+                "No suitable adapter found for {:?}, falling back",
+                skipped_backends
+            );
+        }
+
+        let adapter_info = adapter.get_info();
+        info!(
+            "Using adapter {} ({:?}, {:?})",
+            adapter_info.name, adapter_info.backend, adapter_info.device_type
+        );
+
+        // Profiling times the passes of renders with timestamp queries, whenever the adapter has them.
+        let features = if cfg!(feature = "profiling") {
+            descriptor.features | (adapter.features() & Features::TIMESTAMP_QUERY)
+        } else {
+            descriptor.features
+        };
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: descriptor.features,
-                    limits: descriptor.limits.unwrap_or_else(|| wgpu::Limits::default())
+                    features,
+                    limits: descriptor.limits.unwrap_or_else(|| wgpu::Limits::default()),
                 },
                 None,
             )
@@ -205,7 +824,14 @@ impl GraphicsContext {
 
         if let Some(surface) = &surface {
             if let Ok(Some(surface_config)) = surface_config.as_mut() {
-                surface_config.view_formats.push(surface_config.format);
+                // Surfaces are drawn to through a view in the color space we render in
+                let view_format = descriptor
+                    .color_space
+                    .map_or(surface_config.format, |color_space| {
+                        color_space.apply_to_format(surface_config.format)
+                    });
+
+                surface_config.view_formats.push(view_format);
                 surface_config.present_mode = PresentMode::AutoVsync;
                 surface.configure(&device, surface_config);
             }
@@ -223,10 +849,10 @@ impl GraphicsContext {
             .or(descriptor.texture_format)
             .unwrap_or(Self::DEFAULT_TEXTURE_FORMAT);
 
-        let adapter =
-            wgpu::util::initialize_adapter_from_env_or_default(&instance, surface.as_ref())
-                .await
-                .ok_or(NMSRRenderingError::WgpuAdapterRequestError)?;
+        let color_space = descriptor
+            .color_space
+            .unwrap_or_else(|| ColorSpace::of_format(texture_format));
+        let texture_format = color_space.apply_to_format(texture_format);
 
         // Create a bind group layout for storing the transformation matrix in a uniform
         let transform_bind_group_layout =
@@ -263,6 +889,41 @@ impl GraphicsContext {
                     ty: BindingType::Sampler(SamplerBindingType::Filtering),
                     count: None,
                 },
+                // Normal map
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                // Specular map
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    // The vertex shader picks the texture variant of each instance with it.
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            mem::size_of::<MaterialInformation>() as u64
+                        ),
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -280,6 +941,56 @@ impl GraphicsContext {
             }],
         });
 
+        let shadow_information_size = BufferSize::new(mem::size_of::<ShadowInformation>() as u64);
+
+        let shadow_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Shadow Map Bind Group"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: shadow_information_size,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: TextureViewDimension::D2,
+                            sample_type: TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                ],
+            });
+
+        let light_transform_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Light Transform Bind Group"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: shadow_information_size,
+                    },
+                    count: None,
+                }],
+            });
+
         // Create the pipeline layout
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Scene Pipeline Layout"),
@@ -287,21 +998,22 @@ impl GraphicsContext {
                 &transform_bind_group_layout,
                 &skin_bind_group_layout,
                 &sun_bind_group_layout,
+                &shadow_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
 
+        let shadow_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Shadow Map Pipeline Layout"),
+            bind_group_layouts: &[&light_transform_bind_group_layout, &skin_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
         let shader = device.create_shader_module(ShaderModuleDescriptor {
             label: None,
             source: shader,
         });
 
-        let vertex_buffer_layout = VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3],
-        };
-
         let multisampling_strategy = GraphicsContextDescriptor::get_multisampling_strategy(
             &adapter,
             &texture_format,
@@ -314,42 +1026,19 @@ impl GraphicsContext {
             .blend_state
             .or(Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING));
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[vertex_buffer_layout],
-            },
-            primitive: PrimitiveState {
-                cull_mode: None,
-                front_face: FrontFace::Cw,
-                ..Default::default()
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: Self::DEPTH_TEXTURE_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::LessEqual,
-                stencil: Default::default(),
-                bias: Default::default(),
-            }),
-            multisample: MultisampleState {
-                count: sample_count,
-                alpha_to_coverage_enabled: false,
-                ..Default::default()
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: texture_format,
-                    blend,
-                    write_mask: ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        });
+        let pipeline = Self::create_scene_pipeline(
+            &device,
+            &pipeline_layout,
+            &shader,
+            texture_format,
+            blend,
+            sample_count,
+            PrimitiveTopology::TriangleList,
+            "fs_main",
+        );
+
+        let default_pipelines = OutputPipelines::new(pipeline, texture_format, sample_count);
+        let default_material_maps = DefaultMaterialMaps::new(&device, &queue);
 
         Ok(GraphicsContext {
             instance,
@@ -358,14 +1047,26 @@ impl GraphicsContext {
             surface,
             surface_config,
             texture_format,
+            color_space,
             adapter,
-            pipeline,
+            skipped_backends,
+            default_pipelines: Arc::new(default_pipelines),
+            shadow_pipeline: OnceLock::new(),
             multisampling_strategy,
+            default_material_maps,
+            shader,
+            blend_state: blend,
+            output_pipelines: RwLock::default(),
+            wireframe_pipelines: RwLock::default(),
+            scene_shader_pipelines: RwLock::default(),
             layouts: GraphicsContextLayouts {
                 pipeline_layout,
                 transform_bind_group_layout,
                 skin_sampler_bind_group_layout: skin_bind_group_layout,
                 sun_bind_group_layout,
+                shadow_bind_group_layout,
+                light_transform_bind_group_layout,
+                shadow_pipeline_layout,
             },
         })
     }
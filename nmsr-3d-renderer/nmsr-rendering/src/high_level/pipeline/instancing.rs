@@ -0,0 +1,54 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+
+/// A copy of the player of a scene, drawn along with the others in a single instanced draw.
+///
+/// All the instances share the parts (so the same model and pose), but each can be placed
+/// somewhere else and use a different variant of the textures.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SceneInstance {
+    /// Where this copy of the player is placed, applied on top of the parts' own transforms.
+    pub transform: Mat4,
+    /// Which of the texture variants this copy is drawn with,
+    /// for the texture types that have variants (see [`Scene::set_texture_variants`]).
+    ///
+    /// [`Scene::set_texture_variants`]: super::scene::Scene::set_texture_variants
+    pub texture_variant: u32,
+}
+
+impl Default for SceneInstance {
+    fn default() -> Self {
+        Self {
+            transform: Mat4::IDENTITY,
+            texture_variant: 0,
+        }
+    }
+}
+
+impl SceneInstance {
+    pub fn new(transform: Mat4, texture_variant: u32) -> Self {
+        Self {
+            transform,
+            texture_variant,
+        }
+    }
+}
+
+/// The per-instance data of the instance vertex buffer.
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub(crate) struct InstanceInformation {
+    transform: Mat4,
+    texture_variant: u32,
+    _padding: [u32; 3],
+}
+
+impl From<&SceneInstance> for InstanceInformation {
+    fn from(instance: &SceneInstance) -> Self {
+        Self {
+            transform: instance.transform,
+            texture_variant: instance.texture_variant,
+            _padding: [0; 3],
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use image::{Rgba, RgbaImage};
+use wgpu::{
+    util::DeviceExt, Device, Extent3d, Queue, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+
+use super::textures::SceneTexture;
+
+/// What the shader needs to light the material maps of a texture, and to pick its variant for each instance.
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub(crate) struct MaterialInformation {
+    camera_position: Vec3,
+    /// How many variants are stacked (top to bottom) in the texture.
+    texture_variants: u32,
+    /// Whether the texture is drawn as-is, without any lighting (like nameplates).
+    unlit: u32,
+    _padding: [u32; 3],
+}
+
+impl MaterialInformation {
+    pub(crate) fn new(camera_position: Vec3, texture_variants: u32, unlit: bool) -> Self {
+        Self {
+            camera_position,
+            texture_variants,
+            unlit: unlit as u32,
+            _padding: [0; 3],
+        }
+    }
+
+    pub(crate) fn set_camera_position(&mut self, camera_position: Vec3) {
+        self.camera_position = camera_position;
+    }
+}
+
+/// The maps bound for textures that don't have a normal or specular map of their own, which don't change
+/// how the texture is lit.
+#[derive(Debug)]
+pub struct DefaultMaterialMaps {
+    /// A flat normal map, pointing straight out of the face.
+    pub(crate) normal: SceneTexture,
+    /// A specular map without any highlights.
+    pub(crate) specular: SceneTexture,
+}
+
+impl DefaultMaterialMaps {
+    pub(crate) fn new(device: &Device, queue: &Queue) -> Self {
+        let flat_normal = RgbaImage::from_pixel(1, 1, Rgba([128, 128, 255, 255]));
+        let no_specular = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+
+        Self {
+            normal: upload_material_map(device, queue, &flat_normal, Some("Default Normal Map")),
+            specular: upload_material_map(
+                device,
+                queue,
+                &no_specular,
+                Some("Default Specular Map"),
+            ),
+        }
+    }
+}
+
+/// Uploads a normal or specular map.
+///
+/// Unlike regular textures, these hold data rather than colors, so they're neither premultiplied nor sRGB.
+pub(crate) fn upload_material_map(
+    device: &Device,
+    queue: &Queue,
+    image: &RgbaImage,
+    label: Option<&str>,
+) -> SceneTexture {
+    let texture = device.create_texture_with_data(
+        queue,
+        &TextureDescriptor {
+            size: Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            label,
+            view_formats: &[],
+        },
+        image.as_raw(),
+    );
+    let view = texture.create_view(&Default::default());
+
+    SceneTexture { texture, view }
+}
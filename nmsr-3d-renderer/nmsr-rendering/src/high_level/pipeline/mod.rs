@@ -1,8 +1,23 @@
+pub(crate) mod atlas;
+mod capabilities;
 mod graphics_context;
+pub mod materials;
 pub mod pools;
+pub mod ambient_occlusion;
+pub mod background;
+mod depth_readback;
+pub mod entities;
+pub mod instancing;
+pub mod outline;
+pub mod post_processing;
+pub mod profiling;
 pub mod scene;
 mod scene_context;
+pub mod shader_hooks;
+pub mod shadows;
+pub mod software;
 pub(crate) mod textures;
 
+pub use capabilities::*;
 pub use graphics_context::*;
 pub use scene_context::*;
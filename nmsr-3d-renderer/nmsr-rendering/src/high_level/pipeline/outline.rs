@@ -0,0 +1,164 @@
+use std::{borrow::Cow, mem};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, BufferSize, ColorTargetState, ColorWrites, Device, FragmentState,
+    MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, SamplerBindingType, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+/// How the stylized borders around the player are drawn, when outlines are enabled on a scene.
+///
+/// Outlines are found from the depth of the scene, so they go around the silhouette of the player
+/// as well as around parts in front of others (like an arm in front of the body).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutlineSettings {
+    /// The RGBA color of the outline.
+    pub color: [u8; 4],
+    /// How thick the outline is, in pixels of the output (up to 16).
+    pub thickness: f32,
+    /// How far behind a part a surface needs to be (in pixels of the skin) for the part to be outlined against it.
+    pub edge_threshold: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: [0, 0, 0, 255],
+            thickness: 2.0,
+            edge_threshold: 2.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub(crate) struct OutlineParameters {
+    inverse_view_projection: Mat4,
+    color: [f32; 4],
+    thickness: f32,
+    edge_threshold: f32,
+    _padding: [f32; 2],
+}
+
+impl OutlineParameters {
+    /// The depth is read at the size the scene is drawn at, so the thickness is scaled up along with it when supersampling.
+    pub(crate) fn new(settings: &OutlineSettings, view_projection: Mat4, supersampling: u32) -> Self {
+        let [r, g, b, a] = settings.color.map(|c| c as f32 / 255.0);
+
+        Self {
+            inverse_view_projection: view_projection.inverse(),
+            color: [r * a, g * a, b * a, a],
+            thickness: settings.thickness * supersampling as f32,
+            edge_threshold: settings.edge_threshold,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// The pipeline of the outline pass, which reads the depth of the scene on top of its colors.
+#[derive(Debug)]
+pub(crate) struct OutlinePipeline {
+    pub(crate) bind_group_layout: BindGroupLayout,
+    pub(crate) pipeline: RenderPipeline,
+}
+
+impl OutlinePipeline {
+    pub(crate) fn new(device: &Device, texture_format: TextureFormat, sample_count: u32) -> Self {
+        let multisampled = sample_count > 1;
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Outline Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            mem::size_of::<OutlineParameters>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let depth_texture_type = if multisampled {
+            "texture_depth_multisampled_2d"
+        } else {
+            "texture_depth_2d"
+        };
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: ShaderSource::Wgsl(Cow::Owned(
+                include_str!("outline.wgsl").replace("DEPTH_TEXTURE_TYPE", depth_texture_type),
+            )),
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Outline Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: texture_format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
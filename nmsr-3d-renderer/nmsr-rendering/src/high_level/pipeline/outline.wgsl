@@ -0,0 +1,100 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+struct OutlineParameters {
+    inverse_view_projection: mat4x4<f32>,
+    color: vec4<f32>,
+    thickness: f32,
+    edge_threshold: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var source_sampler: sampler;
+
+@group(0)
+@binding(2)
+var depth: DEPTH_TEXTURE_TYPE;
+
+@group(0)
+@binding(3)
+var<uniform> parameters: OutlineParameters;
+
+// Keeps thick outlines from making the pass too slow.
+const MAX_THICKNESS: i32 = 16;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    result.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    result.tex_coord = uv;
+    return result;
+}
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth));
+    return textureLoad(depth, clamp(pixel, vec2<i32>(0), size - vec2<i32>(1)), 0);
+}
+
+// Turns a pixel and its depth back into the position it was rendered from.
+fn reconstruct_position(pixel: vec2<i32>, pixel_depth: f32) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(depth));
+    let uv = (vec2<f32>(pixel) + vec2<f32>(0.5)) / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, pixel_depth, 1.0);
+    let position = parameters.inverse_view_projection * ndc;
+    return position.xyz / position.w;
+}
+
+// Whether something drawn closer to the camera than this pixel (by more than the threshold) is nearby.
+// Around the player that's the empty background, and inside it, the surfaces behind an arm or a leg.
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(source, source_sampler, vertex.tex_coord, 0.0);
+
+    let size = vec2<f32>(textureDimensions(depth));
+    let pixel = vec2<i32>(vertex.tex_coord * size);
+    let center_depth = load_depth(pixel);
+    let camera = reconstruct_position(pixel, 0.0);
+    let center_distance = distance(reconstruct_position(pixel, center_depth), camera);
+
+    let radius = min(i32(ceil(parameters.thickness)), MAX_THICKNESS);
+    var coverage: f32 = 0.0;
+
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            // Fade the outer edge of the outline, so it isn't jagged.
+            let weight = clamp(parameters.thickness + 0.5 - length(offset), 0.0, 1.0);
+
+            if (weight <= coverage) {
+                continue;
+            }
+
+            let sample_pixel = pixel + vec2<i32>(x, y);
+            let sample_depth = load_depth(sample_pixel);
+
+            if (sample_depth >= 1.0) {
+                continue;
+            }
+
+            let is_edge = center_depth >= 1.0
+                || center_distance - distance(reconstruct_position(sample_pixel, sample_depth), camera) > parameters.edge_threshold;
+
+            if (is_edge) {
+                coverage = weight;
+            }
+        }
+    }
+
+    // Colors are premultiplied, so the outline goes over the render like any other blended color.
+    let outline = parameters.color * coverage;
+    return outline + color * (1.0 - outline.a);
+}
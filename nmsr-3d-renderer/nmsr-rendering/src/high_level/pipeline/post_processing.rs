@@ -0,0 +1,534 @@
+use std::{borrow::Cow, collections::HashMap, mem};
+
+use bytemuck::{Pod, Zeroable};
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
+use tracing::{instrument, trace_span};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, BufferSize, Color,
+    BindGroup, ColorTargetState, ColorWrites, CommandEncoder, Device, FilterMode, FragmentState, LoadOp, MultisampleState,
+    Operations, PipelineLayoutDescriptor, PrimitiveState, RenderPass, RenderPassColorAttachment,
+    RenderPassTimestampWrites, RenderPipeline, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderStages, StoreOp, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDimension, VertexState,
+};
+
+use super::{
+    ambient_occlusion::{AmbientOcclusionParameters, AmbientOcclusionPipeline},
+    outline::{OutlineParameters, OutlinePipeline},
+    profiling::GpuProfiler,
+    scene::Size,
+    textures::{create_texture, SceneTexture},
+    GraphicsContext,
+};
+
+/// An effect applied to a render after the scene is drawn, before it's copied out.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PostProcessingEffect {
+    /// Fast approximate anti-aliasing, for smoothing out edges when SMAA is disabled.
+    Fxaa,
+    /// Makes the parts of the render brighter than the threshold glow.
+    Bloom { threshold: f32, intensity: f32 },
+    /// Darkens the edges of the render.
+    Vignette { strength: f32 },
+}
+
+impl PostProcessingEffect {
+    pub fn kind(&self) -> PostProcessingEffectKind {
+        match self {
+            Self::Fxaa => PostProcessingEffectKind::Fxaa,
+            Self::Bloom { .. } => PostProcessingEffectKind::Bloom,
+            Self::Vignette { .. } => PostProcessingEffectKind::Vignette,
+        }
+    }
+
+    fn parameters(&self, size: Size) -> EffectParameters {
+        let (strength, threshold) = match *self {
+            Self::Fxaa => (1.0, 0.0),
+            Self::Bloom {
+                threshold,
+                intensity,
+            } => (intensity, threshold),
+            Self::Vignette { strength } => (strength, 0.0),
+        };
+
+        EffectParameters {
+            texel_size: [1.0 / size.width.max(1) as f32, 1.0 / size.height.max(1) as f32],
+            strength,
+            threshold,
+        }
+    }
+}
+
+/// The kinds of post-processing effects, each with its own pipeline.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter, IntoStaticStr)]
+pub enum PostProcessingEffectKind {
+    Fxaa,
+    Bloom,
+    Vignette,
+}
+
+impl PostProcessingEffectKind {
+    fn get_entry_point(&self) -> &'static str {
+        match self {
+            Self::Fxaa => "fs_fxaa",
+            Self::Bloom => "fs_bloom",
+            Self::Vignette => "fs_vignette",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+struct EffectParameters {
+    texel_size: [f32; 2],
+    strength: f32,
+    threshold: f32,
+}
+
+/// The pipelines of every post-processing effect for one output format and sample count,
+/// shared by the scenes of a [`GraphicsContext`] drawn with them.
+#[derive(Debug)]
+pub struct PostProcessingPipelines {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    pipelines: HashMap<PostProcessingEffectKind, RenderPipeline>,
+    /// The ambient occlusion pipeline for the sample count these pipelines were created for.
+    pub(crate) ambient_occlusion: AmbientOcclusionPipeline,
+    /// The outline pipeline for the sample count these pipelines were created for.
+    pub(crate) outline: OutlinePipeline,
+    /// Scales supersampled renders back down, averaging blocks of pixels together.
+    downsample_pipeline: RenderPipeline,
+}
+
+impl PostProcessingPipelines {
+    pub(crate) fn new(device: &Device, texture_format: TextureFormat, sample_count: u32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post-processing Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(mem::size_of::<EffectParameters>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Post-processing Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Post-processing Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("post_processing.wgsl"))),
+        });
+
+        let create_pipeline = |label, entry_point| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format: texture_format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+        };
+
+        let pipelines = PostProcessingEffectKind::iter()
+            .map(|kind| (kind, create_pipeline(kind.into(), kind.get_entry_point())))
+            .collect();
+
+        let downsample_pipeline = create_pipeline("Downsample", "fs_downsample");
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Post-processing Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            pipelines,
+            downsample_pipeline,
+            ambient_occlusion: AmbientOcclusionPipeline::new(device, texture_format, sample_count),
+            outline: OutlinePipeline::new(device, texture_format, sample_count),
+        }
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &Device,
+        label: &str,
+        source: &TextureView,
+        parameters: EffectParameters,
+    ) -> BindGroup {
+        let parameters_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Post-processing Parameters Buffer"),
+            contents: bytemuck::cast_slice(&[parameters]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Creates the bind group of a pass reading the depth of the scene on top of its colors
+    /// (the ambient occlusion and outline passes).
+    fn create_depth_pass_bind_group(
+        &self,
+        device: &Device,
+        label: &str,
+        layout: &BindGroupLayout,
+        source: &TextureView,
+        depth: &TextureView,
+        parameters: &[u8],
+    ) -> BindGroup {
+        let parameters_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(&format!("{} Parameters Buffer", label)),
+            contents: parameters,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some(&format!("{} Bind Group", label)),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(depth),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Scales a render made at `factor` times the size of the output down into it.
+    #[instrument(skip(self, graphics_context, profiler, source, output))]
+    pub(crate) fn downsample(
+        &self,
+        graphics_context: &GraphicsContext,
+        profiler: &mut GpuProfiler,
+        source: &TextureView,
+        output: &TextureView,
+        factor: u32,
+    ) {
+        let device = &graphics_context.device;
+
+        let parameters = EffectParameters {
+            texel_size: [0.0, 0.0],
+            strength: factor as f32,
+            threshold: 0.0,
+        };
+
+        let bind_group = self.create_bind_group(device, "Downsample", source, parameters);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Downsample (NMSR)"),
+        });
+
+        {
+            let label = "Downsample pass";
+            let mut rpass = PostProcessingChain::begin_pass(
+                &mut encoder,
+                label,
+                output,
+                profiler.time_pass(label),
+            );
+            rpass.set_pipeline(&self.downsample_pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// The effects a [`SceneContext`](super::SceneContext) applies to its renders, in order.
+#[derive(Debug, Default)]
+pub(crate) struct PostProcessingChain {
+    pub(crate) effects: Vec<PostProcessingEffect>,
+    /// The textures effects render from and into, in turns. Only created once there are effects to apply.
+    targets: Option<(Size, TextureFormat, [SceneTexture; 2])>,
+}
+
+impl PostProcessingChain {
+    /// Makes sure the intermediate textures exist and match the size and format of the render.
+    ///
+    /// The scene is only drawn into them when there are effects (or ambient occlusion or outline passes) to apply.
+    pub(crate) fn prepare(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        format: TextureFormat,
+        size: Size,
+        has_depth_passes: bool,
+    ) {
+        if self.effects.is_empty() && !has_depth_passes {
+            self.targets = None;
+            return;
+        }
+
+        if self
+            .targets
+            .as_ref()
+            .is_some_and(|(target_size, target_format, _)| {
+                *target_size == size && *target_format == format
+            })
+        {
+            return;
+        }
+
+        let _guard = trace_span!("create_post_processing_targets").entered();
+
+        let create_target = |label| {
+            create_texture(
+                graphics_context,
+                size.width,
+                size.height,
+                format,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                Some(label),
+                1,
+            )
+        };
+
+        self.targets = Some((
+            size,
+            format,
+            [
+                create_target("Post-processing Texture A"),
+                create_target("Post-processing Texture B"),
+            ],
+        ));
+    }
+
+    /// Returns the texture the scene should be drawn into, when there are passes to run afterwards.
+    pub(crate) fn scene_target(&self) -> Option<&TextureView> {
+        self.targets.as_ref().map(|(_, _, [first, _])| &first.view)
+    }
+
+    /// Runs the ambient occlusion and outline passes (if any) and then the effects, in order, on what was drawn
+    /// into the scene target, with the last pass drawing into the output.
+    ///
+    /// The effects are drawn with the pipelines of the registry, which need to match the format of the output.
+    #[instrument(skip_all)]
+    pub(crate) fn apply(
+        &self,
+        graphics_context: &GraphicsContext,
+        registry: &PostProcessingPipelines,
+        profiler: &mut GpuProfiler,
+        output: &TextureView,
+        ambient_occlusion: Option<AmbientOcclusionPass>,
+        outline: Option<OutlinePass>,
+    ) {
+        let Some((size, _, targets)) = self.targets.as_ref() else {
+            return;
+        };
+
+        let device = &graphics_context.device;
+
+        let pass_count = self.effects.len()
+            + usize::from(ambient_occlusion.is_some())
+            + usize::from(outline.is_some());
+        let source = |index: usize| &targets[index % 2].view;
+        let destination = |index: usize| {
+            if index == pass_count - 1 {
+                output
+            } else {
+                &targets[(index + 1) % 2].view
+            }
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Post-processing (NMSR)"),
+        });
+
+        let mut index = 0;
+
+        if let Some(ambient_occlusion) = ambient_occlusion {
+            let _pass_span = trace_span!("ambient_occlusion_pass").entered();
+
+            let bind_group = registry.create_depth_pass_bind_group(
+                device,
+                "Ambient Occlusion",
+                &ambient_occlusion.pipeline.bind_group_layout,
+                source(index),
+                ambient_occlusion.depth,
+                bytemuck::cast_slice(&[ambient_occlusion.parameters]),
+            );
+
+            let label = "Ambient occlusion pass";
+            let mut rpass = Self::begin_pass(
+                &mut encoder,
+                label,
+                destination(index),
+                profiler.time_pass(label),
+            );
+            rpass.set_pipeline(&ambient_occlusion.pipeline.pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+
+            index += 1;
+        }
+
+        if let Some(outline) = outline {
+            let _pass_span = trace_span!("outline_pass").entered();
+
+            let bind_group = registry.create_depth_pass_bind_group(
+                device,
+                "Outline",
+                &outline.pipeline.bind_group_layout,
+                source(index),
+                outline.depth,
+                bytemuck::cast_slice(&[outline.parameters]),
+            );
+
+            let label = "Outline pass";
+            let mut rpass = Self::begin_pass(
+                &mut encoder,
+                label,
+                destination(index),
+                profiler.time_pass(label),
+            );
+            rpass.set_pipeline(&outline.pipeline.pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+
+            index += 1;
+        }
+
+        for effect in &self.effects {
+            let kind = effect.kind();
+            let _pass_span = trace_span!("post_processing_pass", effect = Into::<&str>::into(kind))
+                .entered();
+
+            let parameters = effect.parameters(*size);
+            let bind_group = registry.create_bind_group(device, kind.into(), source(index), parameters);
+
+            let label = format!("Post-processing pass for {:?}", kind);
+            let mut rpass = Self::begin_pass(
+                &mut encoder,
+                &label,
+                destination(index),
+                profiler.time_pass(&label),
+            );
+            rpass.set_pipeline(&registry.pipelines[&kind]);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+
+            index += 1;
+        }
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+
+    fn begin_pass<'a>(
+        encoder: &'a mut CommandEncoder,
+        label: &str,
+        destination: &'a TextureView,
+        timestamp_writes: Option<RenderPassTimestampWrites>,
+    ) -> RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: destination,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes,
+            occlusion_query_set: None,
+        })
+    }
+}
+
+/// The ambient occlusion pass of a render, which runs before any other post-processing effect.
+pub(crate) struct AmbientOcclusionPass<'a> {
+    pub(crate) parameters: AmbientOcclusionParameters,
+    /// The pipeline matching the sample count of the scene.
+    pub(crate) pipeline: &'a AmbientOcclusionPipeline,
+    /// The depth the scene was drawn with.
+    pub(crate) depth: &'a TextureView,
+}
+
+/// The outline pass of a render, which runs after ambient occlusion and before any other post-processing effect.
+pub(crate) struct OutlinePass<'a> {
+    pub(crate) parameters: OutlineParameters,
+    /// The pipeline matching the sample count of the scene.
+    pub(crate) pipeline: &'a OutlinePipeline,
+    /// The depth the scene was drawn with.
+    pub(crate) depth: &'a TextureView,
+}
@@ -0,0 +1,146 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+struct EffectParameters {
+    texel_size: vec2<f32>,
+    strength: f32,
+    threshold: f32,
+}
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var source_sampler: sampler;
+
+@group(0)
+@binding(2)
+var<uniform> parameters: EffectParameters;
+
+// A single triangle covering the whole screen, no vertex buffer needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    result.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    result.tex_coord = uv;
+    return result;
+}
+
+fn luma(color: vec4<f32>) -> f32 {
+    return dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_offset(uv: vec2<f32>, offset: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(source, source_sampler, uv + offset * parameters.texel_size, 0.0);
+}
+
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+@fragment
+fn fs_fxaa(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = vertex.tex_coord;
+
+    let center = sample_offset(uv, vec2<f32>(0.0, 0.0));
+    let luma_nw = luma(sample_offset(uv, vec2<f32>(-1.0, -1.0)));
+    let luma_ne = luma(sample_offset(uv, vec2<f32>(1.0, -1.0)));
+    let luma_sw = luma(sample_offset(uv, vec2<f32>(-1.0, 1.0)));
+    let luma_se = luma(sample_offset(uv, vec2<f32>(1.0, 1.0)));
+    let luma_m = luma(center);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+
+    let direction_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN,
+    );
+    let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+
+    direction = clamp(
+        direction * inverse_direction_min,
+        vec2<f32>(-FXAA_SPAN_MAX),
+        vec2<f32>(FXAA_SPAN_MAX),
+    );
+
+    let result_a = 0.5 * (
+        sample_offset(uv, direction * (1.0 / 3.0 - 0.5))
+        + sample_offset(uv, direction * (2.0 / 3.0 - 0.5))
+    );
+    let result_b = result_a * 0.5 + 0.25 * (
+        sample_offset(uv, direction * -0.5)
+        + sample_offset(uv, direction * 0.5)
+    );
+
+    let luma_b = luma(result_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return result_a;
+    }
+
+    return result_b;
+}
+
+@fragment
+fn fs_bloom(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = vertex.tex_coord;
+    let center = sample_offset(uv, vec2<f32>(0.0, 0.0));
+
+    var glow = vec3<f32>(0.0);
+    var total_weight = 0.0;
+
+    for (var x = -3; x <= 3; x++) {
+        for (var y = -3; y <= 3; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * 2.0;
+            let color = sample_offset(uv, offset);
+            let weight = exp(-dot(offset, offset) / 18.0);
+
+            glow += max(color.rgb - vec3<f32>(parameters.threshold * color.a), vec3<f32>(0.0)) * weight;
+            total_weight += weight;
+        }
+    }
+
+    glow = glow / total_weight * parameters.strength;
+
+    // Colors are premultiplied, so the glow can spill onto transparent pixels around the model.
+    let alpha = clamp(max(center.a, luma(vec4<f32>(glow, 1.0))), 0.0, 1.0);
+    return vec4<f32>(min(center.rgb + glow, vec3<f32>(alpha)), alpha);
+}
+
+@fragment
+fn fs_vignette(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = vertex.tex_coord;
+    let center = sample_offset(uv, vec2<f32>(0.0, 0.0));
+
+    let distance = length(uv - vec2<f32>(0.5)) * 1.41421356;
+    let darkening = 1.0 - parameters.strength * smoothstep(0.4, 1.0, distance);
+
+    return vec4<f32>(center.rgb * darkening, center.a);
+}
+
+// Averages each block of `strength` by `strength` pixels of the source into one pixel, to scale supersampled
+// renders back down.
+@fragment
+fn fs_downsample(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let factor = i32(parameters.strength);
+    let origin = vec2<i32>(vertex.position.xy) * factor;
+
+    var total = vec4<f32>(0.0);
+    for (var x = 0; x < factor; x++) {
+        for (var y = 0; y < factor; y++) {
+            total += textureLoad(source, origin + vec2<i32>(x, y), 0);
+        }
+    }
+
+    return total / f32(factor * factor);
+}
@@ -0,0 +1,224 @@
+use std::mem;
+#[cfg(feature = "profiling")]
+use std::time::{Duration, Instant};
+
+use tracing::trace_span;
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Features, QuerySet,
+    QuerySetDescriptor, QueryType, RenderPassTimestampWrites,
+};
+
+use super::{textures::BufferDimensions, GraphicsContext};
+#[cfg(feature = "profiling")]
+use crate::{errors::Result, high_level::utils::buffer::read_buffer};
+
+/// The maximum number of passes of a render that are timed. Any pass after these isn't.
+const MAX_TIMED_PASSES: u32 = 64;
+
+/// How long a render took, to tell whether it's slow on the GPU or while encoding it.
+#[cfg(feature = "profiling")]
+#[derive(Debug, Clone, Default)]
+pub struct RenderTimings {
+    /// How long encoding and submitting the render took on the CPU.
+    pub encoding: Duration,
+    /// How long the GPU spent on each pass of the render, in the order they ran.
+    ///
+    /// This is [`None`] when the adapter doesn't support timestamp queries.
+    pub passes: Option<Vec<PassTiming>>,
+}
+
+#[cfg(feature = "profiling")]
+impl RenderTimings {
+    /// Returns how long the GPU spent on the timed passes of the render altogether.
+    pub fn gpu_time(&self) -> Option<Duration> {
+        self.passes
+            .as_ref()
+            .map(|passes| passes.iter().map(|pass| pass.duration).sum())
+    }
+}
+
+/// How long the GPU spent on one pass of a render.
+#[cfg(feature = "profiling")]
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    /// The label of the pass, like `Background render pass`.
+    pub label: String,
+    pub duration: Duration,
+}
+
+/// Times the passes of renders with timestamp queries, for them to be read back along with the colors.
+///
+/// Nothing is timed unless the `profiling` feature is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct GpuProfiler {
+    resources: Option<GpuProfilerResources>,
+    /// The labels of the passes timed by the last render, in order.
+    passes: Vec<String>,
+    /// When encoding the current render started, and how long encoding the last one took.
+    #[cfg(feature = "profiling")]
+    encoding: (Option<Instant>, Duration),
+}
+
+#[derive(Debug)]
+struct GpuProfilerResources {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    dimensions: BufferDimensions,
+}
+
+impl GpuProfiler {
+    /// Forgets the passes of the last render, creating the query set on first use if the device supports it.
+    pub(crate) fn begin_frame(&mut self, graphics_context: &GraphicsContext) {
+        self.passes.clear();
+
+        #[cfg(feature = "profiling")]
+        {
+            self.encoding.0 = Some(Instant::now());
+        }
+
+        if !cfg!(feature = "profiling")
+            || self.resources.is_some()
+            || !graphics_context
+                .device
+                .features()
+                .contains(Features::TIMESTAMP_QUERY)
+        {
+            return;
+        }
+
+        let _guard = trace_span!("create_gpu_profiler").entered();
+        let device = &graphics_context.device;
+
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Render Timestamps"),
+            ty: QueryType::Timestamp,
+            count: MAX_TIMED_PASSES * 2,
+        });
+
+        let dimensions =
+            BufferDimensions::new((MAX_TIMED_PASSES * 2) as usize, 1, mem::size_of::<u64>());
+
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Render Timestamps Resolve Buffer"),
+            size: dimensions.size(),
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Render Timestamps Readback Buffer"),
+            size: dimensions.size(),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        self.resources = Some(GpuProfilerResources {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            dimensions,
+        });
+    }
+
+    /// Returns where the pass with the given label should write its timestamps, if it's timed.
+    pub(crate) fn time_pass(&mut self, label: &str) -> Option<RenderPassTimestampWrites<'_>> {
+        let resources = self.resources.as_ref()?;
+        let index = self.passes.len() as u32;
+
+        if index >= MAX_TIMED_PASSES {
+            return None;
+        }
+
+        self.passes.push(label.to_string());
+
+        Some(RenderPassTimestampWrites {
+            query_set: &resources.query_set,
+            beginning_of_pass_write_index: Some(index * 2),
+            end_of_pass_write_index: Some(index * 2 + 1),
+        })
+    }
+
+    /// Copies the timestamps of the render into the readback buffer, and keeps how long encoding it took.
+    pub(crate) fn end_frame(&mut self, graphics_context: &GraphicsContext) {
+        #[cfg(feature = "profiling")]
+        if let Some(started) = self.encoding.0.take() {
+            self.encoding.1 = started.elapsed();
+        }
+
+        let Some(resources) = &self.resources else {
+            return;
+        };
+
+        if self.passes.is_empty() {
+            return;
+        }
+
+        let query_count = self.passes.len() as u32 * 2;
+        let mut encoder =
+            graphics_context
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor {
+                    label: Some("Render Timestamps"),
+                });
+
+        encoder.resolve_query_set(
+            &resources.query_set,
+            0..query_count,
+            &resources.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &resources.resolve_buffer,
+            0,
+            &resources.readback_buffer,
+            0,
+            resources.dimensions.size(),
+        );
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Reads back how long the passes of the last render took.
+    #[cfg(feature = "profiling")]
+    pub(crate) async fn read(&self, graphics_context: &GraphicsContext) -> Result<RenderTimings> {
+        let Some(resources) = self.resources.as_ref().filter(|_| !self.passes.is_empty()) else {
+            return Ok(RenderTimings {
+                encoding: self.encoding.1,
+                passes: self.resources.as_ref().map(|_| Vec::new()),
+            });
+        };
+
+        let bytes = read_buffer(
+            &graphics_context.device,
+            &resources.readback_buffer,
+            &resources.dimensions,
+            false,
+        )
+        .await?;
+
+        // Timestamps are counted in ticks of the queue's period, in nanoseconds.
+        let period = graphics_context.queue.get_timestamp_period() as f64;
+        let timestamps = bytes
+            .chunks_exact(mem::size_of::<u64>())
+            .map(|timestamp| u64::from_le_bytes(timestamp.try_into().unwrap_or_default()))
+            .collect::<Vec<_>>();
+
+        let passes = self
+            .passes
+            .iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(label, timestamps)| PassTiming {
+                label: label.clone(),
+                duration: Duration::from_nanos(
+                    (timestamps[1].saturating_sub(timestamps[0]) as f64 * period) as u64,
+                ),
+            })
+            .collect();
+
+        Ok(RenderTimings {
+            encoding: self.encoding.1,
+            passes: Some(passes),
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use super::Scene;
+use crate::high_level::{camera::bounds_in_frustum, pipeline::SceneContext};
+use glam::Mat4;
+use std::ops::DerefMut;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Works out which parts are in view when frustum culling is enabled.
+    ///
+    /// Which parts are in view changes with the camera, so the parts are uploaded again whenever that changes.
+    pub(super) fn update_visible_parts(&mut self) {
+        let view_projection = self.camera.get_view_projection_matrix();
+        let visible_parts = self
+            .frustum_culling
+            .then(|| self.compute_visible_parts(view_projection));
+
+        if visible_parts != self.visible_parts {
+            self.prepared_draws = None;
+            self.visible_parts = visible_parts;
+        }
+    }
+
+    /// Returns which of the parts (the player's, then each entity's) are in view, in any of the instances.
+    fn compute_visible_parts(&self, view_projection: Mat4) -> Vec<bool> {
+        let instance_transforms = if self.instances.is_empty() {
+            vec![Mat4::IDENTITY]
+        } else {
+            self.instances
+                .iter()
+                .map(|instance| instance.transform)
+                .collect()
+        };
+
+        let player_parts = self.computed_body_parts.iter().map(|part| {
+            let bounds = part.get_bounds();
+
+            instance_transforms
+                .iter()
+                .any(|&transform| bounds_in_frustum(view_projection, bounds.transformed(transform)))
+        });
+
+        let entity_parts = self.entities.iter().flat_map(|entity| {
+            entity.parts.iter().map(move |part| {
+                bounds_in_frustum(
+                    view_projection,
+                    part.get_bounds().transformed(entity.transform),
+                )
+            })
+        });
+
+        player_parts.chain(entity_parts).collect()
+    }
+}
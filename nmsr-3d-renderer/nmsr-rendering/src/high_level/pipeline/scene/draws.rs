@@ -0,0 +1,416 @@
+use super::{Scene, RenderMode};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::{
+        pipeline::{
+            atlas::TextureAtlas, materials::MaterialInformation, GraphicsContext, SceneContext,
+        },
+        utils::parts::primitive_convert,
+    },
+    low_level::primitives::{
+        mesh::{Mesh, PrimitiveDispatch},
+        part_primitive::PartPrimitive,
+        quad::Quad,
+    },
+};
+use glam::{Vec2, Vec3};
+use itertools::Itertools;
+use nmsr_player_parts::{
+    parts::{bounds::PartBounds, part::Part},
+    types::PlayerPartTextureType,
+};
+use std::{fmt::Display, ops::DerefMut};
+use tracing::trace_span;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, Buffer, FilterMode,
+    SamplerDescriptor,
+};
+
+/// What the parts of a draw are textured with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum DrawTexture {
+    Single(PlayerPartTextureType),
+    /// The texture atlas of the scene, shared by all the textures packed into it.
+    Atlas,
+    /// A texture of one of the entities of the scene, by index.
+    Entity(usize, PlayerPartTextureType),
+    /// The nameplate above the player.
+    Nameplate,
+}
+
+impl DrawTexture {
+    fn new(texture: PlayerPartTextureType, atlas: Option<&TextureAtlas>) -> Self {
+        if atlas.is_some_and(|a| a.contains(texture)) {
+            Self::Atlas
+        } else {
+            Self::Single(texture)
+        }
+    }
+
+    pub(super) fn is_shadow(&self) -> bool {
+        matches!(self, Self::Single(texture) | Self::Entity(_, texture) if texture.is_shadow())
+    }
+
+    pub(super) fn label(&self) -> &'static str {
+        match self {
+            Self::Single(texture) | Self::Entity(_, texture) => texture.into(),
+            Self::Atlas => "Texture Atlas",
+            Self::Nameplate => "Nameplate",
+        }
+    }
+
+    /// Whether the parts of the draw show up in the shadow map, casting shadows on the other parts.
+    pub(super) fn casts_shadow(&self) -> bool {
+        !self.is_shadow() && !matches!(self, Self::Nameplate)
+    }
+
+    /// Which of the instance buffers the draw uses: the scene's own, or the one of its entity.
+    pub(super) fn instance_set(&self) -> usize {
+        match self {
+            Self::Entity(entity, _) => entity + 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Display for DrawTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// The parts sharing a texture, uploaded and ready to be drawn.
+pub(super) struct TextureDraw {
+    pub(super) texture: DrawTexture,
+    pub(super) texture_bind_group: BindGroup,
+    material: MaterialInformation,
+    /// Rewritten whenever the draw is reused, since it holds the camera position.
+    material_buffer: Buffer,
+    pub(super) vertex_buffer: Buffer,
+    pub(super) index_buffer: Buffer,
+    pub(super) index_count: u32,
+    /// The edges of the triangles, when the scene is drawn as a wireframe.
+    pub(super) line_index_buffer: Option<(Buffer, u32)>,
+}
+
+impl TextureDraw {
+    fn set_camera_position(&mut self, graphics_context: &GraphicsContext, camera_position: Vec3) {
+        self.material.set_camera_position(camera_position);
+
+        graphics_context.queue.write_buffer(
+            &self.material_buffer,
+            0,
+            bytemuck::cast_slice(&[self.material]),
+        );
+    }
+}
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Returns the draws of the parts, reusing the ones of the last render when only the camera or the lighting
+    /// changed since, and uploading them again otherwise.
+    pub(super) fn take_part_draws(
+        &mut self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<Vec<TextureDraw>> {
+        let Some(mut draws) = self.prepared_draws.take() else {
+            return self.prepare_part_draws(graphics_context);
+        };
+
+        let camera_position = self.camera.get_world_position();
+        for draw in &mut draws {
+            draw.set_camera_position(graphics_context, camera_position);
+        }
+
+        Ok(draws)
+    }
+
+    /// Uploads the nameplate, which faces the camera, so it can't be reused between renders.
+    pub(super) fn prepare_nameplate_draw(
+        &self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<TextureDraw> {
+        let nameplate = self.create_nameplate_quad();
+
+        self.prepare_primitives_draw(
+            graphics_context,
+            DrawTexture::Nameplate,
+            vec![nameplate.into()],
+        )
+    }
+
+    /// Uploads the parts of the player and of the entities (that are in view, when culling), grouped by texture.
+    fn prepare_part_draws(&self, graphics_context: &GraphicsContext) -> Result<Vec<TextureDraw>> {
+        let mut visibility = self.visible_parts.iter().flatten().copied();
+
+        let mut draws = self
+            .computed_body_parts
+            .iter()
+            .filter(|_| visibility.next().unwrap_or(true))
+            .group_by(|p| DrawTexture::new(p.get_texture(), self.texture_atlas.as_ref()))
+            .into_iter()
+            .map(|(texture, parts)| self.prepare_draw(graphics_context, texture, parts))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (index, entity) in self.entities.iter().enumerate() {
+            let parts = entity
+                .parts
+                .iter()
+                .filter(|_| visibility.next().unwrap_or(true));
+
+            for (texture, parts) in &parts.group_by(|p| p.get_texture()) {
+                let texture = DrawTexture::Entity(index, texture);
+                draws.push(self.prepare_draw(graphics_context, texture, parts)?);
+            }
+        }
+
+        Ok(draws)
+    }
+
+    /// Uploads the parts sharing the given texture, and binds the texture to draw them with.
+    fn prepare_draw<'a>(
+        &self,
+        graphics_context: &GraphicsContext,
+        texture: DrawTexture,
+        parts: impl Iterator<Item = &'a Part>,
+    ) -> Result<TextureDraw> {
+        let parts = parts.collect::<Vec<&Part>>();
+
+        let to_render: Vec<_> = trace_span!("part_convert")
+            .in_scope(|| match texture {
+                // Only the parts of atlas draws have their uv coordinates moved into the atlas.
+                DrawTexture::Atlas => parts.iter().map(|&p| self.atlas_primitive_convert(p)).collect(),
+                _ => parts.iter().map(|&p| primitive_convert(p)).collect(),
+            });
+
+        self.prepare_primitives_draw(graphics_context, texture, to_render)
+    }
+
+    fn prepare_primitives_draw(
+        &self,
+        graphics_context: &GraphicsContext,
+        texture: DrawTexture,
+        to_render: Vec<PrimitiveDispatch>,
+    ) -> Result<TextureDraw> {
+        let device = &graphics_context.device;
+
+        let texture_view = match texture {
+            DrawTexture::Single(texture) => {
+                &self
+                    .textures
+                    .get(&texture)
+                    .ok_or(NMSRRenderingError::SceneContextTextureNotSet(texture))?
+                    .view
+            }
+            DrawTexture::Atlas => self
+                .texture_atlas
+                .as_ref()
+                .expect("Atlas draws are only made while the scene has an atlas")
+                .view(),
+            DrawTexture::Entity(entity, texture) => {
+                &self
+                    .entities
+                    .get(entity)
+                    .ok_or(NMSRRenderingError::SceneEntityNotFound(entity))?
+                    .textures
+                    .get(&texture)
+                    .ok_or(NMSRRenderingError::SceneContextTextureNotSet(texture))?
+                    .view
+            }
+            DrawTexture::Nameplate => {
+                &self
+                    .nameplate
+                    .as_ref()
+                    .expect("Nameplate draws are only made while the scene has a nameplate")
+                    .view
+            }
+        };
+
+        let filter = if texture.is_shadow() {
+            FilterMode::Linear
+        } else {
+            FilterMode::Nearest
+        };
+
+        let texture_sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some(texture.label()),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: None,
+            anisotropy_clamp: 1,
+            border_color: None,
+        });
+
+        let defaults = &graphics_context.default_material_maps;
+        // Textures with material maps are never packed into the atlas.
+        let (normal_map, specular_map) = match texture {
+            DrawTexture::Single(texture) => (
+                self.normal_maps.get(&texture).unwrap_or(&defaults.normal),
+                self.specular_maps.get(&texture).unwrap_or(&defaults.specular),
+            ),
+            _ => (&defaults.normal, &defaults.specular),
+        };
+
+        let texture_variants = match texture {
+            DrawTexture::Single(texture) => self.texture_variants.get(&texture).copied(),
+            _ => None,
+        };
+
+        let material = MaterialInformation::new(
+            self.camera.get_world_position(),
+            texture_variants.unwrap_or(1),
+            texture == DrawTexture::Nameplate,
+        );
+
+        let material_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Material Information Buffer"),
+            contents: bytemuck::cast_slice(&[material]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let texture_sampler_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &graphics_context.layouts.skin_sampler_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture_sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal_map.view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&specular_map.view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: material_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some(texture.label()),
+        });
+
+        let to_render = Mesh::new(to_render);
+
+        let (vertex_data, index_data) = (to_render.get_vertices(), to_render.get_indices());
+
+        let vertex_buf = trace_span!("vertex_buffer_create").in_scope(|| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertex_data),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+
+        let index_buf = trace_span!("index_buffer_create").in_scope(|| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Index Buffer"),
+                contents: bytemuck::cast_slice(&index_data),
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        });
+
+        let line_index_buf = (self.render_mode == RenderMode::Wireframe).then(|| {
+            let line_index_data = to_render.get_line_indices();
+
+            let buffer = trace_span!("line_index_buffer_create").in_scope(|| {
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Line Index Buffer"),
+                    contents: bytemuck::cast_slice(&line_index_data),
+                    usage: wgpu::BufferUsages::INDEX,
+                })
+            });
+
+            (buffer, line_index_data.len() as u32)
+        });
+
+        Ok(TextureDraw {
+            texture,
+            texture_bind_group: texture_sampler_bind_group,
+            material,
+            material_buffer,
+            vertex_buffer: vertex_buf,
+            index_buffer: index_buf,
+            index_count: index_data.len() as u32,
+            line_index_buffer: line_index_buf,
+        })
+    }
+
+    /// Converts a part to its primitive, moving its uv coordinates into the atlas if its texture is packed in it.
+    fn atlas_primitive_convert(&self, part: &Part) -> PrimitiveDispatch {
+        let primitive = primitive_convert(part);
+
+        let uv_transform = self
+            .texture_atlas
+            .as_ref()
+            .and_then(|atlas| atlas.get_uv_transform(part.get_texture()));
+
+        match uv_transform {
+            Some((offset, scale)) => Mesh::new(vec![primitive])
+                .with_uv_region(offset, scale)
+                .into(),
+            None => primitive,
+        }
+    }
+
+    /// Creates the quad of the nameplate, floating above the highest part of the player and facing the camera.
+    fn create_nameplate_quad(&self) -> Quad {
+        /// How big a pixel of the nameplate is, compared to a pixel of the skin (like in game).
+        const NAMEPLATE_PIXEL_SIZE: f32 = 0.4;
+        /// How far above the player the bottom of the nameplate is.
+        const NAMEPLATE_OFFSET: f32 = 3.0;
+
+        let nameplate = &self
+            .nameplate
+            .as_ref()
+            .expect("Nameplate quads are only made while the scene has a nameplate")
+            .texture;
+
+        let PartBounds { min, max } = self
+            .get_bounds(false)
+            .unwrap_or(PartBounds::new(Vec3::ZERO, Vec3::ZERO));
+
+        let half_width = nameplate.width() as f32 * NAMEPLATE_PIXEL_SIZE / 2.0;
+        let half_height = nameplate.height() as f32 * NAMEPLATE_PIXEL_SIZE / 2.0;
+        let center = Vec3::new(
+            (min.x + max.x) / 2.0,
+            max.y + NAMEPLATE_OFFSET + half_height,
+            (min.z + max.z) / 2.0,
+        );
+
+        // Face the camera, with the text upright.
+        let facing = (self.camera.get_world_position() - center)
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Z);
+        let right = Vec3::Y.cross(facing).try_normalize().unwrap_or(Vec3::NEG_X);
+        let up = facing.cross(right);
+
+        let (right, up) = (right * half_width, up * half_height);
+
+        Quad::new_with_normal(
+            center - right + up,
+            center + right + up,
+            center - right - up,
+            center + right - up,
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            facing,
+        )
+    }
+}
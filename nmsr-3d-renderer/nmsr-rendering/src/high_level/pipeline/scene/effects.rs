@@ -0,0 +1,59 @@
+use super::Scene;
+use crate::high_level::pipeline::{
+    ambient_occlusion::AmbientOcclusionParameters,
+    outline::OutlineParameters,
+    post_processing::{AmbientOcclusionPass, OutlinePass},
+    profiling::GpuProfiler,
+    GraphicsContext, OutputPipelines, SceneContext,
+};
+use glam::Mat4;
+use std::ops::DerefMut;
+use wgpu::TextureView;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Whether the scene is drawn into an intermediate texture first, for effects to be applied on top of it.
+    pub(super) fn has_effects(&self) -> bool {
+        self.ambient_occlusion.is_some() || self.outline.is_some()
+    }
+
+    /// Applies the ambient occlusion and the outline (when enabled) to the drawn scene, writing it into `output`.
+    pub(super) fn apply_effects(
+        &self,
+        graphics_context: &GraphicsContext,
+        pipelines: &OutputPipelines,
+        profiler: &mut GpuProfiler,
+        output: &TextureView,
+        depth: &TextureView,
+        view_projection: Mat4,
+    ) {
+        if self.scene_context.post_processing.scene_target().is_none() {
+            return;
+        }
+
+        let pipelines = pipelines.get_post_processing(&graphics_context.device);
+
+        let ambient_occlusion = self.ambient_occlusion.map(|settings| AmbientOcclusionPass {
+            parameters: AmbientOcclusionParameters::new(&settings, view_projection),
+            pipeline: &pipelines.ambient_occlusion,
+            depth,
+        });
+
+        let outline = self.outline.map(|settings| OutlinePass {
+            parameters: OutlineParameters::new(&settings, view_projection, self.supersampling),
+            pipeline: &pipelines.outline,
+            depth,
+        });
+
+        self.scene_context.post_processing.apply(
+            graphics_context,
+            pipelines,
+            profiler,
+            output,
+            ambient_occlusion,
+            outline,
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use super::Scene;
+use crate::high_level::pipeline::{
+    instancing::{InstanceInformation, SceneInstance},
+    GraphicsContext, SceneContext,
+};
+use std::{mem, ops::DerefMut};
+use wgpu::{Buffer, BufferDescriptor, BufferUsages};
+
+/// A set of instances uploaded for drawing, kept between renders and only recreated when they outgrow it.
+#[derive(Debug)]
+pub(super) struct InstanceBuffer {
+    pub(super) buffer: Buffer,
+    /// How many instances the buffer has room for.
+    capacity: usize,
+    /// How many instances were written to the buffer by the last render.
+    pub(super) count: u32,
+}
+
+impl InstanceBuffer {
+    fn new(graphics_context: &GraphicsContext, capacity: usize) -> Self {
+        let buffer = graphics_context.device.create_buffer(&BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * mem::size_of::<InstanceInformation>()) as u64,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            capacity,
+            count: 0,
+        }
+    }
+}
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Uploads the instances of the scene (falling back to a single untransformed one when there are none),
+    /// followed by the single instance of each entity, placing it in the scene.
+    ///
+    /// The buffers of the last render are written over when the instances still fit in them.
+    pub(super) fn upload_instances(&mut self, graphics_context: &GraphicsContext) {
+        let instances = if self.instances.is_empty() {
+            vec![InstanceInformation::from(&SceneInstance::default())]
+        } else {
+            self.instances.iter().map(InstanceInformation::from).collect()
+        };
+
+        let entity_instances = self.entities.iter().map(|entity| {
+            vec![InstanceInformation::from(&SceneInstance::new(
+                entity.transform,
+                0,
+            ))]
+        });
+
+        let instance_sets = std::iter::once(instances)
+            .chain(entity_instances)
+            .collect::<Vec<_>>();
+
+        self.instance_buffers.truncate(instance_sets.len());
+
+        for (index, instances) in instance_sets.iter().enumerate() {
+            let fits = self
+                .instance_buffers
+                .get(index)
+                .is_some_and(|buffer| buffer.capacity >= instances.len());
+
+            if !fits {
+                let buffer = InstanceBuffer::new(graphics_context, instances.len());
+
+                match self.instance_buffers.get_mut(index) {
+                    Some(existing) => *existing = buffer,
+                    None => self.instance_buffers.push(buffer),
+                }
+            }
+
+            let buffer = &mut self.instance_buffers[index];
+            graphics_context
+                .queue
+                .write_buffer(&buffer.buffer, 0, bytemuck::cast_slice(instances));
+            buffer.count = instances.len() as u32;
+        }
+    }
+}
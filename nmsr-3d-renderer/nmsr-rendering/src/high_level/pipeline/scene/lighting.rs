@@ -0,0 +1,192 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+
+/// The maximum number of lights a scene can have, besides the sun. This matches the size of the array in the shader.
+const MAX_LIGHTS: usize = 8;
+
+/// The lighting of a scene: a sun, and any number of extra lights (up to [`SunInformation::MAX_LIGHTS`]),
+/// like rim or fill lights.
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub struct SunInformation {
+    pub direction: Vec3,
+    pub intensity: f32,
+    pub ambient: f32,
+    light_count: u32,
+    _padding: [u32; 2],
+    lights: [LightInformation; MAX_LIGHTS],
+}
+
+impl Default for SunInformation {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::ONE,
+            intensity: 1.0,
+            ambient: Self::DEFAULT_AMBIENT_LIGHT,
+            light_count: 0,
+            _padding: [0; 2],
+            lights: [LightInformation::zeroed(); Self::MAX_LIGHTS],
+        }
+    }
+}
+
+impl SunInformation {
+    pub const DEFAULT_AMBIENT_LIGHT: f32 = 0.1;
+    pub const MAX_LIGHTS: usize = MAX_LIGHTS;
+
+    /// Creates lighting made of a single white sun, shining along the given direction.
+    pub fn new(direction: Vec3, intensity: f32, ambient: f32) -> Self {
+        Self {
+            direction,
+            intensity,
+            ambient,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a light to the scene, on top of the sun.
+    ///
+    /// Returns false (and leaves the lighting as-is) if there are already [`Self::MAX_LIGHTS`] lights.
+    pub fn add_light(&mut self, light: Light) -> bool {
+        let Some(slot) = self.lights.get_mut(self.light_count as usize) else {
+            return false;
+        };
+
+        *slot = light.into();
+        self.light_count += 1;
+
+        true
+    }
+
+    pub fn with_light(mut self, light: Light) -> Self {
+        self.add_light(light);
+        self
+    }
+
+    /// Returns the lights of the scene, besides the sun.
+    pub fn lights(&self) -> impl Iterator<Item = Light> + '_ {
+        self.lights[..self.light_count as usize]
+            .iter()
+            .map(|light| (*light).into())
+    }
+
+    /// Removes every light but the sun.
+    pub fn clear_lights(&mut self) {
+        self.light_count = 0;
+        self.lights = [LightInformation::zeroed(); Self::MAX_LIGHTS];
+    }
+}
+
+/// A light shining on the scene, besides the sun.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Light {
+    /// A light infinitely far away, shining along the given direction, like the sun.
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+    },
+    /// A light shining in every direction from the given position, fading out until it's out of range.
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+    },
+}
+
+/// A [`Light`], laid out the way the shader expects it.
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+struct LightInformation {
+    /// The direction of a directional light, or the position of a point light.
+    vector: Vec3,
+    kind: u32,
+    color: Vec3,
+    intensity: f32,
+    range: f32,
+    _padding: [f32; 3],
+}
+
+impl LightInformation {
+    const DIRECTIONAL: u32 = 0;
+    const POINT: u32 = 1;
+}
+
+impl From<Light> for LightInformation {
+    fn from(light: Light) -> Self {
+        let (vector, kind, color, intensity, range) = match light {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => (direction, Self::DIRECTIONAL, color, intensity, 0.0),
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => (position, Self::POINT, color, intensity, range),
+        };
+
+        Self {
+            vector,
+            kind,
+            color,
+            intensity,
+            range,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+impl From<LightInformation> for Light {
+    fn from(light: LightInformation) -> Self {
+        if light.kind == LightInformation::POINT {
+            Self::Point {
+                position: light.vector,
+                color: light.color,
+                intensity: light.intensity,
+                range: light.range,
+            }
+        } else {
+            Self::Directional {
+                direction: light.vector,
+                color: light.color,
+                intensity: light.intensity,
+            }
+        }
+    }
+}
+
+impl Light {
+    /// Computes how much this light lights up a surface facing the given normal, at the given position.
+    pub fn compute_lighting(&self, normal: Vec3, position: Vec3) -> Vec3 {
+        match *self {
+            Self::Directional {
+                direction,
+                color,
+                intensity,
+            } => color * intensity * normal.dot(-direction.normalize_or_zero()).max(0.0),
+            Self::Point {
+                position: light_position,
+                color,
+                intensity,
+                range,
+            } => {
+                let to_light = light_position - position;
+                let distance = to_light.length();
+                let attenuation = if range > 0.0 {
+                    (1.0 - distance / range).clamp(0.0, 1.0).powi(2)
+                } else {
+                    0.0
+                };
+
+                color
+                    * intensity
+                    * attenuation
+                    * normal.dot(to_light.normalize_or_zero()).max(0.0)
+            }
+        }
+    }
+}
@@ -0,0 +1,973 @@
+mod culling;
+mod draws;
+mod effects;
+mod instances;
+mod lighting;
+mod passes;
+mod posing;
+mod readback;
+mod shadow_pass;
+mod textures;
+#[cfg(feature = "profiling")]
+mod timing;
+
+pub use lighting::{Light, SunInformation};
+
+use self::{draws::TextureDraw, instances::InstanceBuffer, passes::RenderTarget};
+use super::{
+    ambient_occlusion::AmbientOcclusionSettings,
+    atlas::TextureAtlas,
+    background::{PreparedBackground, SceneBackground},
+    entities::SceneEntity,
+    instancing::SceneInstance,
+    outline::OutlineSettings,
+    shader_hooks::SceneShader,
+    shadows::ShadowMapSettings,
+    textures::SceneTexture,
+    GraphicsContext, SceneContextWrapper,
+};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::{
+        camera::Camera, pipeline::SceneContext, utils::nameplate::rasterize_nameplate,
+    },
+};
+use glam::{Mat4, Vec4};
+use image::RgbaImage;
+use nmsr_player_parts::{
+    model::{ArmorMaterial, PlayerBodyProportions},
+    parts::{
+        bounds::PartBounds,
+        part::Part,
+        provider::{PartsProvider, PlayerPartProviderContext, PlayerPartsProvider},
+    },
+    types::{PlayerBodyPartType, PlayerPartTextureType},
+};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    mem,
+    ops::{Deref, DerefMut},
+};
+use tracing::{instrument, trace_span};
+use wgpu::{CommandEncoder, TextureFormat, TextureView};
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A rectangular region of a texture, in pixels.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How the parts of a scene are drawn.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Textured and lit, as usual.
+    #[default]
+    Shaded,
+    /// Only the edges of the triangles of the parts, for debugging part providers and uv coordinates.
+    Wireframe,
+}
+
+pub struct Scene<T = SceneContextWrapper>
+where
+    T: Deref<Target = SceneContext> + Send + Sync,
+{
+    camera: Camera,
+    viewport_size: Size,
+    scene_context: T,
+    textures: HashMap<PlayerPartTextureType, SceneTexture>,
+    normal_maps: HashMap<PlayerPartTextureType, SceneTexture>,
+    specular_maps: HashMap<PlayerPartTextureType, SceneTexture>,
+    /// How many variants are stacked in each of the textures that have more than one.
+    texture_variants: HashMap<PlayerPartTextureType, u32>,
+    /// The copies of the player to draw, or just the one as-is when empty.
+    instances: Vec<SceneInstance>,
+    /// The other models drawn alongside the player.
+    entities: Vec<SceneEntity>,
+    /// The rasterized nameplate shown above the player, if any.
+    nameplate: Option<SceneTexture>,
+    computed_body_parts: Vec<Part>,
+    /// The computed parts in their resting pose, kept around once the scene is posed.
+    rest_body_parts: Option<Vec<Part>>,
+    proportions: PlayerBodyProportions,
+    sun_information: SunInformation,
+    shadow_mapping: Option<ShadowMapSettings>,
+    ambient_occlusion: Option<AmbientOcclusionSettings>,
+    outline: Option<OutlineSettings>,
+    background: PreparedBackground,
+    supersampling: u32,
+    sample_count: Option<u32>,
+    /// The format the scene is rendered into, instead of the graphics context's.
+    output_format: Option<TextureFormat>,
+    texture_atlas_enabled: bool,
+    /// The atlas the textures are packed into, built on the next render whenever it's enabled and missing.
+    texture_atlas: Option<TextureAtlas>,
+    depth_readback: bool,
+    render_mode: RenderMode,
+    /// The custom shader the scene is drawn with, if any.
+    shader: Option<SceneShader>,
+    /// The uploaded parts and entities of the last render, reused as long as only the camera or the lighting changes.
+    prepared_draws: Option<Vec<TextureDraw>>,
+    /// The instances of the last render (the player's, then each entity's), written over by the next one.
+    instance_buffers: Vec<InstanceBuffer>,
+    frustum_culling: bool,
+    /// Which of the parts (the player's, then each entity's) were in view when the draws were prepared, if culling.
+    visible_parts: Option<Vec<bool>>,
+}
+
+type ExtraRenderFunc<'a> =
+    Box<dyn FnOnce(&TextureView, &mut CommandEncoder, &mut Camera, &mut SunInformation) + 'a>;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    const RECTANGLE_SHADOW_BYTES: &'static [u8] = include_bytes!("../shadow_rectangle.png");
+    const SQUARE_SHADOW_BYTES: &'static [u8] = include_bytes!("../shadow_square.png");
+
+    pub fn get_shadow_bytes(is_square: bool) -> &'static [u8] {
+        if is_square {
+            Self::SQUARE_SHADOW_BYTES
+        } else {
+            Self::RECTANGLE_SHADOW_BYTES
+        }
+    }
+
+    pub fn new<M: ArmorMaterial>(
+        graphics_context: &GraphicsContext,
+        mut scene_context: T,
+        mut camera: Camera,
+        sun: SunInformation,
+        viewport_size: Size,
+        part_context: &PlayerPartProviderContext<M>,
+        body_parts: &[PlayerBodyPartType],
+    ) -> Self {
+        // Initialize our camera with the viewport size
+        Self::update_scene_context(
+            &mut camera,
+            &sun,
+            viewport_size,
+            1,
+            graphics_context
+                .multisampling_strategy
+                .get_msaa_sample_count(),
+            graphics_context.texture_format,
+            &mut scene_context,
+            graphics_context,
+        );
+
+        // Compute the body parts we need to render
+        let computed_body_parts = Self::collect_player_parts(part_context, body_parts);
+
+        let mut scene = Self {
+            camera,
+            viewport_size,
+            scene_context,
+            textures: HashMap::new(),
+            normal_maps: HashMap::new(),
+            specular_maps: HashMap::new(),
+            texture_variants: HashMap::new(),
+            instances: Vec::new(),
+            entities: Vec::new(),
+            nameplate: None,
+            computed_body_parts,
+            rest_body_parts: None,
+            proportions: part_context.proportions,
+            sun_information: sun,
+            shadow_mapping: None,
+            ambient_occlusion: None,
+            outline: None,
+            background: PreparedBackground::Transparent,
+            supersampling: 1,
+            sample_count: None,
+            output_format: None,
+            texture_atlas_enabled: false,
+            texture_atlas: None,
+            depth_readback: false,
+            render_mode: RenderMode::Shaded,
+            shader: None,
+            prepared_draws: None,
+            instance_buffers: Vec::new(),
+            frustum_culling: false,
+            visible_parts: None,
+        };
+
+        if part_context.shadow_y_pos.is_some() {
+            // We need to render the shadow, so upload the shadow texture already
+            let shadow_image = Self::load_shadow_image(part_context.shadow_is_square);
+
+            scene.set_texture(
+                graphics_context,
+                PlayerPartTextureType::Shadow,
+                &shadow_image,
+            );
+        }
+
+        scene
+    }
+
+    fn load_shadow_image(is_square: bool) -> RgbaImage {
+        let shadow_bytes = Self::get_shadow_bytes(is_square);
+
+        image::load_from_memory_with_format(shadow_bytes, image::ImageFormat::Png)
+            .expect("Failed to load shadow texture")
+            .into_rgba8()
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn sun_information_mut(&mut self) -> &mut SunInformation {
+        &mut self.sun_information
+    }
+
+    /// Sets whether the parts cast shadows onto each other (and the ground, when it's rendered), and how.
+    ///
+    /// This is on top of the blob shadow under the player, which is controlled by the part context.
+    pub fn set_shadow_mapping(&mut self, settings: Option<ShadowMapSettings>) {
+        self.shadow_mapping = settings;
+    }
+
+    pub fn shadow_mapping(&self) -> Option<ShadowMapSettings> {
+        self.shadow_mapping
+    }
+
+    /// Sets whether the creases between parts are darkened with screen-space ambient occlusion, and how.
+    pub fn set_ambient_occlusion(&mut self, settings: Option<AmbientOcclusionSettings>) {
+        self.ambient_occlusion = settings;
+    }
+
+    pub fn ambient_occlusion(&self) -> Option<AmbientOcclusionSettings> {
+        self.ambient_occlusion
+    }
+
+    /// Sets whether a stylized border is drawn around the player (and the parts in front of others), and how.
+    pub fn set_outline(&mut self, settings: Option<OutlineSettings>) {
+        self.outline = settings;
+    }
+
+    pub fn outline(&self) -> Option<OutlineSettings> {
+        self.outline
+    }
+
+    /// Sets what is drawn behind the player. Any image is uploaded right away.
+    pub fn set_background(&mut self, graphics_context: &GraphicsContext, background: &SceneBackground) {
+        self.background = PreparedBackground::new(graphics_context, background);
+    }
+
+    /// Sets how many times bigger (in each direction) the scene is drawn internally, before being scaled
+    /// back down to the camera's size. This smooths out edges that multisampling misses, like the ones of thin
+    /// layer quads, at the cost of drawing (factor squared) times as many pixels.
+    pub fn set_supersampling(&mut self, graphics_context: &GraphicsContext, factor: u32) {
+        self.supersampling = factor.max(1);
+        self.update(graphics_context);
+    }
+
+    pub fn supersampling(&self) -> u32 {
+        self.supersampling
+    }
+
+    /// Overrides the number of MSAA samples this scene is drawn with, instead of the graphics context's.
+    ///
+    /// This allows e.g. heavy anti-aliasing for large renders, and none at all for tiny ones.
+    /// Fails if the adapter doesn't support the sample count, in which case the scene is left as it was.
+    pub fn set_sample_count(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        sample_count: Option<u32>,
+    ) -> Result<()> {
+        if let Some(sample_count) = sample_count {
+            let format = self.get_output_format(graphics_context);
+            if !graphics_context.is_sample_count_supported_with(format, sample_count) {
+                return Err(NMSRRenderingError::UnsupportedSampleCount(sample_count));
+            }
+        }
+
+        self.sample_count = sample_count;
+        self.update(graphics_context);
+
+        Ok(())
+    }
+
+    /// Returns the number of MSAA samples this scene is drawn with.
+    pub fn get_sample_count(&self, graphics_context: &GraphicsContext) -> u32 {
+        self.sample_count.unwrap_or_else(|| {
+            graphics_context
+                .multisampling_strategy
+                .get_msaa_sample_count()
+        })
+    }
+
+    /// Overrides the format this scene is rendered into, instead of the graphics context's
+    /// (e.g. [`Rgba16Float`](TextureFormat::Rgba16Float) to keep more precision for [`Scene::copy_output_hdr`]).
+    ///
+    /// The format is adjusted to the [color space](ColorSpace) of the context, so asking for
+    /// [`Rgba8UnormSrgb`](TextureFormat::Rgba8UnormSrgb) in the sRGB color space renders into
+    /// [`Rgba8Unorm`](TextureFormat::Rgba8Unorm), which reads back the same colors.
+    /// Renders are still read back as RGBA8 by [`Scene::copy_output_texture`], whatever the format.
+    /// Scenes drawn to a surface always use the context's format.
+    ///
+    /// Fails if the adapter can't render into the format with the scene's sample count,
+    /// in which case the scene is left as it was.
+    pub fn set_output_format(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        format: Option<TextureFormat>,
+    ) -> Result<()> {
+        let format = format.map(|format| graphics_context.color_space.apply_to_format(format));
+
+        if let Some(format) = format.filter(|&format| format != graphics_context.texture_format) {
+            if !graphics_context.is_output_format_supported(format) {
+                return Err(NMSRRenderingError::UnsupportedOutputFormat(format));
+            }
+
+            let sample_count = self.get_sample_count(graphics_context);
+            if !graphics_context.is_sample_count_supported_with(format, sample_count) {
+                return Err(NMSRRenderingError::UnsupportedSampleCount(sample_count));
+            }
+        }
+
+        self.output_format = format;
+        self.update(graphics_context);
+
+        Ok(())
+    }
+
+    /// Returns the format this scene is rendered into.
+    pub fn get_output_format(&self, graphics_context: &GraphicsContext) -> TextureFormat {
+        match self.output_format {
+            Some(format) if graphics_context.surface.is_none() => format,
+            _ => graphics_context.texture_format,
+        }
+    }
+
+    pub fn viewport_size_mut(&mut self) -> &mut Size {
+        &mut self.viewport_size
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.computed_body_parts
+    }
+
+    /// Consumes the scene, giving back the context it was rendering with.
+    pub fn into_scene_context(self) -> T {
+        self.scene_context
+    }
+
+    /// Sets the copies of the player drawn by the scene, all at once with instancing.
+    ///
+    /// Every instance shares the parts of the scene, so this is meant for crowds of players with the same model
+    /// and pose, or for drawing the same player from many angles (like a sprite sheet).
+    /// Shadow maps are only sized to fit the parts themselves, so instances placed far away may not cast shadows.
+    /// An empty list draws the player once, as-is.
+    pub fn set_instances(&mut self, instances: Vec<SceneInstance>) {
+        self.instances = instances;
+    }
+
+    pub fn instances(&self) -> &[SceneInstance] {
+        &self.instances
+    }
+
+    /// Adds another model to the scene, with its own parts (computed from the given part context) and placement.
+    ///
+    /// Returns the index of the new entity, to set its textures with [`Self::set_entity_texture`].
+    /// Entities are lit like the player, but don't use the texture atlas, texture variants or material maps,
+    /// and are drawn once regardless of the scene's instances.
+    pub fn add_entity<M: ArmorMaterial>(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        part_context: &PlayerPartProviderContext<M>,
+        body_parts: &[PlayerBodyPartType],
+        transform: Mat4,
+    ) -> usize {
+        let parts = Self::collect_player_parts(part_context, body_parts);
+        let mut entity = SceneEntity::new(parts, transform);
+
+        if part_context.shadow_y_pos.is_some() {
+            let shadow_image = Self::load_shadow_image(part_context.shadow_is_square);
+
+            entity.textures.insert(
+                PlayerPartTextureType::Shadow,
+                SceneContext::upload_texture(
+                    graphics_context,
+                    &shadow_image,
+                    Some(PlayerPartTextureType::Shadow.into()),
+                ),
+            );
+        }
+
+        self.entities.push(entity);
+        self.prepared_draws = None;
+        self.entities.len() - 1
+    }
+
+    pub fn set_entity_texture(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        entity: usize,
+        texture_type: PlayerPartTextureType,
+        texture: &RgbaImage,
+    ) -> Result<()> {
+        let entity = self
+            .entities
+            .get_mut(entity)
+            .ok_or(NMSRRenderingError::SceneEntityNotFound(entity))?;
+
+        let texture =
+            SceneContext::upload_texture(graphics_context, texture, Some(texture_type.into()));
+        entity.textures.insert(texture_type, texture);
+        self.prepared_draws = None;
+
+        Ok(())
+    }
+
+    pub fn entities(&self) -> &[SceneEntity] {
+        &self.entities
+    }
+
+    pub fn entity_mut(&mut self, entity: usize) -> Option<&mut SceneEntity> {
+        self.prepared_draws = None;
+        self.entities.get_mut(entity)
+    }
+
+    pub fn clear_entities(&mut self) {
+        self.entities.clear();
+        self.prepared_draws = None;
+    }
+
+    /// Sets (or removes) the nameplate shown above the player's head, like the name tags in game.
+    ///
+    /// The nameplate always faces the camera and isn't affected by lighting.
+    /// Characters outside of printable ASCII are shown as `?`.
+    pub fn set_nameplate(&mut self, graphics_context: &GraphicsContext, text: Option<&str>) {
+        self.nameplate = text.map(|text| {
+            SceneContext::upload_texture(
+                graphics_context,
+                &rasterize_nameplate(text),
+                Some("Nameplate"),
+            )
+        });
+    }
+
+    pub fn has_nameplate(&self) -> bool {
+        self.nameplate.is_some()
+    }
+
+    /// Enables (or disables) packing the textures of the scene into a single texture atlas.
+    ///
+    /// With the atlas, the parts of all the packed textures are drawn together, with one bind group,
+    /// instead of once per texture. This mostly pays off when rendering many scenes with armor or capes.
+    /// The shadow, and textures with variants or normal or specular maps, are always drawn on their own.
+    pub fn set_texture_atlas(&mut self, enabled: bool) {
+        self.texture_atlas_enabled = enabled;
+        self.texture_atlas = None;
+        self.prepared_draws = None;
+    }
+
+    pub fn is_texture_atlas_enabled(&self) -> bool {
+        self.texture_atlas_enabled
+    }
+
+    /// Enables (or disables) keeping the depth of renders, to be read back with [`Scene::copy_depth_texture`].
+    pub fn set_depth_readback(&mut self, enabled: bool) {
+        self.depth_readback = enabled;
+    }
+
+    pub fn is_depth_readback_enabled(&self) -> bool {
+        self.depth_readback
+    }
+
+    /// Enables (or disables) leaving out the parts that are entirely out of view of the camera, before uploading them.
+    ///
+    /// This is meant for tight crops of big scenes (like a face out of a crowd), where most of the parts aren't seen.
+    /// Parts are culled by their bounds, so custom shaders moving vertices around can make parts go missing,
+    /// and culled parts don't cast shadows into view either.
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
+
+    pub fn is_frustum_culling_enabled(&self) -> bool {
+        self.frustum_culling
+    }
+
+    /// Sets how the parts are drawn, like as wireframes to see their geometry.
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.render_mode = mode;
+        self.prepared_draws = None;
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    /// Sets the custom shader the parts are drawn with, or goes back to the built-in one.
+    ///
+    /// Its pipeline is created on the first render using it, and shared with every scene using the same shader.
+    /// The wireframe render mode still draws with the built-in wireframe shader.
+    pub fn set_shader(&mut self, shader: Option<SceneShader>) {
+        self.shader = shader;
+    }
+
+    pub fn shader(&self) -> Option<&SceneShader> {
+        self.shader.as_ref()
+    }
+
+    #[instrument(skip(part_provider_context))]
+    pub(crate) fn collect_player_parts<C: ArmorMaterial>(
+        part_provider_context: &PlayerPartProviderContext<C>,
+        body_parts: &[PlayerBodyPartType],
+    ) -> Vec<Part> {
+        let providers = [
+            PlayerPartsProvider::Minecraft,
+            PlayerPartsProvider::ShoulderBuddies,
+            PlayerPartsProvider::Deadmau5Ears,
+            #[cfg(feature = "ears")]
+            PlayerPartsProvider::Ears,
+        ];
+
+        let mut parts = providers
+            .iter()
+            .flat_map(|provider| {
+                body_parts
+                    .iter()
+                    .flat_map(|part| provider.get_parts(part_provider_context, *part))
+            })
+            .collect::<Vec<Part>>();
+
+        // Sort the parts by texture. This allows us to render all parts with the same texture in one go.
+        parts.sort_by_key(|p| p.get_texture());
+
+        parts
+    }
+
+    pub fn render(&mut self, graphics_context: &GraphicsContext) -> Result<()> {
+        self.render_with_extra(graphics_context, None)
+    }
+
+    #[instrument(skip(self, graphics_context, extra_rendering))]
+    pub fn render_with_extra(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        extra_rendering: Option<ExtraRenderFunc>,
+    ) -> Result<()> {
+        let device = &graphics_context.device;
+        let queue = &graphics_context.queue;
+
+        // Scenes drawn with a different sample count or format than the default need pipelines of their own.
+        let sample_count = self.get_sample_count(graphics_context);
+        let output_format = self.get_output_format(graphics_context);
+        let output_pipelines =
+            graphics_context.get_output_pipelines(sample_count, output_format)?;
+
+        let custom_pipeline =
+            match (self.render_mode, &self.shader) {
+                (RenderMode::Wireframe, _) => {
+                    Some(graphics_context.get_wireframe_pipeline(sample_count, output_format))
+                }
+                (RenderMode::Shaded, Some(shader)) => Some(
+                    graphics_context.get_scene_shader_pipeline(shader, sample_count, output_format),
+                ),
+                (RenderMode::Shaded, None) => None,
+            };
+        let pipeline = custom_pipeline
+            .as_deref()
+            .unwrap_or(&output_pipelines.pipeline);
+
+        self.prepare_render(graphics_context, output_format)?;
+        let view_projection = self.camera.get_view_projection_matrix();
+
+        let mut draws = self.take_part_draws(graphics_context)?;
+        let part_draw_count = draws.len();
+
+        if self.nameplate.is_some() {
+            draws.push(self.prepare_nameplate_draw(graphics_context)?);
+        }
+
+        self.upload_instances(graphics_context);
+
+        let smaa_target = self.scene_context.smaa_target.take();
+
+        let mut smaa_target = match smaa_target {
+            Some(target) => target,
+            _ => unreachable!("SMAA target is always initialized"),
+        };
+
+        let mut profiler = mem::take(&mut self.scene_context.profiler);
+        profiler.begin_frame(graphics_context);
+
+        let textures = self
+            .scene_context
+            .textures
+            .as_ref()
+            .ok_or(NMSRRenderingError::SceneContextTexturesNotInitialized)?;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let surface_texture = graphics_context
+            .surface
+            .as_ref()
+            .and_then(|s| s.get_current_texture().ok());
+
+        // The surface may be in another color space than we render in, so view it in ours.
+        let surface_texture_view = surface_texture.as_ref().map(|t| {
+            t.texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(graphics_context.texture_format),
+                ..Default::default()
+            })
+        });
+
+        let final_view = surface_texture_view
+            .as_ref()
+            .unwrap_or(&textures.output_texture.view);
+
+        // When supersampling, the scene is drawn bigger first, and scaled down into the final view at the end.
+        let render_view = textures
+            .supersampled_output_texture
+            .as_ref()
+            .map_or(final_view, |texture| &texture.view);
+
+        // When there are effects to apply, the scene is drawn into an intermediate texture first.
+        let scene_view = self
+            .scene_context
+            .post_processing
+            .scene_target()
+            .unwrap_or(render_view);
+
+        let smaa_frame = smaa_target.start_frame(device, queue, scene_view);
+
+        let target = match &textures.multisampled_output_texture {
+            Some(multisampled_view) => RenderTarget {
+                view: &multisampled_view.view,
+                resolve_target: Some(&*smaa_frame),
+                depth: &textures.depth_texture.view,
+            },
+            None => RenderTarget {
+                view: &smaa_frame,
+                resolve_target: None,
+                depth: &textures.depth_texture.view,
+            },
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Scene rendering (NMSR)"),
+        });
+
+        if self.shadow_mapping.is_some() {
+            self.render_shadow_map(graphics_context, &mut profiler, &mut encoder, &draws);
+        }
+
+        let background_drawn = self.render_background(
+            graphics_context,
+            &mut encoder,
+            &mut profiler,
+            &output_pipelines,
+            &target,
+        );
+
+        self.render_parts(
+            &mut encoder,
+            &mut profiler,
+            pipeline,
+            &draws,
+            &target,
+            background_drawn,
+        );
+
+        queue.submit(Some(encoder.finish()));
+
+        // Explicitly drop the smaa frame so that it is resolved before we copy it to the output buffer.
+        drop(smaa_frame);
+
+        self.apply_effects(
+            graphics_context,
+            &output_pipelines,
+            &mut profiler,
+            render_view,
+            &textures.depth_texture.view,
+            view_projection,
+        );
+
+        if textures.supersampled_output_texture.is_some() {
+            output_pipelines.get_post_processing(device).downsample(
+                graphics_context,
+                &mut profiler,
+                render_view,
+                final_view,
+                textures.supersampling,
+            );
+        }
+
+        Self::copy_to_output_buffer(graphics_context, textures);
+
+        self.scene_context.depth_readback.resolve(
+            graphics_context,
+            &textures.depth_texture.view,
+            textures.supersampling,
+        );
+
+        if let Some(extra_rendering) = extra_rendering {
+            let mut extra_encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+            extra_rendering(
+                final_view,
+                &mut extra_encoder,
+                &mut self.camera,
+                &mut self.sun_information,
+            );
+
+            queue.submit(Some(extra_encoder.finish()));
+        }
+
+        if let Some(surface_texture) = surface_texture {
+            surface_texture.present();
+        }
+
+        profiler.end_frame(graphics_context);
+
+        self.scene_context.smaa_target = Some(smaa_target);
+        self.scene_context.profiler = profiler;
+
+        draws.truncate(part_draw_count);
+        self.prepared_draws = Some(draws);
+
+        Ok(())
+    }
+
+    /// Gets the scene context ready for the passes the next render needs, and brings the texture atlas
+    /// and the culled parts up to date.
+    fn prepare_render(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        output_format: TextureFormat,
+    ) -> Result<()> {
+        let context_textures = self.scene_context.try_textures()?;
+        let render_size = context_textures.render_size();
+        let (camera_size, viewport_size, multisampled) = (
+            context_textures.camera_size,
+            context_textures.viewport_size,
+            context_textures.sample_count > 1,
+        );
+
+        let has_effects = self.has_effects();
+        self.scene_context.post_processing.prepare(
+            graphics_context,
+            output_format,
+            render_size,
+            has_effects,
+        );
+        self.scene_context.depth_readback.prepare(
+            graphics_context,
+            self.depth_readback,
+            camera_size,
+            viewport_size,
+            multisampled,
+        );
+        self.scene_context.shadow_map.prepare(
+            graphics_context,
+            self.shadow_mapping,
+            self.sun_information.direction,
+            &self.computed_body_parts,
+        );
+
+        if self.texture_atlas_enabled && self.texture_atlas.is_none() {
+            self.prepared_draws = None;
+            self.texture_atlas = trace_span!("texture_atlas_create").in_scope(|| {
+                TextureAtlas::new(
+                    graphics_context,
+                    self.textures
+                        .iter()
+                        .filter(|(texture, _)| self.is_atlas_compatible(texture)),
+                )
+            });
+        }
+
+        self.update_visible_parts();
+
+        self.background
+            .prepare(graphics_context, self.camera.get_view_projection_matrix());
+
+        Ok(())
+    }
+
+    /// Returns the box around the parts of the player (leaving out the blob shadow under it), if there are any.
+    ///
+    /// With `include_entities`, the box also goes around the entities of the scene, where they're placed.
+    pub fn get_bounds(&self, include_entities: bool) -> Option<PartBounds> {
+        let bounds = |parts: &[Part]| {
+            PartBounds::from_parts(parts.iter().filter(|p| !p.get_texture().is_shadow()))
+        };
+
+        let entities = self
+            .entities
+            .iter()
+            .filter(|_| include_entities)
+            .filter_map(|entity| bounds(&entity.parts).map(|b| b.transformed(entity.transform)));
+
+        bounds(&self.computed_body_parts)
+            .into_iter()
+            .chain(entities)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Orbits the camera around the player (and the entities of the scene), zoomed to fit them in view
+    /// with `margin` extra room around them (like 0.1 for 10%), keeping its rotation.
+    ///
+    /// This keeps tall hats, ears or armor from being cropped. The scene needs to be updated before it's rendered again.
+    pub fn frame_camera(&mut self, margin: f32) {
+        if let Some(bounds) = self.get_bounds(true) {
+            self.camera.frame_bounds(bounds, margin);
+        }
+    }
+
+    fn update_scene_context(
+        camera: &mut Camera,
+        sun: &SunInformation,
+        viewport_size: Size,
+        supersampling: u32,
+        sample_count: u32,
+        output_format: TextureFormat,
+        scene_context: &mut SceneContext,
+        graphics_context: &GraphicsContext,
+    ) {
+        if camera.get_size().is_none() {
+            camera.set_size(Some(viewport_size));
+        }
+
+        scene_context.init(
+            graphics_context,
+            camera,
+            sun,
+            viewport_size,
+            supersampling,
+            sample_count,
+            output_format,
+        );
+    }
+
+    /// Applies changes to the camera, lighting or viewport size, so that they're used by the next render.
+    ///
+    /// When only the camera or the lighting changed, the next render reuses the parts that are already uploaded.
+    pub fn update(&mut self, graphics_context: &GraphicsContext) {
+        let sample_count = self.get_sample_count(graphics_context);
+        let output_format = self.get_output_format(graphics_context);
+
+        Self::update_scene_context(
+            &mut self.camera,
+            &self.sun_information,
+            self.viewport_size,
+            self.supersampling,
+            sample_count,
+            output_format,
+            &mut self.scene_context,
+            graphics_context,
+        );
+    }
+
+    /// Reuses the scene for another render, as if it had just been created with [`Scene::new`],
+    /// without recreating its depth and output textures (as long as the viewport size stays the same)
+    /// or the textures it was given.
+    ///
+    /// Every setting goes back to its default, and the instances, entities, nameplate and material maps are removed.
+    /// The textures are kept, so that setting textures of the same size again only uploads their pixels;
+    /// textures that no longer apply can be removed with [`Scene::remove_texture`].
+    pub fn reset<M: ArmorMaterial>(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        camera: Camera,
+        sun: SunInformation,
+        viewport_size: Size,
+        part_context: &PlayerPartProviderContext<M>,
+        body_parts: &[PlayerBodyPartType],
+    ) {
+        self.camera = camera;
+        self.sun_information = sun;
+        self.viewport_size = viewport_size;
+
+        // Texture variants can't be reused as single textures, so those are dropped
+        for texture_type in std::mem::take(&mut self.texture_variants).into_keys() {
+            self.textures.remove(&texture_type);
+        }
+
+        self.normal_maps.clear();
+        self.specular_maps.clear();
+        self.instances.clear();
+        self.entities.clear();
+        self.nameplate = None;
+        self.shadow_mapping = None;
+        self.ambient_occlusion = None;
+        self.outline = None;
+        self.background = PreparedBackground::Transparent;
+        self.supersampling = 1;
+        self.sample_count = None;
+        self.output_format = None;
+        self.texture_atlas_enabled = false;
+        self.texture_atlas = None;
+        self.prepared_draws = None;
+        self.depth_readback = false;
+        self.frustum_culling = false;
+        self.visible_parts = None;
+        self.render_mode = RenderMode::Shaded;
+        self.shader = None;
+
+        self.rebuild_parts(part_context, body_parts.to_vec());
+
+        if part_context.shadow_y_pos.is_some() {
+            let shadow_image = Self::load_shadow_image(part_context.shadow_is_square);
+
+            self.set_texture(
+                graphics_context,
+                PlayerPartTextureType::Shadow,
+                &shadow_image,
+            );
+        } else {
+            self.remove_texture(PlayerPartTextureType::Shadow);
+        }
+
+        self.update(graphics_context);
+    }
+
+    pub fn rebuild_parts<M: ArmorMaterial>(
+        &mut self,
+        part_context: &PlayerPartProviderContext<M>,
+        body_parts: Vec<PlayerBodyPartType>,
+    ) -> &[Part] {
+        self.computed_body_parts = Self::collect_player_parts(part_context, &body_parts);
+        self.rest_body_parts = None;
+        self.proportions = part_context.proportions;
+        self.prepared_draws = None;
+
+        self.parts()
+    }
+
+    /// Tints every part drawn with the given texture, e.g. to dye armor or to render a translucent preview.
+    ///
+    /// Tints are kept while the scene is posed, but not when its parts are rebuilt.
+    pub fn set_texture_tint(
+        &mut self,
+        texture_type: PlayerPartTextureType,
+        tint: Option<Vec4>,
+    ) -> &[Part] {
+        let rest_parts = self.rest_body_parts.iter_mut().flatten();
+
+        for part in self.computed_body_parts.iter_mut().chain(rest_parts) {
+            if part.get_texture() == texture_type {
+                part.set_tint(tint);
+            }
+        }
+
+        self.prepared_draws = None;
+
+        self.parts()
+    }
+}
@@ -0,0 +1,150 @@
+use super::{draws::TextureDraw, Scene};
+use crate::high_level::pipeline::{
+    profiling::GpuProfiler, GraphicsContext, OutputPipelines, SceneContext,
+};
+use std::ops::DerefMut;
+use tracing::trace_span;
+use wgpu::{
+    Color, CommandEncoder, IndexFormat, LoadOp, Operations, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPipeline, StoreOp, TextureView,
+};
+
+/// The textures the background and the parts are drawn into.
+pub(super) struct RenderTarget<'a> {
+    pub(super) view: &'a TextureView,
+    /// The texture the multisampled view is resolved into, if multisampling.
+    pub(super) resolve_target: Option<&'a TextureView>,
+    pub(super) depth: &'a TextureView,
+}
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Draws the background of the scene, unless it's transparent.
+    ///
+    /// Returns whether anything was drawn, in which case the parts need to be drawn on top of it.
+    pub(super) fn render_background(
+        &self,
+        graphics_context: &GraphicsContext,
+        encoder: &mut CommandEncoder,
+        profiler: &mut GpuProfiler,
+        pipelines: &OutputPipelines,
+        target: &RenderTarget,
+    ) -> bool {
+        if self.background.is_transparent() {
+            return false;
+        }
+
+        let pipelines = pipelines.get_background(&graphics_context.device);
+
+        let _pass_span = trace_span!("background_pass").entered();
+
+        let label = "Background render pass";
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target.view,
+                resolve_target: target.resolve_target,
+                ops: Operations {
+                    load: LoadOp::Clear(self.background.clear_color()),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: profiler.time_pass(label),
+            occlusion_query_set: None,
+        });
+
+        self.background.draw(pipelines, &mut rpass);
+
+        true
+    }
+
+    /// Draws the parts, one pass per texture, on top of the background if one was drawn.
+    pub(super) fn render_parts(
+        &self,
+        encoder: &mut CommandEncoder,
+        profiler: &mut GpuProfiler,
+        pipeline: &RenderPipeline,
+        draws: &[TextureDraw],
+        target: &RenderTarget,
+        background_drawn: bool,
+    ) {
+        let transform_bind_group = &self.scene_context.transform_bind_group;
+        let sun_bind_group = &self.scene_context.sun_information_bind_group;
+        let shadow_bind_group = &self.scene_context.shadow_map.shadow_bind_group;
+
+        // Ambient occlusion and outlines need the depth of everything once the scene is drawn,
+        // and so does depth readback, shadow included.
+        let keep_depth =
+            self.ambient_occlusion.is_some() || self.outline.is_some() || self.depth_readback;
+
+        let mut load_op = if background_drawn {
+            LoadOp::Load
+        } else {
+            LoadOp::Clear(Color::TRANSPARENT)
+        };
+        let mut depth_load_op = LoadOp::Clear(1.0);
+
+        for draw in draws {
+            let texture = draw.texture;
+            let _pass_span = trace_span!("render_pass", texture = texture.label()).entered();
+
+            // The depth of anything drawn before the shadow (like another entity) needs to be kept.
+            let store_depth =
+                if !texture.is_shadow() || keep_depth || matches!(depth_load_op, LoadOp::Load) {
+                    StoreOp::Store
+                } else {
+                    StoreOp::Discard
+                };
+
+            let label = format!("Render pass for {}", texture);
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&label),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target.view,
+                    resolve_target: target.resolve_target,
+                    ops: Operations {
+                        load: load_op,
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: target.depth,
+                    depth_ops: Some(Operations {
+                        load: depth_load_op,
+                        store: store_depth,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: profiler.time_pass(&label),
+                occlusion_query_set: None,
+            });
+
+            let (index_buffer, index_count) = draw
+                .line_index_buffer
+                .as_ref()
+                .map_or((&draw.index_buffer, draw.index_count), |(buffer, count)| {
+                    (buffer, *count)
+                });
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, transform_bind_group, &[]);
+            rpass.set_bind_group(1, &draw.texture_bind_group, &[]);
+            rpass.set_bind_group(2, sun_bind_group, &[]);
+            rpass.set_bind_group(3, shadow_bind_group, &[]);
+            rpass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint16);
+            let instances = &self.instance_buffers[texture.instance_set()];
+
+            rpass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+            rpass.set_vertex_buffer(1, instances.buffer.slice(..));
+            rpass.draw_indexed(0..index_count, 0, 0..instances.count);
+
+            load_op = LoadOp::Load;
+            if store_depth == StoreOp::Store {
+                depth_load_op = LoadOp::Load;
+            }
+        }
+    }
+}
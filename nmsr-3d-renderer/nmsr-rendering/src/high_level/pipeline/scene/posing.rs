@@ -0,0 +1,42 @@
+use super::Scene;
+use crate::high_level::{animation::Animation, pipeline::SceneContext};
+use nmsr_player_parts::parts::{part::Part, skeleton::Skeleton};
+use std::ops::DerefMut;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Returns an empty skeleton matching the proportions of the parts in this scene.
+    pub fn create_skeleton(&self) -> Skeleton {
+        Skeleton::new(self.proportions)
+    }
+
+    /// Poses the parts of the scene at the given time (in seconds) of the animation.
+    ///
+    /// Frames don't build on each other, so they can be applied in any order.
+    pub fn apply_animation_frame(&mut self, animation: &Animation, time: f32) -> &[Part] {
+        let mut skeleton = self.create_skeleton();
+        animation.apply_frame(&mut skeleton, time);
+
+        self.set_pose(&skeleton)
+    }
+
+    /// Moves the parts of the scene from their resting pose into the pose of the given skeleton.
+    ///
+    /// Parts follow the bone their provider attached them to, and the bones that one is attached to in turn.
+    pub fn set_pose(&mut self, skeleton: &Skeleton) -> &[Part] {
+        let rest_parts = self
+            .rest_body_parts
+            .get_or_insert_with(|| self.computed_body_parts.clone());
+
+        for (part, rest_part) in self.computed_body_parts.iter_mut().zip(rest_parts.iter()) {
+            part.clone_from(rest_part);
+            skeleton.apply_to_part(part);
+        }
+
+        self.prepared_draws = None;
+
+        self.parts()
+    }
+}
@@ -0,0 +1,212 @@
+use super::{Scene, Size};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::{
+        camera_track::CameraTrack,
+        pipeline::{
+            textures::{composite_over_color, decode_float_pixels, SceneContextTextures},
+            ColorSpace, GraphicsContext, SceneContext,
+        },
+    },
+};
+use image::{GrayImage, Rgba32FImage, RgbaImage};
+use std::{ops::DerefMut, time::Instant};
+use wgpu::Extent3d;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Copies the final render into the output buffer of the scene context, for it to be read back.
+    pub(super) fn copy_to_output_buffer(graphics_context: &GraphicsContext, textures: &SceneContextTextures) {
+        let mut encoder = graphics_context
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &textures.output_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &textures.texture_output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(
+                        textures
+                            .texture_output_buffer_dimensions
+                            .padded_bytes_per_row,
+                    ),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: textures.viewport_size.width,
+                height: textures.viewport_size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        graphics_context.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Reads back the last render, row by row in RGBA8, whatever the [output format](Scene::set_output_format).
+    ///
+    /// With a [chroma key](SceneBackground::ChromaKey) background, the key color is filled in here,
+    /// and every pixel comes back opaque.
+    pub async fn copy_output_texture(
+        &self,
+        graphics_context: &GraphicsContext,
+        cleanup_alpha: bool,
+    ) -> Result<Vec<u8>> {
+        self.copy_output_texture_with_deadline(graphics_context, cleanup_alpha, None)
+            .await
+    }
+
+    /// Reads back the last render like [`Scene::copy_output_texture`], but gives up with
+    /// [`NMSRRenderingError::RenderTimedOut`] if the GPU isn't done with it by the deadline,
+    /// instead of waiting forever on a wedged driver.
+    ///
+    /// The scene can be rendered and read back again afterwards, once the GPU catches up.
+    /// Deadlines are ignored in browsers, which have no way to give up on the GPU.
+    #[cfg(feature = "deadlines")]
+    pub async fn copy_output_texture_until(
+        &self,
+        graphics_context: &GraphicsContext,
+        cleanup_alpha: bool,
+        deadline: Instant,
+    ) -> Result<Vec<u8>> {
+        self.copy_output_texture_with_deadline(graphics_context, cleanup_alpha, Some(deadline))
+            .await
+    }
+
+    async fn copy_output_texture_with_deadline(
+        &self,
+        graphics_context: &GraphicsContext,
+        cleanup_alpha: bool,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<u8>> {
+        let Some(key_color) = self.background.chroma_key() else {
+            return self
+                .scene_context
+                .copy_output_texture_with_deadline(graphics_context, cleanup_alpha, deadline)
+                .await;
+        };
+
+        // Composite the premultiplied pixels, there's no alpha left to clean up afterwards.
+        let mut pixels = self
+            .scene_context
+            .copy_output_texture_with_deadline(graphics_context, false, deadline)
+            .await?;
+        composite_over_color(&mut pixels, key_color);
+
+        Ok(pixels)
+    }
+
+    /// Reads back the last render as premultiplied RGBA floats in linear space, for HDR pipelines
+    /// to do their own tone mapping and compositing.
+    ///
+    /// Values beyond the 0 to 1 range only survive with a float [output format](Scene::set_output_format)
+    /// (like [`Rgba16Float`](TextureFormat::Rgba16Float)) in the [linear](ColorSpace::Linear) color space.
+    /// Other outputs are converted to linear space.
+    pub async fn copy_output_hdr(
+        &self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<Rgba32FImage> {
+        let Size { width, height } = self.viewport_size;
+
+        let (format, bytes) = self
+            .scene_context
+            .copy_raw_output_texture(graphics_context, None)
+            .await?;
+
+        let is_float = format.block_size(None) != Some(4);
+        let srgb_encoded = !is_float || graphics_context.color_space == ColorSpace::Srgb;
+
+        let pixels = decode_float_pixels(format, srgb_encoded, &bytes)
+            .ok_or(NMSRRenderingError::UnsupportedFloatOutputFormat(format))?;
+
+        Rgba32FImage::from_raw(width, height, pixels)
+            .ok_or(NMSRRenderingError::OutputSizeMismatch(width, height))
+    }
+
+    /// Reads back the last render as an OpenEXR image, with [`Scene::copy_output_hdr`].
+    #[cfg(feature = "exr")]
+    pub async fn copy_output_exr(&self, graphics_context: &GraphicsContext) -> Result<Vec<u8>> {
+        let image = self.copy_output_hdr(graphics_context).await?;
+        let mut bytes = std::io::Cursor::new(Vec::new());
+
+        image
+            .write_to(&mut bytes, image::ImageOutputFormat::OpenExr)
+            .map_err(NMSRRenderingError::ExrEncodeError)?;
+
+        Ok(bytes.into_inner())
+    }
+
+    /// Reads back which pixels of the last render the player covers, as a mask of either 0 or 255.
+    ///
+    /// Pixels count as covered when they're at least half opaque, so that antialiased edges go one way or the other.
+    /// This is meant to go along with a [chroma key](SceneBackground::ChromaKey) background,
+    /// but works with any background that isn't drawn (i.e. transparent ones).
+    pub async fn copy_coverage_mask(
+        &self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<GrayImage> {
+        let Size { width, height } = self.viewport_size;
+        let pixels = self
+            .scene_context
+            .copy_output_texture(graphics_context, false)
+            .await?;
+
+        let mask = pixels
+            .chunks_exact(4)
+            .map(|pixel| if pixel[3] >= 128 { u8::MAX } else { 0 })
+            .collect();
+
+        GrayImage::from_raw(width, height, mask)
+            .ok_or(NMSRRenderingError::OutputSizeMismatch(width, height))
+    }
+
+    /// Renders the scene from `frame_count` points along a camera track, like a turntable, and reads them back.
+    ///
+    /// The scene context's textures and buffers are reused between frames, and the camera is left at the last frame.
+    pub async fn render_camera_track(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        track: &CameraTrack,
+        frame_count: usize,
+    ) -> Result<Vec<RgbaImage>> {
+        let mut frames = Vec::with_capacity(frame_count);
+
+        for time in track.frame_times(frame_count) {
+            if let Some(pose) = track.sample(time) {
+                pose.apply(&mut self.camera);
+            }
+
+            self.update(graphics_context);
+            self.render(graphics_context)?;
+
+            let Size { width, height } = self.viewport_size;
+            let pixels = self.copy_output_texture(graphics_context, true).await?;
+            let frame = RgbaImage::from_raw(width, height, pixels)
+                .ok_or(NMSRRenderingError::OutputSizeMismatch(width, height))?;
+
+            frames.push(frame);
+        }
+
+        Ok(frames)
+    }
+
+    /// Reads back the depth of the last render, one value per pixel of the viewport, row by row.
+    ///
+    /// Depth goes from 0 (at the camera's near plane) to 1 (at its far plane), and is 1 wherever nothing was drawn.
+    /// Depth readback needs to be enabled with [`Scene::set_depth_readback`] before rendering.
+    pub async fn copy_depth_texture(&self, graphics_context: &GraphicsContext) -> Result<Vec<f32>> {
+        self.scene_context
+            .depth_readback
+            .read(graphics_context)
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use super::{draws::TextureDraw, Scene};
+use crate::high_level::pipeline::{profiling::GpuProfiler, GraphicsContext, SceneContext};
+use std::ops::DerefMut;
+use tracing::instrument;
+use wgpu::{CommandEncoder, IndexFormat, LoadOp, Operations, RenderPassDepthStencilAttachment, StoreOp};
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Renders the depth of the parts as seen from the sun, for the main pass to tell which parts are in shadow.
+    #[instrument(skip_all)]
+    pub(super) fn render_shadow_map(
+        &self,
+        graphics_context: &GraphicsContext,
+        profiler: &mut GpuProfiler,
+        encoder: &mut CommandEncoder,
+        draws: &[TextureDraw],
+    ) {
+        let shadow_map = &self.scene_context.shadow_map;
+
+        let label = "Shadow map pass";
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &shadow_map.texture.view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: profiler.time_pass(label),
+            occlusion_query_set: None,
+        });
+
+        rpass.set_pipeline(graphics_context.get_shadow_pipeline());
+        rpass.set_bind_group(0, &shadow_map.light_bind_group, &[]);
+
+        // The blob shadow is on the ground, it doesn't cast a shadow itself.
+        for draw in draws.iter().filter(|d| d.texture.casts_shadow()) {
+            rpass.set_bind_group(1, &draw.texture_bind_group, &[]);
+            rpass.set_index_buffer(draw.index_buffer.slice(..), IndexFormat::Uint16);
+            let instances = &self.instance_buffers[draw.texture.instance_set()];
+
+            rpass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+            rpass.set_vertex_buffer(1, instances.buffer.slice(..));
+            rpass.draw_indexed(0..draw.index_count, 0, 0..instances.count);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use super::{Scene, TextureRegion};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::pipeline::{
+        materials::upload_material_map,
+        textures::{premultiply_alpha, SceneTexture},
+        GraphicsContext, SceneContext,
+    },
+};
+use image::RgbaImage;
+use nmsr_player_parts::types::PlayerPartTextureType;
+use std::{collections::HashMap, ops::DerefMut};
+use tracing::instrument;
+use wgpu::{Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect};
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    pub fn has_texture(&self, texture_type: PlayerPartTextureType) -> Result<bool> {
+        Ok(self.textures.contains_key(&texture_type))
+    }
+
+    pub fn set_texture(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        texture: &RgbaImage,
+    ) {
+        // Reuse the texture that's already there when it's the same size, e.g. when the scene is reused
+        if self.can_reuse_texture(texture_type, texture.width(), texture.height()) {
+            let region = TextureRegion {
+                x: 0,
+                y: 0,
+                width: texture.width(),
+                height: texture.height(),
+            };
+
+            if self
+                .update_texture_region(graphics_context, texture_type, region, texture.as_raw())
+                .is_ok()
+            {
+                return;
+            }
+        }
+
+        let texture =
+            SceneContext::upload_texture(graphics_context, texture, Some(texture_type.into()));
+        self.textures.insert(texture_type, texture);
+        self.texture_variants.remove(&texture_type);
+        self.texture_atlas = None;
+        self.prepared_draws = None;
+    }
+
+    fn can_reuse_texture(
+        &self,
+        texture_type: PlayerPartTextureType,
+        width: u32,
+        height: u32,
+    ) -> bool {
+        !self.texture_variants.contains_key(&texture_type)
+            && self.textures.get(&texture_type).is_some_and(|existing| {
+                existing.texture.width() == width && existing.texture.height() == height
+            })
+    }
+
+    /// Sets several textures at once, like [`Scene::set_texture`] does for one.
+    ///
+    /// Their pixels are staged together and written with a single command submission,
+    /// which returns once it's queued, so batch renders don't wait on each texture in turn.
+    #[instrument(skip_all, fields(count = textures.len()))]
+    pub fn set_textures(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        textures: &[(PlayerPartTextureType, &RgbaImage)],
+    ) {
+        for (texture_type, image) in textures {
+            if !self.can_reuse_texture(*texture_type, image.width(), image.height()) {
+                let texture = SceneContext::create_image_texture(
+                    graphics_context,
+                    image.width(),
+                    image.height(),
+                    Some((*texture_type).into()),
+                );
+
+                self.textures.insert(*texture_type, texture);
+                self.texture_variants.remove(texture_type);
+                self.texture_atlas = None;
+                self.prepared_draws = None;
+            }
+        }
+
+        let uploads = textures
+            .iter()
+            .map(|(texture_type, image)| (&self.textures[texture_type], *image))
+            .collect::<Vec<_>>();
+
+        SceneContext::write_textures(graphics_context, &uploads);
+
+        // The textures were all reused, so the atlas holding copies of them is still around
+        if let Some(atlas) = &self.texture_atlas {
+            for (texture_type, image) in textures {
+                let region = TextureRegion {
+                    x: 0,
+                    y: 0,
+                    width: image.width(),
+                    height: image.height(),
+                };
+
+                atlas.copy_region(
+                    graphics_context,
+                    *texture_type,
+                    &self.textures[texture_type],
+                    region,
+                );
+            }
+        }
+    }
+
+    /// Removes a texture from the scene, e.g. one left over from a previous use of a [reset](Self::reset) scene.
+    pub fn remove_texture(&mut self, texture_type: PlayerPartTextureType) {
+        if self.textures.remove(&texture_type).is_some() {
+            self.texture_variants.remove(&texture_type);
+            self.texture_atlas = None;
+            self.prepared_draws = None;
+        }
+    }
+
+    /// Sets several variants of a texture at once, for the [instances](Self::set_instances) of the scene
+    /// to pick from with their [`SceneInstance::texture_variant`].
+    ///
+    /// The variants need to all be the same size. They're stacked into a single texture,
+    /// so that all the instances can still be drawn together.
+    pub fn set_texture_variants(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        variants: &[RgbaImage],
+    ) -> Result<()> {
+        let (width, height) = variants
+            .first()
+            .map(|v| v.dimensions())
+            .ok_or(NMSRRenderingError::TextureVariantsMismatch(texture_type))?;
+
+        if variants.iter().any(|v| v.dimensions() != (width, height)) {
+            return Err(NMSRRenderingError::TextureVariantsMismatch(texture_type));
+        }
+
+        let stacked = RgbaImage::from_raw(
+            width,
+            height * variants.len() as u32,
+            variants.iter().flat_map(|v| v.as_raw()).copied().collect(),
+        )
+        .ok_or(NMSRRenderingError::TextureVariantsMismatch(texture_type))?;
+
+        self.set_texture(graphics_context, texture_type, &stacked);
+        self.texture_variants
+            .insert(texture_type, variants.len() as u32);
+        self.prepared_draws = None;
+
+        Ok(())
+    }
+
+    /// Whether a texture can be packed into the texture atlas, its parts' uv coordinates being remapped to it.
+    pub(super) fn is_atlas_compatible(&self, texture_type: &PlayerPartTextureType) -> bool {
+        !texture_type.is_shadow()
+            && !self.texture_variants.contains_key(texture_type)
+            && !self.normal_maps.contains_key(texture_type)
+            && !self.specular_maps.contains_key(texture_type)
+    }
+
+    /// Sets (or removes) the normal map of a texture type, so its parts get non-flat lighting.
+    ///
+    /// Normal maps are in tangent space, with green pointing up the texture.
+    /// They need to line up with the texture, but don't need to be the same size.
+    pub fn set_normal_map(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        normal_map: Option<&RgbaImage>,
+    ) {
+        Self::set_material_map(
+            &mut self.normal_maps,
+            graphics_context,
+            texture_type,
+            normal_map,
+            "Normal Map",
+        );
+        self.texture_atlas = None;
+        self.prepared_draws = None;
+    }
+
+    /// Sets (or removes) the specular map of a texture type, which makes its parts shine in the sun.
+    ///
+    /// The red channel is how strong the highlights are, and the green channel how glossy (sharp) they are.
+    pub fn set_specular_map(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        specular_map: Option<&RgbaImage>,
+    ) {
+        Self::set_material_map(
+            &mut self.specular_maps,
+            graphics_context,
+            texture_type,
+            specular_map,
+            "Specular Map",
+        );
+        self.texture_atlas = None;
+        self.prepared_draws = None;
+    }
+
+    fn set_material_map(
+        maps: &mut HashMap<PlayerPartTextureType, SceneTexture>,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        image: Option<&RgbaImage>,
+        label: &str,
+    ) {
+        match image {
+            Some(image) => {
+                let texture = upload_material_map(
+                    &graphics_context.device,
+                    &graphics_context.queue,
+                    image,
+                    Some(label),
+                );
+                maps.insert(texture_type, texture);
+            }
+            None => {
+                maps.remove(&texture_type);
+            }
+        }
+    }
+
+    /// Updates a region of a texture that has already been set, without re-uploading the whole texture.
+    ///
+    /// This is meant for live previews (e.g. skin editors), where only a few pixels change between frames.
+    /// The pixels are expected to be in RGBA8 format, row by row, covering exactly the given region.
+    #[instrument(skip(self, graphics_context, pixels))]
+    pub fn update_texture_region(
+        &mut self,
+        graphics_context: &GraphicsContext,
+        texture_type: PlayerPartTextureType,
+        region: TextureRegion,
+        pixels: &[u8],
+    ) -> Result<()> {
+        let texture = &self
+            .textures
+            .get(&texture_type)
+            .ok_or(NMSRRenderingError::SceneContextTextureNotSet(texture_type))?
+            .texture;
+
+        let fits_horizontally = region
+            .x
+            .checked_add(region.width)
+            .is_some_and(|end| end <= texture.width());
+        let fits_vertically = region
+            .y
+            .checked_add(region.height)
+            .is_some_and(|end| end <= texture.height());
+
+        if !fits_horizontally || !fits_vertically {
+            return Err(NMSRRenderingError::TextureRegionOutOfBounds(
+                region,
+                texture_type,
+            ));
+        }
+
+        let expected_len = region.width as usize * region.height as usize * 4;
+        let mut image = RgbaImage::from_raw(region.width, region.height, pixels.to_vec())
+            .filter(|_| pixels.len() == expected_len)
+            .ok_or(NMSRRenderingError::TextureRegionSizeMismatch(
+                region,
+                expected_len,
+                pixels.len(),
+            ))?;
+
+        // Textures are stored with premultiplied alpha, so the region needs to be too.
+        premultiply_alpha(&mut image);
+
+        graphics_context.queue.write_texture(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            image.as_raw(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(region.width * 4),
+                rows_per_image: Some(region.height),
+            },
+            Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        // The atlas holds a copy of the texture, which needs to be updated too.
+        if let Some(atlas) = &self.texture_atlas {
+            atlas.copy_region(
+                graphics_context,
+                texture_type,
+                &self.textures[&texture_type],
+                region,
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+use super::Scene;
+use crate::{
+    errors::Result,
+    high_level::pipeline::{profiling::RenderTimings, GraphicsContext, SceneContext},
+};
+use std::ops::DerefMut;
+
+impl<T> Scene<T>
+where
+    T: DerefMut<Target = SceneContext> + Send + Sync,
+{
+    /// Reads back how long the last render took to encode, and how long the GPU spent on each of its passes.
+    ///
+    /// GPU times are only measured when the adapter supports timestamp queries.
+    pub async fn render_timings(
+        &self,
+        graphics_context: &GraphicsContext,
+    ) -> Result<RenderTimings> {
+        self.scene_context.profiler.read(graphics_context).await
+    }
+}
@@ -1,25 +1,36 @@
 use super::{
     scene::{Size, SunInformation},
     textures::{
-        create_texture, premultiply_alpha, BufferDimensions, SceneContextTextures, SceneTexture,
+        create_texture, encode_rgba8_pixels, premultiply_alpha, unmultiply_alpha, BufferDimensions,
+        SceneContextTextures, SceneTexture,
     },
 };
 use crate::{
     errors::{NMSRRenderingError, Result},
     high_level::{
         camera::Camera,
-        pipeline::graphics_context::GraphicsContext,
-        utils::buffer::{create_buffer_and_bind_group, read_buffer},
+        pipeline::{
+            depth_readback::DepthReadback,
+            graphics_context::{ColorSpace, GraphicsContext},
+            post_processing::{PostProcessingChain, PostProcessingEffect},
+            profiling::GpuProfiler,
+            shadows::ShadowMap,
+        },
+        utils::buffer::{create_buffer_and_bind_group, read_buffer_with_deadline},
     },
 };
 
+use std::time::Instant;
+
 use derive_more::{Debug, Deref, DerefMut, From};
 use glam::Mat4;
 use image::{buffer::ConvertBuffer, RgbaImage};
 use smaa::SmaaTarget;
 use tracing::{instrument, trace_span};
 use wgpu::{
-    util::DeviceExt, BindGroup, Buffer, BufferDescriptor, BufferUsages, TextureDescriptor,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d,
+    ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect, TextureDescriptor,
     TextureDimension, TextureFormat, TextureUsages,
 };
 
@@ -32,6 +43,10 @@ pub struct SceneContext {
     pub(crate) textures: Option<SceneContextTextures>,
     #[debug(skip)]
     pub(crate) smaa_target: Option<SmaaTarget>,
+    pub(crate) post_processing: PostProcessingChain,
+    pub(crate) shadow_map: ShadowMap,
+    pub(crate) depth_readback: DepthReadback,
+    pub(crate) profiler: GpuProfiler,
 }
 
 #[derive(Deref, DerefMut, From)]
@@ -62,9 +77,27 @@ impl SceneContext {
             sun_information_bind_group,
             textures: None,
             smaa_target: None,
+            post_processing: PostProcessingChain::default(),
+            shadow_map: ShadowMap::new(context),
+            depth_readback: DepthReadback::default(),
+            profiler: GpuProfiler::default(),
         }
     }
 
+    /// Returns the post-processing effects applied to renders made with this context, in order.
+    pub fn post_processing_effects(&self) -> &[PostProcessingEffect] {
+        &self.post_processing.effects
+    }
+
+    /// Sets the post-processing effects to apply to renders made with this context, in order.
+    pub fn set_post_processing_effects(&mut self, effects: Vec<PostProcessingEffect>) {
+        self.post_processing.effects = effects;
+    }
+
+    pub fn add_post_processing_effect(&mut self, effect: PostProcessingEffect) {
+        self.post_processing.effects.push(effect);
+    }
+
     fn set_camera_parameters(&self, context: &GraphicsContext, camera: &mut Camera) {
         let matrix = camera.get_view_projection_matrix();
         context.queue.write_buffer(
@@ -89,6 +122,9 @@ impl SceneContext {
         camera: &mut Camera,
         sun: &SunInformation,
         viewport_size: Size,
+        supersampling: u32,
+        sample_count: u32,
+        format: TextureFormat,
     ) {
         // Setup camera matrix
         self.set_camera_parameters(graphics_context, camera);
@@ -97,29 +133,35 @@ impl SceneContext {
         self.set_sun_information(graphics_context, sun);
 
         let camera_size = camera.get_size().unwrap_or_default();
+        let supersampling = supersampling.max(1);
+        let render_size = Size {
+            width: camera_size.width * supersampling,
+            height: camera_size.height * supersampling,
+        };
 
-        let msaa_sample_count = graphics_context
-            .multisampling_strategy
-            .get_msaa_sample_count();
+        let msaa_sample_count = sample_count;
 
-        let needs_texture_resize = self
-            .textures
-            .as_ref()
-            .map_or(true, |textures| textures.camera_size != camera_size);
+        let needs_texture_resize = self.textures.as_ref().map_or(true, |textures| {
+            textures.camera_size != camera_size
+                || textures.supersampling != supersampling
+                || textures.sample_count != sample_count
+                || textures.format != format
+        });
 
-        let needs_output_buffer_resize = self
+        let format_changed = self
             .textures
             .as_ref()
-            .map_or(true, |textures| textures.viewport_size != viewport_size);
+            .is_some_and(|textures| textures.format != format);
+
+        let needs_output_buffer_resize = self.textures.as_ref().map_or(true, |textures| {
+            textures.viewport_size != viewport_size || textures.format != format
+        });
 
         let output = if needs_output_buffer_resize {
             let output_buffer_dimensions = BufferDimensions::new(
                 viewport_size.width as usize,
                 viewport_size.height as usize,
-                graphics_context
-                    .texture_format
-                    .block_size(None)
-                    .unwrap_or(4) as usize,
+                format.block_size(None).unwrap_or(4) as usize,
             );
 
             let output_buffer_desc = BufferDescriptor {
@@ -140,16 +182,18 @@ impl SceneContext {
         if needs_texture_resize {
             let old_textures = self.textures.take();
 
+            // The output buffer is only kept when neither the viewport size nor the format changed
             let old_output =
                 old_textures.map(|t| (t.texture_output_buffer_dimensions, t.texture_output_buffer));
 
             // Setup our depth texture
             let depth_texture = create_texture(
                 graphics_context,
-                camera_size.width,
-                camera_size.height,
+                render_size.width,
+                render_size.height,
                 GraphicsContext::DEPTH_TEXTURE_FORMAT,
-                TextureUsages::RENDER_ATTACHMENT,
+                // Ambient occlusion reads back the depth of the scene
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
                 Some("Depth Texture"),
                 msaa_sample_count,
             );
@@ -158,9 +202,9 @@ impl SceneContext {
             let multisampled_output_texture = if msaa_sample_count > 1 {
                 Some(create_texture(
                     graphics_context,
-                    camera_size.width,
-                    camera_size.height,
-                    graphics_context.texture_format,
+                    render_size.width,
+                    render_size.height,
+                    format,
                     TextureUsages::RENDER_ATTACHMENT,
                     Some("MultiSampled Output Texture"),
                     msaa_sample_count,
@@ -169,32 +213,48 @@ impl SceneContext {
                 None
             };
 
+            // Setup the texture we render into before scaling down, if we're supersampling
+            let supersampled_output_texture = if supersampling > 1 {
+                Some(create_texture(
+                    graphics_context,
+                    render_size.width,
+                    render_size.height,
+                    format,
+                    TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    Some("Supersampled Output Texture"),
+                    1,
+                ))
+            } else {
+                None
+            };
+
             // Setup our output texture
             let output_texture = create_texture(
                 graphics_context,
                 camera_size.width,
                 camera_size.height,
-                graphics_context.texture_format,
+                format,
                 TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
                 Some("Final Output Texture"),
                 1,
             );
 
-            if let Some(target) = self.smaa_target.as_mut() {
+            // SMAA targets can be resized, but not converted to another format
+            if let Some(target) = self.smaa_target.as_mut().filter(|_| !format_changed) {
                 let _guard = trace_span!("resize_smaa_target").entered();
                 target.resize(
                     &graphics_context.device,
-                    camera_size.width,
-                    camera_size.height,
+                    render_size.width,
+                    render_size.height,
                 );
             } else {
                 let _guard = trace_span!("create_smaa_target").entered();
                 let smaa_target = SmaaTarget::new(
                     &graphics_context.device,
                     &graphics_context.queue,
-                    camera_size.width,
-                    camera_size.height,
-                    graphics_context.texture_format,
+                    render_size.width,
+                    render_size.height,
+                    format,
                     graphics_context.multisampling_strategy.get_smaa_mode(),
                 );
 
@@ -209,10 +269,14 @@ impl SceneContext {
                     depth_texture,
                     output_texture,
                     multisampled_output_texture,
+                    supersampled_output_texture,
                     texture_output_buffer,
                     camera_size,
                     viewport_size,
                     texture_output_buffer_dimensions,
+                    supersampling,
+                    sample_count,
+                    format,
                 });
             }
         } else if let Some((texture_output_buffer_dimensions, texture_output_buffer)) = output {
@@ -222,6 +286,7 @@ impl SceneContext {
                 textures.texture_output_buffer = texture_output_buffer;
                 textures.texture_output_buffer_dimensions = texture_output_buffer_dimensions;
                 textures.viewport_size = viewport_size;
+                textures.format = format;
 
                 self.textures = Some(textures);
             }
@@ -237,33 +302,142 @@ impl SceneContext {
 
         premultiply_alpha(&mut image);
 
-        let format = if context.texture_format.is_srgb() {
+        let texture = context.device.create_texture_with_data(
+            &context.queue,
+            &Self::image_texture_descriptor(context, image.width(), image.height(), label),
+            image.as_raw(),
+        );
+        let view = texture.create_view(&Default::default());
+
+        SceneTexture { texture, view }
+    }
+
+    /// Creates a texture for an image of the given size, without uploading anything to it yet.
+    pub(crate) fn create_image_texture(
+        context: &GraphicsContext,
+        width: u32,
+        height: u32,
+        label: Option<&str>,
+    ) -> SceneTexture {
+        let texture = context
+            .device
+            .create_texture(&Self::image_texture_descriptor(
+                context, width, height, label,
+            ));
+        let view = texture.create_view(&Default::default());
+
+        SceneTexture { texture, view }
+    }
+
+    fn image_texture_descriptor<'a>(
+        context: &GraphicsContext,
+        width: u32,
+        height: u32,
+        label: Option<&'a str>,
+    ) -> TextureDescriptor<'a> {
+        // Textures are decoded to linear space when lighting happens there
+        let format = if context.color_space == ColorSpace::Linear {
             TextureFormat::Rgba8UnormSrgb
         } else {
             TextureFormat::Rgba8Unorm
         };
 
-        let texture = context.device.create_texture_with_data(
-            &context.queue,
-            &TextureDescriptor {
-                size: wgpu::Extent3d {
-                    width: image.width(),
-                    height: image.height(),
+        TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            // Allow copying into the texture, that way regions of it can be updated between frames,
+            // and out of it, into a texture atlas.
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC,
+            label,
+            view_formats: &[],
+        }
+    }
+
+    /// Writes each image into its texture, which needs to be exactly the size of the image.
+    ///
+    /// All the images go through a single staging buffer and command submission,
+    /// which returns as soon as it's queued instead of waiting for the copies to be done.
+    #[instrument(skip_all, fields(count = uploads.len()))]
+    pub(crate) fn write_textures(
+        context: &GraphicsContext,
+        uploads: &[(&SceneTexture, &RgbaImage)],
+    ) {
+        if uploads.is_empty() {
+            return;
+        }
+
+        let mut staging = Vec::new();
+        let mut copies = Vec::with_capacity(uploads.len());
+
+        for (texture, image) in uploads {
+            let mut image: RgbaImage = image.convert();
+            premultiply_alpha(&mut image);
+
+            let dimensions =
+                BufferDimensions::new(image.width() as usize, image.height() as usize, 4);
+            let offset = staging.len() as u64;
+
+            // Rows need to be padded for buffer to texture copies, which also keeps every image aligned
+            for row in image
+                .as_raw()
+                .chunks_exact(dimensions.unpadded_bytes_per_row)
+            {
+                staging.extend_from_slice(row);
+                staging.resize(
+                    staging.len() + dimensions.padded_bytes_per_row as usize - row.len(),
+                    0,
+                );
+            }
+
+            copies.push((*texture, offset, dimensions, image.width(), image.height()));
+        }
+
+        let staging_buffer = context.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Texture upload staging buffer"),
+            contents: &staging,
+            usage: BufferUsages::COPY_SRC,
+        });
+
+        let mut encoder = context
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Texture upload encoder"),
+            });
+
+        for (texture, offset, dimensions, width, height) in copies {
+            encoder.copy_buffer_to_texture(
+                ImageCopyBuffer {
+                    buffer: &staging_buffer,
+                    layout: ImageDataLayout {
+                        offset,
+                        bytes_per_row: Some(dimensions.padded_bytes_per_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                ImageCopyTexture {
+                    texture: &texture.texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                Extent3d {
+                    width,
+                    height,
                     depth_or_array_layers: 1,
                 },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format,
-                usage: TextureUsages::TEXTURE_BINDING,
-                label,
-                view_formats: &[],
-            },
-            image.as_raw(),
-        );
-        let view = texture.create_view(&Default::default());
+            );
+        }
 
-        SceneTexture { texture, view }
+        context.queue.submit(Some(encoder.finish()));
     }
 
     pub(crate) fn try_textures(&self) -> Result<&SceneContextTextures> {
@@ -272,19 +446,71 @@ impl SceneContext {
             .ok_or(NMSRRenderingError::SceneContextTexturesNotInitialized)
     }
 
+    /// Reads back the last render, row by row in RGBA8, converting it from the output format if needed.
     pub async fn copy_output_texture(
         &self,
         graphics_context: &GraphicsContext,
         cleanup_alpha: bool,
     ) -> Result<Vec<u8>> {
+        self.copy_output_texture_with_deadline(graphics_context, cleanup_alpha, None)
+            .await
+    }
+
+    /// Reads back the last render like [`SceneContext::copy_output_texture`], but gives up with
+    /// [`NMSRRenderingError::RenderTimedOut`] if the GPU isn't done with it by the deadline.
+    ///
+    /// The output buffer can be read from again afterwards, once the GPU catches up.
+    /// Deadlines are ignored in browsers, which have no way to give up on the GPU.
+    #[cfg(feature = "deadlines")]
+    pub async fn copy_output_texture_until(
+        &self,
+        graphics_context: &GraphicsContext,
+        cleanup_alpha: bool,
+        deadline: Instant,
+    ) -> Result<Vec<u8>> {
+        self.copy_output_texture_with_deadline(graphics_context, cleanup_alpha, Some(deadline))
+            .await
+    }
+
+    pub(crate) async fn copy_output_texture_with_deadline(
+        &self,
+        graphics_context: &GraphicsContext,
+        cleanup_alpha: bool,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<u8>> {
+        let (format, bytes) = self
+            .copy_raw_output_texture(graphics_context, deadline)
+            .await?;
+
+        // Float outputs hold sRGB values when rendering in the sRGB color space, 8-bit ones always do.
+        let srgb_encoded = graphics_context.color_space == ColorSpace::Srgb;
+        let mut pixels = encode_rgba8_pixels(format, srgb_encoded, bytes)
+            .ok_or(NMSRRenderingError::UnsupportedOutputFormat(format))?;
+
+        if cleanup_alpha {
+            unmultiply_alpha(&mut pixels);
+        }
+
+        Ok(pixels)
+    }
+
+    /// Reads back the last render row by row, as it's stored in the output format.
+    pub(crate) async fn copy_raw_output_texture(
+        &self,
+        graphics_context: &GraphicsContext,
+        deadline: Option<Instant>,
+    ) -> Result<(TextureFormat, Vec<u8>)> {
         let textures = self.try_textures()?;
 
-        read_buffer(
+        let bytes = read_buffer_with_deadline(
             &graphics_context.device,
             &textures.texture_output_buffer,
             &textures.texture_output_buffer_dimensions,
-            cleanup_alpha,
+            false,
+            deadline,
         )
-        .await
+        .await?;
+
+        Ok((textures.format, bytes))
     }
 }
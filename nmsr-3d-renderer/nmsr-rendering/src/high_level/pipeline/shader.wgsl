@@ -2,18 +2,60 @@ struct VertexInput {
     @location(0) position: vec4<f32>,
     @location(1) tex_coord: vec2<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) tint: vec4<f32>,
+    @location(4) tangent: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(5) transform_0: vec4<f32>,
+    @location(6) transform_1: vec4<f32>,
+    @location(7) transform_2: vec4<f32>,
+    @location(8) transform_3: vec4<f32>,
+    @location(9) texture_variant: u32,
 };
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coord: vec2<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) tint: vec4<f32>,
+    @location(4) tangent: vec4<f32>,
+    // The coordinates in the normal and specular maps, which aren't split into variants like the texture.
+    @location(5) material_tex_coord: vec2<f32>,
 };
 
+const MAX_LIGHTS: u32 = 8u;
+const LIGHT_POINT: u32 = 1u;
+
+struct Light {
+    // The direction of a directional light, or the position of a point light.
+    vector: vec3<f32>,
+    kind: u32,
+    color: vec3<f32>,
+    intensity: f32,
+    range: f32,
+}
+
 struct SunInformation {
     direction: vec3<f32>,
     intensity: f32,
     ambient: f32,
+    light_count: u32,
+    lights: array<Light, MAX_LIGHTS>,
+}
+
+struct MaterialInformation {
+    camera_position: vec3<f32>,
+    texture_variants: u32,
+    unlit: u32,
+}
+
+struct ShadowInformation {
+    light_transform: mat4x4<f32>,
+    enabled: f32,
+    bias: f32,
+    texel_size: f32,
 }
 
 @group(0)
@@ -28,32 +70,165 @@ var texture: texture_2d<f32>;
 @binding(1)
 var texture_sampler: sampler;
 
+@group(1)
+@binding(2)
+var normal_map: texture_2d<f32>;
+
+@group(1)
+@binding(3)
+var specular_map: texture_2d<f32>;
+
+@group(1)
+@binding(4)
+var<uniform> material: MaterialInformation;
+
 @group(2)
 @binding(0)
 var<uniform> sun: SunInformation;
 
+@group(3)
+@binding(0)
+var<uniform> shadow: ShadowInformation;
+
+@group(3)
+@binding(1)
+var shadow_map: texture_depth_2d;
+
+@group(3)
+@binding(2)
+var shadow_sampler: sampler_comparison;
+
+// Moves the coordinates into the given variant of a texture made of variants stacked top to bottom.
+fn compute_variant_tex_coord(tex_coord: vec2<f32>, variant: u32, variants: u32) -> vec2<f32> {
+    let count = max(variants, 1u);
+    let index = min(variant, count - 1u);
+
+    return vec2<f32>(tex_coord.x, (tex_coord.y + f32(index)) / f32(count));
+}
+
 @vertex
 fn vs_main(
     vertex: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let instance_transform = mat4x4<f32>(
+        instance.transform_0,
+        instance.transform_1,
+        instance.transform_2,
+        instance.transform_3,
+    );
+    let world_position = instance_transform * vec4<f32>(vertex.position.xyz, 1.0);
+
     var result: VertexOutput;
-    result.tex_coord = vertex.tex_coord;
-    result.position = transform * vertex.position;
-    result.normal = vertex.normal;
+    result.tex_coord = compute_variant_tex_coord(vertex.tex_coord, instance.texture_variant, material.texture_variants);
+    result.material_tex_coord = vertex.tex_coord;
+    result.position = transform * world_position;
+    result.normal = normalize((instance_transform * vec4<f32>(vertex.normal, 0.0)).xyz);
+    result.world_position = world_position.xyz;
+    result.tint = vertex.tint;
+    result.tangent = vec4<f32>((instance_transform * vec4<f32>(vertex.tangent.xyz, 0.0)).xyz, vertex.tangent.w);
     return result;
 }
 const MAX_LIGHT: f32 = 1.0;
 
+fn compute_light(
+    light: Light,
+    normal: vec3<f32>,
+    world_position: vec3<f32>,
+) -> vec3<f32> {
+    if (light.kind == LIGHT_POINT) {
+        let to_light = light.vector - world_position;
+        let distance = length(to_light);
+
+        var attenuation: f32 = 0.0;
+        if (light.range > 0.0) {
+            let falloff = clamp(1.0 - distance / light.range, 0.0, 1.0);
+            attenuation = falloff * falloff;
+        }
+
+        return light.color * light.intensity * attenuation * max(dot(normal, normalize(to_light)), 0.0);
+    }
+
+    return light.color * light.intensity * max(dot(normal, -normalize(light.vector)), 0.0);
+}
+
+// Returns how lit the given position is by the sun, from 0 (fully in shadow) to 1.
+fn compute_shadow(world_position: vec3<f32>) -> f32 {
+    if (shadow.enabled == 0.0) {
+        return 1.0;
+    }
+
+    let light_position = shadow.light_transform * vec4<f32>(world_position, 1.0);
+    let projected = light_position.xyz / light_position.w;
+    let uv = projected.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || projected.z > 1.0) {
+        return 1.0;
+    }
+
+    // Average a few samples around the position, to soften the edges of the shadow.
+    var lit: f32 = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, projected.z - shadow.bias);
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// Bends the normal of the face with the normal map, which points up (in the texture) with green.
+fn compute_mapped_normal(vertex: VertexOutput) -> vec3<f32> {
+    let mapped = textureSample(normal_map, texture_sampler, vertex.material_tex_coord).xyz * 2.0 - 1.0;
+
+    let normal = normalize(vertex.normal);
+    let tangent = normalize(vertex.tangent.xyz);
+    // The bitangent points along increasing v, which goes down the texture.
+    let bitangent = cross(normal, tangent) * vertex.tangent.w;
+
+    return normalize(tangent * mapped.x - bitangent * mapped.y + normal * mapped.z);
+}
+
+// Returns the strength of the sun's highlight, given the specular map's strength (red) and glossiness (green).
+fn compute_specular(
+    specular: vec4<f32>,
+    normal: vec3<f32>,
+    world_position: vec3<f32>,
+    sun_direction: vec3<f32>,
+) -> f32 {
+    if (specular.r == 0.0 || dot(normal, -sun_direction) <= 0.0) {
+        return 0.0;
+    }
+
+    let view_direction = normalize(material.camera_position - world_position);
+    let half_direction = normalize(view_direction - sun_direction);
+    let exponent = 1.0 + specular.g * 127.0;
+
+    return specular.r * sun.intensity * pow(max(dot(normal, half_direction), 0.0001), exponent);
+}
+
 fn compute_sun_lighting(
     color: vec4<f32>,
     normal: vec3<f32>,
+    world_position: vec3<f32>,
+    specular: vec4<f32>,
 ) -> vec4<f32> {
     var sun_direction: vec3<f32> = normalize(sun.direction);
-    var sun_dot: f32 = dot(normal, -sun_direction);
+    let sun_visibility = compute_shadow(world_position);
+    var sun_dot: f32 = dot(normal, -sun_direction) * sun_visibility;
     
     var sun_color: vec3<f32> = vec3<f32>(1.0, 1.0, 1.0) * clamp(sun.intensity * sun_dot, sun.ambient, MAX_LIGHT);
-    
-    return color * vec4<f32>(sun_color, 1.0);
+
+    for (var i = 0u; i < min(sun.light_count, MAX_LIGHTS); i++) {
+        sun_color += compute_light(sun.lights[i], normal, world_position);
+    }
+
+    let lit = color * vec4<f32>(min(sun_color, vec3<f32>(MAX_LIGHT)), 1.0);
+    let highlight = compute_specular(specular, normal, world_position, sun_direction) * sun_visibility;
+
+    // Colors are premultiplied, so the highlight can't get brighter than the alpha.
+    return vec4<f32>(min(lit.rgb + vec3<f32>(highlight * lit.a), vec3<f32>(lit.a)), lit.a);
 }
 
 @fragment
@@ -64,9 +239,40 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
         vec2<f32>(vertex.tex_coord)
     );
     
+    // Sampled before discarding, while all the fragments still sample together.
+    let normal = compute_mapped_normal(vertex);
+    let specular = textureSample(specular_map, texture_sampler, vertex.material_tex_coord);
+    
     if (color.a == 0.0) {
         discard;
     }
     
-    return compute_sun_lighting(color, vertex.normal);
-}
\ No newline at end of file
+    // Colors are premultiplied, so the tint's alpha scales the color too.
+    color = vec4<f32>(color.rgb * vertex.tint.rgb * vertex.tint.a, color.a * vertex.tint.a);
+    
+    // @hook(base_color)
+    
+    if (material.unlit == 0u) {
+        color = compute_sun_lighting(color, normal, vertex.world_position, specular);
+    }
+    
+    // @hook(final_color)
+    
+    return color;
+}
+
+// Draws the edges of the triangles in the color of the texture under them, ignoring its transparency and the lighting,
+// so the geometry and uv layout of parts show up even where their texture is empty.
+@fragment
+fn fs_wireframe(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(texture, texture_sampler, vec2<f32>(vertex.tex_coord), 0.0);
+
+    // Empty texels are shown by their uv coordinates instead, which makes misplaced uvs easy to spot.
+    if (color.a == 0.0) {
+        return vec4<f32>(fract(vertex.tex_coord), 1.0, 1.0);
+    }
+
+    return vec4<f32>(color.rgb / color.a, 1.0);
+}
+
+// @hook(declarations)
@@ -0,0 +1,127 @@
+use strum::{EnumIter, IntoStaticStr};
+
+/// The built-in scene shader, which hooks are injected into.
+const SCENE_SHADER: &str = include_str!("shader.wgsl");
+
+/// The entry point of the fragment stage of the built-in scene shader.
+const DEFAULT_FRAGMENT_ENTRY_POINT: &str = "fs_main";
+
+/// The named places of the scene shader where WGSL snippets can be injected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ShaderHookPoint {
+    /// Module scope, for the functions, constants and entry points used by the other hooks.
+    Declarations,
+    /// In the fragment stage, once the texture is sampled and tinted, before lighting.
+    ///
+    /// Snippets can read and change `color` (a premultiplied `vec4<f32>`), and read `vertex` (the `VertexOutput`),
+    /// `normal` and `specular`.
+    BaseColor,
+    /// In the fragment stage, once lighting is applied, right before `color` is returned.
+    ///
+    /// The same variables as in [`ShaderHookPoint::BaseColor`] are available.
+    FinalColor,
+}
+
+impl ShaderHookPoint {
+    /// The comment marking the hook point in the shader.
+    fn marker(&self) -> String {
+        format!("// @hook({})", Into::<&str>::into(*self))
+    }
+}
+
+/// Changes made to the scene shader for a scene, without replacing the whole shader of the [`GraphicsContext`](super::GraphicsContext).
+///
+/// Snippets are injected into the built-in shader at their hook points, in the order they were added.
+/// The fragment stage can also be replaced entirely, with an entry point declared in a [`ShaderHookPoint::Declarations`] snippet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SceneShader {
+    hooks: Vec<(ShaderHookPoint, String)>,
+    fragment_entry_point: Option<String>,
+}
+
+impl SceneShader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Injects a WGSL snippet at a hook point.
+    pub fn with_hook(mut self, point: ShaderHookPoint, snippet: impl Into<String>) -> Self {
+        self.hooks.push((point, snippet.into()));
+        self
+    }
+
+    /// Replaces the fragment stage with the given entry point, declared in the given source.
+    ///
+    /// The entry point takes the `VertexOutput` of the vertex stage, and can use the bindings of the scene shader.
+    pub fn with_fragment_stage(
+        self,
+        entry_point: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Self {
+        let mut shader = self.with_hook(ShaderHookPoint::Declarations, source);
+        shader.fragment_entry_point = Some(entry_point.into());
+        shader
+    }
+
+    pub fn hooks(&self) -> &[(ShaderHookPoint, String)] {
+        &self.hooks
+    }
+
+    pub(crate) fn fragment_entry_point(&self) -> &str {
+        self.fragment_entry_point
+            .as_deref()
+            .unwrap_or(DEFAULT_FRAGMENT_ENTRY_POINT)
+    }
+
+    /// Returns the source of the scene shader with the snippets injected.
+    pub(crate) fn source(&self) -> String {
+        self.hooks
+            .iter()
+            .fold(SCENE_SHADER.to_owned(), |source, (point, snippet)| {
+                let marker = point.marker();
+                // Keep the marker after the snippet, so later snippets for the same hook go after it.
+                source.replace(&marker, &format!("{}\n{}", snippet, marker))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::{SceneShader, ShaderHookPoint, SCENE_SHADER};
+
+    #[test]
+    fn every_hook_point_is_marked_once() {
+        for point in ShaderHookPoint::iter() {
+            assert_eq!(SCENE_SHADER.matches(&point.marker()).count(), 1, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn snippets_are_injected_in_order() {
+        let shader = SceneShader::new()
+            .with_hook(ShaderHookPoint::FinalColor, "color = color.bgra;")
+            .with_hook(ShaderHookPoint::FinalColor, "color = color * 0.5;");
+
+        let source = shader.source();
+        let first = source.find("color = color.bgra;").unwrap();
+        let second = source.find("color = color * 0.5;").unwrap();
+
+        assert!(first < second);
+        assert!(second < source.find(&ShaderHookPoint::FinalColor.marker()).unwrap());
+        assert_eq!(shader.fragment_entry_point(), "fs_main");
+    }
+
+    #[test]
+    fn fragment_stage_can_be_replaced() {
+        let shader = SceneShader::new().with_fragment_stage(
+            "fs_flat",
+            "@fragment fn fs_flat(vertex: VertexOutput) -> @location(0) vec4<f32> { return vertex.tint; }",
+        );
+
+        assert!(shader.source().contains("fn fs_flat"));
+        assert_eq!(shader.fragment_entry_point(), "fs_flat");
+    }
+}
@@ -0,0 +1,79 @@
+struct VertexInput {
+    @location(0) position: vec4<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) transform_0: vec4<f32>,
+    @location(6) transform_1: vec4<f32>,
+    @location(7) transform_2: vec4<f32>,
+    @location(8) transform_3: vec4<f32>,
+    @location(9) texture_variant: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+};
+
+struct MaterialInformation {
+    camera_position: vec3<f32>,
+    texture_variants: u32,
+    unlit: u32,
+}
+
+struct ShadowInformation {
+    light_transform: mat4x4<f32>,
+    enabled: f32,
+    bias: f32,
+    texel_size: f32,
+}
+
+@group(0)
+@binding(0)
+var<uniform> shadow: ShadowInformation;
+
+@group(1)
+@binding(0)
+var texture: texture_2d<f32>;
+
+@group(1)
+@binding(1)
+var texture_sampler: sampler;
+
+@group(1)
+@binding(4)
+var<uniform> material: MaterialInformation;
+
+@vertex
+fn vs_main(
+    vertex: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let instance_transform = mat4x4<f32>(
+        instance.transform_0,
+        instance.transform_1,
+        instance.transform_2,
+        instance.transform_3,
+    );
+
+    // Same as the main shader, the variants of a texture are stacked top to bottom.
+    let variants = max(material.texture_variants, 1u);
+    let variant = min(instance.texture_variant, variants - 1u);
+
+    var result: VertexOutput;
+    result.tex_coord = vec2<f32>(vertex.tex_coord.x, (vertex.tex_coord.y + f32(variant)) / f32(variants));
+    result.position = shadow.light_transform * instance_transform * vec4<f32>(vertex.position.xyz, 1.0);
+    return result;
+}
+
+// Only writes depth, but transparent pixels (like the holes in a hat layer) shouldn't cast a shadow.
+@fragment
+fn fs_main(vertex: VertexOutput) {
+    let color = textureSample(texture, texture_sampler, vertex.tex_coord);
+
+    if (color.a == 0.0) {
+        discard;
+    }
+}
@@ -0,0 +1,218 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use nmsr_player_parts::parts::part::Part;
+use tracing::trace_span;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer,
+    CompareFunction, FilterMode, SamplerDescriptor, TextureUsages,
+};
+
+use super::{
+    textures::{create_texture, SceneTexture},
+    GraphicsContext,
+};
+use crate::high_level::utils::buffer::create_buffer_and_bind_group;
+
+/// How the shadows cast by the sun are rendered, when shadow mapping is enabled on a scene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowMapSettings {
+    /// The width and height of the shadow map, in pixels. Bigger shadow maps give sharper shadows.
+    pub resolution: u32,
+    /// How far a surface needs to be behind another one (as seen from the sun) to be in its shadow.
+    /// This avoids surfaces shadowing themselves.
+    pub bias: f32,
+}
+
+impl Default for ShadowMapSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.002,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub(crate) struct ShadowInformation {
+    light_transform: Mat4,
+    enabled: f32,
+    bias: f32,
+    texel_size: f32,
+    _padding: f32,
+}
+
+impl ShadowInformation {
+    fn disabled() -> Self {
+        Self {
+            light_transform: Mat4::IDENTITY,
+            enabled: 0.0,
+            bias: 0.0,
+            texel_size: 0.0,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The shadow map of a scene context, along with what the shaders need to render into and sample it.
+#[derive(Debug)]
+pub(crate) struct ShadowMap {
+    pub(crate) texture: SceneTexture,
+    resolution: u32,
+    information_buffer: Buffer,
+    /// Binds the light transform for rendering the shadow map.
+    pub(crate) light_bind_group: BindGroup,
+    /// Binds the shadow map for the main pass to sample.
+    pub(crate) shadow_bind_group: BindGroup,
+}
+
+impl ShadowMap {
+    /// The resolution of the placeholder shadow map bound while shadow mapping is disabled.
+    const DISABLED_RESOLUTION: u32 = 1;
+
+    pub(crate) fn new(context: &GraphicsContext) -> Self {
+        let (information_buffer, light_bind_group) = create_buffer_and_bind_group(
+            &context.device,
+            "Shadow Information",
+            &context.layouts.light_transform_bind_group_layout,
+            &[ShadowInformation::disabled()],
+        );
+
+        let (texture, shadow_bind_group) =
+            Self::create_texture(context, &information_buffer, Self::DISABLED_RESOLUTION);
+
+        Self {
+            texture,
+            resolution: Self::DISABLED_RESOLUTION,
+            information_buffer,
+            light_bind_group,
+            shadow_bind_group,
+        }
+    }
+
+    fn create_texture(
+        context: &GraphicsContext,
+        information_buffer: &Buffer,
+        resolution: u32,
+    ) -> (SceneTexture, BindGroup) {
+        let texture = create_texture(
+            context,
+            resolution,
+            resolution,
+            GraphicsContext::DEPTH_TEXTURE_FORMAT,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            Some("Shadow Map"),
+            1,
+        );
+
+        let sampler = context.device.create_sampler(&SamplerDescriptor {
+            label: Some("Shadow Map Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Shadow Map Bind Group"),
+            layout: &context.layouts.shadow_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: information_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        (texture, bind_group)
+    }
+
+    /// Gets the shadow map ready for a render, resizing it if needed.
+    ///
+    /// The sun shines along the given direction onto the given parts, which the shadow map is fitted around.
+    pub(crate) fn prepare(
+        &mut self,
+        context: &GraphicsContext,
+        settings: Option<ShadowMapSettings>,
+        sun_direction: Vec3,
+        parts: &[Part],
+    ) {
+        let resolution = settings.map_or(Self::DISABLED_RESOLUTION, |s| s.resolution.max(1));
+
+        if resolution != self.resolution {
+            let _guard = trace_span!("create_shadow_map").entered();
+
+            (self.texture, self.shadow_bind_group) =
+                Self::create_texture(context, &self.information_buffer, resolution);
+            self.resolution = resolution;
+        }
+
+        let information = match settings {
+            Some(settings) => ShadowInformation {
+                light_transform: Self::compute_light_transform(sun_direction, parts),
+                enabled: 1.0,
+                bias: settings.bias,
+                texel_size: 1.0 / resolution as f32,
+                _padding: 0.0,
+            },
+            None => ShadowInformation::disabled(),
+        };
+
+        context.queue.write_buffer(
+            &self.information_buffer,
+            0,
+            bytemuck::cast_slice(&[information]),
+        );
+    }
+
+    /// Computes an orthographic projection looking along the sun's direction, that fits all the given parts.
+    fn compute_light_transform(sun_direction: Vec3, parts: &[Part]) -> Mat4 {
+        let (min, max) = parts
+            .iter()
+            .flat_map(|part| {
+                let transform = part.get_rotation_matrix();
+                let position = part.get_position();
+                let size = part.get_size();
+
+                [
+                    transform.transform_point3(position),
+                    transform.transform_point3(position + size),
+                ]
+            })
+            .fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)), |(min, max), point| {
+                (min.min(point), max.max(point))
+            });
+
+        if min.cmpgt(max).any() {
+            return Mat4::IDENTITY;
+        }
+
+        let center = (min + max) / 2.0;
+        // Parts can be rotated, so fit a sphere around them instead of trusting the corners.
+        let radius = (max - min).length() / 2.0 + 1.0;
+
+        let direction = sun_direction.try_normalize().unwrap_or(Vec3::NEG_Y);
+        let up = if direction.abs().abs_diff_eq(Vec3::Y, 1e-3) {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+
+        let view = Mat4::look_at_rh(center - direction * radius * 2.0, center, up);
+        let projection =
+            Mat4::orthographic_rh(-radius, radius, -radius, radius, radius, radius * 3.0);
+
+        projection * view
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec3, Vec4};
+use image::RgbaImage;
+use nmsr_player_parts::{
+    model::ArmorMaterial,
+    parts::{part::Part, provider::PlayerPartProviderContext},
+    types::{PlayerBodyPartType, PlayerPartTextureType},
+};
+use tracing::{instrument, trace_span};
+
+use super::{
+    scene::{Scene, Size, SunInformation},
+    textures::{premultiply_alpha, unmultiply_alpha},
+    SceneContextWrapper,
+};
+use crate::{
+    errors::{NMSRRenderingError, Result},
+    high_level::{camera::Camera, utils::parts::primitive_convert},
+    low_level::primitives::{part_primitive::PartPrimitive, vertex::Vertex},
+};
+
+/// A scene rendered on the CPU, for machines without a (working) graphics adapter.
+///
+/// This mimics what [`Scene`] does on the GPU: parts are drawn in texture order with a depth test,
+/// premultiplied alpha blending and the same sun lighting as the shader. It's a lot slower, but it
+/// doesn't need anything besides the CPU.
+pub struct SoftwareScene {
+    camera: Camera,
+    viewport_size: Size,
+    textures: HashMap<PlayerPartTextureType, RgbaImage>,
+    computed_body_parts: Vec<Part>,
+    sun_information: SunInformation,
+}
+
+/// A vertex after it went through the camera's view projection matrix.
+#[derive(Clone, Copy)]
+struct ProjectedVertex {
+    /// The position of the vertex on the render target, in pixels.
+    screen: Vec2,
+    depth: f32,
+    /// The reciprocal of the clip space w, used to interpolate attributes with perspective correction.
+    inverse_w: f32,
+    uv: Vec2,
+}
+
+/// The color and depth buffers a [`SoftwareScene`] is rendered into.
+struct RenderTarget {
+    width: u32,
+    height: u32,
+    /// Premultiplied RGBA colors.
+    color: Vec<Vec4>,
+    depth: Vec<f32>,
+}
+
+impl SoftwareScene {
+    /// Each side of a pixel is sampled this many times, to smooth out edges like multisampling does on the GPU.
+    const SUPERSAMPLING: u32 = 2;
+
+    pub fn new<M: ArmorMaterial>(
+        mut camera: Camera,
+        sun: SunInformation,
+        viewport_size: Size,
+        part_context: &PlayerPartProviderContext<M>,
+        body_parts: &[PlayerBodyPartType],
+    ) -> Self {
+        if camera.get_size().is_none() {
+            camera.set_size(Some(viewport_size));
+        }
+
+        let computed_body_parts =
+            Scene::<SceneContextWrapper>::collect_player_parts(part_context, body_parts);
+
+        let mut scene = Self {
+            camera,
+            viewport_size,
+            textures: HashMap::new(),
+            computed_body_parts,
+            sun_information: sun,
+        };
+
+        if part_context.shadow_y_pos.is_some() {
+            let shadow_bytes =
+                Scene::<SceneContextWrapper>::get_shadow_bytes(part_context.shadow_is_square);
+
+            let shadow_image =
+                image::load_from_memory_with_format(shadow_bytes, image::ImageFormat::Png)
+                    .expect("Failed to load shadow texture")
+                    .into_rgba8();
+
+            scene.set_texture(PlayerPartTextureType::Shadow, &shadow_image);
+        }
+
+        scene
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn sun_information_mut(&mut self) -> &mut SunInformation {
+        &mut self.sun_information
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.computed_body_parts
+    }
+
+    pub fn set_texture(&mut self, texture_type: PlayerPartTextureType, texture: &RgbaImage) {
+        let mut texture = texture.clone();
+
+        // Like on the GPU, textures are stored with premultiplied alpha.
+        premultiply_alpha(&mut texture);
+
+        self.textures.insert(texture_type, texture);
+    }
+
+    /// Renders the scene, returning the RGBA pixels of the render, row by row.
+    #[instrument(skip(self))]
+    pub fn render(&mut self) -> Result<Vec<u8>> {
+        let camera_size = self.camera.get_size().unwrap_or(self.viewport_size);
+        let view_projection = self.camera.get_view_projection_matrix();
+
+        let mut target = RenderTarget::new(
+            camera_size.width * Self::SUPERSAMPLING,
+            camera_size.height * Self::SUPERSAMPLING,
+        );
+
+        for part in &self.computed_body_parts {
+            let texture_type = part.get_texture();
+
+            let texture = self
+                .textures
+                .get(&texture_type)
+                .ok_or(NMSRRenderingError::SceneContextTextureNotSet(texture_type))?;
+
+            let triangles = trace_span!("part_convert")
+                .in_scope(|| primitive_convert(part).get_vertices_grouped());
+
+            for triangle in triangles {
+                // Triangles crossing the camera's near plane aren't clipped, they're just skipped.
+                let [Some(a), Some(b), Some(c)] =
+                    triangle.map(|vertex| project_vertex(&vertex, view_projection, &target))
+                else {
+                    continue;
+                };
+
+                let center = (triangle[0].position + triangle[1].position + triangle[2].position) / 3.0;
+                let light = self.compute_sun_lighting(triangle[0].normal, center);
+
+                target.draw_triangle(
+                    [a, b, c],
+                    texture,
+                    texture_type.is_shadow(),
+                    light,
+                    triangle[0].tint,
+                );
+            }
+        }
+
+        let mut pixels = target.resolve(Self::SUPERSAMPLING, self.viewport_size);
+
+        unmultiply_alpha(&mut pixels);
+
+        Ok(pixels)
+    }
+
+    /// The same lighting as the shader, given the normal and the position of the face being drawn.
+    fn compute_sun_lighting(&self, normal: Vec3, position: Vec3) -> Vec3 {
+        let sun = &self.sun_information;
+        let sun_dot = normal.dot(-sun.direction.normalize());
+
+        // Not using f32::clamp, since it panics if the ambient light is greater than the maximum.
+        let sun_light = Vec3::splat((sun.intensity * sun_dot).max(sun.ambient).min(1.0));
+
+        sun.lights()
+            .fold(sun_light, |total, light| {
+                total + light.compute_lighting(normal, position)
+            })
+            .min(Vec3::ONE)
+    }
+}
+
+fn project_vertex(
+    vertex: &Vertex,
+    view_projection: glam::Mat4,
+    target: &RenderTarget,
+) -> Option<ProjectedVertex> {
+    let clip = view_projection * vertex.position.extend(1.0);
+
+    if clip.w <= f32::EPSILON {
+        return None;
+    }
+
+    let inverse_w = clip.w.recip();
+    let ndc = clip.truncate() * inverse_w;
+
+    Some(ProjectedVertex {
+        screen: Vec2::new(
+            (ndc.x + 1.0) * 0.5 * target.width as f32,
+            (1.0 - ndc.y) * 0.5 * target.height as f32,
+        ),
+        depth: ndc.z,
+        inverse_w,
+        uv: vertex.uv,
+    })
+}
+
+impl RenderTarget {
+    fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+
+        Self {
+            width,
+            height,
+            color: vec![Vec4::ZERO; len],
+            depth: vec![1.0; len],
+        }
+    }
+
+    fn draw_triangle(
+        &mut self,
+        [a, b, c]: [ProjectedVertex; 3],
+        texture: &RgbaImage,
+        is_shadow: bool,
+        light: Vec3,
+        tint: Vec4,
+    ) {
+        let edge = |from: Vec2, to: Vec2, point: Vec2| {
+            (to.x - from.x) * (point.y - from.y) - (to.y - from.y) * (point.x - from.x)
+        };
+
+        let area = edge(a.screen, b.screen, c.screen);
+        if area.abs() <= f32::EPSILON {
+            return;
+        }
+
+        let min = a.screen.min(b.screen).min(c.screen).floor().max(Vec2::ZERO);
+        let max = a
+            .screen
+            .max(b.screen)
+            .max(c.screen)
+            .ceil()
+            .min(Vec2::new(self.width as f32, self.height as f32));
+
+        for y in (min.y as u32)..(max.y as u32) {
+            for x in (min.x as u32)..(max.x as u32) {
+                let point = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                // Barycentric coordinates, faces are drawn regardless of their winding order.
+                let weights = Vec3::new(
+                    edge(b.screen, c.screen, point),
+                    edge(c.screen, a.screen, point),
+                    edge(a.screen, b.screen, point),
+                ) / area;
+
+                if weights.min_element() < 0.0 {
+                    continue;
+                }
+
+                let index = y as usize * self.width as usize + x as usize;
+
+                let depth = weights.dot(Vec3::new(a.depth, b.depth, c.depth));
+                if !(0.0..=1.0).contains(&depth) || depth > self.depth[index] {
+                    continue;
+                }
+
+                let inverse_w = weights.dot(Vec3::new(a.inverse_w, b.inverse_w, c.inverse_w));
+                let uv = (a.uv * a.inverse_w * weights.x
+                    + b.uv * b.inverse_w * weights.y
+                    + c.uv * c.inverse_w * weights.z)
+                    / inverse_w;
+
+                let color = if is_shadow {
+                    sample_linear(texture, uv)
+                } else {
+                    sample_nearest(texture, uv)
+                };
+
+                if color.w == 0.0 {
+                    continue;
+                }
+
+                // Colors are premultiplied, so the tint's alpha scales the color too.
+                let color = (color.truncate() * light * tint.truncate() * tint.w)
+                    .extend(color.w * tint.w);
+                let destination = self.color[index];
+
+                self.color[index] = color + destination * (1.0 - color.w);
+
+                // The shadow doesn't write to the depth buffer, so it never hides anything.
+                if !is_shadow {
+                    self.depth[index] = depth;
+                }
+            }
+        }
+    }
+
+    /// Averages the samples of each pixel, returning the premultiplied RGBA pixels of the given region.
+    fn resolve(&self, samples: u32, size: Size) -> Vec<u8> {
+        let width = size.width.min(self.width / samples);
+        let height = size.height.min(self.height / samples);
+
+        let mut pixels = vec![0; size.width as usize * size.height as usize * 4];
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut color = Vec4::ZERO;
+
+                for sample_y in 0..samples {
+                    for sample_x in 0..samples {
+                        let index = (y * samples + sample_y) as usize * self.width as usize
+                            + (x * samples + sample_x) as usize;
+
+                        color += self.color[index];
+                    }
+                }
+
+                color /= (samples * samples) as f32;
+
+                let offset = (y as usize * size.width as usize + x as usize) * 4;
+                for (pixel, channel) in pixels[offset..offset + 4].iter_mut().zip(color.to_array())
+                {
+                    *pixel = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+fn texel(texture: &RgbaImage, x: i64, y: i64) -> Vec4 {
+    // Textures are clamped to their edges, like the GPU sampler does.
+    let x = x.clamp(0, i64::from(texture.width()) - 1) as u32;
+    let y = y.clamp(0, i64::from(texture.height()) - 1) as u32;
+
+    Vec4::from_array(texture.get_pixel(x, y).0.map(f32::from)) / 255.0
+}
+
+fn sample_nearest(texture: &RgbaImage, uv: Vec2) -> Vec4 {
+    let x = (uv.x * texture.width() as f32).floor() as i64;
+    let y = (uv.y * texture.height() as f32).floor() as i64;
+
+    texel(texture, x, y)
+}
+
+fn sample_linear(texture: &RgbaImage, uv: Vec2) -> Vec4 {
+    let x = uv.x * texture.width() as f32 - 0.5;
+    let y = uv.y * texture.height() as f32 - 0.5;
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let top = texel(texture, x0, y0).lerp(texel(texture, x0 + 1, y0), fx);
+    let bottom = texel(texture, x0, y0 + 1).lerp(texel(texture, x0 + 1, y0 + 1), fx);
+
+    top.lerp(bottom, fy)
+}
@@ -44,10 +44,26 @@ pub(crate) struct SceneContextTextures {
     pub(crate) depth_texture: SceneTexture,
     pub(crate) output_texture: SceneTexture,
     pub(crate) multisampled_output_texture: Option<SceneTexture>,
+    /// The texture the scene is drawn into when supersampling, before it's scaled down into the output texture.
+    pub(crate) supersampled_output_texture: Option<SceneTexture>,
     pub(crate) texture_output_buffer: Buffer,
     pub(crate) texture_output_buffer_dimensions: BufferDimensions,
     pub(crate) camera_size: Size,
     pub(crate) viewport_size: Size,
+    pub(crate) supersampling: u32,
+    pub(crate) sample_count: u32,
+    /// The format of the output textures and buffer.
+    pub(crate) format: TextureFormat,
+}
+
+impl SceneContextTextures {
+    /// The size the scene is actually drawn at, which is bigger than the camera's when supersampling.
+    pub(crate) fn render_size(&self) -> Size {
+        Size {
+            width: self.camera_size.width * self.supersampling,
+            height: self.camera_size.height * self.supersampling,
+        }
+    }
 }
 
 pub fn premultiply_alpha(image: &mut RgbaImage) {
@@ -70,6 +86,141 @@ pub fn unmultiply_alpha(image: &mut [u8]) {
     }
 }
 
+/// Composites premultiplied pixels over an opaque color, leaving them all opaque.
+pub fn composite_over_color(image: &mut [u8], [r, g, b]: [u8; 3]) {
+    for pixel in image.chunks_exact_mut(4) {
+        let remaining = 1.0 - pixel[3] as f32 / 255.0;
+        pixel[0] = pixel[0].saturating_add((r as f32 * remaining) as u8);
+        pixel[1] = pixel[1].saturating_add((g as f32 * remaining) as u8);
+        pixel[2] = pixel[2].saturating_add((b as f32 * remaining) as u8);
+        pixel[3] = u8::MAX;
+    }
+}
+
+/// Decodes the pixels read back from an output texture to premultiplied RGBA floats, in linear space.
+///
+/// `srgb_encoded` tells whether the pixels are stored in sRGB space, which 8-bit outputs always are.
+/// Returns [`None`] for formats that can't be decoded.
+pub fn decode_float_pixels(
+    format: TextureFormat,
+    srgb_encoded: bool,
+    bytes: &[u8],
+) -> Option<Vec<f32>> {
+    let mut pixels: Vec<f32> = match format {
+        TextureFormat::Rgba32Float => bytes
+            .chunks_exact(4)
+            .map(|channel| f32::from_le_bytes([channel[0], channel[1], channel[2], channel[3]]))
+            .collect(),
+        TextureFormat::Rgba16Float => bytes
+            .chunks_exact(2)
+            .map(|channel| f16_to_f32(u16::from_le_bytes([channel[0], channel[1]])))
+            .collect(),
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => bytes
+            .iter()
+            .map(|&channel| channel as f32 / 255.0)
+            .collect(),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => bytes
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .map(|channel| channel as f32 / 255.0)
+            .collect(),
+        _ => return None,
+    };
+
+    if srgb_encoded || format.block_size(None) == Some(4) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = srgb_to_linear(*channel);
+            }
+        }
+    }
+
+    Some(pixels)
+}
+
+/// Converts the pixels read back from an output texture to premultiplied RGBA8, in sRGB space.
+///
+/// `srgb_encoded` tells whether float pixels are stored in sRGB space, like for [`decode_float_pixels`].
+/// Returns [`None`] for formats that can't be decoded.
+pub fn encode_rgba8_pixels(
+    format: TextureFormat,
+    srgb_encoded: bool,
+    mut bytes: Vec<u8>,
+) -> Option<Vec<u8>> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Some(bytes),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            for pixel in bytes.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+
+            Some(bytes)
+        }
+        _ => {
+            let pixels = decode_float_pixels(format, srgb_encoded, &bytes)?;
+
+            Some(
+                pixels
+                    .chunks_exact(4)
+                    .flat_map(|pixel| {
+                        [
+                            linear_to_srgb(pixel[0]),
+                            linear_to_srgb(pixel[1]),
+                            linear_to_srgb(pixel[2]),
+                            pixel[3],
+                        ]
+                    })
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Returns whether renders in the given format can be read back, with [`decode_float_pixels`] and [`encode_rgba8_pixels`].
+pub fn is_readable_output_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+            | TextureFormat::Rgba16Float
+            | TextureFormat::Rgba32Float
+    )
+}
+
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.0031308 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a half-precision float (as stored in [`TextureFormat::Rgba16Float`] textures) to a regular one.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    sign * match exponent {
+        // Subnormal numbers
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
 #[instrument(skip(context, usage))]
 pub fn create_texture(
     context: &GraphicsContext,
@@ -98,3 +249,51 @@ pub fn create_texture(
 
     SceneTexture { texture, view }
 }
+
+#[cfg(test)]
+mod tests {
+    use wgpu::TextureFormat;
+
+    use super::{decode_float_pixels, encode_rgba8_pixels, f16_to_f32};
+
+    #[test]
+    fn half_floats_are_decoded() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn eight_bit_outputs_are_decoded_to_linear() {
+        let pixels =
+            decode_float_pixels(TextureFormat::Bgra8Unorm, false, &[0, 0, 255, 255]).unwrap();
+
+        assert_eq!(pixels, vec![1.0, 0.0, 0.0, 1.0]);
+
+        let pixels =
+            decode_float_pixels(TextureFormat::Rgba8Unorm, false, &[128, 128, 128, 255]).unwrap();
+
+        assert!((pixels[0] - 0.2158605).abs() < 1e-4);
+        assert_eq!(pixels[3], 1.0);
+    }
+
+    #[test]
+    fn outputs_are_encoded_to_rgba8() {
+        let pixels =
+            encode_rgba8_pixels(TextureFormat::Bgra8Unorm, false, vec![1, 2, 3, 4]).unwrap();
+
+        assert_eq!(pixels, vec![3, 2, 1, 4]);
+
+        // 1.0, 0.5 and 0.0 as half floats, in linear space
+        let bytes = [0x3c00u16, 0x3800, 0x0000, 0x3c00]
+            .iter()
+            .flat_map(|channel| channel.to_le_bytes())
+            .collect();
+        let pixels = encode_rgba8_pixels(TextureFormat::Rgba16Float, false, bytes).unwrap();
+
+        assert_eq!(pixels, vec![255, 188, 0, 255]);
+    }
+}
@@ -3,8 +3,12 @@ use crate::{
     high_level::pipeline::textures::{unmultiply_alpha, BufferDimensions},
 };
 
+#[cfg(all(feature = "deadlines", not(target_arch = "wasm32")))]
+use std::time::Duration;
+use std::time::Instant;
+
 use bytemuck::Pod;
-use tokio::sync::oneshot::channel;
+use tokio::sync::oneshot::{channel, Receiver};
 use tracing::{instrument, trace_span};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
@@ -36,21 +40,94 @@ pub fn create_buffer_and_bind_group<T: Pod>(
     (buffer, transform_bind_group)
 }
 
+/// How long to wait for the GPU before polling the device again at first, when there's a deadline.
+#[cfg(all(feature = "deadlines", not(target_arch = "wasm32")))]
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The longest to wait for the GPU before polling the device again, so that slow renders don't keep the CPU busy.
+#[cfg(all(feature = "deadlines", not(target_arch = "wasm32")))]
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
 #[instrument(name = "buffer_slice_wait", skip(output_buffer, device))]
 async fn wait_for_buffer_slice<'a>(
     output_buffer: &'a Buffer,
     device: &wgpu::Device,
+    deadline: Option<Instant>,
 ) -> Result<BufferSlice<'a>> {
     let buffer_slice = output_buffer.slice(..);
     let (tx, rx) = channel();
     buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-        tx.send(result).unwrap();
+        // Nobody is listening anymore if the caller gave up on waiting for the buffer (e.g. it timed out).
+        let _ = tx.send(result);
     });
-    device.poll(wgpu::Maintain::Wait);
-    rx.await??;
+
+    let mapped = wait_for_work(device, rx, deadline)
+        .await
+        .and_then(|result| Ok(result?));
+
+    if let Err(error) = mapped {
+        // Cancel the pending mapping, so that the buffer can be used again once the GPU catches up.
+        output_buffer.unmap();
+        return Err(error);
+    }
+
     Ok(buffer_slice)
 }
 
+/// Waits until the GPU reports back on the receiver.
+///
+/// Without a deadline, the thread is blocked on the device until the GPU is done, which works with any async runtime
+/// (or none at all). Deadlines are only given with the `deadlines` feature, see [`poll_until`].
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn wait_for_work<T>(
+    device: &wgpu::Device,
+    rx: Receiver<T>,
+    deadline: Option<Instant>,
+) -> Result<T> {
+    match deadline {
+        #[cfg(feature = "deadlines")]
+        Some(deadline) => poll_until(device, rx, deadline).await,
+        _ => {
+            device.poll(wgpu::Maintain::Wait);
+            Ok(rx.await?)
+        }
+    }
+}
+
+/// Polls the device on Tokio's timer until the GPU reports back on the receiver, without blocking the thread,
+/// that way callers are able to give up on a wedged render.
+///
+/// Fails with [`RenderTimedOut`](crate::errors::NMSRRenderingError::RenderTimedOut) if the deadline passes first.
+#[cfg(all(feature = "deadlines", not(target_arch = "wasm32")))]
+async fn poll_until<T>(device: &wgpu::Device, mut rx: Receiver<T>, deadline: Instant) -> Result<T> {
+    let mut interval = MIN_POLL_INTERVAL;
+
+    loop {
+        device.poll(wgpu::Maintain::Poll);
+
+        if let Ok(result) = tokio::time::timeout(interval, &mut rx).await {
+            return Ok(result?);
+        }
+
+        if Instant::now() >= deadline {
+            return Err(crate::errors::NMSRRenderingError::RenderTimedOut);
+        }
+
+        interval = (interval * 2).min(MAX_POLL_INTERVAL);
+    }
+}
+
+/// Browsers report back on their own event loop once the GPU is done, there's nothing to poll.
+/// There's no timer to give up with either, so the deadline is ignored.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn wait_for_work<T>(
+    _device: &wgpu::Device,
+    rx: Receiver<T>,
+    _deadline: Option<Instant>,
+) -> Result<T> {
+    Ok(rx.await?)
+}
+
 //#[instrument(skip_all)]
 pub async fn read_buffer(
     device: &wgpu::Device,
@@ -58,7 +135,17 @@ pub async fn read_buffer(
     dimensions: &BufferDimensions,
     cleanup_alpha: bool,
 ) -> Result<Vec<u8>> {
-    let buffer_slice = wait_for_buffer_slice(output_buffer, device).await?;
+    read_buffer_with_deadline(device, output_buffer, dimensions, cleanup_alpha, None).await
+}
+
+pub(crate) async fn read_buffer_with_deadline(
+    device: &wgpu::Device,
+    output_buffer: &wgpu::Buffer,
+    dimensions: &BufferDimensions,
+    cleanup_alpha: bool,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>> {
+    let buffer_slice = wait_for_buffer_slice(output_buffer, device, deadline).await?;
 
     let data = buffer_slice.get_mapped_range();
 
@@ -1,3 +1,4 @@
+pub mod nameplate;
 pub mod parts;
 
 #[cfg(feature = "pipeline")]
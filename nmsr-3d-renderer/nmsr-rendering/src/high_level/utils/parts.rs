@@ -1,9 +1,18 @@
 use glam::{Vec2, Vec3};
 use nmsr_player_parts::parts::{uv::FaceUv, part::Part};
 
-use crate::low_level::primitives::{quad::Quad, cube::Cube, mesh::PrimitiveDispatch};
+use crate::low_level::primitives::{quad::Quad, cube::Cube, mesh::{Mesh, PrimitiveDispatch}};
 
 pub fn primitive_convert(part: &Part) -> PrimitiveDispatch {
+    let primitive = untinted_primitive_convert(part);
+
+    match part.get_tint() {
+        Some(tint) => Mesh::new(vec![primitive]).with_tint(tint).into(),
+        None => primitive,
+    }
+}
+
+fn untinted_primitive_convert(part: &Part) -> PrimitiveDispatch {
     let position = part.get_position();
     let center = position + part.get_size() / 2.0;
 
@@ -31,6 +40,8 @@ pub fn primitive_convert(part: &Part) -> PrimitiveDispatch {
             face_uv,
             texture,
             normal,
+            double_sided,
+            back_face_uv,
             ..
         } => {
             let x_left = position.x + size.x;
@@ -45,6 +56,9 @@ pub fn primitive_convert(part: &Part) -> PrimitiveDispatch {
             let texture_size = texture.get_texture_size();
             let final_face_uv = uv(face_uv, texture_size);
 
+            // Like the faces of cubes, the normal turns with the quad, so its back face ends up behind it.
+            let normal = model_transform.transform_vector3(*normal).normalize();
+
             Quad::new_with_normal(
                 model_transform.transform_point3(Vec3::new(x_right, y_top, z_back)),
                 model_transform.transform_point3(Vec3::new(x_left, y_top, z_back)),
@@ -54,7 +68,13 @@ pub fn primitive_convert(part: &Part) -> PrimitiveDispatch {
                 final_face_uv[1],
                 final_face_uv[2],
                 final_face_uv[3],
-                *normal,
+                normal,
+            )
+            .with_double_sided(*double_sided)
+            .with_back_face_uvs(
+                back_face_uv
+                    .as_ref()
+                    .map(|back_face_uv| uv(back_face_uv, texture_size)),
             )
             .into()
         }
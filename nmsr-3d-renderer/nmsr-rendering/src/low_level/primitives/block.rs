@@ -0,0 +1,100 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::low_level::primitives::cube::Cube;
+use crate::low_level::primitives::mesh::PrimitiveDispatch;
+use crate::low_level::primitives::part_primitive::PartPrimitive;
+use crate::low_level::primitives::vertex::Vertex;
+
+use super::vertex::VertexUvCoordinates;
+
+/// The area of the texture each face of a block shows, as its top left and bottom right uv coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BlockFaces {
+    pub north: [VertexUvCoordinates; 2],
+    pub south: [VertexUvCoordinates; 2],
+    pub up: [VertexUvCoordinates; 2],
+    pub down: [VertexUvCoordinates; 2],
+    pub west: [VertexUvCoordinates; 2],
+    pub east: [VertexUvCoordinates; 2],
+}
+
+impl BlockFaces {
+    /// Every face shows the same area of the texture (like stone or dirt).
+    pub fn all(uv: [VertexUvCoordinates; 2]) -> Self {
+        Self {
+            north: uv,
+            south: uv,
+            up: uv,
+            down: uv,
+            west: uv,
+            east: uv,
+        }
+    }
+
+    /// The top and bottom faces differ from the sides (like logs or grass blocks).
+    pub fn column(
+        up: [VertexUvCoordinates; 2],
+        down: [VertexUvCoordinates; 2],
+        side: [VertexUvCoordinates; 2],
+    ) -> Self {
+        Self {
+            up,
+            down,
+            ..Self::all(side)
+        }
+    }
+}
+
+/// A textured block, like the ones worn on the head or placed around the player as props.
+pub struct Block {
+    cube: Cube,
+}
+
+impl Block {
+    /// Create a new block with the given parameters
+    pub fn new(center: Vec3, size: Vec3, model_transform: Mat4, faces: BlockFaces) -> Self {
+        // Faces are textured upright, with the top left of their area at the top left of the face.
+        let corners = |[top_left, bottom_right]: [VertexUvCoordinates; 2]| {
+            [
+                top_left,
+                Vec2::new(bottom_right.x, top_left.y),
+                Vec2::new(top_left.x, bottom_right.y),
+                bottom_right,
+            ]
+        };
+
+        Block {
+            cube: Cube::new(
+                center,
+                size,
+                model_transform,
+                corners(faces.north),
+                corners(faces.south),
+                corners(faces.up),
+                corners(faces.down),
+                corners(faces.west),
+                corners(faces.east),
+            ),
+        }
+    }
+}
+
+impl PartPrimitive for Block {
+    fn get_vertices(&self) -> Vec<Vertex> {
+        self.cube.get_vertices()
+    }
+
+    fn get_indices(&self) -> Vec<u16> {
+        self.cube.get_indices()
+    }
+
+    fn get_vertices_grouped(&self) -> Vec<[Vertex; 3]> {
+        self.cube.get_vertices_grouped()
+    }
+}
+
+impl From<Block> for PrimitiveDispatch {
+    fn from(block: Block) -> Self {
+        PrimitiveDispatch::Cube(block.cube)
+    }
+}
@@ -0,0 +1,226 @@
+use glam::{Mat4, Vec2, Vec3};
+use image::RgbaImage;
+
+use crate::low_level::primitives::mesh::{Mesh, PrimitiveDispatch};
+use crate::low_level::primitives::part_primitive::PartPrimitive;
+use crate::low_level::primitives::quad::Quad;
+use crate::low_level::primitives::vertex::Vertex;
+
+use super::vertex::VertexUvCoordinates;
+
+/// An item sprite extruded into a thin 3D model, the way Minecraft draws held and dropped items.
+///
+/// The sprite is drawn on the front and back faces, and every edge of its opaque pixels gets a side face
+/// the color of that pixel, so the item looks solid from any angle.
+pub struct ExtrudedItem {
+    mesh: Mesh,
+}
+
+impl ExtrudedItem {
+    /// Create a new extruded item.
+    ///
+    /// The sprite decides which pixels are solid (any with some alpha), while the uv coordinates
+    /// (top left and bottom right) are where the sprite is in the texture the item is drawn with.
+    /// The size's z is how thick the item is, usually the width of one of its pixels.
+    pub fn new(
+        center: Vec3,
+        size: Vec3,
+        model_transform: Mat4,
+        sprite: &RgbaImage,
+        [uv_top_left, uv_bottom_right]: [VertexUvCoordinates; 2],
+    ) -> Self {
+        let (width, height) = sprite.dimensions();
+
+        let x_left = center.x - size.x / 2.0;
+        let x_right = center.x + size.x / 2.0;
+
+        let y_up = center.y + size.y / 2.0;
+        let y_down = center.y - size.y / 2.0;
+
+        let z_front = center.z - size.z / 2.0;
+        let z_back = center.z + size.z / 2.0;
+
+        let uv_top_right = Vec2::new(uv_bottom_right.x, uv_top_left.y);
+        let uv_bottom_left = Vec2::new(uv_top_left.x, uv_bottom_right.y);
+
+        // Like the front of a cube, the front faces the negative Z axis (North),
+        // so the left of the sprite is on the positive X side.
+        let mut quads = vec![
+            Quad::new_with_normal(
+                Vec3::new(x_right, y_up, z_front),
+                Vec3::new(x_left, y_up, z_front),
+                Vec3::new(x_right, y_down, z_front),
+                Vec3::new(x_left, y_down, z_front),
+                uv_top_left,
+                uv_top_right,
+                uv_bottom_left,
+                uv_bottom_right,
+                Vec3::NEG_Z,
+            ),
+            // The back shows the sprite mirrored, as if seen through the item.
+            Quad::new_with_normal(
+                Vec3::new(x_right, y_up, z_back),
+                Vec3::new(x_left, y_up, z_back),
+                Vec3::new(x_right, y_down, z_back),
+                Vec3::new(x_left, y_down, z_back),
+                uv_top_left,
+                uv_top_right,
+                uv_bottom_left,
+                uv_bottom_right,
+                Vec3::Z,
+            ),
+        ];
+
+        let pixel_size = Vec2::new(size.x / width as f32, size.y / height as f32);
+        let pixel_uv_size = (uv_bottom_right - uv_top_left) / Vec2::new(width as f32, height as f32);
+
+        let is_solid = |x: i64, y: i64| {
+            x >= 0
+                && y >= 0
+                && x < width as i64
+                && y < height as i64
+                && sprite.get_pixel(x as u32, y as u32).0[3] > 0
+        };
+
+        for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+            if !is_solid(x as i64, y as i64) {
+                continue;
+            }
+
+            // The pixel's area, in space and in the texture
+            let (pixel_left, pixel_right) = (
+                x_right - x as f32 * pixel_size.x,
+                x_right - (x + 1) as f32 * pixel_size.x,
+            );
+            let (pixel_up, pixel_down) = (
+                y_up - y as f32 * pixel_size.y,
+                y_up - (y + 1) as f32 * pixel_size.y,
+            );
+
+            let uv = uv_top_left + Vec2::new(x as f32, y as f32) * pixel_uv_size;
+            let (uv_left, uv_right) = (uv.x, uv.x + pixel_uv_size.x);
+            let (uv_up, uv_down) = (uv.y, uv.y + pixel_uv_size.y);
+
+            let side = |top_left, top_right, bottom_left, bottom_right, normal| {
+                Quad::new_with_normal(
+                    top_left,
+                    top_right,
+                    bottom_left,
+                    bottom_right,
+                    Vec2::new(uv_left, uv_up),
+                    Vec2::new(uv_right, uv_up),
+                    Vec2::new(uv_left, uv_down),
+                    Vec2::new(uv_right, uv_down),
+                    normal,
+                )
+            };
+
+            let (x, y) = (x as i64, y as i64);
+
+            if !is_solid(x - 1, y) {
+                quads.push(side(
+                    Vec3::new(pixel_left, pixel_up, z_back),
+                    Vec3::new(pixel_left, pixel_up, z_front),
+                    Vec3::new(pixel_left, pixel_down, z_back),
+                    Vec3::new(pixel_left, pixel_down, z_front),
+                    Vec3::X,
+                ));
+            }
+
+            if !is_solid(x + 1, y) {
+                quads.push(side(
+                    Vec3::new(pixel_right, pixel_up, z_front),
+                    Vec3::new(pixel_right, pixel_up, z_back),
+                    Vec3::new(pixel_right, pixel_down, z_front),
+                    Vec3::new(pixel_right, pixel_down, z_back),
+                    Vec3::NEG_X,
+                ));
+            }
+
+            if !is_solid(x, y - 1) {
+                quads.push(side(
+                    Vec3::new(pixel_left, pixel_up, z_back),
+                    Vec3::new(pixel_right, pixel_up, z_back),
+                    Vec3::new(pixel_left, pixel_up, z_front),
+                    Vec3::new(pixel_right, pixel_up, z_front),
+                    Vec3::Y,
+                ));
+            }
+
+            if !is_solid(x, y + 1) {
+                quads.push(side(
+                    Vec3::new(pixel_right, pixel_down, z_back),
+                    Vec3::new(pixel_left, pixel_down, z_back),
+                    Vec3::new(pixel_right, pixel_down, z_front),
+                    Vec3::new(pixel_left, pixel_down, z_front),
+                    Vec3::NEG_Y,
+                ));
+            }
+        }
+
+        ExtrudedItem {
+            mesh: Mesh::new_with_transform(
+                quads.into_iter().map(|quad| quad.into()).collect(),
+                model_transform,
+            ),
+        }
+    }
+}
+
+impl PartPrimitive for ExtrudedItem {
+    fn get_vertices(&self) -> Vec<Vertex> {
+        self.mesh.get_vertices()
+    }
+
+    fn get_indices(&self) -> Vec<u16> {
+        self.mesh.get_indices()
+    }
+
+    fn get_vertices_grouped(&self) -> Vec<[Vertex; 3]> {
+        self.mesh.get_vertices_grouped()
+    }
+}
+
+impl From<ExtrudedItem> for PrimitiveDispatch {
+    fn from(item: ExtrudedItem) -> Self {
+        PrimitiveDispatch::Mesh(item.mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec2, Vec3};
+    use image::{Rgba, RgbaImage};
+
+    use super::ExtrudedItem;
+    use crate::low_level::primitives::part_primitive::PartPrimitive;
+
+    fn extrude(sprite: &RgbaImage) -> ExtrudedItem {
+        ExtrudedItem::new(
+            Vec3::ZERO,
+            Vec3::new(sprite.width() as f32, sprite.height() as f32, 1.0),
+            Mat4::IDENTITY,
+            sprite,
+            [Vec2::ZERO, Vec2::ONE],
+        )
+    }
+
+    #[test]
+    fn a_single_pixel_is_a_cube() {
+        let sprite = RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 255]));
+
+        // Front, back, and the four sides
+        assert_eq!(extrude(&sprite).get_vertices().len(), 6 * 4);
+    }
+
+    #[test]
+    fn only_the_outline_of_solid_pixels_gets_sides() {
+        let mut sprite = RgbaImage::new(3, 3);
+        for x in 0..2 {
+            sprite.put_pixel(x, 1, Rgba([255, 255, 255, 255]));
+        }
+
+        // Front, back, and the six sides around the two pixels (but not between them)
+        assert_eq!(extrude(&sprite).get_vertices().len(), (2 + 6) * 4);
+    }
+}
@@ -1,4 +1,4 @@
-use glam::Mat4;
+use glam::{Mat4, Vec2, Vec4};
 
 use crate::low_level::primitives::part_primitive::PartPrimitive;
 use crate::low_level::primitives::vertex::Vertex;
@@ -6,6 +6,9 @@ use crate::low_level::primitives::vertex::Vertex;
 pub struct Mesh {
     primitives: Vec<PrimitiveDispatch>,
     model_transform: Mat4,
+    tint: Vec4,
+    uv_offset: Vec2,
+    uv_scale: Vec2,
 }
 
 impl Mesh {
@@ -13,14 +16,34 @@ impl Mesh {
         Mesh {
             primitives,
             model_transform: Mat4::IDENTITY,
+            tint: Vec4::ONE,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
         }
     }
     pub fn new_with_transform(primitives: Vec<PrimitiveDispatch>, model_transform: Mat4) -> Self {
         Mesh {
             primitives,
             model_transform,
+            tint: Vec4::ONE,
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
         }
     }
+
+    /// Multiplies the color of every vertex of the mesh by the given RGBA tint.
+    pub fn with_tint(mut self, tint: Vec4) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Moves the uv coordinates of the mesh into the region of a bigger texture starting at the given offset,
+    /// with the given size (both in uv coordinates of the bigger texture).
+    pub fn with_uv_region(mut self, offset: Vec2, scale: Vec2) -> Self {
+        self.uv_offset = offset;
+        self.uv_scale = scale;
+        self
+    }
 }
 
 impl PartPrimitive for Mesh {
@@ -29,7 +52,11 @@ impl PartPrimitive for Mesh {
         self.primitives
             .iter()
             .flat_map(|quad| quad.get_vertices())
-            .map(|v| v.transform(self.model_transform))
+            .map(|v| {
+                v.transform(self.model_transform)
+                    .tinted(self.tint)
+                    .with_uv_region(self.uv_offset, self.uv_scale)
+            })
             .collect()
     }
 
@@ -54,7 +81,13 @@ impl PartPrimitive for Mesh {
         self.primitives
             .iter()
             .flat_map(|quad| quad.get_vertices_grouped())
-            .map(|v| [v[0].transform(self.model_transform), v[1].transform(self.model_transform), v[2].transform(self.model_transform)])
+            .map(|v| {
+                v.map(|v| {
+                    v.transform(self.model_transform)
+                        .tinted(self.tint)
+                        .with_uv_region(self.uv_offset, self.uv_scale)
+                })
+            })
             .collect()
     }
 }
@@ -1,4 +1,6 @@
+pub mod block;
 pub mod cube;
+pub mod item;
 pub mod mesh;
 pub mod part_primitive;
 pub mod quad;
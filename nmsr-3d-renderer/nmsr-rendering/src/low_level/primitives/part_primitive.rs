@@ -8,6 +8,18 @@ pub trait PartPrimitive {
     /// in the order they should be drawn
     fn get_indices(&self) -> Vec<u16>;
 
+    /// Returns the indices of the edges of every triangle of the primitive, two per line,
+    /// for drawing it as a wireframe
+    fn get_line_indices(&self) -> Vec<u16> {
+        self.get_indices()
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                [a, b, b, c, c, a]
+            })
+            .collect()
+    }
+
     /// Returns the vertices of the primitive
     fn get_vertices_grouped(&self) -> Vec<[Vertex; 3]>;
 }
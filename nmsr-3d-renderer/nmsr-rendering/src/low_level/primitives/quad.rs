@@ -1,4 +1,4 @@
-use glam::Vec3;
+use glam::{Vec3, Vec4};
 
 use crate::low_level::primitives::part_primitive::PartPrimitive;
 use crate::low_level::primitives::vertex::Vertex;
@@ -10,8 +10,15 @@ pub struct Quad {
     pub top_right: Vertex,
     pub bottom_left: Vertex,
     pub bottom_right: Vertex,
+    /// Whether the quad also has a back face, so it can be seen (and is lit) from behind.
+    pub double_sided: bool,
+    /// The uv coordinates of the back face, if it doesn't just show the texture of the front face mirrored.
+    pub back_face_uvs: Option<[VertexUvCoordinates; 4]>,
 }
 
+/// How far behind the quad its back face is, for the depth test to pick the side facing the camera.
+const BACK_FACE_OFFSET: f32 = 0.01;
+
 impl Quad {
     /// Create a new quad with the given vertices
     pub fn new_from_vec(
@@ -25,9 +32,11 @@ impl Quad {
             top_right,
             bottom_left,
             bottom_right,
+            double_sided: false,
+            back_face_uvs: None,
         }
     }
-    
+
     #[allow(clippy::too_many_arguments)]
     pub fn new_with_normal(
         top_left: Vec3,
@@ -40,38 +49,292 @@ impl Quad {
         bottom_right_uv: VertexUvCoordinates,
         normal: Vec3,
     ) -> Self {
+        let tangent = compute_tangent(
+            [top_left, top_right, bottom_left],
+            [top_left_uv, top_right_uv, bottom_left_uv],
+            normal,
+        );
+
         Quad {
-            top_left: Vertex::new(top_left, top_left_uv, normal),
-            top_right: Vertex::new(top_right, top_right_uv, normal),
-            bottom_left: Vertex::new(bottom_left, bottom_left_uv, normal),
-            bottom_right: Vertex::new(bottom_right, bottom_right_uv, normal),
+            top_left: Vertex::new(top_left, top_left_uv, normal).with_tangent(tangent),
+            top_right: Vertex::new(top_right, top_right_uv, normal).with_tangent(tangent),
+            bottom_left: Vertex::new(bottom_left, bottom_left_uv, normal).with_tangent(tangent),
+            bottom_right: Vertex::new(bottom_right, bottom_right_uv, normal).with_tangent(tangent),
+            double_sided: false,
+            back_face_uvs: None,
         }
     }
+
+    /// Makes the quad visible from both sides, e.g. for capes or ears.
+    pub fn with_double_sided(mut self, double_sided: bool) -> Self {
+        self.double_sided = double_sided;
+        self
+    }
+
+    /// Shows another part of the texture on the back face, e.g. for ears with a back texture of their own.
+    pub fn with_back_face_uvs(mut self, back_face_uvs: Option<[VertexUvCoordinates; 4]>) -> Self {
+        self.back_face_uvs = back_face_uvs;
+        self
+    }
+
+    /// Returns the back face of the quad if it's double-sided, facing the other way with the same uvs.
+    fn back_face(&self) -> Option<[Vertex; 4]> {
+        if !self.double_sided {
+            return None;
+        }
+
+        // The normal of the vertices isn't always rotated along with the quad, so go by its corners instead
+        let edges = (
+            self.top_right.position - self.top_left.position,
+            self.bottom_left.position - self.top_left.position,
+        );
+        let mut face_normal = edges.0.cross(edges.1).normalize_or_zero();
+        if face_normal.dot(self.top_left.normal) < 0.0 {
+            face_normal = -face_normal;
+        }
+
+        let back_face_tangent = self
+            .back_face_uvs
+            .map(|[top_left, top_right, bottom_left, _]| {
+                compute_tangent(
+                    [
+                        self.top_left.position,
+                        self.top_right.position,
+                        self.bottom_left.position,
+                    ],
+                    [top_left, top_right, bottom_left],
+                    -self.top_left.normal,
+                )
+            });
+
+        let flip = |vertex: Vertex, back_face_uv: Option<VertexUvCoordinates>| {
+            // The bitangent is the cross product of the normal and the tangent, so flip its handedness too
+            let tangent = back_face_tangent
+                .unwrap_or_else(|| vertex.tangent.truncate().extend(-vertex.tangent.w));
+
+            Vertex {
+                position: vertex.position - face_normal * BACK_FACE_OFFSET,
+                uv: back_face_uv.unwrap_or(vertex.uv),
+                normal: -vertex.normal,
+                ..vertex.with_tangent(tangent)
+            }
+        };
+
+        let back_face_uv = |index: usize| self.back_face_uvs.map(|uvs| uvs[index]);
+
+        Some([
+            flip(self.top_left, back_face_uv(0)),
+            flip(self.top_right, back_face_uv(1)),
+            flip(self.bottom_left, back_face_uv(2)),
+            flip(self.bottom_right, back_face_uv(3)),
+        ])
+    }
+}
+
+/// Computes the tangent of a flat face from three of its corners and their uv coordinates.
+///
+/// The w component is the handedness of the bitangent, since faces can have flipped uvs.
+fn compute_tangent(
+    [origin, u_corner, v_corner]: [Vec3; 3],
+    [origin_uv, u_corner_uv, v_corner_uv]: [VertexUvCoordinates; 3],
+    normal: Vec3,
+) -> Vec4 {
+    let (edge_u, edge_v) = (u_corner - origin, v_corner - origin);
+    let (delta_u, delta_v) = (u_corner_uv - origin_uv, v_corner_uv - origin_uv);
+
+    let determinant = delta_u.x * delta_v.y - delta_v.x * delta_u.y;
+    if determinant.abs() <= f32::EPSILON {
+        // The face has no texture space to speak of (e.g. all its uvs are the same)
+        return normal.any_orthonormal_vector().extend(1.0);
+    }
+
+    let tangent = (edge_u * delta_v.y - edge_v * delta_u.y) / determinant;
+    let bitangent = (edge_v * delta_u.x - edge_u * delta_v.x) / determinant;
+
+    // Make sure the tangent lies on the face
+    let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+    let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+
+    tangent.extend(handedness)
 }
 
 impl PartPrimitive for Quad {
     fn get_vertices(&self) -> Vec<Vertex> {
-        vec![
+        let mut vertices = vec![
             self.top_left,
             self.top_right,
             self.bottom_left,
             self.bottom_right,
-        ]
+        ];
+
+        if let Some(back_face) = self.back_face() {
+            vertices.extend(back_face);
+        }
+
+        vertices
     }
 
     fn get_indices(&self) -> Vec<u16> {
         // We're going in clockwise order
-        vec![
+        let mut indices = vec![
             // First triangle (bottom left, top left, bottom right)
             2, 0, 3, // Second triangle (top left, top right, bottom right)
             0, 1, 3,
-        ]
+        ];
+
+        if self.double_sided {
+            // The back face goes the other way around
+            indices.extend([7, 4, 6, 7, 5, 4]);
+        }
+
+        indices
     }
 
     fn get_vertices_grouped(&self) -> Vec<[Vertex; 3]> {
-        vec![
+        let mut triangles = vec![
             [self.bottom_left, self.top_left, self.bottom_right],
             [self.top_left, self.top_right, self.bottom_right],
-        ]
+        ];
+
+        if let Some([top_left, top_right, bottom_left, bottom_right]) = self.back_face() {
+            triangles.push([bottom_right, top_left, bottom_left]);
+            triangles.push([bottom_right, top_right, top_left]);
+        }
+
+        triangles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3};
+
+    use super::{compute_tangent, Quad};
+    use crate::low_level::primitives::part_primitive::PartPrimitive;
+
+    #[test]
+    fn tangent_follows_the_u_coordinate() {
+        let tangent = compute_tangent(
+            [Vec3::ZERO, Vec3::X, Vec3::Y],
+            [Vec2::ZERO, Vec2::X, Vec2::Y],
+            Vec3::Z,
+        );
+
+        assert!(tangent.truncate().abs_diff_eq(Vec3::X, 1e-6));
+        assert_eq!(tangent.w, 1.0);
+    }
+
+    #[test]
+    fn flipped_uvs_flip_the_handedness() {
+        let tangent = compute_tangent(
+            [Vec3::ZERO, Vec3::X, Vec3::Y],
+            [Vec2::X, Vec2::ZERO, Vec2::ONE],
+            Vec3::Z,
+        );
+
+        assert!(tangent.truncate().abs_diff_eq(Vec3::NEG_X, 1e-6));
+        assert_eq!(tangent.w, -1.0);
+    }
+
+    #[test]
+    fn line_indices_outline_both_triangles() {
+        let quad = Quad::new_with_normal(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec2::ZERO,
+            Vec2::X,
+            Vec2::Y,
+            Vec2::ONE,
+            Vec3::Z,
+        );
+
+        let indices = quad.get_indices();
+        let lines = quad.get_line_indices();
+
+        assert_eq!(lines.len(), indices.len() * 2);
+        assert_eq!(
+            &lines[..6],
+            &[indices[0], indices[1], indices[1], indices[2], indices[2], indices[0]]
+        );
+    }
+
+    #[test]
+    fn double_sided_quads_have_a_back_face() {
+        let quad = Quad::new_with_normal(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec2::ZERO,
+            Vec2::X,
+            Vec2::Y,
+            Vec2::ONE,
+            Vec3::NEG_Z,
+        );
+
+        assert_eq!(quad.get_vertices().len(), 4);
+
+        let quad = quad.with_double_sided(true);
+        let vertices = quad.get_vertices();
+        let indices = quad.get_indices();
+
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(indices.len(), 12);
+
+        let [front, back] = [&vertices[..4], &vertices[4..]];
+        for (front, back) in front.iter().zip(back) {
+            assert_eq!(back.normal, -front.normal);
+            assert_eq!(back.uv, front.uv);
+            assert_eq!(back.tangent.w, -front.tangent.w);
+            // The back face is behind the front one
+            assert!(back.position.z > front.position.z);
+        }
+
+        // Each back triangle is a front one wound the other way around
+        for (front, back) in indices[..6]
+            .chunks_exact(3)
+            .zip(indices[6..].chunks_exact(3))
+        {
+            let mut back = back.iter().map(|index| index - 4).rev().collect::<Vec<_>>();
+            let first = back.iter().position(|index| *index == front[0]).unwrap();
+            back.rotate_left(first);
+
+            assert_eq!(back, front);
+        }
+    }
+
+    #[test]
+    fn back_face_uvs_are_shown_on_the_back_face() {
+        let back_face_uvs = [Vec2::X, Vec2::ZERO, Vec2::ONE, Vec2::Y];
+        let quad = Quad::new_with_normal(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec2::ZERO,
+            Vec2::X,
+            Vec2::Y,
+            Vec2::ONE,
+            Vec3::NEG_Z,
+        )
+        .with_double_sided(true)
+        .with_back_face_uvs(Some(back_face_uvs));
+
+        let vertices = quad.get_vertices();
+
+        assert_eq!(
+            vertices[..4].iter().map(|v| v.uv).collect::<Vec<_>>(),
+            [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE]
+        );
+        assert_eq!(
+            vertices[4..].iter().map(|v| v.uv).collect::<Vec<_>>(),
+            back_face_uvs
+        );
     }
 }
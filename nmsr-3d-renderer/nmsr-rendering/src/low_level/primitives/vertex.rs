@@ -1,5 +1,5 @@
 use bytemuck::{Pod, Zeroable};
-use glam::{Vec2, Vec3, Mat4};
+use glam::{Vec2, Vec3, Vec4, Mat4};
 
 pub type VertexUvCoordinates = Vec2;
 
@@ -11,11 +11,42 @@ pub struct Vertex {
     /// The uv coordinates of the vertex
     pub uv: VertexUvCoordinates,
     pub normal: Vec3,
+    /// The RGBA color the texture is multiplied by at this vertex
+    pub tint: Vec4,
+    /// The direction the u texture coordinate increases in along the face, with the handedness
+    /// of the v direction in w. Used to orient normal maps.
+    pub tangent: Vec4,
 }
 
 impl Vertex {
     pub fn new(position: Vec3, uv: VertexUvCoordinates, normal: Vec3) -> Self {
-        Vertex { position, uv, normal }
+        Vertex {
+            position,
+            uv,
+            normal,
+            tint: Vec4::ONE,
+            tangent: normal.any_orthonormal_vector().extend(1.0),
+        }
+    }
+
+    pub fn with_tangent(mut self, tangent: Vec4) -> Self {
+        self.tangent = tangent;
+        self
+    }
+
+    pub(crate) fn tinted(&self, tint: Vec4) -> Self {
+        Vertex {
+            tint: self.tint * tint,
+            ..*self
+        }
+    }
+
+    /// Moves the uv coordinates of the vertex into a region of a bigger texture (e.g. a texture atlas).
+    pub(crate) fn with_uv_region(&self, offset: Vec2, scale: Vec2) -> Self {
+        Vertex {
+            uv: offset + self.uv * scale,
+            ..*self
+        }
     }
     
     pub(crate) fn transform(&self, model_transform: Mat4) -> Self {
@@ -24,11 +55,16 @@ impl Vertex {
         }
         
         let normal = model_transform.transform_vector3(self.normal).normalize();
+        let tangent = model_transform
+            .transform_vector3(self.tangent.truncate())
+            .normalize_or_zero();
         
         Vertex {
             position: model_transform.transform_point3(self.position),
             uv: self.uv,
-            normal
+            normal,
+            tint: self.tint,
+            tangent: tangent.extend(self.tangent.w),
         }
     }
 }
@@ -8,7 +8,8 @@ use egui_winit_platform::{Platform, PlatformDescriptor};
 use nmsr_player_parts::parts::part::Part;
 use nmsr_rendering::high_level::pipeline::scene::{self, Scene, SunInformation, Size};
 use nmsr_rendering::high_level::pipeline::{
-    GraphicsContext, GraphicsContextDescriptor, SceneContext, SceneContextWrapper,
+    AdapterSelector, GraphicsContext, GraphicsContextDescriptor, SceneContext,
+    SceneContextWrapper,
 };
 use nmsr_rendering::high_level::utils::parts::primitive_convert;
 use nmsr_rendering::low_level::Vec3;
@@ -21,7 +22,7 @@ use winit::event::WindowEvent;
 use winit::event_loop::EventLoop;
 
 use nmsr_player_parts::parts::provider::PlayerPartProviderContext;
-use nmsr_player_parts::model::PlayerModel;
+use nmsr_player_parts::model::{PlayerBodyProportions, PlayerModel};
 use nmsr_player_parts::types::PlayerBodyPartType;
 use nmsr_rendering::high_level::camera::{
     Camera, CameraPositionParameters, CameraRotation, ProjectionParameters,
@@ -53,11 +54,13 @@ async fn main() -> anyhow::Result<()> {
 
     let graphics = GraphicsContext::new(GraphicsContextDescriptor {
         backends: Some(wgpu::Backends::all()),
+        adapter: AdapterSelector::default(),
         surface_provider: Box::new(|i: &Instance| unsafe {
             Some(i.create_surface(&window).unwrap())
         }),
         default_size: (size.width, size.height),
         texture_format: None,
+        color_space: None,
         features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
         limits: None,
         blend_state: None,
@@ -113,6 +116,9 @@ async fn main() -> anyhow::Result<()> {
         shadow_y_pos: Some(0.0),
         shadow_is_square: false,
         armor_slots: None,
+        proportions: PlayerBodyProportions::Adult,
+        shoulder_buddies: None,
+        has_deadmau5_ears: false,
         #[cfg(feature = "ears")] ears_features: None
     };
 
@@ -4,5 +4,13 @@ use vergen::EmitBuilder;
 fn main() -> Result<(), Box<dyn Error>> {
     // Emit the instructions to the cargo build script (currently, just the current git sha hash)
     EmitBuilder::builder().git_sha(true).emit()?;
+
+    #[cfg(feature = "grpc")]
+    {
+        // Use a vendored protoc, that way building doesn't require it to be installed.
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        tonic_build::compile_protos("proto/nmsr.proto")?;
+    }
+
     Ok(())
 }
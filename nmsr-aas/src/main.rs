@@ -19,80 +19,98 @@ mod routes;
 mod utils;
 
 use crate::{
-    routes::{render, render_get_warning, render_post_warning, NMSRState},
-    utils::tracing::NmsrTracing,
+    routes::{
+        admin, composite, profile, render, render_get_warning, render_post_warning, version,
+        version::VersionInformation, NMSRState,
+    },
+    utils::{
+        access_log, client_ip,
+        listener::{ListenAddress, Listener},
+        rate_limit, server,
+        tracing::NmsrTracing,
+        url_signing,
+    },
 };
 
 use crate::utils::config::NmsrConfiguration;
 use anyhow::Context;
-use axum::routing::post;
+use axum::middleware;
+use axum::routing::{delete, post};
 use axum::{routing::get, Router};
-use http::HeaderName;
+use http::{HeaderName, HeaderValue};
 use opentelemetry::StringValue;
 use opentelemetry::{
     global,
     KeyValue,
 };
-use opentelemetry_sdk::{propagation::TraceContextPropagator, trace, Resource};
+use opentelemetry_sdk::{
+    metrics::MeterProvider as SdkMeterProvider, propagation::TraceContextPropagator, trace,
+    Resource,
+};
 use opentelemetry_otlp::{new_exporter, WithExportConfig};
-use std::{net::SocketAddr, path::PathBuf};
-use tokio::{main, signal};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::{main, signal, sync::oneshot};
 use tower_http::{
-    cors::{AllowMethods, Any, CorsLayer},
+    cors::{AllowMethods, AllowOrigin, Any, CorsLayer},
     request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
     services::ServeDir, normalize_path::NormalizePathLayer,
 };
 use tower_http::request_id::MakeRequestUuid;
-use tracing::info;
-use tracing::info_span;
+use tracing::{error, info, warn};
+use tracing::{info_span, Metadata};
 use tracing_subscriber::EnvFilter;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, util::SubscriberInitExt, Layer as _};
 use twelf::Layer;
-use utils::config::TracingConfiguration;
+use utils::config::{CorsConfiguration, ServerConfiguration, TracingConfiguration};
 pub use utils::{caching, config, error};
 
 #[main]
 async fn main() -> anyhow::Result<()> {
     let init_guard = info_span!("NMSRaaS init").entered();
-    let toml_path: PathBuf = "config.toml".into();
-    let toml_layer = Some(Layer::Toml(toml_path.clone())).filter(|_| toml_path.exists());
-
-    let layers: Vec<_> = vec![
-        Some(Layer::DefaultTrait),
-        toml_layer,
-        Some(Layer::Env(Some("NMSR_".into()))),
-    ]
-    .into_iter()
-    .flatten()
-    .collect();
-
-    let config = NmsrConfiguration::with_layers(&layers).context("Unable to load configuration")?;
+    let config = load_configuration()?;
 
-    setup_tracing(config.tracing.as_ref())?;
+    let meter_provider = setup_tracing(config.tracing.as_ref())?;
 
     info!("Loaded configuration: {:#?}", config);
 
     let state = NMSRState::new(&config).await?;
 
+    if std::env::args().any(|arg| arg == "--diagnose") {
+        let information = VersionInformation::new(&state);
+        println!("{}", serde_json::to_string_pretty(&information)?);
+
+        return Ok(());
+    }
+
     state.init().await?;
 
-    let adapter = &state.graphics_context.adapter.get_info();
-    let samples = &state.graphics_context.multisampling_strategy;
+    if let Some(warmup) = &config.warmup {
+        state.spawn_warm_up(warmup.players.clone(), &warmup.modes);
+    }
 
-    info!(
-        "Initialized state with adapter {:?} and using {:?} multisampling strategy",
-        adapter, samples
-    );
+    #[cfg(feature = "grpc")]
+    routes::grpc::spawn_server(config.grpc.as_ref(), &state)?;
 
-    // build our application with a route
-    let router = Router::new()
-        .route("/:mode/:texture", get(render))
-        .route("/:mode/:texture", post(render_post_warning))
-        .route("/:mode", get(render_get_warning))
-        .route("/:mode", post(render))
+    #[cfg(unix)]
+    spawn_reload_on_hangup(state.clone())?;
+
+    let resolver = state.resolver.clone();
+    let client_ip_layer =
+        middleware::from_fn_with_state(state.clone(), client_ip::resolve_client_ip);
+    let access_log_layer = middleware::from_fn_with_state(state.clone(), access_log::log_access);
+
+    let router = create_router(&config, &state)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit,
+        ))
         .with_state(state);
 
-    let router = if let Some(path) = config.server.static_files_directory {
+    let router = if let Some(path) = &config.server.static_files_directory {
         let serve_dir = ServeDir::new(path)
             .precompressed_br()
             .precompressed_gzip()
@@ -115,83 +133,353 @@ async fn main() -> anyhow::Result<()> {
     > = NmsrTracing::new_trace_layer();
 
     let app = router
+        // Access logs include the request ID and the client's address, so they're logged once both are known.
+        .layer(access_log_layer)
         .layer(SetRequestIdLayer::new(
             HeaderName::from_static("x-request-id"),
             MakeRequestUuid,
         ))
         .layer(trace_layer)
+        // The client's address is needed by the rate limiter and the logs, so it's resolved before anything else.
+        .layer(client_ip_layer)
         .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
             "x-request-id",
         )))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(AllowMethods::any()),
-        );
-
-    let addr: SocketAddr =
-        (config.server.address + ":" + &config.server.port.to_string()).parse()?;
+        .layer(create_cors_layer(config.server.cors.as_ref())?);
 
-    tracing::info!("Listening on {}", &addr);
+    let listeners = bind_listeners(&config.server).await?;
 
     drop(init_guard);
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
+    let proxy_protocol = config
+        .server
+        .trusted_proxies
+        .as_ref()
+        .is_some_and(|trusted_proxies| trusted_proxies.proxy_protocol);
+
+    serve_until_shutdown(
+        listeners,
+        app,
+        proxy_protocol,
+        config.server.shutdown_timeout,
     )
-    .await?;
+    .await;
+
+    // Make sure nothing kept in memory is lost.
+    resolver.flush_caches().await?;
+    global::shutdown_tracer_provider();
+
+    if let Some(meter_provider) = meter_provider {
+        // Export whatever was recorded since the last export.
+        if let Err(err) = meter_provider.shutdown() {
+            warn!("Unable to export metrics: {err}");
+        }
+    }
 
     Ok(())
 }
 
-fn setup_tracing(tracing: Option<&TracingConfiguration>) -> anyhow::Result<()> {
+fn load_configuration() -> anyhow::Result<NmsrConfiguration> {
+    let toml_path: PathBuf = "config.toml".into();
+    let toml_layer = Some(Layer::Toml(toml_path.clone())).filter(|_| toml_path.exists());
+
+    let layers: Vec<_> = vec![
+        Some(Layer::DefaultTrait),
+        toml_layer,
+        Some(Layer::Env(Some("NMSR_".into()))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let config = NmsrConfiguration::with_layers(&layers).context("Unable to load configuration")?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// Reloads the configuration whenever we receive a SIGHUP, applying the settings that don't need a restart.
+/// If the new configuration can't be loaded, the previous one is kept.
+#[cfg(unix)]
+fn spawn_reload_on_hangup(state: NMSRState) -> anyhow::Result<()> {
+    let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading configuration");
+
+            let result = load_configuration().and_then(|config| Ok(state.reload(&config)?));
+
+            match result {
+                Ok(()) => info!("Reloaded configuration"),
+                Err(err) => {
+                    error!("Unable to reload configuration, keeping the previous one: {err:#}");
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Binds to the addresses the server is configured to listen on.
+async fn bind_listeners(server: &ServerConfiguration) -> anyhow::Result<Vec<Listener>> {
+    let listen_addresses = if server.listen.is_empty() {
+        let addr: SocketAddr = format!("{}:{}", server.address, server.port).parse()?;
+
+        vec![ListenAddress::Tcp(addr)]
+    } else {
+        server.listen.clone()
+    };
+
+    let trusts_local_proxies = server.trusted_proxies.as_ref().is_some_and(|trusted_proxies| {
+        trusted_proxies
+            .addresses
+            .iter()
+            .any(|range| range.contains(Ipv4Addr::LOCALHOST.into()))
+    });
+
+    let mut listeners = Vec::with_capacity(listen_addresses.len());
+    for address in &listen_addresses {
+        let listener = Listener::bind(address)
+            .await
+            .with_context(|| format!("Unable to listen on {address}"))?;
+
+        tracing::info!("Listening on {address}");
+        listeners.push(listener);
+
+        // Unix socket connections all appear to come from 127.0.0.1, so they'd share a single rate limit.
+        if matches!(address, ListenAddress::Unix(_)) && !trusts_local_proxies {
+            warn!(
+                "Listening on {address} without trusting 127.0.0.1 as a proxy, so all of its clients share the same address"
+            );
+        }
+    }
+
+    Ok(listeners)
+}
+
+/// Serves requests until we're told to shut down, at which point we stop accepting connections and wait for
+/// in-flight requests (and their renders) to finish, for up to the given timeout.
+/// When the proxies in front of us use the PROXY protocol, every connection is expected to start with its header.
+async fn serve_until_shutdown(
+    listeners: Vec<Listener>,
+    app: Router,
+    proxy_protocol: bool,
+    shutdown_timeout: Duration,
+) {
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+
+    let shutdown = async move {
+        shutdown_signal().await;
+        let _ = shutdown_sender.send(());
+    };
+
+    let server = server::serve(listeners, app, proxy_protocol, shutdown);
+
+    let timeout = async move {
+        if shutdown_receiver.await.is_ok() {
+            info!("Waiting up to {shutdown_timeout:?} for in-flight requests to finish");
+            tokio::time::sleep(shutdown_timeout).await;
+        } else {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    tokio::select! {
+        () = server => {},
+        () = timeout => warn!("Some requests didn't finish in time, shutting down anyway"),
+    }
+}
+
+/// Creates the router with every route available with the given configuration.
+fn create_router(config: &NmsrConfiguration, state: &NMSRState) -> Router<NMSRState> {
+    // Only renders need to be signed, since that's where the GPU time goes.
+    // GraphQL and gRPC check the signing token themselves, since they aren't bound to a URL.
+    let render_router = Router::new()
+        .route("/:mode/:texture", get(render))
+        .route("/:mode/:texture", post(render_post_warning))
+        .route("/:mode", get(render_get_warning))
+        .route("/:mode", post(render))
+        .route("/composite/:mode/:texture", post(composite::composite))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            url_signing::verify_signature,
+        ));
+
+    let mut router = Router::new()
+        .route("/version", get(version::version))
+        .route("/profile/:uuid", get(profile::profile))
+        .merge(render_router);
+
+    if config.admin.is_some() {
+        router = router
+            .route("/admin/cache", delete(admin::purge_all))
+            .route("/admin/cache/player/:player", delete(admin::purge_player))
+            .route("/admin/cache/texture/:hash", delete(admin::purge_texture))
+            .route("/admin/cache/warmup", post(admin::warm_up))
+            .route("/stats", get(admin::stats));
+    }
+
+    if let Some(embed) = config.embed.clone() {
+        router = router.route(
+            "/embed/:player",
+            get(move |state, player| routes::embed::embed(state, player, embed.clone())),
+        );
+    }
+
+    #[cfg(feature = "live_preview")]
+    if let Some(live_preview) = config
+        .live_preview
+        .filter(|_| state.serving_mode.supports_live_preview())
+    {
+        // A live preview can render anything once connected, so connecting needs a signed URL.
+        let preview_router = Router::new()
+            .route(
+                "/ws/preview",
+                get(move |state, host, ws| {
+                    routes::preview::preview(state, host, ws, live_preview)
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                url_signing::verify_signature,
+            ));
+
+        router = router.merge(preview_router);
+    }
+
+    #[cfg(feature = "graphql")]
+    if let Some(graphql) = config.graphql {
+        let schema = routes::graphql::create_schema(graphql);
+
+        router = router.route(
+            "/graphql",
+            post(move |state, headers, request| {
+                routes::graphql::graphql(schema.clone(), state, headers, request)
+            }),
+        );
+    }
+
+    router
+}
+
+/// Creates the CORS layer allowing browsers to make requests from the configured origins, or from anywhere if
+/// CORS isn't configured.
+fn create_cors_layer(config: Option<&CorsConfiguration>) -> anyhow::Result<CorsLayer> {
+    let Some(config) = config else {
+        return Ok(CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(AllowMethods::any()));
+    };
+
+    let allowed_origins = if config.allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .with_context(|| format!("Invalid CORS origin {origin}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        AllowOrigin::list(origins)
+    };
+
+    let mut cors = CorsLayer::new()
+        .allow_origin(allowed_origins)
+        .allow_methods(config.allowed_methods.clone())
+        // Browsers need to be allowed to send headers like X-API-Key along with their requests.
+        .allow_headers(Any);
+
+    if let Some(max_age) = config.max_age {
+        cors = cors.max_age(max_age);
+    }
+
+    Ok(cors)
+}
+
+/// Sets up logging, and exporting traces (and metrics) if configured to. Returns the provider metrics are exported
+/// with, which has to be shut down to export the last of them.
+fn setup_tracing(
+    tracing: Option<&TracingConfiguration>,
+) -> anyhow::Result<Option<SdkMeterProvider>> {
     let base_filter = "info,h2=off,wgpu_core=warn,wgpu_hal=error,naga=warn";
     let otel_filter = format!("{base_filter},nmsr_aas=trace,nmsr_rendering=trace");
 
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| base_filter.into());
     let otel_env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| otel_filter.into());
 
-    let fmt_layer = tracing_subscriber::Layer::with_filter(
-        tracing_subscriber::fmt::layer()
-            .compact()
-            .with_line_number(false)
-            .with_file(false),
-        env_filter,
-    );
+    // Access log entries are already formatted, so they're written as-is and kept out of the other logs.
+    let is_access_log = |metadata: &Metadata| metadata.target() == access_log::ACCESS_LOG_TARGET;
+
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_line_number(false)
+        .with_file(false)
+        .with_filter(env_filter)
+        .with_filter(filter_fn(move |metadata| !is_access_log(metadata)));
+
+    let access_log_layer = tracing_subscriber::fmt::layer()
+        .without_time()
+        .with_level(false)
+        .with_target(false)
+        .with_ansi(false)
+        .with_writer(std::io::stdout)
+        .with_filter(filter_fn(is_access_log));
 
     global::set_text_map_propagator(TraceContextPropagator::new());
 
-    let registry = tracing_subscriber::registry().with(fmt_layer);
+    let registry = tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(access_log_layer);
 
     if let Some(tracing) = tracing {
+        let resource = Resource::new(vec![KeyValue::new(
+            "service.name",
+            Into::<StringValue>::into(tracing.service_name.clone()),
+        )]);
+
         let tracer = opentelemetry_otlp::new_pipeline()
             .tracing()
             .with_exporter(new_exporter().tonic().with_endpoint(&tracing.endpoint))
-            .with_trace_config(
-                trace::config().with_resource(Resource::new(vec![KeyValue::new(
-                    "service.name",
-                    Into::<StringValue>::into(tracing.service_name.clone()),
-                )])),
-            )
+            .with_trace_config(trace::config().with_resource(resource.clone()))
             .install_batch(opentelemetry_sdk::runtime::Tokio)?;
 
-        let otel_layer = tracing_subscriber::Layer::with_filter(
-            tracing_opentelemetry::layer().with_tracer(tracer),
-            otel_env_filter,
-        );
+        let meter_provider = tracing
+            .metrics
+            .as_ref()
+            .map(|metrics| {
+                opentelemetry_otlp::new_pipeline()
+                    .metrics(opentelemetry_sdk::runtime::Tokio)
+                    .with_exporter(new_exporter().tonic().with_endpoint(&metrics.endpoint))
+                    .with_period(metrics.interval)
+                    .with_resource(resource)
+                    .build()
+            })
+            .transpose()?;
+
+        if let Some(meter_provider) = &meter_provider {
+            global::set_meter_provider(meter_provider.clone());
+        }
+
+        let otel_layer = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(otel_env_filter)
+            .with_filter(filter_fn(move |metadata| !is_access_log(metadata)));
 
         registry.with(otel_layer).init();
+
+        Ok(meter_provider)
     } else {
         registry.init();
-    }
 
-    Ok(())
+        Ok(None)
+    }
 }
 
-#[allow(dead_code)] // TODO: Replace when axum supports this again
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -216,8 +504,6 @@ async fn shutdown_signal() {
     }
 
     info!("Received shutdown signal... Shutting down.");
-
-    global::shutdown_tracer_provider();
 }
 
 // basic handler that responds with a static string
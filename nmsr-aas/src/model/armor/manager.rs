@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use ears_rs::utils::upgrade_skin_if_needed;
 use hyper::Method;
@@ -13,7 +13,7 @@ use tracing::Span;
 
 use crate::{
     error::{ArmorManagerError, ArmorManagerResult, ExplainableExt, Result},
-    utils::http_client::NmsrHttpClient,
+    utils::{http_client::NmsrHttpClient, stats::UsageStatistics},
 };
 
 use super::{
@@ -68,7 +68,7 @@ impl<'a> VanillaArmorApplicable<'a> {
 }
 
 impl VanillaMinecraftArmorManager {
-    pub async fn new(cache_path: PathBuf) -> Result<Self> {
+    pub async fn new(cache_path: PathBuf, statistics: Arc<UsageStatistics>) -> Result<Self> {
         let armor_location = cache_path.join("armor");
 
         let material_location = armor_location.join("material");
@@ -83,7 +83,7 @@ impl VanillaMinecraftArmorManager {
             .explain("Unable to create armor cache folder".to_string())?;
 
         let manager = Self {
-            client: NmsrHttpClient::new(20),
+            client: NmsrHttpClient::new(20, statistics),
             material_location,
             trims_location,
         };
@@ -93,6 +93,19 @@ impl VanillaMinecraftArmorManager {
         Ok(manager)
     }
 
+    /// Creates a manager without downloading the armor textures, so that tests don't need to reach GitHub.
+    /// Rendering armor fails unless its textures were downloaded before.
+    #[cfg(test)]
+    pub(crate) fn new_offline(cache_path: &std::path::Path, statistics: Arc<UsageStatistics>) -> Self {
+        let armor_location = cache_path.join("armor");
+
+        Self {
+            client: NmsrHttpClient::new(20, statistics),
+            material_location: armor_location.join("material"),
+            trims_location: armor_location.join("trims"),
+        }
+    }
+
     fn get_material_file_path(&self, material: VanillaMinecraftArmorMaterial) -> PathBuf {
         self.material_location.join(material.to_string())
     }
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use image::{
+    imageops::{self, FilterType},
+    Pixel, Rgba, RgbaImage,
+};
+use nmsr_rendering::errors::NMSRRenderingError;
+
+use crate::{
+    config::ProfileConfiguration,
+    error::{ExplainableExt, RenderRequestError, Result},
+};
+
+/// What to put behind a render, in place of the transparent pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderRequestBackground {
+    /// A solid color, specified as `RRGGBB` or `RRGGBBAA` (optionally prefixed with `#`).
+    Color(Rgba<u8>),
+    /// One of the background images configured by name.
+    Image(String),
+}
+
+impl FromStr for RenderRequestBackground {
+    type Err = RenderRequestError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let hex = value.strip_prefix('#').unwrap_or(value);
+
+        if matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            let channel = |index: usize| {
+                hex.get(index * 2..index * 2 + 2)
+                    .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+            };
+
+            if let (Some(r), Some(g), Some(b)) = (channel(0), channel(1), channel(2)) {
+                return Ok(Self::Color(Rgba([r, g, b, channel(3).unwrap_or(u8::MAX)])));
+            }
+        }
+
+        let is_name = !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+        if is_name {
+            return Ok(Self::Image(value.to_string()));
+        }
+
+        Err(RenderRequestError::InvalidRenderSettingError(
+            "background",
+            "a hex color (RRGGBB or RRGGBBAA) or the name of a configured background image"
+                .to_string(),
+        ))
+    }
+}
+
+impl Display for RenderRequestBackground {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Color(color) => color
+                .0
+                .iter()
+                .try_for_each(|channel| write!(f, "{channel:02x}")),
+            Self::Image(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The background images that can be requested by name, loaded on startup.
+#[derive(Default)]
+pub struct BackgroundImages {
+    images: HashMap<String, RgbaImage>,
+}
+
+impl BackgroundImages {
+    /// Loads the configured background images, if there are any.
+    pub fn load(config: Option<&HashMap<String, PathBuf>>) -> Result<Self> {
+        let images = config
+            .into_iter()
+            .flatten()
+            .map(|(name, path)| Ok((name.clone(), load_image("background image", name, path)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { images })
+    }
+
+    /// Composites a render (as RGBA8 pixels) on top of the given background, in place.
+    /// Background images are stretched to the size of the render.
+    pub fn composite(
+        &self,
+        background: &RenderRequestBackground,
+        size: (u32, u32),
+        pixels: &mut [u8],
+    ) -> Result<()> {
+        match background {
+            RenderRequestBackground::Color(color) => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    composite_pixel(*color, pixel);
+                }
+            }
+            RenderRequestBackground::Image(name) => {
+                let image = self.images.get(name).ok_or_else(|| {
+                    RenderRequestError::InvalidRenderSettingError(
+                        "background",
+                        format!(
+                            "a hex color or one of the configured background images ({})",
+                            self.names()
+                        ),
+                    )
+                })?;
+
+                let resized;
+                let image = if image.dimensions() == size {
+                    image
+                } else {
+                    resized = imageops::resize(image, size.0, size.1, FilterType::Triangle);
+                    &resized
+                };
+
+                for (pixel, background) in pixels.chunks_exact_mut(4).zip(image.pixels()) {
+                    composite_pixel(*background, pixel);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn names(&self) -> String {
+        let mut names = self.images.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+
+        names.join(", ")
+    }
+}
+
+/// The watermarks of the profiles that have one, by profile name.
+#[derive(Default)]
+pub struct Watermarks {
+    images: HashMap<String, RgbaImage>,
+}
+
+impl Watermarks {
+    /// Loads the watermarks of the given profiles.
+    pub fn load<'a>(
+        profiles: impl IntoIterator<Item = (&'a String, &'a ProfileConfiguration)>,
+    ) -> Result<Self> {
+        let images = profiles
+            .into_iter()
+            .filter_map(|(name, profile)| Some((name, profile.watermark.as_ref()?)))
+            .map(|(name, path)| Ok((name.clone(), load_image("watermark", name, path)?)))
+            .collect::<Result<_>>()?;
+
+        Ok(Self { images })
+    }
+
+    /// Composites the watermark of the given profile over the bottom right corner of a render (as RGBA8 pixels),
+    /// in place. Watermarks larger than a quarter of the render are scaled down to fit.
+    pub fn composite(&self, profile: &str, size: (u32, u32), pixels: &mut [u8]) {
+        let Some(watermark) = self.images.get(profile) else {
+            return;
+        };
+
+        let (width, height) = size;
+        let (max_width, max_height) = ((width / 4).max(1), (height / 4).max(1));
+
+        let resized;
+        let watermark = if watermark.width() <= max_width && watermark.height() <= max_height {
+            watermark
+        } else {
+            let scale = f64::min(
+                f64::from(max_width) / f64::from(watermark.width()),
+                f64::from(max_height) / f64::from(watermark.height()),
+            );
+
+            resized = imageops::resize(
+                watermark,
+                ((f64::from(watermark.width()) * scale) as u32).max(1),
+                ((f64::from(watermark.height()) * scale) as u32).max(1),
+                FilterType::Triangle,
+            );
+            &resized
+        };
+
+        let margin = width.min(height) / 32;
+        let left = width - watermark.width() - margin;
+        let top = height - watermark.height() - margin;
+
+        for (x, y, watermark) in watermark.enumerate_pixels() {
+            let index = (((top + y) * width + left + x) * 4) as usize;
+
+            if let Some(pixel) = pixels.get_mut(index..index + 4) {
+                Rgba::from_slice_mut(pixel).blend(watermark);
+            }
+        }
+    }
+}
+
+fn load_image(kind: &str, name: &str, path: &Path) -> Result<RgbaImage> {
+    let bytes = std::fs::read(path)
+        .explain_closure(|| format!("Unable to read {kind} {name} from {}", path.display()))?;
+
+    Ok(image::load_from_memory(&bytes)
+        .map_err(NMSRRenderingError::ImageFromRawError)?
+        .into_rgba8())
+}
+
+fn composite_pixel(mut background: Rgba<u8>, pixel: &mut [u8]) {
+    background.blend(Rgba::from_slice(pixel));
+    pixel.copy_from_slice(&background.0);
+}
+
+#[cfg(test)]
+mod test {
+    use image::Rgba;
+
+    use std::collections::HashMap;
+
+    use image::RgbaImage;
+
+    use super::{BackgroundImages, RenderRequestBackground, Watermarks};
+
+    #[test]
+    fn parse_background() {
+        assert_eq!(
+            "222222".parse::<RenderRequestBackground>().unwrap(),
+            RenderRequestBackground::Color(Rgba([0x22, 0x22, 0x22, 0xff]))
+        );
+        assert_eq!(
+            "#ff000080".parse::<RenderRequestBackground>().unwrap(),
+            RenderRequestBackground::Color(Rgba([0xff, 0, 0, 0x80]))
+        );
+        assert_eq!(
+            "grass_block".parse::<RenderRequestBackground>().unwrap(),
+            RenderRequestBackground::Image("grass_block".to_string())
+        );
+        assert!("../secret.png".parse::<RenderRequestBackground>().is_err());
+    }
+
+    #[test]
+    fn composite_color_background() {
+        let background = RenderRequestBackground::Color(Rgba([0, 0, 255, 255]));
+        let mut pixels = vec![255, 0, 0, 255, 0, 0, 0, 0];
+
+        BackgroundImages::default()
+            .composite(&background, (2, 1), &mut pixels)
+            .unwrap();
+
+        assert_eq!(pixels, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn composite_watermark_in_corner() {
+        let watermarks = Watermarks {
+            images: HashMap::from([(
+                "example".to_string(),
+                RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255])),
+            )]),
+        };
+        let mut pixels = vec![0; 64 * 64 * 4];
+
+        watermarks.composite("example", (64, 64), &mut pixels);
+
+        // The watermark is scaled down to a quarter of the render, 2 pixels away from the corner.
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..(y * 64 + x) * 4 + 4];
+        assert_eq!(pixel(61, 61), [255, 0, 0, 255]);
+        assert_eq!(pixel(46, 46), [255, 0, 0, 255]);
+        assert_eq!(pixel(45, 45), [0, 0, 0, 0]);
+        assert_eq!(pixel(62, 62), [0, 0, 0, 0]);
+
+        // Renders for other profiles are left alone.
+        let mut pixels = vec![0; 64 * 64 * 4];
+        watermarks.composite("other", (64, 64), &mut pixels);
+        assert!(pixels.iter().all(|channel| *channel == 0));
+    }
+}
@@ -8,7 +8,9 @@ use std::{
 };
 
 use super::entry::RenderRequestEntry;
-use crate::error::{ExplainableExt, ModelCacheError, ModelCacheResult, Result};
+use crate::error::{
+    ExplainableExt, ModelCacheError, ModelCacheResult, RenderRequestError, Result,
+};
 #[cfg(feature = "ears")]
 use crate::model::resolver::ResolvedRenderEntryEarsTextureType;
 use async_trait::async_trait;
@@ -17,9 +19,10 @@ use serde_json::Value;
 use serde_with::serde_as;
 use tokio::fs;
 use tracing::trace;
+use uuid::Uuid;
 
 use crate::{
-    caching::{CacheHandler, CacheSystem},
+    caching::{write_atomically, CacheHandler, CacheLimits, CacheSystem},
     config::ModelCacheConfiguration,
     model::resolver::{MojangTexture, ResolvedRenderEntryTextureType, ResolvedRenderEntryTextures},
 };
@@ -31,6 +34,17 @@ pub enum CacheBias {
     CacheIndefinitely,
 }
 
+impl CacheBias {
+    /// The duration of time to keep the entry cached, which is [`Duration::MAX`] when it's cached indefinitely.
+    #[must_use]
+    pub const fn get_duration(&self) -> &Duration {
+        match self {
+            Self::KeepCachedFor(duration) => duration,
+            Self::CacheIndefinitely => &Duration::MAX,
+        }
+    }
+}
+
 impl TryFrom<String> for CacheBias {
     type Error = ModelCacheError;
 
@@ -60,6 +74,9 @@ impl TryFrom<CacheBias> for String {
     }
 }
 
+/// The prefix of the cache keys of offline players, that way they have their own namespace in the cache.
+const OFFLINE_PLAYER_KEY_PREFIX: &str = "offline-";
+
 struct MojangTextureCacheHandler;
 
 struct ResolvedModelTexturesCacheHandler {
@@ -121,7 +138,7 @@ impl CacheHandler<str, MojangTexture, ModelCacheConfiguration, ()> for MojangTex
         _config: &ModelCacheConfiguration,
         file: &Path,
     ) -> Result<()> {
-        fs::write(file, value.data())
+        write_atomically(file, value.data())
             .await
             .explain(format!("Unable to write texture {entry:?} to cache"))?;
 
@@ -155,6 +172,10 @@ impl CacheHandler<str, MojangTexture, ModelCacheConfiguration, ()> for MojangTex
         Ok(Some(MojangTexture::new_named(entry.to_string(), data)))
     }
 
+    fn get_cache_limits(&self, config: &ModelCacheConfiguration) -> CacheLimits {
+        config.get_cache_limits()
+    }
+
     async fn read_marker(
         &self,
         _entry: &str,
@@ -244,12 +265,47 @@ impl ModelCache {
             .map(|_| ())
     }
 
+    pub async fn invalidate_resolved_texture(&self, entry: &RenderRequestEntry) -> Result<bool> {
+        self.resolved_textures.invalidate_entry(entry).await
+    }
+
+    pub async fn invalidate_texture(&self, texture_id: &str) -> Result<bool> {
+        let texture = self.mojang.invalidate_entry(texture_id).await?;
+
+        // Skins uploaded by hash are resolved from the texture cache, so get rid of those as well.
+        let resolved = self
+            .resolved_textures
+            .invalidate_entry(&RenderRequestEntry::TextureHash(texture_id.to_owned()))
+            .await?;
+
+        Ok(texture || resolved)
+    }
+
+    pub async fn invalidate_all(&self) -> Result<()> {
+        self.resolved_textures.invalidate_all().await?;
+        self.mojang.invalidate_all().await?;
+
+        Ok(())
+    }
+
     pub(crate) async fn do_cache_clean_up(&self) -> Result<()> {
         self.resolved_textures.perform_cache_cleanup().await?;
         self.mojang.perform_cache_cleanup().await?;
 
         Ok(())
     }
+
+    pub(crate) async fn flush(&self) -> Result<()> {
+        self.resolved_textures.flush().await?;
+        self.mojang.flush().await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn set_config(&self, cache_config: &ModelCacheConfiguration) {
+        self.resolved_textures.set_config(cache_config.clone());
+        self.mojang.set_config(cache_config.clone());
+    }
 }
 
 #[async_trait]
@@ -266,8 +322,15 @@ impl CacheHandler<RenderRequestEntry, ResolvedRenderEntryTextures, ModelCacheCon
             RenderRequestEntry::MojangPlayerUuid(u) | RenderRequestEntry::GeyserPlayerUuid(u) => {
                 Some(u.to_string())
             }
+            // Offline players are kept apart, since their profiles don't come from the same place.
+            RenderRequestEntry::OfflinePlayerUuid(u) => {
+                Some(format!("{OFFLINE_PLAYER_KEY_PREFIX}{u}"))
+            }
             RenderRequestEntry::TextureHash(hash) => Some(hash.clone()),
-            RenderRequestEntry::PlayerSkin(_) => None,
+            // Names are resolved to UUIDs before reaching the cache, and they can change anyway.
+            RenderRequestEntry::MojangPlayerName(_)
+            | RenderRequestEntry::GeyserPlayerGamertag(_)
+            | RenderRequestEntry::PlayerSkin(_) => None,
         })
     }
 
@@ -284,7 +347,12 @@ impl CacheHandler<RenderRequestEntry, ResolvedRenderEntryTextures, ModelCacheCon
             .unwrap_or_default()
             .to_string();
 
-        let entry = RenderRequestEntry::try_from(file_name)?;
+        let entry = match file_name.strip_prefix(OFFLINE_PLAYER_KEY_PREFIX) {
+            Some(uuid) => RenderRequestEntry::OfflinePlayerUuid(
+                Uuid::parse_str(uuid).map_err(RenderRequestError::InvalidUUID)?,
+            ),
+            None => RenderRequestEntry::try_from(file_name)?,
+        };
 
         Ok(Some(Cow::Owned(entry)))
     }
@@ -410,6 +478,10 @@ impl CacheHandler<RenderRequestEntry, ResolvedRenderEntryTextures, ModelCacheCon
         )))
     }
 
+    fn get_cache_limits(&self, config: &ModelCacheConfiguration) -> CacheLimits {
+        config.get_cache_limits()
+    }
+
     async fn read_marker(
         &self,
         entry: &RenderRequestEntry,
@@ -434,7 +506,7 @@ impl CacheHandler<RenderRequestEntry, ResolvedRenderEntryTextures, ModelCacheCon
         _config: &ModelCacheConfiguration,
         marker: &Path,
     ) -> Result<()> {
-        fs::write(marker, value.to_marker_slice())
+        write_atomically(marker, &value.to_marker_slice())
             .await
             .explain(format!("Unable to write marker file for {entry:?}"))?;
 
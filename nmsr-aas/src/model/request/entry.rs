@@ -11,23 +11,46 @@ use crate::error::{RenderRequestError, RenderRequestResult};
 #[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
 pub enum RenderRequestEntry {
     MojangPlayerUuid(Uuid),
+    MojangPlayerName(String),
+    /// The (version 3) UUID of an offline-mode player, derived from their name.
+    OfflinePlayerUuid(Uuid),
     GeyserPlayerUuid(Uuid),
+    GeyserPlayerGamertag(String),
     TextureHash(String),
     PlayerSkin(#[debug(skip)] Vec<u8>),
 }
 
 static VALID_TEXTURE_HASH_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static VALID_PLAYER_NAME_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static VALID_GAMERTAG_REGEX: OnceLock<regex::Regex> = OnceLock::new();
 
 impl TryFrom<String> for RenderRequestEntry {
     type Error = RenderRequestError;
 
     fn try_from(value: String) -> RenderRequestResult<Self> {
+        // Floodgate prefixes the names of Bedrock players with a dot, so we do the same.
+        if let Some(gamertag) = value.strip_prefix('.') {
+            let regex = VALID_GAMERTAG_REGEX
+                .get_or_init(|| regex::Regex::new(r"^[A-Za-z0-9_ ]{1,16}$").unwrap());
+
+            if !regex.is_match(gamertag) {
+                return Err(RenderRequestError::InvalidPlayerRequest(formatdoc! {"
+                    You've provided an invalid Bedrock gamertag ({gamertag}).
+                    Gamertags should be 1-16 characters long and only contain the characters A-Z, a-z, 0-9, spaces and _.
+                "}));
+            }
+
+            return Ok(Self::GeyserPlayerGamertag(gamertag.to_owned()));
+        }
+
         if value.len() == 32 || value.len() == 36 {
             let uuid = Uuid::parse_str(&value).map_err(RenderRequestError::InvalidUUID)?;
             let uuid_version = uuid.get_version_num();
 
             if uuid_version == 4 {
                 Ok(Self::MojangPlayerUuid(uuid))
+            } else if uuid_version == 3 {
+                Ok(Self::OfflinePlayerUuid(uuid))
             } else if uuid_version == 0 {
                 Ok(Self::GeyserPlayerUuid(uuid))
             } else {
@@ -55,25 +78,49 @@ impl TryFrom<String> for RenderRequestEntry {
 
             Ok(Self::TextureHash(value))
         } else {
+            let regex = VALID_PLAYER_NAME_REGEX
+                .get_or_init(|| regex::Regex::new(r"^[A-Za-z0-9_]{1,16}$").unwrap());
+
+            if regex.is_match(&value) {
+                return Ok(Self::MojangPlayerName(value));
+            }
+
             Err(RenderRequestError::InvalidPlayerRequest(formatdoc! {"
                 You've provided an invalid player request ({value}).
                 I don't know what to do with this.
                 
                 If it's a texture hash, make sure that it's a valid texture hash.
                 If you've provided a UUID, make sure that it's a valid UUID and isn't truncated.
-                Otherwise, if you're using a player name, you should resolve it to a UUID first.
+                If you're using a player name, make sure that it's 1-16 characters long and only contains the characters A-Z, a-z, 0-9 and _.
             "}))
         }
     }
 }
 
+impl RenderRequestEntry {
+    /// The UUID of deadmau5, the only player with ears in-game.
+    pub const DEADMAU5_UUID: Uuid = uuid::uuid!("1e18d5ff-643d-45c8-b509-43b8461d8614");
+
+    /// Whether this entry is deadmau5, by UUID or by name.
+    pub fn is_deadmau5(&self) -> bool {
+        match self {
+            Self::MojangPlayerUuid(uuid) => *uuid == Self::DEADMAU5_UUID,
+            Self::MojangPlayerName(name) => name.eq_ignore_ascii_case("deadmau5"),
+            _ => false,
+        }
+    }
+}
+
 impl TryFrom<RenderRequestEntry> for String {
     type Error = RenderRequestError;
 
     fn try_from(value: RenderRequestEntry) -> Result<Self, Self::Error> {
         match value {
             RenderRequestEntry::MojangPlayerUuid(uuid)
+            | RenderRequestEntry::OfflinePlayerUuid(uuid)
             | RenderRequestEntry::GeyserPlayerUuid(uuid) => Ok(uuid.to_string()),
+            RenderRequestEntry::MojangPlayerName(name) => Ok(name),
+            RenderRequestEntry::GeyserPlayerGamertag(gamertag) => Ok(format!(".{gamertag}")),
             RenderRequestEntry::TextureHash(hash) => Ok(hash),
             RenderRequestEntry::PlayerSkin(_) => Err(RenderRequestError::InvalidPlayerRequest(
                 "Unable to convert PlayerSkin to String".to_string(),
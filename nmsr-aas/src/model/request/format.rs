@@ -0,0 +1,169 @@
+use image::{codecs::jpeg::JpegEncoder, ColorType, Pixel, Rgba};
+use nmsr_rendering::errors::NMSRRenderingError;
+use strum::{Display, EnumString};
+
+use crate::{config::AvifConfiguration, error::Result, utils::png::create_png_from_bytes};
+
+/// The image format a render is encoded in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum RenderRequestOutputFormat {
+    /// Lossless, with transparency.
+    #[default]
+    Png,
+    /// Lossy and without transparency, for small opaque thumbnails.
+    #[strum(to_string = "jpeg", serialize = "jpg")]
+    Jpeg,
+    /// Lossy, with transparency. Much smaller than the others, but slow to encode.
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl RenderRequestOutputFormat {
+    /// The quality JPEG renders are encoded with, unless the request asked for another one.
+    pub const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+    /// The color transparent pixels are flattened onto for formats without transparency, if the request
+    /// didn't ask for a background.
+    const FLATTEN_COLOR: Rgba<u8> = Rgba([u8::MAX; 4]);
+
+    #[must_use]
+    pub const fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            #[cfg(feature = "avif")]
+            Self::Avif => "image/avif",
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            #[cfg(feature = "avif")]
+            Self::Avif => "avif",
+        }
+    }
+
+    #[must_use]
+    pub const fn is_lossy(self) -> bool {
+        !matches!(self, Self::Png)
+    }
+
+    /// Whether encoding takes long enough that it shouldn't be done on the async runtime.
+    #[must_use]
+    pub const fn is_slow(self) -> bool {
+        #[cfg(feature = "avif")]
+        if matches!(self, Self::Avif) {
+            return true;
+        }
+
+        false
+    }
+
+    /// Encodes a render (as RGBA8 pixels) in this format.
+    ///
+    /// Formats without transparency have it flattened onto white, so backgrounds need to be applied beforehand.
+    #[cfg_attr(not(feature = "avif"), allow(unused_variables))]
+    pub fn encode(
+        self,
+        size: (u32, u32),
+        pixels: &[u8],
+        quality: Option<u8>,
+        avif: AvifConfiguration,
+    ) -> Result<Vec<u8>> {
+        match self {
+            Self::Png => create_png_from_bytes(size, pixels),
+            Self::Jpeg => {
+                let pixels: Vec<u8> = pixels
+                    .chunks_exact(4)
+                    .flat_map(|pixel| {
+                        let mut flattened = Self::FLATTEN_COLOR;
+                        flattened.blend(Rgba::from_slice(pixel));
+
+                        flattened.to_rgb().0
+                    })
+                    .collect();
+
+                let mut bytes = Vec::new();
+
+                JpegEncoder::new_with_quality(
+                    &mut bytes,
+                    quality.unwrap_or(Self::DEFAULT_JPEG_QUALITY),
+                )
+                .encode(&pixels, size.0, size.1, ColorType::Rgb8)
+                .map_err(NMSRRenderingError::ImageFromRawError)?;
+
+                Ok(bytes)
+            }
+            #[cfg(feature = "avif")]
+            Self::Avif => {
+                let pixels: Vec<_> = pixels
+                    .chunks_exact(4)
+                    .map(|pixel| ravif::RGBA8::new(pixel[0], pixel[1], pixel[2], pixel[3]))
+                    .collect();
+
+                let quality = f32::from(quality.unwrap_or(avif.quality).clamp(1, 100));
+
+                let image = ravif::Encoder::new()
+                    .with_quality(quality)
+                    .with_alpha_quality(quality)
+                    .with_speed(avif.speed.clamp(1, 10))
+                    // Fully transparent pixels can have any color, which makes them compress better.
+                    .with_alpha_color_mode(ravif::AlphaColorMode::UnassociatedClean)
+                    .encode_rgba(ravif::Img::new(
+                        pixels.as_slice(),
+                        size.0 as usize,
+                        size.1 as usize,
+                    ))?;
+
+                Ok(image.avif_file)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RenderRequestOutputFormat;
+    use crate::config::AvifConfiguration;
+
+    #[test]
+    fn jpeg_renders_are_flattened() {
+        assert_eq!(
+            "jpg".parse::<RenderRequestOutputFormat>().unwrap(),
+            RenderRequestOutputFormat::Jpeg
+        );
+
+        // Rows of transparent pixels on the left, and opaque red ones on the right.
+        let row = [[0, 0, 0, 0].repeat(4), [255, 0, 0, 255].repeat(4)].concat();
+        let pixels = row.repeat(8);
+
+        let jpeg = RenderRequestOutputFormat::Jpeg
+            .encode((8, 8), &pixels, Some(100), AvifConfiguration::default())
+            .unwrap();
+        let image = image::load_from_memory(&jpeg).unwrap().into_rgb8();
+
+        let [r, g, b] = image.get_pixel(0, 0).0;
+        assert!(
+            r > 240 && g > 240 && b > 240,
+            "Transparency wasn't flattened onto white"
+        );
+
+        let [r, g, b] = image.get_pixel(7, 0).0;
+        assert!(r > 200 && g < 60 && b < 60, "Opaque pixels weren't kept");
+    }
+    #[cfg(feature = "avif")]
+    #[test]
+    fn avif_renders_are_encoded() {
+        let pixels = [255, 0, 0, 128].repeat(64);
+
+        let avif = RenderRequestOutputFormat::Avif
+            .encode((8, 8), &pixels, None, AvifConfiguration::default())
+            .unwrap();
+
+        assert_eq!(avif.get(4..12), Some(b"ftypavif".as_slice()));
+    }
+}
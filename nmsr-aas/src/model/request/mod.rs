@@ -3,22 +3,33 @@ use enumset::{enum_set, EnumSet, EnumSetType};
 use is_empty::IsEmpty;
 use nmsr_rendering::{
     high_level::{
-        camera::Camera,
+        camera::{Camera, ProjectionParameters},
+        model::PlayerBodyProportions,
         pipeline::scene::{Size, SunInformation},
+        types::PlayerBodyPartType,
     },
     low_level::{EulerRot, Quat, Vec3},
 };
+use std::sync::Arc;
 use strum::{Display, EnumString};
 
-use self::entry::{RenderRequestEntry, RenderRequestEntryModel};
+use self::{
+    background::RenderRequestBackground,
+    entry::{RenderRequestEntry, RenderRequestEntryModel},
+    format::RenderRequestOutputFormat,
+};
 
+pub mod background;
 pub mod cache;
 pub mod entry;
+pub mod format;
 mod mode;
+pub mod render_cache;
 
 pub use mode::*;
 
 use super::armor::VanillaMinecraftArmorMaterialData;
+use crate::config::CustomModeConfiguration;
 
 #[derive(EnumSetType, EnumString, Debug, Display)]
 #[strum(serialize_all = "snake_case")]
@@ -43,6 +54,16 @@ pub enum RenderRequestFeatures {
     Ears,
 }
 
+/// The projection of the camera, which overrides the one used by the mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum RenderRequestProjection {
+    #[strum(to_string = "perspective", serialize = "persp")]
+    Perspective,
+    #[strum(to_string = "orthographic", serialize = "ortho")]
+    Orthographic,
+}
+
 #[derive(Debug, Clone, PartialEq, Default, IsEmpty)]
 pub struct RenderRequestExtraSettings {
     pub yaw: Option<f32>,
@@ -54,6 +75,15 @@ pub struct RenderRequestExtraSettings {
 
     pub arm_rotation: Option<f32>,
     pub distance: Option<f32>,
+    pub dpr: Option<f32>,
+    pub fov: Option<f32>,
+    pub projection: Option<RenderRequestProjection>,
+    pub proportions: Option<PlayerBodyProportions>,
+    /// Whether to render deadmau5's ears, which are only shown on deadmau5 by default.
+    pub deadmau5_ears: Option<bool>,
+
+    pub parallax_offset: Option<f32>,
+    pub parallax_shadow: Option<f32>,
 
     pub x_pos: Option<f32>,
     pub y_pos: Option<f32>,
@@ -63,13 +93,29 @@ pub struct RenderRequestExtraSettings {
     pub chestplate: Option<VanillaMinecraftArmorMaterialData>,
     pub leggings: Option<VanillaMinecraftArmorMaterialData>,
     pub boots: Option<VanillaMinecraftArmorMaterialData>,
+
+    pub background: Option<RenderRequestBackground>,
+
+    pub format: Option<RenderRequestOutputFormat>,
+    pub quality: Option<u8>,
+
+    /// The name of the mode defined in the configuration this request was made for, if any.
+    pub custom_mode: Option<String>,
+
+    /// The name of the profile whose watermark is composited over the render, set from the profile the request
+    /// was made for.
+    pub watermark: Option<String>,
 }
 
 impl RenderRequestExtraSettings {
     pub(crate) fn get_size_for_mode(&self, mode: RenderRequestMode) -> Size {
         let mut size = mode.get_size();
 
-        if mode.is_custom() {
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            // Both sides were specified, use them as-is
+            size.width = width;
+            size.height = height;
+        } else if mode.is_custom() {
             // Custom mode, use the extra settings as-is
             if let Some(width) = self.width {
                 size.width = width;
@@ -93,6 +139,12 @@ impl RenderRequestExtraSettings {
             }
         }
 
+        if let Some(dpr) = self.dpr {
+            // Scale the image for high-DPI displays, the aspect ratio (and so the framing) stays the same.
+            size.width = (size.width as f32 * dpr).round() as u32;
+            size.height = (size.height as f32 * dpr).round() as u32;
+        }
+
         size
     }
 }
@@ -104,6 +156,9 @@ pub struct RenderRequest {
     pub model: Option<RenderRequestEntryModel>,
     pub features: EnumSet<RenderRequestFeatures>,
     pub extra_settings: Option<RenderRequestExtraSettings>,
+    /// The modes as set in the configuration when this request was made.
+    #[debug(skip)]
+    pub modes: Arc<RenderModes>,
 }
 
 impl RenderRequest {
@@ -139,13 +194,105 @@ impl RenderRequest {
             model,
             features: EnumSet::all().difference(excluded_features),
             extra_settings,
+            modes: Arc::default(),
+        })
+    }
+
+    /// Renders this request with the given modes instead of the built-in ones.
+    #[must_use]
+    pub fn with_modes(mut self, modes: Arc<RenderModes>) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    /// The name of the mode defined in the configuration this request was made for, if any.
+    pub(crate) fn get_custom_mode_name(&self) -> Option<&str> {
+        self.extra_settings.as_ref()?.custom_mode.as_deref()
+    }
+
+    /// The name of the mode this request was made for, which is the custom mode's if there is one.
+    pub(crate) fn get_mode_name(&self) -> String {
+        self.get_custom_mode_name()
+            .map_or_else(|| self.mode.to_string(), ToString::to_string)
+    }
+
+    /// Whether deadmau5's ears should be rendered, which they are on deadmau5 unless the request said otherwise.
+    pub(crate) fn has_deadmau5_ears(&self) -> bool {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.deadmau5_ears)
+            .unwrap_or_else(|| self.entry.is_deadmau5())
+    }
+
+    /// Whether this is a face render that would look the same drawn flat from the skin, in which case it doesn't
+    /// need to go through the renderer. That's the case unless the camera, proportions or extra parts were changed.
+    pub(crate) fn is_plain_face(&self) -> bool {
+        if !self.mode.is_face()
+            || self.get_custom_mode_name().is_some()
+            || self.modes.get_overrides(self.mode).is_some()
+            || self.has_deadmau5_ears()
+        {
+            return false;
+        }
+
+        self.extra_settings.as_ref().map_or(true, |settings| {
+            settings.yaw.is_none()
+                && settings.pitch.is_none()
+                && settings.roll.is_none()
+                && settings.distance.is_none()
+                && settings.fov.is_none()
+                && settings.projection.is_none()
+                && settings.proportions.is_none()
+                && settings.helmet.is_none()
         })
     }
 
+    /// The mode defined in the configuration this request was made for, if any.
+    pub(crate) fn get_custom_mode(&self) -> Option<&CustomModeConfiguration> {
+        self.modes.get_custom_mode(self.get_custom_mode_name()?)
+    }
+
+    pub(crate) fn get_body_parts(&self) -> Vec<PlayerBodyPartType> {
+        let Some(parts) = self
+            .get_custom_mode()
+            .and_then(|custom_mode| custom_mode.parts.as_ref())
+        else {
+            return self.mode.get_body_parts();
+        };
+
+        let mut body_parts = Vec::new();
+
+        for part in parts {
+            for part in [part.get_non_layer_part(), part.get_layer_part()] {
+                if !body_parts.contains(&part) {
+                    body_parts.push(part);
+                }
+            }
+        }
+
+        body_parts
+    }
+
     pub(crate) fn get_camera(&self) -> Camera {
-        let mut camera = self.mode.get_camera();
+        let mut camera = self.mode.get_camera(&self.modes);
+
+        if let Some(custom_mode) = self.get_custom_mode() {
+            if let Some(projection) = custom_mode.projection {
+                Self::apply_projection(projection, &mut camera);
+            }
+
+            RenderRequestMode::apply_camera_overrides(custom_mode.settings, &mut camera);
+        }
 
         if let Some(settings) = &self.extra_settings {
+            if let Some(projection) = settings.projection {
+                Self::apply_projection(projection, &mut camera);
+            }
+
+            if let Some(fov) = settings.fov {
+                camera.set_fov(fov);
+            }
+
             // Only allow to set the yaw, pitch and roll if we are not in a front mode
             if !self.mode.is_front() {
                 if let Some(yaw) = settings.yaw {
@@ -175,9 +322,15 @@ impl RenderRequest {
                 }
             }
 
+            if !self.mode.is_custom() {
+                if let Some(proportions) = settings.proportions {
+                    self.apply_proportions_camera_settings(proportions, &mut camera);
+                }
+            }
+
             let mut distance = settings.distance.unwrap_or_default();
 
-            if !self.mode.is_isometric()
+            if !is_orthographic(&camera)
                 && settings
                     .helmet
                     .as_ref()
@@ -191,7 +344,7 @@ impl RenderRequest {
                 distance += 0.5;
             }
 
-            if self.mode.is_isometric() {
+            if is_orthographic(&camera) {
                 camera.set_aspect(camera.get_aspect() + distance);
             } else {
                 camera.set_distance(camera.get_distance() + distance);
@@ -208,6 +361,56 @@ impl RenderRequest {
         camera
     }
 
+    /// Switches the camera to the given projection, keeping the model framed about the same way.
+    fn apply_projection(projection: RenderRequestProjection, camera: &mut Camera) {
+        match (projection, camera.get_projection()) {
+            (
+                RenderRequestProjection::Perspective,
+                ProjectionParameters::Orthographic { aspect },
+            ) => {
+                // Move the camera to where the perspective frustum is as tall as the orthographic one.
+                let fov = RenderRequestMode::DEFAULT_FOV;
+
+                camera.set_projection(ProjectionParameters::Perspective { fov });
+                camera.set_distance(aspect / (fov.to_radians() / 2.0).tan());
+            }
+            (RenderRequestProjection::Orthographic, ProjectionParameters::Perspective { fov }) => {
+                let aspect = camera.get_distance() * (fov.to_radians() / 2.0).tan();
+
+                camera.set_projection(ProjectionParameters::Orthographic { aspect });
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_proportions_camera_settings(
+        &self,
+        proportions: PlayerBodyProportions,
+        camera: &mut Camera,
+    ) {
+        // Keep the model framed the same way it would be with the regular proportions.
+        let (look_at_y, scale) = if self.mode.is_head_or_face() {
+            let look_at = proportions.transform_position(
+                PlayerBodyPartType::Head,
+                [0.0, camera.get_look_at_y(), 0.0].into(),
+            );
+
+            (look_at.y, proportions.get_part_scale(PlayerBodyPartType::Head))
+        } else {
+            let scale = proportions.get_height() / PlayerBodyProportions::Adult.get_height();
+
+            (camera.get_look_at_y() * scale, scale)
+        };
+
+        camera.set_look_at_y(look_at_y);
+
+        if is_orthographic(camera) {
+            camera.set_aspect(camera.get_aspect() * scale);
+        } else {
+            camera.set_distance(camera.get_distance() * scale);
+        }
+    }
+
     pub(crate) fn get_size(&self) -> Size {
         self.extra_settings.as_ref().map_or_else(
             || self.mode.get_size(),
@@ -215,6 +418,37 @@ impl RenderRequest {
         )
     }
 
+    pub(crate) fn get_dpr(&self) -> Option<f32> {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.dpr)
+    }
+
+    pub(crate) fn get_background(&self) -> Option<&RenderRequestBackground> {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.background.as_ref())
+    }
+
+    pub(crate) fn get_watermark(&self) -> Option<&str> {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.watermark.as_deref())
+    }
+
+    pub(crate) fn get_output_format(&self) -> RenderRequestOutputFormat {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.format)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn get_quality(&self) -> Option<u8> {
+        self.extra_settings
+            .as_ref()
+            .and_then(|settings| settings.quality)
+    }
+
     pub(crate) fn get_lighting(&self) -> SunInformation {
         if !self.features.contains(RenderRequestFeatures::Shading) {
             return SunInformation::new([0.0; 3].into(), 0.0, 1.0);
@@ -242,16 +476,45 @@ impl RenderRequest {
         let light = Vec3::new(0.0, -6.21, 6.21);
         let front_lighting = rot_quat.mul_vec3(light) * Vec3::new(1.0, 1.0, -1.0);
 
-        SunInformation::new(front_lighting, 2.0, 0.621)
+        let mut sun = SunInformation::new(front_lighting, 2.0, 0.621);
+
+        let overrides = self.modes.get_overrides(self.mode).into_iter().chain(
+            self.get_custom_mode()
+                .map(|custom_mode| custom_mode.settings),
+        );
+
+        for overrides in overrides {
+            if let Some(direction) = overrides.sun_direction {
+                sun.direction = direction.into();
+            }
+
+            if let Some(intensity) = overrides.sun_intensity {
+                sun.intensity = intensity;
+            }
+
+            if let Some(ambient) = overrides.ambient_light {
+                sun.ambient = ambient;
+            }
+        }
+
+        sun
     }
 
-    pub(crate) const fn get_arm_rotation(&self) -> f32 {
+    pub(crate) fn get_arm_rotation(&self) -> f32 {
         if let Some(settings) = &self.extra_settings {
             if let Some(rotation) = settings.arm_rotation {
                 return rotation;
             }
         }
-        self.mode.get_arm_rotation()
+
+        if let Some(arms) = self
+            .get_custom_mode()
+            .and_then(|custom_mode| custom_mode.settings.arms)
+        {
+            return arms;
+        }
+
+        self.mode.get_arm_rotation(&self.modes)
     }
 
     pub(crate) fn get_shadow_y_pos(&self) -> Option<f32> {
@@ -279,6 +542,15 @@ impl RenderRequest {
                 .remove(RenderRequestFeatures::UnProcessedSkin);
         }
 
+        // Skins and exported models aren't rendered at a size, so they can't be scaled for high-DPI displays.
+        if request.mode.is_skin() || request.mode.is_blockbench_export() {
+            if let Some(settings) = request.extra_settings.as_mut() {
+                settings.dpr = None;
+            }
+
+            request.extra_settings = request.extra_settings.filter(|s| !s.is_empty());
+        }
+
         // If we're rendering just the head or face, remove the armor except for the helmet
         // And remove some extra features we know aren't targeting the head
         if request.mode.is_head_or_face() {
@@ -291,6 +563,18 @@ impl RenderRequest {
             request.features.remove(RenderRequestFeatures::BodyLayers);
             request.features.remove(RenderRequestFeatures::Cape);
         }
+
+        // Blocks in the inventory don't cast a shadow
+        if request.mode.is_head_block() {
+            request.features.remove(RenderRequestFeatures::Shadow);
+        }
+
+        // If we're rendering the face with the hat layer parallax, we only care about the hat layer and its shadow
+        if request.mode.is_face_parallax() {
+            request.features = request
+                .features
+                .intersection(RenderRequestFeatures::HatLayer | RenderRequestFeatures::Shadow);
+        }
         
         // If the request is custom, we add the custom feature, otherwise we remove it
         if request.mode.is_custom() {
@@ -309,3 +593,10 @@ impl RenderRequest {
         request
     }
 }
+
+fn is_orthographic(camera: &Camera) -> bool {
+    matches!(
+        camera.get_projection(),
+        ProjectionParameters::Orthographic { .. }
+    )
+}
@@ -1,5 +1,5 @@
 use core::fmt::Debug;
-use std::f32::consts::FRAC_1_SQRT_2;
+use std::{collections::HashMap, f32::consts::FRAC_1_SQRT_2};
 
 use nmsr_rendering::high_level::{
     camera::{Camera, CameraRotation, ProjectionParameters},
@@ -9,9 +9,73 @@ use nmsr_rendering::high_level::{
 use strum::{EnumIter, EnumString, IntoEnumIterator, Display};
 use tracing::instrument;
 
-use crate::error::{RenderRequestError, Result};
+use crate::{
+    config::{CustomModeConfiguration, ModeOverridesConfiguration},
+    error::{RenderRequestError, Result},
+};
+
+pub(crate) type ModeOverrides = HashMap<RenderRequestMode, ModeOverridesConfiguration>;
+
+pub(crate) type CustomModes = HashMap<String, CustomModeConfiguration>;
+
+/// The modes as set in the configuration.
+///
+/// Requests keep the ones they were created with, that way a reload never changes a request halfway through.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderModes {
+    /// The values each mode is rendered with instead of the built-in ones.
+    overrides: ModeOverrides,
+    /// The modes defined in addition to the built-in ones, by name.
+    custom_modes: CustomModes,
+}
+
+impl RenderModes {
+    #[must_use]
+    pub(crate) const fn new(overrides: ModeOverrides, custom_modes: CustomModes) -> Self {
+        Self {
+            overrides,
+            custom_modes,
+        }
+    }
+
+    /// The values the given mode is rendered with instead of the built-in ones, see [`ModeOverridesConfiguration`].
+    pub(crate) fn get_overrides(
+        &self,
+        mode: RenderRequestMode,
+    ) -> Option<ModeOverridesConfiguration> {
+        self.overrides.get(&mode).copied()
+    }
+
+    /// The mode defined in the configuration with the given name, see [`CustomModeConfiguration`].
+    pub(crate) fn get_custom_mode(&self, name: &str) -> Option<&CustomModeConfiguration> {
+        self.custom_modes.get(name)
+    }
+
+    /// The names of the modes defined in the configuration, along with the mode they're based on.
+    pub(crate) fn get_custom_modes(
+        &self,
+    ) -> impl Iterator<Item = (String, RenderRequestMode)> + '_ {
+        self.custom_modes
+            .iter()
+            .map(|(name, custom_mode)| (name.clone(), custom_mode.base))
+    }
+
+    /// Parses the name of a mode, which is either a built-in mode or one defined in the configuration.
+    ///
+    /// Modes defined in the configuration are rendered as the mode they're based on, so their name is
+    /// returned alongside it.
+    pub(crate) fn parse_name(&self, name: &str) -> Option<(RenderRequestMode, Option<String>)> {
+        if let Ok(mode) = RenderRequestMode::try_from(name) {
+            return Some((mode, None));
+        }
+
+        let custom_mode = self.get_custom_mode(name)?;
+
+        Some((custom_mode.base, Some(name.to_owned())))
+    }
+}
 
-#[derive(EnumString, Debug, PartialEq, Eq, Clone, Copy, EnumIter, Display)]
+#[derive(EnumString, Debug, PartialEq, Eq, Hash, Clone, Copy, EnumIter, Display)]
 #[strum(serialize_all = "snake_case")]
 pub enum RenderRequestMode {
     #[strum(serialize = "skin", serialize = "texture")]
@@ -32,6 +96,11 @@ pub enum RenderRequestMode {
     FullBodyIso,
     #[strum(serialize = "head_iso", serialize = "headiso")]
     HeadIso,
+    /// The head framed like player head blocks in the inventory.
+    #[strum(serialize = "head_block", serialize = "headblock")]
+    HeadBlock,
+    #[strum(serialize = "face_parallax", serialize = "faceparallax")]
+    FaceParallax,
     Custom,
 }
 
@@ -48,7 +117,12 @@ impl RenderRequestMode {
     pub(crate) const fn is_isometric(self) -> bool {
         matches!(
             self,
-            Self::FullBodyIso | Self::HeadIso | Self::FrontBust | Self::FrontFull | Self::Face
+            Self::FullBodyIso
+                | Self::HeadIso
+                | Self::HeadBlock
+                | Self::FrontBust
+                | Self::FrontFull
+                | Self::Face
         )
     }
 
@@ -65,7 +139,7 @@ impl RenderRequestMode {
     }
 
     pub(crate) const fn is_head_or_face(self) -> bool {
-        matches!(self, Self::Head | Self::Face | Self::HeadIso)
+        matches!(self, Self::Head | Self::Face | Self::HeadIso | Self::HeadBlock)
     }
 
     pub(crate) const fn is_head(self) -> bool {
@@ -75,12 +149,20 @@ impl RenderRequestMode {
         matches!(self, Self::HeadIso)
     }
 
+    pub(crate) const fn is_head_block(self) -> bool {
+        matches!(self, Self::HeadBlock)
+    }
+
     pub(crate) const fn is_face(self) -> bool {
         matches!(self, Self::Face)
     }
 
+    pub(crate) const fn is_face_parallax(self) -> bool {
+        matches!(self, Self::FaceParallax)
+    }
+
     pub(crate) const fn is_square(self) -> bool {
-        self.is_bust() || self.is_head_or_face()
+        self.is_bust() || self.is_head_or_face() || self.is_face_parallax()
     }
 
     pub(crate) const fn is_skin(self) -> bool {
@@ -92,7 +174,7 @@ impl RenderRequestMode {
     }
     
     pub(crate) const fn uses_rendering_pipeline(self) -> bool {
-        !self.is_skin() && !self.is_blockbench_export()
+        !self.is_skin() && !self.is_blockbench_export() && !self.is_face_parallax()
     }
 
     // [min_w, min_h, max_w, max_h]
@@ -145,6 +227,10 @@ impl RenderRequestMode {
 impl RenderRequestMode {
     pub const DEFAULT_RENDER_WIDTH: u32 = 512;
     pub const DEFAULT_RENDER_HEIGHT: u32 = 869;
+    pub const DEFAULT_FOV: f32 = 45.0;
+
+    pub const MIN_DPR: f32 = 1.0;
+    pub const MAX_DPR: f32 = 4.0;
 
     pub const MAX_RENDER_WIDTH: u32 = Self::DEFAULT_RENDER_WIDTH * 2;
     pub const MAX_RENDER_HEIGHT: u32 = Self::DEFAULT_RENDER_HEIGHT * 2;
@@ -166,9 +252,52 @@ impl RenderRequestMode {
         }
     }
 
-    pub(crate) fn get_camera(self) -> Camera {
+    pub(crate) fn get_camera(self, modes: &RenderModes) -> Camera {
+        let mut camera = self.get_default_camera(modes);
+
+        if let Some(overrides) = modes.get_overrides(self) {
+            Self::apply_camera_overrides(overrides, &mut camera);
+        }
+
+        camera
+    }
+
+    pub(crate) fn apply_camera_overrides(
+        overrides: ModeOverridesConfiguration,
+        camera: &mut Camera,
+    ) {
+        if let Some(yaw) = overrides.yaw {
+            camera.set_yaw(yaw);
+        }
+
+        if let Some(pitch) = overrides.pitch {
+            camera.set_pitch(pitch);
+        }
+
+        if let Some(roll) = overrides.roll {
+            camera.set_roll(roll);
+        }
+
+        if let Some(look_at_y) = overrides.look_at_y {
+            camera.set_look_at_y(look_at_y);
+        }
+
+        if let Some(distance) = overrides.distance {
+            camera.set_distance(distance);
+        }
+
+        if let Some(fov) = overrides.fov {
+            camera.set_fov(fov);
+        }
+
+        if let Some(aspect) = overrides.aspect {
+            camera.set_aspect(aspect);
+        }
+    }
+
+    fn get_default_camera(self, modes: &RenderModes) -> Camera {
         if let Some(base_mode) = self.get_base_render_mode() {
-            let mut camera = base_mode.get_camera();
+            let mut camera = base_mode.get_camera(modes);
             camera.set_size(Some(base_mode.get_size()));
 
             return camera;
@@ -198,9 +327,16 @@ impl RenderRequestMode {
                 aspect -= 3.0;
             }
 
+            // Block icons are seen from higher up, which makes them a bit smaller.
+            if self.is_head_block() {
+                aspect -= 0.5;
+            }
+
             ProjectionParameters::Orthographic { aspect }
         } else {
-            ProjectionParameters::Perspective { fov: 45.0 }
+            ProjectionParameters::Perspective {
+                fov: Self::DEFAULT_FOV,
+            }
         };
 
         let rotation = if self.is_front() || self.is_custom() {
@@ -209,6 +345,13 @@ impl RenderRequestMode {
                 pitch: 0.0,
                 roll: 0.0,
             }
+        } else if self.is_head_block() {
+            // The rotation blocks are shown with in the inventory, which isn't quite isometric.
+            CameraRotation {
+                yaw: 45.0,
+                pitch: 30.0,
+                roll: 0.0,
+            }
         } else if self.is_isometric() {
             CameraRotation {
                 yaw: 45.0,
@@ -233,7 +376,11 @@ impl RenderRequestMode {
         Camera::new_orbital(look_at, distance, rotation, projection, None)
     }
 
-    pub(crate) const fn get_arm_rotation(self) -> f32 {
+    pub(crate) fn get_arm_rotation(self, modes: &RenderModes) -> f32 {
+        if let Some(arms) = modes.get_overrides(self).and_then(|overrides| overrides.arms) {
+            return arms;
+        }
+
         if self.is_arms_open() {
             return 10.0;
         }
@@ -246,7 +393,7 @@ impl RenderRequestMode {
             Self::Custom | Self::FullBody | Self::FrontFull | Self::FullBodyIso => {
                 PlayerBodyPartType::iter().collect()
             }
-            Self::Head | Self::HeadIso | Self::Face => {
+            Self::Head | Self::HeadIso | Self::HeadBlock | Self::Face => {
                 vec![PlayerBodyPartType::Head, PlayerBodyPartType::HeadLayer]
             }
             Self::BodyBust | Self::FrontBust => {
@@ -256,7 +403,7 @@ impl RenderRequestMode {
                     .filter(|m| !excluded.contains(&m.get_non_layer_part()))
                     .collect()
             }
-            Self::Skin | Self::BlockbenchExport => unreachable!(),
+            Self::Skin | Self::BlockbenchExport | Self::FaceParallax => unreachable!(),
         }
     }
 }
@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use object_store::{aws::AmazonS3Builder, path::Path, ObjectStore};
+use tokio_stream::StreamExt;
+use tracing::{instrument, trace};
+use xxhash_rust::xxh3::Xxh3;
+
+use super::RenderRequest;
+use crate::{
+    config::{ModelCacheConfiguration, S3CacheConfiguration},
+    error::Result,
+    model::resolver::ResolvedTexturesKey,
+    utils::reloadable::Reloadable,
+};
+
+/// A cache for rendered images backed by an S3-compatible object storage.
+///
+/// Since the storage is shared, multiple instances pointing at the same bucket will
+/// reuse each other's renders, and the cache survives restarts.
+pub struct RenderCache {
+    store: Box<dyn ObjectStore>,
+    prefix: Path,
+    cache_config: Reloadable<ModelCacheConfiguration>,
+    cache_duration: Duration,
+}
+
+impl RenderCache {
+    pub fn new(
+        cache_config: ModelCacheConfiguration,
+        s3_config: &S3CacheConfiguration,
+    ) -> Result<Self> {
+        // Start from the environment so that the usual AWS_* variables are honoured.
+        let mut builder = AmazonS3Builder::from_env()
+            .with_bucket_name(&s3_config.bucket)
+            .with_region(&s3_config.region)
+            .with_allow_http(s3_config.allow_http);
+
+        if let Some(endpoint) = &s3_config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+
+        if !s3_config.access_key_id.is_empty() {
+            builder = builder
+                .with_access_key_id(&s3_config.access_key_id)
+                .with_secret_access_key(&s3_config.secret_access_key);
+        }
+
+        Ok(Self {
+            store: Box::new(builder.build()?),
+            prefix: Path::from(s3_config.prefix.as_str()),
+            cache_duration: s3_config.render_cache_duration,
+            cache_config: Reloadable::new(cache_config),
+        })
+    }
+
+    /// Replaces the configuration used to decide when cached renders expire.
+    pub fn set_cache_config(&self, cache_config: ModelCacheConfiguration) {
+        self.cache_config.set(cache_config);
+    }
+
+    fn get_render_path(&self, request: &RenderRequest, textures: &ResolvedTexturesKey) -> Path {
+        // Renders only depend on who they're for through their textures, so players wearing the same skin share their
+        // renders. The one exception is deadmau5, who gets his ears.
+        let RenderRequest {
+            mode,
+            entry: _,
+            model,
+            features,
+            extra_settings,
+            modes,
+        } = request;
+
+        let mut hasher = Xxh3::new();
+        hasher.update(format!("{mode:?} {model:?} {features:?} {extra_settings:?}").as_bytes());
+        hasher.update(&[u8::from(request.has_deadmau5_ears())]);
+        hasher.update(format!("{:?}", textures.model).as_bytes());
+        hasher.update(&textures.textures_hash.to_le_bytes());
+
+        // Changing the framing of a mode in the configuration results in a new render.
+        if let Some(overrides) = modes.get_overrides(*mode) {
+            hasher.update(format!("{overrides:?}").as_bytes());
+        }
+
+        if let Some(custom_mode) = request.get_custom_mode() {
+            hasher.update(format!("{custom_mode:?}").as_bytes());
+        }
+
+        self.prefix.child(format!(
+            "{:x}.{}",
+            hasher.digest128(),
+            request.get_output_format().extension()
+        ))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn get_cached_render(
+        &self,
+        request: &RenderRequest,
+        textures: &ResolvedTexturesKey,
+    ) -> Result<Option<Vec<u8>>> {
+        let path = self.get_render_path(request, textures);
+
+        let result = match self.store.get(&path).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => {
+                trace!("Cached render {path} doesn't exist.");
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let cache_config = self.cache_config.get();
+        let duration = cache_config.apply_jitter(
+            *cache_config.get_render_cache_duration(request, &self.cache_duration),
+            path.as_ref(),
+        );
+
+        // Short-circuit never expiring entry.
+        if duration != Duration::MAX {
+            let age = (Utc::now() - result.meta.last_modified)
+                .to_std()
+                .unwrap_or_default();
+
+            if age > duration {
+                trace!("Cached render {path} is expired, discarding.");
+                return Ok(None);
+            }
+        }
+
+        trace!("Cached render {path} found.");
+
+        Ok(Some(result.bytes().await?.to_vec()))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn cache_render(
+        &self,
+        request: &RenderRequest,
+        textures: &ResolvedTexturesKey,
+        render: &[u8],
+    ) -> Result<()> {
+        let path = self.get_render_path(request, textures);
+
+        self.store.put(&path, render.to_vec().into()).await?;
+
+        Ok(())
+    }
+
+    /// Removes every render from the cache, returning how many were removed.
+    #[instrument(skip_all)]
+    pub async fn purge(&self) -> Result<usize> {
+        let mut renders = self.store.list(Some(&self.prefix));
+        let mut purged = 0;
+
+        while let Some(render) = renders.next().await {
+            self.store.delete(&render?.location).await?;
+            purged += 1;
+        }
+
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use enumset::EnumSet;
+    use uuid::uuid;
+
+    use super::RenderCache;
+    use crate::{
+        config::{ModelCacheConfiguration, S3CacheConfiguration},
+        model::{
+            request::{
+                entry::{RenderRequestEntry, RenderRequestEntryModel},
+                RenderRequest, RenderRequestMode,
+            },
+            resolver::{ResolvedRenderEntryTextureType, ResolvedRenderRequest},
+        },
+    };
+
+    #[test]
+    fn players_with_the_same_skin_share_renders() {
+        let cache = RenderCache::new(
+            ModelCacheConfiguration::default(),
+            &S3CacheConfiguration {
+                bucket: "renders".to_string(),
+                region: "us-east-1".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let request = |entry| {
+            RenderRequest::new_from_excluded_features(
+                RenderRequestMode::FullBody,
+                entry,
+                None,
+                EnumSet::EMPTY,
+                None,
+            )
+        };
+        let resolved = |skin: &[u8]| ResolvedRenderRequest {
+            model: RenderRequestEntryModel::Steve,
+            textures: HashMap::from([(ResolvedRenderEntryTextureType::Skin, skin.to_vec())]),
+            shoulder_buddies: None,
+        };
+
+        let notch = request(RenderRequestEntry::MojangPlayerUuid(uuid!(
+            "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+        )));
+        let nickac = request(RenderRequestEntry::MojangPlayerUuid(uuid!(
+            "ad4569f3-7576-4376-a7c7-8e8cfcd9b832"
+        )));
+
+        assert_eq!(
+            cache.get_render_path(&notch, &resolved(b"skin").key()),
+            cache.get_render_path(&nickac, &resolved(b"skin").key())
+        );
+        assert_ne!(
+            cache.get_render_path(&notch, &resolved(b"skin").key()),
+            cache.get_render_path(&notch, &resolved(b"other skin").key())
+        );
+
+        // deadmau5 gets his ears, so his renders can't be shared.
+        let deadmau5 = request(RenderRequestEntry::MojangPlayerUuid(
+            RenderRequestEntry::DEADMAU5_UUID,
+        ));
+
+        assert_ne!(
+            cache.get_render_path(&notch, &resolved(b"skin").key()),
+            cache.get_render_path(&deadmau5, &resolved(b"skin").key())
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use crate::{
+    config::AccessListsConfiguration,
+    error::{NMSRaaSError, Result},
+    model::request::entry::RenderRequestEntry,
+};
+
+/// The players and textures the operator has blocked from being rendered, or exclusively allowed to be.
+#[derive(Default)]
+pub struct AccessLists {
+    blocked: HashSet<RenderRequestEntry>,
+    allowed: HashSet<RenderRequestEntry>,
+}
+
+impl AccessLists {
+    #[must_use]
+    pub fn new(config: &AccessListsConfiguration) -> Self {
+        Self {
+            blocked: config.blocked.iter().map(Self::normalize).collect(),
+            allowed: config.allowed.iter().map(Self::normalize).collect(),
+        }
+    }
+
+    /// Player names and gamertags aren't case-sensitive, so they're compared in lowercase.
+    fn normalize(entry: &RenderRequestEntry) -> RenderRequestEntry {
+        match entry {
+            RenderRequestEntry::MojangPlayerName(name) => {
+                RenderRequestEntry::MojangPlayerName(name.to_lowercase())
+            }
+            RenderRequestEntry::GeyserPlayerGamertag(gamertag) => {
+                RenderRequestEntry::GeyserPlayerGamertag(gamertag.to_lowercase())
+            }
+            entry => entry.clone(),
+        }
+    }
+
+    fn describe(entry: &RenderRequestEntry) -> String {
+        String::try_from(entry.clone()).unwrap_or_else(|_| "uploaded skin".to_string())
+    }
+
+    /// Rejects a blocked entry before it's resolved, that way blocked names aren't even looked up.
+    pub fn check_requested(&self, entry: &RenderRequestEntry) -> Result<()> {
+        if self.blocked.contains(&Self::normalize(entry)) {
+            return Err(NMSRaaSError::PlayerBlocked(Self::describe(entry)));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects an entry if either it or the player it was resolved to is blocked, or if neither of them is allowed.
+    pub fn check_resolved(
+        &self,
+        requested: &RenderRequestEntry,
+        resolved: &RenderRequestEntry,
+    ) -> Result<()> {
+        let entries = [Self::normalize(requested), Self::normalize(resolved)];
+
+        if entries.iter().any(|entry| self.blocked.contains(entry)) {
+            return Err(NMSRaaSError::PlayerBlocked(Self::describe(requested)));
+        }
+
+        if !self.allowed.is_empty() && !entries.iter().any(|entry| self.allowed.contains(entry)) {
+            return Err(NMSRaaSError::PlayerNotAllowed(Self::describe(requested)));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::uuid;
+
+    use super::AccessLists;
+    use crate::{
+        config::AccessListsConfiguration, error::NMSRaaSError,
+        model::request::entry::RenderRequestEntry,
+    };
+
+    fn create_access_lists(blocked: &[&str], allowed: &[&str]) -> AccessLists {
+        let parse = |entries: &[&str]| {
+            entries
+                .iter()
+                .map(|entry| RenderRequestEntry::try_from(entry.to_string()).unwrap())
+                .collect()
+        };
+
+        AccessLists::new(&AccessListsConfiguration {
+            blocked: parse(blocked),
+            allowed: parse(allowed),
+        })
+    }
+
+    #[test]
+    fn blocked_players_are_rejected() {
+        let access_lists =
+            create_access_lists(&["notch", "ad4569f3-7576-4376-a7c7-8e8cfcd9b832"], &[]);
+
+        let name = RenderRequestEntry::MojangPlayerName("Notch".to_string());
+        let uuid =
+            RenderRequestEntry::MojangPlayerUuid(uuid!("ad4569f3-7576-4376-a7c7-8e8cfcd9b832"));
+        let other = RenderRequestEntry::MojangPlayerName("jeb_".to_string());
+
+        // Names are blocked regardless of their case.
+        assert!(matches!(
+            access_lists.check_requested(&name),
+            Err(NMSRaaSError::PlayerBlocked(_))
+        ));
+
+        // Names are blocked by the UUID they resolve to.
+        assert!(matches!(
+            access_lists.check_resolved(&other, &uuid),
+            Err(NMSRaaSError::PlayerBlocked(_))
+        ));
+
+        assert!(access_lists.check_requested(&other).is_ok());
+    }
+
+    #[test]
+    fn only_allowed_players_are_accepted() {
+        let access_lists = create_access_lists(&[], &["ad4569f3-7576-4376-a7c7-8e8cfcd9b832"]);
+
+        let name = RenderRequestEntry::MojangPlayerName("NickAc".to_string());
+        let uuid =
+            RenderRequestEntry::MojangPlayerUuid(uuid!("ad4569f3-7576-4376-a7c7-8e8cfcd9b832"));
+        let skin = RenderRequestEntry::PlayerSkin(Vec::new());
+
+        // Names are allowed by the UUID they resolve to.
+        assert!(access_lists.check_resolved(&name, &uuid).is_ok());
+
+        assert!(matches!(
+            access_lists.check_resolved(&skin, &skin),
+            Err(NMSRaaSError::PlayerNotAllowed(_))
+        ));
+    }
+}
@@ -0,0 +1,66 @@
+use uuid::Uuid;
+
+use crate::{
+    config::FallbackSkinConfiguration,
+    error::{ExplainableExt, Result},
+    model::request::entry::RenderRequestEntryModel,
+};
+
+/// The skin rendered instead of a player's skin when it can't be resolved.
+pub enum FallbackSkin {
+    /// Steve or Alex's skin, chosen from the player's UUID.
+    Default,
+    /// A custom skin loaded from disk.
+    Custom {
+        skin: Vec<u8>,
+        model: RenderRequestEntryModel,
+    },
+}
+
+impl FallbackSkin {
+    pub const STEVE_TEXTURE_HASH: &'static str =
+        "1a4af718455d4aab528e7a61f86fa25e6a369d1768dcb13f7df319a713eb810b";
+    pub const ALEX_TEXTURE_HASH: &'static str =
+        "3b60a1f6d562f52aaebbf1434f1de147933a3affe0e764fa49ea057536623cd3";
+
+    pub async fn load(config: &FallbackSkinConfiguration) -> Result<Self> {
+        let Some(path) = config.skin_path.as_ref() else {
+            return Ok(Self::Default);
+        };
+
+        let skin = tokio::fs::read(path)
+            .await
+            .explain_closure(|| format!("Unable to read fallback skin from {}", path.display()))?;
+
+        let model = if config.slim {
+            RenderRequestEntryModel::Alex
+        } else {
+            RenderRequestEntryModel::Steve
+        };
+
+        Ok(Self::Custom { skin, model })
+    }
+
+    /// Picks the default model of a player the same way the game does,
+    /// using the parity of the UUID's hash code (as computed by Java's `UUID#hashCode`).
+    #[must_use]
+    pub const fn default_model_for(id: &Uuid) -> RenderRequestEntryModel {
+        let (most_significant, least_significant) = id.as_u64_pair();
+        let hilo = most_significant ^ least_significant;
+        let hash_code = ((hilo >> 32) as u32) ^ (hilo as u32);
+
+        if hash_code & 1 == 1 {
+            RenderRequestEntryModel::Alex
+        } else {
+            RenderRequestEntryModel::Steve
+        }
+    }
+
+    #[must_use]
+    pub const fn default_texture_hash(model: RenderRequestEntryModel) -> &'static str {
+        match model {
+            RenderRequestEntryModel::Steve => Self::STEVE_TEXTURE_HASH,
+            RenderRequestEntryModel::Alex => Self::ALEX_TEXTURE_HASH,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{collections::HashMap, sync::Arc, time::Duration, time::Instant};
+
+use tokio::sync::RwLock;
+use tracing::trace;
+use uuid::Uuid;
+
+use super::mojang::{client::SkinServer, model::GameProfile};
+use crate::utils::reloadable::Reloadable;
+
+struct CachedGameProfile {
+    profile: Arc<(GameProfile, SkinServer)>,
+    resolved_at: Instant,
+}
+
+/// An in-memory cache of player UUIDs to their game profile, along with the skin server it was found on.
+///
+/// Both renders and the profile endpoint look up game profiles, this way they only hit the skin servers once.
+/// Entries are kept for as long as resolved models are, see [`crate::config::ModelCacheConfiguration::resolve_cache_duration`].
+pub struct GameProfileCache {
+    entries: RwLock<HashMap<Uuid, CachedGameProfile>>,
+    duration: Reloadable<Duration>,
+}
+
+impl GameProfileCache {
+    #[must_use]
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            duration: Reloadable::new(duration),
+        }
+    }
+
+    /// Returns the cached game profile of the player with the given UUID, if any.
+    pub async fn get(&self, id: &Uuid) -> Option<Arc<(GameProfile, SkinServer)>> {
+        let (profile, resolved_at) = self
+            .entries
+            .read()
+            .await
+            .get(id)
+            .map(|entry| (entry.profile.clone(), entry.resolved_at))?;
+
+        if resolved_at.elapsed() > *self.duration.get() {
+            trace!("Cached game profile of {id} is expired.");
+            return None;
+        }
+
+        Some(profile)
+    }
+
+    pub async fn insert(&self, id: Uuid, profile: Arc<(GameProfile, SkinServer)>) {
+        let entry = CachedGameProfile {
+            profile,
+            resolved_at: Instant::now(),
+        };
+
+        self.entries.write().await.insert(id, entry);
+    }
+
+    pub fn set_duration(&self, duration: Duration) {
+        self.duration.set(duration);
+    }
+
+    pub async fn invalidate(&self, id: &Uuid) -> bool {
+        self.entries.write().await.remove(id).is_some()
+    }
+
+    pub async fn invalidate_all(&self) {
+        self.entries.write().await.clear();
+    }
+
+    /// Removes every expired entry from the cache.
+    pub async fn do_cache_clean_up(&self) {
+        let duration = *self.duration.get();
+
+        self.entries
+            .write()
+            .await
+            .retain(|_, entry| entry.resolved_at.elapsed() <= duration);
+    }
+}
@@ -1,9 +1,11 @@
-use super::mojang::client::MojangClient;
+use super::{mojang::client::MojangClient, MojangTexture};
 use crate::{
-    error::{MojangRequestError, MojangRequestResult},
+    error::{MojangRequestError, MojangRequestResult, Result},
     model::request::entry::RenderRequestEntryModel,
+    utils::png::create_png_from_bytes,
 };
 use hyper::Method;
+use image::{imageops::FilterType, GenericImageView};
 use serde::Deserialize;
 use tracing::{instrument, Span};
 use uuid::Uuid;
@@ -14,6 +16,74 @@ pub struct GeyserSkinResponse {
     texture_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GeyserXuidResponse {
+    xuid: Option<u64>,
+}
+
+/// Resolves a Bedrock gamertag to the UUID Floodgate gives that player.
+#[instrument(skip(client))]
+pub async fn resolve_gamertag_to_floodgate_uuid(
+    client: &MojangClient,
+    gamertag: &str,
+) -> MojangRequestResult<Uuid> {
+    // Floodgate replaces the spaces in gamertags with underscores, but Xbox Live doesn't know about that.
+    let xbox_gamertag = gamertag.replace(['_', ' '], "%20");
+
+    let url = format!(
+        "{geysermc_api_server}/v2/xbox/xuid/{xbox_gamertag}",
+        geysermc_api_server = client.mojank_config().geysermc_api_server
+    );
+
+    let bytes = client
+        .do_request(&url, Method::GET, &Span::current(), || {
+            Some(MojangRequestError::GamertagNotFound(gamertag.to_owned()))
+        })
+        .await?;
+
+    let response: GeyserXuidResponse = serde_json::from_slice(&bytes)?;
+    let xuid = response
+        .xuid
+        .ok_or_else(|| MojangRequestError::GamertagNotFound(gamertag.to_owned()))?;
+
+    // Floodgate UUIDs are just the XUID in the least significant bits.
+    Ok(Uuid::from_u64_pair(0, xuid))
+}
+
+/// Resizes Bedrock skins that don't have the size of a Java skin to the closest one.
+///
+/// The Geyser skin API already converts skins to the Java layout, except for skins made for custom geometry,
+/// whose regions don't map to the Java model at all. These are only scaled, that way they can still be rendered,
+/// even though they won't look like they do in game.
+/// Skins that have the size of a Java skin (including HD ones) are kept as-is.
+pub fn resize_bedrock_skin_to_java_size(texture: MojangTexture) -> Result<MojangTexture> {
+    let Ok(image) = image::load_from_memory(texture.data()) else {
+        return Ok(texture);
+    };
+
+    let (width, height) = image.dimensions();
+    let is_java_size = width % 64 == 0 && (height == width || height * 2 == width);
+
+    if is_java_size {
+        return Ok(texture);
+    }
+
+    // Wide skins are closer to the size of legacy skins, which are upgraded when the skin is processed.
+    let (new_width, new_height) = if height * 2 <= width {
+        (64, 32)
+    } else {
+        (64, 64)
+    };
+
+    let image = image::imageops::resize(&image, new_width, new_height, FilterType::Nearest);
+    let data = create_png_from_bytes((new_width, new_height), &image)?;
+
+    Ok(match texture.hash() {
+        Some(hash) => MojangTexture::new_named(hash.clone(), data),
+        None => MojangTexture::new_unnamed(data),
+    })
+}
+
 #[instrument(skip(client))]
 pub async fn resolve_geyser_uuid_to_texture_and_model(
     client: &MojangClient,
@@ -1,29 +1,69 @@
 use self::{
-    geyser::resolve_geyser_uuid_to_texture_and_model,
-    mojang::{client::MojangClient, model::GameProfileTexture},
+    access_list::AccessLists,
+    fallback::FallbackSkin,
+    game_profile::GameProfileCache,
+    geyser::{
+        resize_bedrock_skin_to_java_size, resolve_gamertag_to_floodgate_uuid,
+        resolve_geyser_uuid_to_texture_and_model,
+    },
+    mojang::{
+        client::{MojangClient, SkinServer},
+        model::{GameProfile, GameProfileTexture, GameProfileTextures},
+    },
+    player_name::PlayerNameCache,
+    texture_hash::{IndexedTextures, TextureHashIndex},
 };
 use super::request::{
     cache::ModelCache,
     entry::{RenderRequestEntry, RenderRequestEntryModel},
     RenderRequest,
 };
-use crate::error::{MojangRequestError, Result};
+use crate::{
+    config::{
+        AccessListsConfiguration, MojankConfiguration, ModelCacheConfiguration,
+        ShoulderBuddiesConfiguration,
+    },
+    error::{MojangRequestError, NMSRaaSError, RenderRequestError, Result},
+    utils::{
+        access_log::{AccessLog, AccessLogTiming},
+        reloadable::Reloadable,
+        stats::{StatisticsCache, UsageStatistics},
+    },
+};
 use derive_more::Debug;
 #[cfg(feature = "ears")]
 use ears_rs::{alfalfa::AlfalfaDataKey, features::EarsFeatures, parser::EarsParser};
 #[cfg(feature = "ears")]
 use nmsr_rendering::high_level::parts::provider::ears::PlayerPartEarsTextureType;
-use nmsr_rendering::high_level::types::PlayerPartTextureType;
-use std::{collections::HashMap, sync::Arc};
+use image::ImageFormat;
+use nmsr_rendering::high_level::{
+    parts::provider::shoulder_buddies::ShoulderBuddies, types::PlayerPartTextureType,
+};
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::Instant};
 use strum::EnumCount;
-use tracing::{instrument, Span};
+use tracing::{instrument, warn, Span};
+use uuid::Uuid;
+use xxhash_rust::xxh3::Xxh3;
 
+pub mod access_list;
+pub mod fallback;
+pub mod game_profile;
 pub mod geyser;
 pub mod mojang;
+pub mod player_name;
+pub mod texture_hash;
+pub mod validation;
 
 pub struct RenderRequestResolver {
     model_cache: ModelCache,
+    player_name_cache: PlayerNameCache,
+    game_profile_cache: GameProfileCache,
+    texture_hashes: TextureHashIndex,
     mojang_requests_client: Arc<MojangClient>,
+    fallback_skin: Option<FallbackSkin>,
+    shoulder_buddies: Option<ShoulderBuddiesConfiguration>,
+    access_lists: Reloadable<AccessLists>,
+    statistics: Arc<UsageStatistics>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -172,21 +212,97 @@ impl ResolvedRenderEntryTextures {
 }
 
 impl RenderRequestResolver {
-    pub fn new(model_cache: ModelCache, client: Arc<MojangClient>) -> Self {
+    pub fn new(
+        model_cache: ModelCache,
+        player_name_cache: PlayerNameCache,
+        game_profile_cache: GameProfileCache,
+        texture_hashes: TextureHashIndex,
+        client: Arc<MojangClient>,
+        fallback_skin: Option<FallbackSkin>,
+        shoulder_buddies: Option<ShoulderBuddiesConfiguration>,
+        access_lists: AccessLists,
+        statistics: Arc<UsageStatistics>,
+    ) -> Self {
         Self {
             model_cache,
+            player_name_cache,
+            game_profile_cache,
+            texture_hashes,
             mojang_requests_client: client,
+            fallback_skin,
+            shoulder_buddies,
+            access_lists: Reloadable::new(access_lists),
+            statistics,
+        }
+    }
+
+    /// Resolves a player name (or Bedrock gamertag) into the UUID of the player currently using it.
+    /// Any other entry is returned as-is.
+    #[instrument(skip(self))]
+    async fn resolve_player_name<'a>(
+        &self,
+        entry: &'a RenderRequestEntry,
+    ) -> Result<Cow<'a, RenderRequestEntry>> {
+        let (name, is_gamertag) = match entry {
+            RenderRequestEntry::MojangPlayerName(name) => (name.clone(), false),
+            RenderRequestEntry::GeyserPlayerGamertag(gamertag) => (format!(".{gamertag}"), true),
+            _ => return Ok(Cow::Borrowed(entry)),
+        };
+
+        let uuid = if let Some(uuid) = self.player_name_cache.get(&name).await {
+            uuid
+        } else {
+            let client = &self.mojang_requests_client;
+
+            let uuid = if is_gamertag {
+                resolve_gamertag_to_floodgate_uuid(client, &name[1..]).await?
+            } else {
+                client.resolve_name_to_uuid(&name).await?
+            };
+
+            self.player_name_cache.insert(&name, uuid).await;
+
+            uuid
+        };
+
+        Ok(Cow::Owned(if is_gamertag {
+            RenderRequestEntry::GeyserPlayerUuid(uuid)
+        } else {
+            RenderRequestEntry::MojangPlayerUuid(uuid)
+        }))
+    }
+
+    /// Resolves the game profile of a Java player, along with the skin server it was found on.
+    async fn resolve_game_profile(&self, id: &Uuid) -> Result<Arc<(GameProfile, SkinServer)>> {
+        if let Some(profile) = self.game_profile_cache.get(id).await {
+            return Ok(profile);
         }
+
+        let profile = Arc::new(
+            self.mojang_requests_client
+                .resolve_uuid_to_game_profile(id)
+                .await?,
+        );
+
+        self.game_profile_cache.insert(*id, profile.clone()).await;
+
+        Ok(profile)
     }
 
     async fn fetch_game_profile_texture(
         &self,
         texture: Option<&GameProfileTexture>,
+        server: &SkinServer,
     ) -> Result<Option<MojangTexture>> {
         if let Some(texture) = texture {
             let texture_id = texture.hash()?;
 
-            let texture = self.fetch_texture_from_mojang(texture_id).await?;
+            let texture = if server.is_mojang() {
+                self.fetch_texture_from_mojang(texture_id).await?
+            } else {
+                self.fetch_texture_from_url(texture_id, texture.url())
+                    .await?
+            };
 
             Ok(Some(texture))
         } else {
@@ -194,8 +310,31 @@ impl RenderRequestResolver {
         }
     }
 
+    async fn fetch_texture_from_url(&self, texture_id: &str, url: &str) -> Result<MojangTexture> {
+        let cached = self.model_cache.get_cached_texture(texture_id).await?;
+        self.statistics.record_cache_lookup(StatisticsCache::Textures, cached.is_some());
+
+        if let Some(result) = cached {
+            return Ok(result);
+        }
+
+        let bytes = self
+            .mojang_requests_client
+            .fetch_texture_from_url(url, &Span::current())
+            .await?;
+
+        let texture = MojangTexture::new_named(texture_id.to_owned(), bytes);
+
+        self.model_cache.cache_texture(&texture).await?;
+
+        Ok(texture)
+    }
+
     async fn fetch_texture_from_mojang(&self, texture_id: &str) -> Result<MojangTexture> {
-        if let Some(result) = self.model_cache.get_cached_texture(texture_id).await? {
+        let cached = self.model_cache.get_cached_texture(texture_id).await?;
+        self.statistics.record_cache_lookup(StatisticsCache::Textures, cached.is_some());
+
+        if let Some(result) = cached {
             return Ok(result);
         }
 
@@ -211,12 +350,51 @@ impl RenderRequestResolver {
         Ok(texture)
     }
 
+    /// Fetches the skin and cape of a game profile from the skin server it came from, along with the model of the skin.
+    async fn fetch_game_profile_textures(
+        &self,
+        id: &Uuid,
+        profile: &GameProfile,
+        server: &SkinServer,
+    ) -> Result<(
+        Option<RenderRequestEntryModel>,
+        Option<MojangTexture>,
+        Option<MojangTexture>,
+    )> {
+        let textures = profile.textures()?;
+
+        let skin = textures
+            .skin()
+            .ok_or_else(|| MojangRequestError::MissingSkinPropertyError(*id))?;
+        let cape = textures.cape();
+
+        let model = if skin.is_slim() {
+            RenderRequestEntryModel::Alex
+        } else {
+            RenderRequestEntryModel::Steve
+        };
+
+        let skin_texture = self.fetch_game_profile_texture(Some(skin), server).await?;
+
+        // Mojang makes sure skins are valid when they're uploaded, other skin servers might not.
+        if let Some(skin_texture) = skin_texture.as_ref().filter(|_| !server.is_mojang()) {
+            validation::validate_skin(skin_texture.data())?;
+        }
+
+        let cape_texture = self.fetch_game_profile_texture(cape, server).await?;
+
+        Ok((Some(model), skin_texture, cape_texture))
+    }
+
     #[instrument(skip(self))]
     async fn resolve_entry_textures(
         &self,
         entry: &RenderRequestEntry,
     ) -> Result<ResolvedRenderEntryTextures> {
-        if let Some(result) = self.model_cache.get_cached_resolved_texture(entry).await? {
+        let cached = self.model_cache.get_cached_resolved_texture(entry).await?;
+        self.statistics.record_cache_lookup(StatisticsCache::Resolved, cached.is_some());
+
+        if let Some(result) = cached {
             return Ok(result);
         }
 
@@ -226,32 +404,47 @@ impl RenderRequestResolver {
 
         match &entry {
             RenderRequestEntry::MojangPlayerUuid(id) => {
-                let result = self
+                let profile = self.resolve_game_profile(id).await?;
+                let (result, server) = profile.as_ref();
+
+                (model, skin_texture, cape_texture) =
+                    self.fetch_game_profile_textures(id, result, server).await?;
+            }
+            RenderRequestEntry::OfflinePlayerUuid(id) => {
+                let profile = self
                     .mojang_requests_client
-                    .resolve_uuid_to_game_profile(id)
-                    .await?;
-                let textures = result.textures()?;
-
-                let skin = textures
-                    .skin()
-                    .ok_or_else(|| MojangRequestError::MissingSkinPropertyError(*id))?;
-                let cape = textures.cape();
-
-                model = if skin.is_slim() {
-                    Some(RenderRequestEntryModel::Alex)
-                } else {
-                    Some(RenderRequestEntryModel::Steve)
-                };
-
-                skin_texture = self.fetch_game_profile_texture(textures.skin()).await?;
-                cape_texture = self.fetch_game_profile_texture(cape).await?;
+                    .resolve_offline_uuid_to_game_profile(id)
+                    .await;
+
+                match profile {
+                    Ok(Some((result, server))) => {
+                        (model, skin_texture, cape_texture) =
+                            self.fetch_game_profile_textures(id, &result, &server).await?;
+                    }
+                    // Players unknown to the lookup get the default skin, the same way they do in game.
+                    Ok(None) | Err(MojangRequestError::GameProfileNotFound(_)) => {
+                        let player_model = FallbackSkin::default_model_for(id);
+                        let texture_hash = FallbackSkin::default_texture_hash(player_model);
+
+                        skin_texture = Some(self.fetch_texture_from_mojang(texture_hash).await?);
+                        cape_texture = None;
+                        model = Some(player_model);
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            RenderRequestEntry::MojangPlayerName(_)
+            | RenderRequestEntry::GeyserPlayerGamertag(_) => {
+                unreachable!("Player names are resolved to UUIDs before resolving their textures")
             }
             RenderRequestEntry::GeyserPlayerUuid(id) => {
                 let (texture_id, player_model) =
                     resolve_geyser_uuid_to_texture_and_model(&self.mojang_requests_client, id)
                         .await?;
 
-                skin_texture = Some(self.fetch_texture_from_mojang(&texture_id).await?);
+                let texture = self.fetch_texture_from_mojang(&texture_id).await?;
+
+                skin_texture = Some(resize_bedrock_skin_to_java_size(texture)?);
                 cape_texture = None;
 
                 model = Some(player_model);
@@ -263,6 +456,8 @@ impl RenderRequestResolver {
                 model = None;
             }
             RenderRequestEntry::PlayerSkin(bytes) => {
+                validation::validate_skin(bytes)?;
+
                 skin_texture = Some(MojangTexture::new_unnamed(bytes.clone()));
                 cape_texture = None;
                 model = None;
@@ -291,6 +486,47 @@ impl RenderRequestResolver {
         Ok(result)
     }
 
+    /// Resolves the fallback skin for a player whose textures couldn't be resolved, if one is configured.
+    /// Fallback textures are never cached, that way the player's skin is used as soon as it's available.
+    async fn resolve_fallback_textures(
+        &self,
+        entry: &RenderRequestEntry,
+        err: &NMSRaaSError,
+    ) -> Option<ResolvedRenderEntryTextures> {
+        let fallback_skin = self.fallback_skin.as_ref()?;
+
+        let (RenderRequestEntry::MojangPlayerUuid(id)
+        | RenderRequestEntry::OfflinePlayerUuid(id)
+        | RenderRequestEntry::GeyserPlayerUuid(id)) = entry
+        else {
+            return None;
+        };
+
+        warn!("Using fallback skin for {id}: {err}");
+
+        let (skin, model) = match fallback_skin {
+            FallbackSkin::Default => {
+                let model = FallbackSkin::default_model_for(id);
+                let texture_hash = FallbackSkin::default_texture_hash(model);
+
+                let skin = self
+                    .fetch_texture_from_mojang(texture_hash)
+                    .await
+                    .inspect_err(|err| warn!("Unable to fetch default skin: {err}"))
+                    .ok()?;
+
+                (skin, model)
+            }
+            FallbackSkin::Custom { skin, model } => {
+                (MojangTexture::new_unnamed(skin.clone()), *model)
+            }
+        };
+
+        let textures = HashMap::from([(ResolvedRenderEntryTextureType::Skin, skin)]);
+
+        Some(ResolvedRenderEntryTextures::new(textures, Some(model)))
+    }
+
     #[cfg(feature = "ears")]
     fn resolve_ears_textures(
         skin_texture: &MojangTexture,
@@ -343,22 +579,79 @@ impl RenderRequestResolver {
         })
     }
 
-    pub async fn resolve(&self, request: &RenderRequest) -> Result<ResolvedRenderRequest> {
-        // First, we need to resolve the skin and cape textures.
-        let resolved_textures = self
-            .resolve_entry_textures(&request.entry)
-            .await
-            .map_err(|e| {
-                MojangRequestError::UnableToResolveRenderRequestEntity(
-                    Box::new(e),
-                    request.entry.clone(),
+    /// Checks whether the requested entry may be resolved at all, before knowing who it is.
+    fn check_requested(&self, entry: &RenderRequestEntry) -> Result<()> {
+        // Offline players are only known to us when they're allowed, their UUIDs are invalid otherwise.
+        if let RenderRequestEntry::OfflinePlayerUuid(id) = entry {
+            if !self.mojang_requests_client.mojank_config().allow_offline_players {
+                return Err(RenderRequestError::InvalidPlayerUuidRequest(
+                    id.to_string(),
+                    id.get_version_num(),
                 )
-            })?;
+                .into());
+            }
+        }
 
-        let final_model = request
-            .model
-            .or(resolved_textures.model)
-            .unwrap_or_default();
+        self.access_lists.get().check_requested(entry)
+    }
+
+    /// Returns the textures the requested player was resolved to last, if they were resolved recently and are still
+    /// allowed to be rendered. This way their render can be looked up in the render cache without resolving them again.
+    pub async fn get_indexed_textures(&self, request: &RenderRequest) -> Option<ResolvedTexturesKey> {
+        self.check_requested(&request.entry).ok()?;
+
+        let textures = self.texture_hashes.get(&request.entry).await?;
+
+        if let RenderRequestEntry::MojangPlayerUuid(uuid)
+        | RenderRequestEntry::OfflinePlayerUuid(uuid)
+        | RenderRequestEntry::GeyserPlayerUuid(uuid) = &request.entry
+        {
+            AccessLog::record_uuid(*uuid);
+        }
+
+        Some(ResolvedTexturesKey {
+            model: request.model.or(textures.model).unwrap_or_default(),
+            textures_hash: textures.textures_hash,
+        })
+    }
+
+    pub async fn resolve(&self, request: &RenderRequest) -> Result<ResolvedRenderRequest> {
+        let started = Instant::now();
+
+        self.check_requested(&request.entry)?;
+        let access_lists = self.access_lists.get();
+
+        // If we've been given a player name, we need to know who it belongs to first.
+        let entry = self.resolve_player_name(&request.entry).await?;
+        access_lists.check_resolved(&request.entry, &entry)?;
+
+        if let RenderRequestEntry::MojangPlayerUuid(uuid)
+        | RenderRequestEntry::OfflinePlayerUuid(uuid)
+        | RenderRequestEntry::GeyserPlayerUuid(uuid) = &*entry
+        {
+            AccessLog::record_uuid(*uuid);
+        }
+
+        // Then, we need to resolve the skin and cape textures.
+        let (resolved_textures, is_fallback) = match self.resolve_entry_textures(&entry).await {
+            Ok(resolved_textures) => (resolved_textures, false),
+            Err(err) => {
+                let fallback = self
+                    .resolve_fallback_textures(&entry, &err)
+                    .await
+                    .ok_or_else(|| {
+                        MojangRequestError::UnableToResolveRenderRequestEntity(
+                            Box::new(err),
+                            request.entry.clone(),
+                        )
+                    })?;
+
+                (fallback, true)
+            }
+        };
+
+        let model = resolved_textures.model;
+        let final_model = request.model.or(model).unwrap_or_default();
 
         // Load the textures into memory.
         let mut textures = HashMap::new();
@@ -366,16 +659,144 @@ impl RenderRequestResolver {
             textures.insert(texture_type, texture.data);
         }
 
-        Ok(ResolvedRenderRequest {
+        // Only rendered modes show shoulder buddies, so the skin is only decoded for those.
+        let shoulder_buddies = self
+            .shoulder_buddies
+            .filter(|_| request.mode.uses_rendering_pipeline())
+            .and_then(|config| {
+                detect_shoulder_buddies(config, textures.get(&ResolvedRenderEntryTextureType::Skin)?)
+            });
+
+        let resolved = ResolvedRenderRequest {
             model: final_model,
             textures,
-        })
+            shoulder_buddies,
+        };
+
+        // The fallback skin is only used until the player can be resolved again, so it isn't worth remembering.
+        if !is_fallback {
+            let textures = IndexedTextures {
+                model,
+                textures_hash: resolved.textures_hash(),
+            };
+
+            self.texture_hashes.insert(&entry, textures).await;
+        }
+
+        AccessLog::record_timing(AccessLogTiming::Resolve, started.elapsed());
+
+        Ok(resolved)
+    }
+
+    /// Resolves the game profile textures of a Java player, along with the name of the skin server they were found on.
+    /// Player names are resolved to their UUID first, and Bedrock players are rejected since they don't have one.
+    #[instrument(skip(self))]
+    pub(crate) async fn resolve_game_profile_textures(
+        &self,
+        entry: &RenderRequestEntry,
+    ) -> Result<(Uuid, GameProfileTextures, String)> {
+        let access_lists = self.access_lists.get();
+        access_lists.check_requested(entry)?;
+
+        let resolved = self.resolve_player_name(entry).await?;
+        access_lists.check_resolved(entry, &resolved)?;
+
+        let RenderRequestEntry::MojangPlayerUuid(id) = resolved.as_ref() else {
+            return Err(RenderRequestError::InvalidPlayerRequest(
+                "Profiles are only available for Java players".to_string(),
+            )
+            .into());
+        };
+
+        let profile = self.resolve_game_profile(id).await?;
+        let (profile, server) = profile.as_ref();
+
+        Ok((*id, profile.textures()?, server.name.clone()))
     }
 
     #[inline]
     pub(crate) async fn do_cache_clean_up(&self) -> Result<()> {
+        self.player_name_cache.do_cache_clean_up().await;
+        self.game_profile_cache.do_cache_clean_up().await;
+        self.texture_hashes.do_cache_clean_up().await;
         self.model_cache.do_cache_clean_up().await
     }
+
+    #[inline]
+    pub(crate) async fn flush_caches(&self) -> Result<()> {
+        self.model_cache.flush().await
+    }
+
+    /// Applies the cache, skin server and access list configuration, e.g. when the configuration file is reloaded.
+    pub(crate) fn reload(
+        &self,
+        cache_config: &ModelCacheConfiguration,
+        mojank: MojankConfiguration,
+        access_lists: &AccessListsConfiguration,
+    ) {
+        self.model_cache.set_config(cache_config);
+        self.game_profile_cache
+            .set_duration(cache_config.resolve_cache_duration);
+        self.texture_hashes
+            .set_duration(cache_config.resolve_cache_duration);
+        self.mojang_requests_client.set_config(mojank);
+        self.access_lists.set(AccessLists::new(access_lists));
+    }
+
+    #[inline]
+    pub(crate) async fn invalidate_entry(&self, entry: &RenderRequestEntry) -> Result<bool> {
+        // Names are purged along with the player they were last resolved to, if we still know who that is.
+        let resolved_name = match entry {
+            RenderRequestEntry::MojangPlayerName(name) => self
+                .player_name_cache
+                .invalidate(name)
+                .await
+                .map(RenderRequestEntry::MojangPlayerUuid),
+            RenderRequestEntry::GeyserPlayerGamertag(gamertag) => self
+                .player_name_cache
+                .invalidate(&format!(".{gamertag}"))
+                .await
+                .map(RenderRequestEntry::GeyserPlayerUuid),
+            _ => None,
+        };
+
+        let purged_name = resolved_name.is_some();
+        let entry = resolved_name.as_ref().unwrap_or(entry);
+
+        let purged_profile = match entry {
+            RenderRequestEntry::MojangPlayerUuid(id) => self.game_profile_cache.invalidate(id).await,
+            _ => false,
+        };
+        let purged_textures = self.texture_hashes.invalidate(entry).await;
+
+        Ok(self.model_cache.invalidate_resolved_texture(entry).await?
+            || purged_name
+            || purged_profile
+            || purged_textures)
+    }
+
+    #[inline]
+    pub(crate) async fn invalidate_texture(&self, texture_id: &str) -> Result<bool> {
+        // The index only knows the hashes of the textures, not which players wear the one that's gone.
+        self.texture_hashes.invalidate_all().await;
+        self.model_cache.invalidate_texture(texture_id).await
+    }
+
+    #[inline]
+    pub(crate) async fn invalidate_all(&self) -> Result<()> {
+        self.player_name_cache.invalidate_all().await;
+        self.game_profile_cache.invalidate_all().await;
+        self.texture_hashes.invalidate_all().await;
+        self.model_cache.invalidate_all().await
+    }
+}
+
+/// What cached renders are stored by instead of the player they're for, that way players wearing the same skin
+/// share their renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedTexturesKey {
+    pub model: RenderRequestEntryModel,
+    pub textures_hash: u128,
 }
 
 #[derive(Debug, Clone)]
@@ -383,4 +804,83 @@ pub struct ResolvedRenderRequest {
     pub model: RenderRequestEntryModel,
     #[debug(skip)]
     pub textures: HashMap<ResolvedRenderEntryTextureType, Vec<u8>>,
+    /// The shoulder buddies drawn on the skin, detected once when resolving since the skin doesn't change.
+    pub shoulder_buddies: Option<ShoulderBuddies>,
+}
+
+impl ResolvedRenderRequest {
+    /// Hashes the textures in a stable order, that way a skin change results in a new hash.
+    #[must_use]
+    pub fn textures_hash(&self) -> u128 {
+        let mut textures: Vec<_> = self.textures.iter().collect();
+        textures.sort_by_key(|(texture_type, _)| Into::<&'static str>::into(**texture_type));
+
+        let mut hasher = Xxh3::new();
+        for (texture_type, texture) in textures {
+            hasher.update(Into::<&'static str>::into(*texture_type).as_bytes());
+            hasher.update(texture);
+        }
+
+        hasher.digest128()
+    }
+
+    #[must_use]
+    pub fn key(&self) -> ResolvedTexturesKey {
+        ResolvedTexturesKey {
+            model: self.model,
+            textures_hash: self.textures_hash(),
+        }
+    }
+}
+
+fn detect_shoulder_buddies(
+    config: ShoulderBuddiesConfiguration,
+    skin: &[u8],
+) -> Option<ShoulderBuddies> {
+    let skin = image::load_from_memory_with_format(skin, ImageFormat::Png)
+        .ok()?
+        .into_rgba8();
+
+    ShoulderBuddies::detect(
+        skin.as_raw(),
+        skin.width(),
+        skin.height(),
+        config.size,
+        config.offset,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    use super::detect_shoulder_buddies;
+    use crate::config::ShoulderBuddiesConfiguration;
+
+    fn create_skin(height: u32) -> Vec<u8> {
+        let mut skin = RgbaImage::new(64, height);
+        // Draw something in the region of the buddy on the right shoulder.
+        skin.put_pixel(33, 1, Rgba([255, 0, 0, 255]));
+
+        let mut bytes = Vec::new();
+        skin.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .expect("Skin should be encoded");
+
+        bytes
+    }
+
+    #[test]
+    fn shoulder_buddies_are_only_detected_on_modern_skins() {
+        let config = ShoulderBuddiesConfiguration::default();
+
+        let buddies = detect_shoulder_buddies(config, &create_skin(64))
+            .expect("Buddy should be detected on a modern skin");
+
+        assert!(buddies.right);
+        assert!(!buddies.left);
+
+        assert_eq!(detect_shoulder_buddies(config, &create_skin(32)), None);
+    }
 }
@@ -1,17 +1,91 @@
-use super::model::GameProfile;
+use super::model::{GameProfile, PlayerNameProfile};
 use crate::{
-    config::MojankConfiguration,
+    config::{MojankConfiguration, SkinServerConfiguration},
     error::{MojangRequestError, MojangRequestResult},
-    utils::http_client::NmsrHttpClient,
+    utils::{http_client::NmsrHttpClient, reloadable::Reloadable, stats::UsageStatistics},
 };
 use hyper::{body::Bytes, Method};
+use serde::de::DeserializeOwned;
 use std::sync::Arc;
-use tracing::{instrument, Span};
+use tracing::{debug, instrument, Span};
 use uuid::Uuid;
 
 pub struct MojangClient {
     client: NmsrHttpClient,
-    mojank_config: Arc<MojankConfiguration>,
+    mojank_config: Reloadable<MojankConfiguration>,
+    skin_servers: Reloadable<Vec<SkinServer>>,
+}
+
+/// A server that players can be resolved from.
+#[derive(Debug, Clone)]
+pub struct SkinServer {
+    pub name: String,
+    priority: i32,
+    profile_url: String,
+    name_url: Option<String>,
+    is_mojang: bool,
+}
+
+impl SkinServer {
+    const UUID_PLACEHOLDER: &'static str = "{uuid}";
+    const NAME_PLACEHOLDER: &'static str = "{name}";
+
+    fn mojang(config: &MojankConfiguration) -> Self {
+        Self {
+            name: "Mojang".to_string(),
+            priority: config.mojang_priority,
+            profile_url: format!(
+                "{session_server}/session/minecraft/profile/{placeholder}",
+                session_server = config.session_server,
+                placeholder = Self::UUID_PLACEHOLDER
+            ),
+            name_url: Some(format!(
+                "{api_server}/users/profiles/minecraft/{placeholder}",
+                api_server = config.api_server,
+                placeholder = Self::NAME_PLACEHOLDER
+            )),
+            is_mojang: true,
+        }
+    }
+
+    fn offline(profile_url: String) -> Self {
+        Self {
+            name: "Offline players".to_string(),
+            priority: 0,
+            profile_url,
+            name_url: None,
+            is_mojang: false,
+        }
+    }
+
+    fn profile_url(&self, id: &Uuid) -> String {
+        self.profile_url
+            .replace(Self::UUID_PLACEHOLDER, &id.simple().to_string())
+    }
+
+    fn name_url(&self, name: &str) -> Option<String> {
+        let name_url = self.name_url.as_ref()?;
+
+        Some(name_url.replace(Self::NAME_PLACEHOLDER, name))
+    }
+
+    /// Whether this is Mojang's server, whose textures are downloaded from the configured textures server.
+    #[must_use]
+    pub const fn is_mojang(&self) -> bool {
+        self.is_mojang
+    }
+}
+
+impl From<&SkinServerConfiguration> for SkinServer {
+    fn from(config: &SkinServerConfiguration) -> Self {
+        Self {
+            name: config.name.clone(),
+            priority: config.priority,
+            profile_url: config.profile_url.clone(),
+            name_url: config.name_url.clone(),
+            is_mojang: false,
+        }
+    }
 }
 
 #[test]
@@ -20,13 +94,39 @@ fn owo() {
 }
 
 impl MojangClient {
-    pub fn new(mojank: Arc<MojankConfiguration>) -> MojangRequestResult<Self> {
+    pub fn new(
+        mojank: MojankConfiguration,
+        statistics: Arc<UsageStatistics>,
+    ) -> MojangRequestResult<Self> {
         Ok(Self {
-            client: NmsrHttpClient::new(mojank.session_server_rate_limit),
-            mojank_config: mojank,
+            client: NmsrHttpClient::new(mojank.session_server_rate_limit, statistics),
+            skin_servers: Reloadable::new(Self::create_skin_servers(&mojank)),
+            mojank_config: Reloadable::new(mojank),
         })
     }
 
+    fn create_skin_servers(mojank: &MojankConfiguration) -> Vec<SkinServer> {
+        let mojang_server = mojank.use_mojang.then(|| SkinServer::mojang(mojank));
+
+        let mut skin_servers: Vec<SkinServer> = mojang_server
+            .into_iter()
+            .chain(mojank.skin_servers.iter().map(SkinServer::from))
+            .collect();
+
+        // Stable sort, so Mojang is queried first amongst the servers with the same priority.
+        skin_servers.sort_by_key(|server| server.priority);
+
+        skin_servers
+    }
+
+    /// Replaces the servers players are resolved from, e.g. when the configuration file is reloaded.
+    ///
+    /// The rate limit of requests to the session server can't be changed without a restart.
+    pub fn set_config(&self, mojank: MojankConfiguration) {
+        self.skin_servers.set(Self::create_skin_servers(&mojank));
+        self.mojank_config.set(mojank);
+    }
+
     #[instrument(skip(self, parent_span, on_error), parent = parent_span)]
     pub(crate) async fn do_request(
         &self,
@@ -40,22 +140,88 @@ impl MojangClient {
             .await
     }
 
+    /// Queries each skin server (in order of priority) until one of them replies with a result.
+    async fn query_skin_servers<T: DeserializeOwned>(
+        &self,
+        url: impl Fn(&SkinServer) -> Option<String>,
+        not_found: impl Fn() -> MojangRequestError,
+    ) -> MojangRequestResult<(T, SkinServer)> {
+        let mut last_error = None;
+
+        for server in self.skin_servers.get().iter() {
+            let Some(url) = url(server) else {
+                continue;
+            };
+
+            let result = self
+                .do_request(&url, Method::GET, &Span::current(), || Some(not_found()))
+                .await
+                .and_then(|bytes| {
+                    // Mojang replies with an empty body instead of an error for unknown players.
+                    if bytes.is_empty() {
+                        return Err(not_found());
+                    }
+
+                    Ok(serde_json::from_slice(&bytes)?)
+                });
+
+            match result {
+                Ok(result) => return Ok((result, server.clone())),
+                Err(err) => {
+                    debug!("Skin server {} was unable to reply: {err}", server.name);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(not_found))
+    }
+
     pub async fn resolve_uuid_to_game_profile(
         &self,
         id: &Uuid,
-    ) -> MojangRequestResult<GameProfile> {
-        let url = format!(
-            "{session_server}/session/minecraft/profile/{id}",
-            session_server = self.mojank_config.session_server
-        );
+    ) -> MojangRequestResult<(GameProfile, SkinServer)> {
+        self.query_skin_servers(
+            |server| Some(server.profile_url(id)),
+            || MojangRequestError::GameProfileNotFound(id.to_owned()),
+        )
+        .await
+    }
+
+    /// Resolves the game profile of an offline-mode player from the offline profile URL, if one is configured.
+    pub async fn resolve_offline_uuid_to_game_profile(
+        &self,
+        id: &Uuid,
+    ) -> MojangRequestResult<Option<(GameProfile, SkinServer)>> {
+        let Some(profile_url) = self.mojank_config.get().offline_profile_url.clone() else {
+            return Ok(None);
+        };
+
+        let server = SkinServer::offline(profile_url);
+        let not_found = || MojangRequestError::GameProfileNotFound(id.to_owned());
 
         let bytes = self
-            .do_request(&url, Method::GET, &Span::current(), || {
-                Some(MojangRequestError::GameProfileNotFound(id.to_owned()))
+            .do_request(&server.profile_url(id), Method::GET, &Span::current(), || {
+                Some(not_found())
             })
             .await?;
 
-        Ok(serde_json::from_slice(&bytes)?)
+        if bytes.is_empty() {
+            return Err(not_found());
+        }
+
+        Ok(Some((serde_json::from_slice(&bytes)?, server)))
+    }
+
+    pub async fn resolve_name_to_uuid(&self, name: &str) -> MojangRequestResult<Uuid> {
+        let (profile, _): (PlayerNameProfile, _) = self
+            .query_skin_servers(
+                |server| server.name_url(name),
+                || MojangRequestError::PlayerNameNotFound(name.to_owned()),
+            )
+            .await?;
+
+        Ok(profile.id)
     }
 
     #[instrument(skip(self, parent_span), parent = parent_span)]
@@ -66,7 +232,7 @@ impl MojangClient {
     ) -> MojangRequestResult<Vec<u8>> {
         let url = format!(
             "{textures_server}/texture/{texture_id}",
-            textures_server = self.mojank_config.textures_server
+            textures_server = self.mojank_config.get().textures_server
         );
 
         let bytes = self
@@ -80,7 +246,23 @@ impl MojangClient {
         Ok(bytes.to_vec())
     }
 
-    pub fn mojank_config(&self) -> &MojankConfiguration {
-        self.mojank_config.as_ref()
+    /// Downloads a texture hosted by a skin server other than Mojang's.
+    #[instrument(skip(self, parent_span), parent = parent_span)]
+    pub async fn fetch_texture_from_url(
+        &self,
+        url: &str,
+        parent_span: &Span,
+    ) -> MojangRequestResult<Vec<u8>> {
+        let bytes = self
+            .do_request(url, Method::GET, &Span::current(), || {
+                Some(MojangRequestError::InvalidTextureUrlError(url.to_string()))
+            })
+            .await?;
+
+        Ok(bytes.to_vec())
+    }
+
+    pub fn mojank_config(&self) -> Arc<MojankConfiguration> {
+        self.mojank_config.get()
     }
 }
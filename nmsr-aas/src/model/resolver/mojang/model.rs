@@ -3,6 +3,14 @@ use base64::{engine::general_purpose::STANDARD, Engine};
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The profile returned when looking up a player by their name.
+#[derive(Deserialize, Debug)]
+pub struct PlayerNameProfile {
+    pub id: Uuid,
+    pub name: String,
+}
 
 #[derive(Deserialize, Debug)]
 pub struct GameProfileTextureMetadata {
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+use tracing::trace;
+use uuid::Uuid;
+
+struct CachedPlayerName {
+    uuid: Uuid,
+    resolved_at: Instant,
+}
+
+/// An in-memory cache of player names to UUIDs.
+///
+/// Names can be changed by players at any time, so they're only cached for a short period of time.
+/// Bedrock gamertags are kept with Floodgate's `.` prefix, that way they never clash with Java names.
+pub struct PlayerNameCache {
+    entries: RwLock<HashMap<String, CachedPlayerName>>,
+    duration: Duration,
+}
+
+impl PlayerNameCache {
+    #[must_use]
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            duration,
+        }
+    }
+
+    /// Player names are case-insensitive, so we always use the lowercase name as the key.
+    fn key(name: &str) -> String {
+        name.to_ascii_lowercase()
+    }
+
+    /// Returns the cached UUID of the player with the given name, if any.
+    pub async fn get(&self, name: &str) -> Option<Uuid> {
+        let (uuid, resolved_at) = self
+            .entries
+            .read()
+            .await
+            .get(&Self::key(name))
+            .map(|entry| (entry.uuid, entry.resolved_at))?;
+
+        if resolved_at.elapsed() > self.duration {
+            trace!("Cached player name {name} is expired.");
+            return None;
+        }
+
+        Some(uuid)
+    }
+
+    pub async fn insert(&self, name: &str, uuid: Uuid) {
+        let entry = CachedPlayerName {
+            uuid,
+            resolved_at: Instant::now(),
+        };
+
+        self.entries.write().await.insert(Self::key(name), entry);
+    }
+
+    /// Removes the given player name from the cache, returning the UUID it was resolved to.
+    pub async fn invalidate(&self, name: &str) -> Option<Uuid> {
+        self.entries
+            .write()
+            .await
+            .remove(&Self::key(name))
+            .map(|entry| entry.uuid)
+    }
+
+    pub async fn invalidate_all(&self) {
+        self.entries.write().await.clear();
+    }
+
+    /// Removes every expired entry from the cache.
+    pub async fn do_cache_clean_up(&self) {
+        self.entries
+            .write()
+            .await
+            .retain(|_, entry| entry.resolved_at.elapsed() <= self.duration);
+    }
+}
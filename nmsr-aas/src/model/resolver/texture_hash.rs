@@ -0,0 +1,139 @@
+use std::{collections::HashMap, time::Duration, time::Instant};
+
+use tokio::sync::RwLock;
+use tracing::trace;
+
+use crate::{
+    model::request::entry::{RenderRequestEntry, RenderRequestEntryModel},
+    utils::reloadable::Reloadable,
+};
+
+/// The textures a player was resolved to, as known by the render cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedTextures {
+    /// The model of the player's skin, if their skin server told us.
+    pub model: Option<RenderRequestEntryModel>,
+    /// The hash of the player's textures, see [`super::ResolvedRenderRequest::textures_hash`].
+    pub textures_hash: u128,
+}
+
+struct IndexedEntry {
+    textures: IndexedTextures,
+    resolved_at: Instant,
+}
+
+/// An in-memory index of players to the hash of the textures they were last resolved to.
+///
+/// Cached renders are stored by the hash of their textures instead of the player they're for, this way renders of
+/// players we've resolved recently can be looked up without resolving them again. Entries are kept for as long as
+/// resolved models are, see [`crate::config::ModelCacheConfiguration::resolve_cache_duration`].
+///
+/// Player names (and gamertags) aren't indexed, since they can move to another player at any time.
+pub struct TextureHashIndex {
+    entries: RwLock<HashMap<RenderRequestEntry, IndexedEntry>>,
+    duration: Reloadable<Duration>,
+}
+
+impl TextureHashIndex {
+    #[must_use]
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            duration: Reloadable::new(duration),
+        }
+    }
+
+    /// Whether the textures of the given entry can be indexed.
+    const fn is_indexable(entry: &RenderRequestEntry) -> bool {
+        matches!(
+            entry,
+            RenderRequestEntry::MojangPlayerUuid(_)
+                | RenderRequestEntry::OfflinePlayerUuid(_)
+                | RenderRequestEntry::GeyserPlayerUuid(_)
+                | RenderRequestEntry::TextureHash(_)
+        )
+    }
+
+    /// Returns the textures the given entry was last resolved to, if it was resolved recently.
+    pub async fn get(&self, entry: &RenderRequestEntry) -> Option<IndexedTextures> {
+        let (textures, resolved_at) = self
+            .entries
+            .read()
+            .await
+            .get(entry)
+            .map(|entry| (entry.textures, entry.resolved_at))?;
+
+        if resolved_at.elapsed() > *self.duration.get() {
+            trace!("Indexed textures of {entry:?} are expired.");
+            return None;
+        }
+
+        Some(textures)
+    }
+
+    pub async fn insert(&self, entry: &RenderRequestEntry, textures: IndexedTextures) {
+        if !Self::is_indexable(entry) {
+            return;
+        }
+
+        let indexed = IndexedEntry {
+            textures,
+            resolved_at: Instant::now(),
+        };
+
+        self.entries.write().await.insert(entry.clone(), indexed);
+    }
+
+    pub fn set_duration(&self, duration: Duration) {
+        self.duration.set(duration);
+    }
+
+    pub async fn invalidate(&self, entry: &RenderRequestEntry) -> bool {
+        self.entries.write().await.remove(entry).is_some()
+    }
+
+    pub async fn invalidate_all(&self) {
+        self.entries.write().await.clear();
+    }
+
+    /// Removes every expired entry from the index.
+    pub async fn do_cache_clean_up(&self) {
+        let duration = *self.duration.get();
+
+        self.entries
+            .write()
+            .await
+            .retain(|_, entry| entry.resolved_at.elapsed() <= duration);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::{IndexedTextures, TextureHashIndex};
+    use crate::model::request::entry::RenderRequestEntry;
+
+    #[tokio::test]
+    async fn only_players_that_keep_their_textures_are_indexed() {
+        let index = TextureHashIndex::new(Duration::from_secs(60));
+        let textures = IndexedTextures {
+            model: None,
+            textures_hash: 42,
+        };
+
+        let uuid = RenderRequestEntry::MojangPlayerUuid(Uuid::nil());
+        let name = RenderRequestEntry::MojangPlayerName("Notch".to_string());
+
+        index.insert(&uuid, textures).await;
+        index.insert(&name, textures).await;
+
+        assert_eq!(index.get(&uuid).await, Some(textures));
+        assert_eq!(index.get(&name).await, None);
+
+        assert!(index.invalidate(&uuid).await);
+        assert_eq!(index.get(&uuid).await, None);
+    }
+}
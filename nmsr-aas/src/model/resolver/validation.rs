@@ -0,0 +1,106 @@
+use std::io::Cursor;
+
+use image::{codecs::png::PngDecoder, io::Limits, ImageDecoder, ImageError};
+use nmsr_rendering::high_level::skin_regions::SKIN_WIDTH;
+
+use crate::error::{RenderRequestError, RenderRequestResult};
+
+/// The signature every PNG file starts with.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The maximum size (in bytes) of a skin once decoded, which is enough for 16x HD skins.
+const MAX_SKIN_DECODED_SIZE: u64 = 1024 * 1024 * 4;
+
+/// The number of bytes every pixel of a skin takes once decoded, since skins are always rendered as RGBA8.
+const SKIN_PIXEL_SIZE: u64 = 4;
+
+/// Checks that a skin we didn't get from Mojang (e.g. uploaded or from another skin server) can be rendered.
+///
+/// Only the PNG header is read, that way invalid skins are rejected before anything is decoded.
+/// Any color type and bit depth the decoder supports is fine, since skins are converted to RGBA8 once decoded.
+pub fn validate_skin(data: &[u8]) -> RenderRequestResult<()> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return Err(RenderRequestError::InvalidSkinFormat);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_SKIN_DECODED_SIZE);
+
+    let decoder = PngDecoder::with_limits(Cursor::new(data), limits).map_err(|err| match err {
+        ImageError::Limits(_) => RenderRequestError::SkinTooLarge(MAX_SKIN_DECODED_SIZE),
+        err => RenderRequestError::MalformedSkin(err.to_string()),
+    })?;
+
+    let (width, height) = decoder.dimensions();
+
+    if width == 0 || !width.is_multiple_of(SKIN_WIDTH) || (height != width && height * 2 != width) {
+        return Err(RenderRequestError::InvalidSkinDimensions(width, height));
+    }
+
+    if u64::from(width) * u64::from(height) * SKIN_PIXEL_SIZE > MAX_SKIN_DECODED_SIZE {
+        return Err(RenderRequestError::SkinTooLarge(MAX_SKIN_DECODED_SIZE));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+
+    use super::validate_skin;
+    use crate::{error::RenderRequestError, utils::png::create_png_from_bytes};
+
+    fn create_skin(width: u32, height: u32) -> Vec<u8> {
+        let pixels = vec![0; (width * height * 4) as usize];
+
+        create_png_from_bytes((width, height), &pixels).unwrap()
+    }
+
+    #[test]
+    fn valid_skins_are_accepted() {
+        assert!(validate_skin(&create_skin(64, 64)).is_ok());
+        assert!(validate_skin(&create_skin(64, 32)).is_ok());
+        assert!(validate_skin(&create_skin(128, 128)).is_ok());
+    }
+
+    #[test]
+    fn skins_with_other_color_types_are_accepted() {
+        for (color_type, channel_bytes) in [
+            (ColorType::L8, 1),
+            (ColorType::La8, 2),
+            (ColorType::Rgba16, 8),
+        ] {
+            let mut skin = Vec::new();
+            PngEncoder::new(&mut skin)
+                .write_image(&vec![0; 64 * 64 * channel_bytes], 64, 64, color_type)
+                .unwrap();
+
+            assert!(validate_skin(&skin).is_ok(), "{color_type:?} skin was rejected");
+        }
+    }
+
+    #[test]
+    fn invalid_skins_are_rejected() {
+        assert!(matches!(
+            validate_skin(b"GIF89a"),
+            Err(RenderRequestError::InvalidSkinFormat)
+        ));
+
+        assert!(matches!(
+            validate_skin(&create_skin(64, 48)),
+            Err(RenderRequestError::InvalidSkinDimensions(64, 48))
+        ));
+
+        assert!(matches!(
+            validate_skin(&create_skin(2048, 2048)),
+            Err(RenderRequestError::SkinTooLarge(_))
+        ));
+
+        // A truncated header isn't a valid PNG, even if it starts like one.
+        assert!(matches!(
+            validate_skin(&create_skin(64, 64)[..16]),
+            Err(RenderRequestError::MalformedSkin(_))
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, State},
+    http::request::Parts,
+    Json,
+};
+use hyper::{header::AUTHORIZATION, HeaderMap, StatusCode};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, TryFromInto};
+use tracing::{info, instrument};
+
+use super::NMSRState;
+use crate::{
+    config::WarmupConfiguration,
+    error::{NMSRaaSError, RenderRequestError, Result},
+    model::request::{entry::RenderRequestEntry, RenderRequestMode},
+    utils::stats::StatisticsSnapshot,
+};
+
+/// Proof that a request was sent with the configured admin token.
+///
+/// Requests are rejected if no admin token is configured.
+pub struct AdminAuthorization;
+
+#[async_trait]
+impl FromRequestParts<NMSRState> for AdminAuthorization {
+    type Rejection = NMSRaaSError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &NMSRState) -> Result<Self> {
+        let token = bearer_token(&parts.headers);
+
+        let expected = state
+            .admin_config
+            .as_ref()
+            .map(|config| config.token.as_str())
+            .filter(|token| !token.is_empty());
+
+        match (token, expected) {
+            (Some(token), Some(expected)) if constant_time_eq(token, expected) => Ok(Self),
+            _ => Err(NMSRaaSError::Unauthorized),
+        }
+    }
+}
+
+/// Returns the token of the `Authorization: Bearer` header of a request, if any.
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Compares two strings without short-circuiting, that way the token can't be guessed by timing our responses.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+const fn purge_status(purged: bool) -> StatusCode {
+    if purged {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Purges the resolved textures of a player, that way their skin is fetched again on the next request.
+///
+/// Players are given the same way they are when rendering them, i.e. by UUID (Mojang, offline or Geyser), name,
+/// Bedrock gamertag or texture hash. Purging a name also purges the player it was last resolved to.
+///
+/// Rendered images don't need to be purged, since they are cached based on the textures used.
+#[instrument(skip(state, _auth))]
+pub async fn purge_player(
+    State(state): State<NMSRState>,
+    _auth: AdminAuthorization,
+    Path(player): Path<String>,
+) -> Result<StatusCode> {
+    let entry = RenderRequestEntry::try_from(player)?;
+
+    let purged = state.resolver.invalidate_entry(&entry).await?;
+
+    info!("Purged player {entry:?} from the cache: {purged}");
+
+    Ok(purge_status(purged))
+}
+
+/// Purges a skin (or any other texture) by its hash.
+#[instrument(skip(state, _auth))]
+pub async fn purge_texture(
+    State(state): State<NMSRState>,
+    _auth: AdminAuthorization,
+    Path(hash): Path<String>,
+) -> Result<StatusCode> {
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(RenderRequestError::InvalidPlayerRequest(format!(
+            "Invalid texture hash: {hash}"
+        ))
+        .into());
+    }
+
+    let purged = state.resolver.invalidate_texture(&hash).await?;
+
+    info!("Purged texture {hash} from the cache: {purged}");
+
+    Ok(purge_status(purged))
+}
+
+/// Purges every cached texture and rendered image.
+#[instrument(skip(state, _auth))]
+pub async fn purge_all(
+    State(state): State<NMSRState>,
+    _auth: AdminAuthorization,
+) -> Result<StatusCode> {
+    state.resolver.invalidate_all().await?;
+
+    if let Some(render_cache) = &state.render_cache {
+        let purged = render_cache.purge().await?;
+
+        info!("Purged {purged} rendered images from the render cache");
+    }
+
+    info!("Purged the whole cache");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The players to warm up the cache with, and optionally the modes to render them in.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+pub struct WarmupRequest {
+    #[serde_as(as = "Vec<TryFromInto<String>>")]
+    players: Vec<RenderRequestEntry>,
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    #[serde(default)]
+    modes: Option<Vec<RenderRequestMode>>,
+}
+
+/// Returns the usage statistics of this instance since it was started, for lightweight dashboards.
+pub async fn stats(
+    State(state): State<NMSRState>,
+    _auth: AdminAuthorization,
+) -> Json<StatisticsSnapshot> {
+    Json(state.statistics.snapshot())
+}
+
+/// Resolves and renders the given players in the background, that way the cache is primed before they're requested.
+#[instrument(skip(state, _auth))]
+pub async fn warm_up(
+    State(state): State<NMSRState>,
+    _auth: AdminAuthorization,
+    Json(request): Json<WarmupRequest>,
+) -> StatusCode {
+    let modes = request
+        .modes
+        .unwrap_or_else(|| WarmupConfiguration::DEFAULT_MODES.to_vec());
+
+    info!(
+        "Warming up the cache with {} players",
+        request.players.len()
+    );
+
+    state.spawn_warm_up(request.players, &modes);
+
+    StatusCode::ACCEPTED
+}
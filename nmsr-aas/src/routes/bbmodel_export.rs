@@ -15,6 +15,7 @@ use nmsr_rendering_blockbench_model_generator_experiment::{
     error::BlockbenchGeneratorError,
     generator::{ModelGenerationProject, ModelProjectImageIO},
 };
+use tokio::time::Instant;
 use tracing::instrument;
 
 use crate::{
@@ -24,7 +25,7 @@ use crate::{
         request::{RenderRequest, RenderRequestFeatures},
     },
     routes::render_model::create_part_context,
-    utils::png::create_png_from_bytes,
+    utils::{metrics::Metrics, png::create_png_from_bytes},
 };
 
 use super::{render_model::load_image, NMSRState};
@@ -70,6 +71,7 @@ pub(crate) async fn internal_bbmodel_export(
         .into_response());
     }
 
+    let started = Instant::now();
     let mut part_context = create_part_context(&request, &resolved);
     
     if let Some(pos) = part_context.shadow_y_pos {
@@ -106,7 +108,12 @@ pub(crate) async fn internal_bbmodel_export(
     }
 
     let mut blockbench_project =
-        ModelGenerationProject::new_with_part_context(NMSRaaSImageIO, part_context);
+        ModelGenerationProject::new_with_part_context(NMSRaaSImageIO, part_context)
+            .with_limits(state.export_limits);
+
+    if let Some(determinism) = state.determinism {
+        blockbench_project = blockbench_project.with_seed(determinism.seed);
+    }
 
     for (texture_type, mut texture) in textures {
         if texture_type == PlayerPartTextureType::Skin {
@@ -117,6 +124,7 @@ pub(crate) async fn internal_bbmodel_export(
     }
 
     let result = generate_project(blockbench_project)?;
+    Metrics::record_render_duration(&request.get_mode_name(), started.elapsed());
 
     let mut res = result.into_response();
 
@@ -136,3 +144,65 @@ pub(crate) async fn internal_bbmodel_export(
 
     Ok(res)
 }
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+    use nmsr_rendering::high_level::{
+        model::{PlayerBodyProportions, PlayerModel},
+        parts::provider::PlayerPartProviderContext,
+        types::PlayerPartTextureType,
+    };
+    use nmsr_rendering_blockbench_model_generator_experiment::{
+        blockbench::generate_project, generator::ModelGenerationProject,
+    };
+
+    use super::NMSRaaSImageIO;
+    use crate::model::armor::VanillaMinecraftArmorMaterialData;
+
+    fn export(seed: Option<u64>) -> serde_json::Value {
+        let context = PlayerPartProviderContext::<VanillaMinecraftArmorMaterialData> {
+            model: PlayerModel::Steve,
+            has_hat_layer: false,
+            has_layers: false,
+            has_cape: false,
+            arm_rotation: 0.0,
+            shadow_y_pos: None,
+            shadow_is_square: false,
+            armor_slots: None,
+            proportions: PlayerBodyProportions::default(),
+            shoulder_buddies: None,
+            has_deadmau5_ears: false,
+            #[cfg(feature = "ears")]
+            ears_features: None,
+        };
+
+        let mut project = ModelGenerationProject::new_with_part_context(NMSRaaSImageIO, context);
+
+        if let Some(seed) = seed {
+            project = project.with_seed(seed);
+        }
+
+        let skin = RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]));
+        project
+            .add_texture(PlayerPartTextureType::Skin, skin, false)
+            .expect("Skin should be added");
+
+        let output = generate_project(project).expect("Project should be generated");
+        serde_json::from_str(&output).expect("Project should be JSON")
+    }
+
+    #[test]
+    fn seeded_exports_are_identical() {
+        let seeded = export(Some(42));
+
+        assert_eq!(seeded, export(Some(42)));
+        // The names are derived from the seed alone, so they have to stay the same across versions too.
+        assert_eq!(
+            seeded["elements"][0]["faces"]["face"]["vertices"][0],
+            "top_left98d3554ba7973a40"
+        );
+        assert_ne!(seeded, export(Some(43)));
+        assert_ne!(export(None), export(None));
+    }
+}
@@ -0,0 +1,195 @@
+use axum::{
+    extract::{FromRequest, Request, State},
+    response::Response,
+};
+use axum_extra::extract::Multipart;
+use image::{imageops, RgbaImage};
+use nmsr_rendering::high_level::{skin, skin_regions::SkinRegion};
+use strum::EnumString;
+use tracing::instrument;
+
+use super::{
+    extractors::render_request_from_path,
+    render::create_image_response,
+    render_face_parallax::internal_render_face_parallax,
+    render_model::{internal_render_model, load_image},
+    render_skin::internal_render_skin,
+    NMSRState,
+};
+use crate::{
+    error::{RenderRequestError, Result},
+    model::{request::RenderRequestMode, resolver::ResolvedRenderEntryTextureType},
+    utils::{png::create_png_from_bytes, render_scheduler::RenderClass},
+};
+
+/// The layers of a skin an overlay is composited onto.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum CompositeLayers {
+    /// Only the base layer, e.g. to recolor the shirt of a skin.
+    Base,
+    /// Only the second layer, e.g. for a jersey or a hat worn on top of the skin.
+    Second,
+    /// Both layers.
+    #[default]
+    All,
+}
+
+impl CompositeLayers {
+    const fn regions(self) -> &'static [&'static [SkinRegion]] {
+        match self {
+            Self::Base => &[&skin::BASE_LAYER_REGIONS],
+            Self::Second => &[&skin::SECOND_LAYER_REGIONS],
+            Self::All => &[&skin::BASE_LAYER_REGIONS, &skin::SECOND_LAYER_REGIONS],
+        }
+    }
+}
+
+/// Renders a player with an uploaded overlay (e.g. a team jersey or a hat) composited on top of their skin.
+///
+/// URLs have the format `POST /composite/:mode/:entry?options`, with the same options as a regular render.
+/// The body is a multipart form with the overlay (a PNG laid out like a skin) in the `overlay` field, and the
+/// layers of the skin it's composited onto (`base`, `second` or `all`, the default) in the optional `layers` field.
+#[instrument(skip(state, request))]
+pub async fn composite(state: State<NMSRState>, mut request: Request) -> Result<Response> {
+    let render_request = render_request_from_path(&mut request, &state.0).await?;
+
+    if render_request.mode.is_blockbench_export() {
+        return Err(RenderRequestError::InvalidRenderMode(render_request.mode.to_string()).into());
+    }
+
+    let multipart = Multipart::from_request(request, &state.0)
+        .await
+        .map_err(RenderRequestError::from)?;
+
+    let (overlay, layers) = read_overlay(multipart).await?;
+
+    let mut resolved = state.resolver.resolve(&render_request).await?;
+
+    let skin = resolved
+        .textures
+        .get(&ResolvedRenderEntryTextureType::Skin)
+        .ok_or_else(|| {
+            RenderRequestError::InvalidPlayerRequest("Missing skin texture".to_string())
+        })?;
+
+    let skin = composite_skin(load_image(skin)?, &overlay, layers);
+
+    resolved.textures.insert(
+        ResolvedRenderEntryTextureType::Skin,
+        create_png_from_bytes((skin.width(), skin.height()), &skin)?,
+    );
+
+    // Composited skins are one-offs, so they aren't worth storing in the render cache.
+    let render = match render_request.mode {
+        RenderRequestMode::Skin => internal_render_skin(&render_request, resolved).await?,
+        RenderRequestMode::FaceParallax => {
+            internal_render_face_parallax(&render_request, &state, &resolved, skin).await?
+        }
+        _ => internal_render_model(&render_request, &state, &resolved, RenderClass::Single).await?,
+    };
+
+    Ok(create_image_response(render, &state, &render_request))
+}
+
+async fn read_overlay(mut multipart: Multipart) -> Result<(RgbaImage, CompositeLayers)> {
+    let mut overlay = None;
+    let mut layers = CompositeLayers::default();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(RenderRequestError::from)?
+    {
+        match field.name() {
+            Some("overlay") => {
+                let bytes = field.bytes().await.map_err(RenderRequestError::from)?;
+
+                let image = load_image(&bytes).map_err(|_| {
+                    RenderRequestError::InvalidPlayerRequest(
+                        "The overlay must be a PNG image".to_string(),
+                    )
+                })?;
+
+                if image.width() != image.height() {
+                    return Err(RenderRequestError::InvalidPlayerRequest(
+                        "The overlay must be laid out like a modern skin (e.g. 64x64)".to_string(),
+                    )
+                    .into());
+                }
+
+                overlay = Some(image);
+            }
+            Some("layers") => {
+                let value = field.text().await.map_err(RenderRequestError::from)?;
+
+                layers = value.trim().parse().map_err(|_| {
+                    RenderRequestError::InvalidRenderSettingError(
+                        "layers",
+                        "base, second or all".to_string(),
+                    )
+                })?;
+            }
+            _ => {}
+        }
+    }
+
+    let overlay = overlay.ok_or_else(|| {
+        RenderRequestError::InvalidPlayerRequest(
+            "Missing overlay. Did you forget to upload it in the overlay field?".to_string(),
+        )
+    })?;
+
+    Ok((overlay, layers))
+}
+
+/// Composites the overlay onto the given layers of a skin, upgrading it to the modern format first.
+///
+/// Overlays that aren't the same size as the skin (e.g. a regular overlay on an HD skin) are scaled to fit it.
+pub fn composite_skin(skin: RgbaImage, overlay: &RgbaImage, layers: CompositeLayers) -> RgbaImage {
+    let mut skin = skin::upgrade_legacy_skin(skin);
+
+    let scaled;
+    let overlay = if overlay.dimensions() == skin.dimensions() {
+        overlay
+    } else {
+        scaled = imageops::resize(
+            overlay,
+            skin.width(),
+            skin.height(),
+            imageops::FilterType::Nearest,
+        );
+        &scaled
+    };
+
+    for regions in layers.regions() {
+        skin::composite_overlay(&mut skin, overlay, regions);
+    }
+
+    skin
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::{composite_skin, CompositeLayers};
+
+    #[test]
+    fn overlays_only_affect_the_chosen_layers() {
+        let skin = RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]));
+        let overlay = RgbaImage::from_pixel(32, 32, Rgba([0, 0, 255, 255]));
+
+        let composited = composite_skin(skin.clone(), &overlay, CompositeLayers::Second);
+
+        // The hat is part of the second layer, while the face is part of the base layer.
+        assert_eq!(composited.get_pixel(40, 8), &Rgba([0, 0, 255, 255]));
+        assert_eq!(composited.get_pixel(8, 8), &Rgba([255, 0, 0, 255]));
+
+        let composited = composite_skin(skin, &overlay, CompositeLayers::All);
+
+        assert_eq!(composited.get_pixel(8, 8), &Rgba([0, 0, 255, 255]));
+        // Unused parts of the skin are left untouched.
+        assert_eq!(composited.get_pixel(60, 20), &Rgba([255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderValue,
+    response::{Html, IntoResponse, Response},
+};
+use hyper::header::CACHE_CONTROL;
+use indoc::formatdoc;
+use tracing::instrument;
+
+use super::{
+    extractors::create_render_request, profile::ProfileInformation,
+    query::RenderRequestQueryParams, NMSRState, RenderRequestValidator,
+};
+use crate::{
+    config::EmbedConfiguration,
+    error::{RenderRequestError, Result},
+    model::request::entry::RenderRequestEntry,
+};
+
+/// Serves a tiny HTML page with Open Graph and Twitter card meta tags pointing at the render of a player,
+/// that way pasting a link to it in chat apps (e.g. Discord or Slack) shows the render in the link preview.
+#[instrument(skip(state, config))]
+pub(crate) async fn embed(
+    State(state): State<NMSRState>,
+    Path(player): Path<String>,
+    config: EmbedConfiguration,
+) -> Result<Response> {
+    let entry = RenderRequestEntry::try_from(player.clone())?;
+    let profile = ProfileInformation::resolve(&state, &entry).await?;
+
+    let (mode, custom_mode) = state.modes().parse_name(&config.mode)
+        .filter(|(mode, _)| state.validate_mode(mode, None))
+        .ok_or_else(|| RenderRequestError::InvalidRenderMode(config.mode.clone()))?;
+
+    let options = config.options.as_deref().unwrap_or_default();
+
+    let mut query = RenderRequestQueryParams::from_query_string(options)?;
+    query.custom_mode = custom_mode;
+
+    // The render isn't made here, but the request tells us its size and format.
+    let request = create_render_request(
+        &state,
+        None,
+        mode,
+        RenderRequestEntry::MojangPlayerUuid(profile.uuid),
+        query,
+    )?;
+
+    let size = request.get_size();
+    let base_url = config.public_url.trim_end_matches('/');
+
+    let render_path = format!("/{}/{}", config.mode, profile.uuid.simple());
+    let render_url = state.sign_url(&render_path, Some(options).filter(|o| !o.is_empty()));
+
+    let site_name = config
+        .site_name
+        .as_deref()
+        .map(|site_name| {
+            format!(
+                r#"<meta property="og:site_name" content="{}">"#,
+                escape_html(site_name)
+            )
+        })
+        .unwrap_or_default();
+
+    let html = formatdoc! {r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+        <meta charset="utf-8">
+        <title>{title}</title>
+        <meta property="og:type" content="profile">
+        <meta property="og:title" content="{title}">
+        <meta property="og:url" content="{base_url}/embed/{player}">
+        <meta property="og:image" content="{image}">
+        <meta property="og:image:type" content="{image_type}">
+        <meta property="og:image:width" content="{width}">
+        <meta property="og:image:height" content="{height}">
+        {site_name}
+        <meta name="twitter:card" content="summary_large_image">
+        <meta name="twitter:title" content="{title}">
+        <meta name="twitter:image" content="{image}">
+        </head>
+        <body>
+        <img src="{image}" alt="{title}" width="{width}" height="{height}">
+        </body>
+        </html>
+        "#,
+        title = escape_html(&player),
+        player = escape_html(&player),
+        image = escape_html(&format!("{base_url}{render_url}")),
+        image_type = request.get_output_format().mime_type(),
+        width = size.width,
+        height = size.height,
+    };
+
+    let mut response = Html(html).into_response();
+
+    if let Ok(cache_ctrl) = HeaderValue::from_str(&state.get_cache_control_for_entry(&entry)) {
+        response.headers_mut().insert(CACHE_CONTROL, cache_ctrl);
+    }
+
+    Ok(response)
+}
+
+/// Escapes the characters that have a special meaning in HTML text and attribute values.
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape_html;
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(
+            escape_html(r#""><script>alert('hi')</script>&"#),
+            "&quot;&gt;&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt;&amp;"
+        );
+    }
+}
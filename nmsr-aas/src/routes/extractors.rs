@@ -10,14 +10,15 @@ use crate::{
 };
 use async_trait::async_trait;
 use axum::{
-    extract::{FromRequest, Path, Query, Request},
+    extract::{FromRequest, FromRequestParts, Path, Query, Request},
+    http::request::Parts,
     RequestExt,
 };
 use axum_extra::extract::Multipart;
-use hyper::Method;
+use hyper::{header::HOST, http::uri::Authority, HeaderMap, Method, Uri};
 use is_empty::IsEmpty;
 use serde_json::{json, Value};
-use std::{borrow::ToOwned, collections::HashMap};
+use std::{borrow::ToOwned, collections::HashMap, convert::Infallible};
 
 #[async_trait]
 impl<S> FromRequest<S> for RenderRequest
@@ -37,124 +38,286 @@ where
     /// The entry is in the URL path, and the options are in the query string.
     ///
     async fn from_request(mut request: Request, state: &S) -> Result<Self> {
-        let (mode, entry, mut query) = if request.method() == Method::POST {
-            let Path(mode_str) = request
-                .extract_parts_with_state::<Path<String>, S>(state)
-                .await
-                .map_err(RenderRequestError::from)?;
-
-            let mode = RenderRequestMode::try_from(mode_str.as_str())
-                .ok()
-                .filter(|r| state.validate_mode(r))
-                .ok_or_else(|| RenderRequestError::InvalidRenderMode(mode_str))?;
-
-            let mut multipart = Multipart::from_request(request, state)
-                .await
-                .map_err(RenderRequestError::from)?;
-
-            let mut data: HashMap<String, Value> = HashMap::new();
-
-            while let Some(field) = multipart
-                .next_field()
-                .await
-                .map_err(RenderRequestError::from)?
-            {
-                if let Some(name) = field.name().map(ToOwned::to_owned) {
-                    let entry_content = if field.content_type().is_none() {
-                        let str = field.text().await.map_err(RenderRequestError::from)?;
-                        serde_json::from_str(&str).unwrap_or(Value::String(str))
-                    } else {
-                        Value::from(
-                            field
-                                .bytes()
-                                .await
-                                .map_err(RenderRequestError::from)?
-                                .to_vec(),
-                        )
-                    };
-
-                    data.insert(name.clone(), entry_content);
-                }
+        if request.method() != Method::POST {
+            return render_request_from_path(&mut request, state).await;
+        }
+
+        let host = get_request_host(request.uri(), request.headers());
+        let host = host.as_deref();
+        let hints = ClientHints::from_headers(request.headers());
+
+        let Path(mode_str) = request
+            .extract_parts_with_state::<Path<String>, S>(state)
+            .await
+            .map_err(RenderRequestError::from)?;
+
+        let (mode, custom_mode) = state.modes().parse_name(&mode_str)
+            .filter(|(r, _)| state.validate_mode(r, host))
+            .ok_or_else(|| RenderRequestError::InvalidRenderMode(mode_str))?;
+
+        let mut multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(RenderRequestError::from)?;
+
+        let mut data: HashMap<String, Value> = HashMap::new();
+
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(RenderRequestError::from)?
+        {
+            if let Some(name) = field.name().map(ToOwned::to_owned) {
+                let entry_content = if field.content_type().is_none() {
+                    let str = field.text().await.map_err(RenderRequestError::from)?;
+                    serde_json::from_str(&str).unwrap_or(Value::String(str))
+                } else {
+                    Value::from(
+                        field
+                            .bytes()
+                            .await
+                            .map_err(RenderRequestError::from)?
+                            .to_vec(),
+                    )
+                };
+
+                data.insert(name.clone(), entry_content);
             }
+        }
 
-            let object = json!(data);
+        let object = json!(data);
 
-            let query = serde_json::from_value::<RenderRequestMultipartParams>(object.clone())
-                .map_err(|e| RenderRequestError::MultipartDecodeError(e, object.clone()))?;
+        let query = serde_json::from_value::<RenderRequestMultipartParams>(object.clone())
+            .map_err(|e| RenderRequestError::MultipartDecodeError(e, object.clone()))?;
 
-            let entry = RenderRequestEntry::try_from(query.skin)?;
+        let entry = RenderRequestEntry::try_from(query.skin)?;
 
-            (mode, entry, query.query)
-        } else {
-            let Path((mode_str, entry_str)) = request
-                .extract_parts_with_state::<Path<(String, String)>, S>(state)
-                .await
-                .map_err(RenderRequestError::from)?;
+        finish_render_request(state, host, hints, (mode, custom_mode), entry, query.query)
+    }
+}
 
-            let mode = RenderRequestMode::try_from(mode_str.as_str())
-                .ok()
-                .filter(|r| state.validate_mode(r))
-                .ok_or_else(|| RenderRequestError::InvalidRenderMode(mode_str))?;
+/// Extracts a [`RenderRequest`] from the path (`/:mode/:entry`) and query string of a request, leaving its body
+/// untouched for the caller.
+pub(crate) async fn render_request_from_path<S>(
+    request: &mut Request,
+    state: &S,
+) -> Result<RenderRequest>
+where
+    S: Send + Sync + RenderRequestValidator,
+{
+    let host = get_request_host(request.uri(), request.headers());
+    let host = host.as_deref();
+    let hints = ClientHints::from_headers(request.headers());
+
+    let Path((mode_str, entry_str)) = request
+        .extract_parts_with_state::<Path<(String, String)>, S>(state)
+        .await
+        .map_err(RenderRequestError::from)?;
+
+    let (mode, custom_mode) = state.modes().parse_name(&mode_str)
+        .filter(|(r, _)| state.validate_mode(r, host))
+        .ok_or_else(|| RenderRequestError::InvalidRenderMode(mode_str))?;
 
-            let entry = RenderRequestEntry::try_from(entry_str)?;
+    let entry = RenderRequestEntry::try_from(entry_str)?;
 
-            let Query(query) = request
-                .extract_parts_with_state::<Query<RenderRequestQueryParams>, S>(state)
-                .await
-                .map_err(RenderRequestError::from)?;
+    let Query(query) = request
+        .extract_parts_with_state::<Query<RenderRequestQueryParams>, S>(state)
+        .await
+        .map_err(RenderRequestError::from)?;
 
-            (mode, entry, query)
+    finish_render_request(state, host, hints, (mode, custom_mode), entry, query)
+}
+
+fn finish_render_request<S: RenderRequestValidator>(
+    state: &S,
+    host: Option<&str>,
+    hints: ClientHints,
+    (mode, custom_mode): (RenderRequestMode, Option<String>),
+    entry: RenderRequestEntry,
+    mut query: RenderRequestQueryParams,
+) -> Result<RenderRequest> {
+    query.custom_mode = custom_mode;
+    hints.apply(&mut query, mode, state.size_constraints(mode));
+
+    create_render_request(state, host, mode, entry, query)
+}
+
+/// The client hints a browser sends about the display an image will be shown on.
+#[derive(Debug, Default, Clone, Copy)]
+struct ClientHints {
+    /// The device pixel ratio of the display.
+    dpr: Option<f32>,
+    /// The width the image will be shown at, in physical pixels.
+    width: Option<u32>,
+}
+
+impl ClientHints {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |names: [&str; 2]| {
+            names
+                .into_iter()
+                .find_map(|name| headers.get(name))
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
         };
 
-        query.validate(mode)?;
-
-        let excluded_features = query.get_excluded_features();
-
-        let model = query.get_model();
-
-        let extra_settings = Some(RenderRequestExtraSettings {
-            width: query.width,
-            height: query.height,
-
-            yaw: query.yaw,
-            pitch: query.pitch,
-            roll: query.roll,
-
-            arm_rotation: query.arms,
-            distance: query.distance,
-
-            x_pos: query.x_pos,
-            y_pos: query.y_pos,
-            z_pos: query.z_pos,
-
-            helmet: query.helmet,
-            chestplate: query.chestplate,
-            leggings: query.leggings,
-            boots: query.boots,
-        })
-        .filter(|s| !s.is_empty());
-
-        let mut request = Self::new_from_excluded_features(
-            mode,
-            entry,
-            model,
-            excluded_features,
-            extra_settings,
-        );
-        
-        state.cleanup_request(&mut request);
-        
-        Ok(request)
+        Self {
+            dpr: get(["sec-ch-dpr", "dpr"])
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|dpr| dpr.is_finite()),
+            width: get(["sec-ch-width", "width"]).and_then(|value| value.parse().ok()),
+        }
     }
+
+    /// Applies the hints to the options of a render. The DPR hint takes precedence over `?dpr`, while the width hint
+    /// is only used if the render's size wasn't specified.
+    ///
+    /// Unlike the query parameters, hints aren't chosen by whoever wrote the URL, so they're clamped to what
+    /// the mode supports instead of being rejected.
+    fn apply(
+        self,
+        query: &mut RenderRequestQueryParams,
+        mode: RenderRequestMode,
+        size_constraints: [u32; 4],
+    ) {
+        let [min_w, _, max_w, max_h] = size_constraints;
+
+        let dpr = self
+            .dpr
+            .map(|dpr| dpr.clamp(RenderRequestMode::MIN_DPR, RenderRequestMode::MAX_DPR));
+
+        let has_size = query.width.is_some() || query.height.is_some();
+        if let Some(width) = self.width.filter(|_| !has_size) {
+            // The hint is in physical pixels, but the width of a render gets scaled by its DPR.
+            let width = width as f32 / dpr.or(query.dpr).unwrap_or(1.0);
+            query.width = Some((width.round() as u32).clamp(min_w, max_w));
+        }
+
+        if let Some(dpr) = dpr {
+            // Lower the DPR if the scaled render wouldn't fit.
+            let size = RenderRequestExtraSettings {
+                width: query.width,
+                height: query.height,
+                ..Default::default()
+            }
+            .get_size_for_mode(mode);
+
+            let max_dpr = (max_w as f32 / size.width as f32).min(max_h as f32 / size.height as f32);
+
+            query.dpr = Some(dpr.min(max_dpr).max(RenderRequestMode::MIN_DPR));
+        }
+    }
+}
+
+/// Creates a [`RenderRequest`] from its entry and options, applying the defaults and restrictions of the given host.
+pub(crate) fn create_render_request<S: RenderRequestValidator>(
+    state: &S,
+    host: Option<&str>,
+    mode: RenderRequestMode,
+    entry: RenderRequestEntry,
+    mut query: RenderRequestQueryParams,
+) -> Result<RenderRequest> {
+    let modes = state.modes();
+
+    query.apply_custom_mode_defaults(&modes);
+    state.apply_defaults(&mut query, host);
+    state.camera_limits().clamp(&mut query);
+
+    query.validate(mode, state.size_constraints(mode))?;
+
+    let excluded_features = query.get_excluded_features();
+
+    let model = query.get_model();
+
+    let extra_settings = Some(RenderRequestExtraSettings {
+        width: query.width,
+        height: query.height,
+
+        yaw: query.yaw,
+        pitch: query.pitch,
+        roll: query.roll,
+
+        arm_rotation: query.arms,
+        distance: query.distance,
+        dpr: query.dpr,
+        fov: query.fov,
+        projection: query.projection,
+        proportions: query.proportions,
+        deadmau5_ears: query.mau5,
+
+        parallax_offset: query.parallax_offset,
+        parallax_shadow: query.parallax_shadow,
+
+        x_pos: query.x_pos,
+        y_pos: query.y_pos,
+        z_pos: query.z_pos,
+
+        helmet: query.helmet,
+        chestplate: query.chestplate,
+        leggings: query.leggings,
+        boots: query.boots,
+
+        background: query.background,
+
+        format: query.format,
+        quality: query.quality,
+
+        custom_mode: query.custom_mode,
+
+        // Set along with the other settings of the profile, below.
+        watermark: None,
+    })
+    .filter(|s| !s.is_empty());
+
+    let mut request = RenderRequest::new_from_excluded_features(
+        mode,
+        entry,
+        model,
+        excluded_features,
+        extra_settings,
+    )
+    .with_modes(modes);
+
+    state.cleanup_request(&mut request, host);
+
+    Ok(request)
+}
+
+/// The host a request was made to, for the handlers creating render requests themselves (e.g. the live preview).
+#[cfg_attr(not(feature = "live_preview"), allow(dead_code))]
+pub struct RequestHost(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestHost {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Infallible> {
+        Ok(Self(get_request_host(&parts.uri, &parts.headers)))
+    }
+}
+
+/// Returns the host the request was made to, without the port.
+fn get_request_host(uri: &Uri, headers: &HeaderMap) -> Option<String> {
+    // HTTP/2 requests carry the host in the URI instead of the Host header.
+    uri.host().map(ToOwned::to_owned).or_else(|| {
+        headers
+            .get(HOST)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Authority>().ok())
+            .map(|authority| authority.host().to_owned())
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::{collections::HashMap, sync::Arc};
 
     use axum::{debug_handler, extract::State, routing::get, Router, body::Body};
     use enumset::{enum_set, EnumSet};
     use hyper::Request;
+    use nmsr_rendering::high_level::model::PlayerBodyProportions;
     use tokio::sync::mpsc::Sender;
     use tower::ServiceExt;
     use uuid::uuid;
@@ -162,13 +325,13 @@ mod tests {
     use crate::{
         model::request::{
             entry::{RenderRequestEntry, RenderRequestEntryModel},
-            RenderRequest, RenderRequestFeatures, RenderRequestMode,
+            RenderRequest, RenderRequestExtraSettings, RenderRequestFeatures, RenderRequestMode,
         },
         routes::RenderRequestValidator,
     };
 
     impl RenderRequestValidator for Sender<RenderRequest> {
-        fn validate_mode(&self, _mode: &RenderRequestMode) -> bool {
+        fn validate_mode(&self, _mode: &RenderRequestMode, _host: Option<&str>) -> bool {
             true
         }
     }
@@ -208,7 +371,8 @@ mod tests {
                     entry: entry.clone(),
                     model: None,
                     features: EnumSet::only(RenderRequestFeatures::UnProcessedSkin),
-                    extra_settings: None
+                    extra_settings: None,
+                    modes: Arc::default()
                 },
             ),
             (
@@ -218,7 +382,8 @@ mod tests {
                     entry: entry.clone(),
                     model: None,
                     features: EnumSet::only(RenderRequestFeatures::UnProcessedSkin),
-                    extra_settings: None
+                    extra_settings: None,
+                    modes: Arc::default()
                 },
             ),
             (
@@ -228,7 +393,8 @@ mod tests {
                     entry: entry.clone(),
                     model: Some(RenderRequestEntryModel::Alex),
                     features: EnumSet::only(RenderRequestFeatures::UnProcessedSkin),
-                    extra_settings: None
+                    extra_settings: None,
+                    modes: Arc::default()
                 },
             ),
             (
@@ -238,7 +404,98 @@ mod tests {
                     entry: entry.clone(),
                     model: None,
                     features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::BodyLayers | RenderRequestFeatures::HatLayer | RenderRequestFeatures::Cape | RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom | RenderRequestFeatures::ExtraSettings)),
-                    extra_settings: None
+                    extra_settings: None,
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/fullbody/ad4569f3-7576-4376-a7c7-8e8cfcd9b832?proportions=child",
+                RenderRequest {
+                    mode: RenderRequestMode::FullBody,
+                    entry: entry.clone(),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom)),
+                    extra_settings: Some(RenderRequestExtraSettings {
+                        proportions: Some(PlayerBodyProportions::Child),
+                        ..Default::default()
+                    }),
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/fullbody/ad4569f3-7576-4376-a7c7-8e8cfcd9b832?mau5=true",
+                RenderRequest {
+                    mode: RenderRequestMode::FullBody,
+                    entry: entry.clone(),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom)),
+                    extra_settings: Some(RenderRequestExtraSettings {
+                        deadmau5_ears: Some(true),
+                        ..Default::default()
+                    }),
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/fullbody/ad4569f3-7576-4376-a7c7-8e8cfcd9b832?distance=40",
+                RenderRequest {
+                    mode: RenderRequestMode::FullBody,
+                    entry: entry.clone(),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom)),
+                    extra_settings: Some(RenderRequestExtraSettings {
+                        distance: Some(30.0),
+                        ..Default::default()
+                    }),
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/face_parallax/ad4569f3-7576-4376-a7c7-8e8cfcd9b832?parallax_offset=1&parallax_shadow=0.25",
+                RenderRequest {
+                    mode: RenderRequestMode::FaceParallax,
+                    entry: entry.clone(),
+                    model: None,
+                    features: enum_set!(RenderRequestFeatures::HatLayer | RenderRequestFeatures::Shadow | RenderRequestFeatures::ExtraSettings),
+                    extra_settings: Some(RenderRequestExtraSettings {
+                        parallax_offset: Some(1.0),
+                        parallax_shadow: Some(0.25),
+                        ..Default::default()
+                    }),
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/headblock/ad4569f3-7576-4376-a7c7-8e8cfcd9b832",
+                RenderRequest {
+                    mode: RenderRequestMode::HeadBlock,
+                    entry: entry.clone(),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::BodyLayers | RenderRequestFeatures::Cape | RenderRequestFeatures::Shadow | RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom | RenderRequestFeatures::ExtraSettings)),
+                    extra_settings: None,
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/fullbody/Notch",
+                RenderRequest {
+                    mode: RenderRequestMode::FullBody,
+                    entry: RenderRequestEntry::MojangPlayerName("Notch".to_string()),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom | RenderRequestFeatures::ExtraSettings)),
+                    extra_settings: None,
+                    modes: Arc::default()
+                },
+            ),
+            (
+                "http://localhost:8621/fullbody/.Some_Gamertag",
+                RenderRequest {
+                    mode: RenderRequestMode::FullBody,
+                    entry: RenderRequestEntry::GeyserPlayerGamertag("Some_Gamertag".to_string()),
+                    model: None,
+                    features: EnumSet::all().difference(enum_set!(RenderRequestFeatures::UnProcessedSkin | RenderRequestFeatures::Custom | RenderRequestFeatures::ExtraSettings)),
+                    extra_settings: None,
+                    modes: Arc::default()
                 },
             ),
         ]);
@@ -0,0 +1,145 @@
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use axum::{extract::State, http::HeaderMap, Json};
+
+use super::{
+    admin::bearer_token,
+    extractors::create_render_request,
+    profile::{ProfileInformation, ProfileTextureInformation},
+    NMSRState, RenderRequestValidator,
+};
+use crate::{
+    config::GraphQlConfiguration,
+    error::{NMSRaaSError, RenderRequestError},
+    model::request::entry::RenderRequestEntry,
+    routes::query::RenderRequestQueryParams,
+};
+
+pub type NmsrSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn create_schema(config: GraphQlConfiguration) -> NmsrSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(config.max_depth)
+        .limit_complexity(config.max_complexity)
+        .finish()
+}
+
+/// The bearer token a GraphQL request was sent with, if any.
+struct AuthorizationToken(Option<String>);
+
+pub async fn graphql(
+    schema: NmsrSchema,
+    State(state): State<NMSRState>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let token = AuthorizationToken(bearer_token(&headers).map(ToOwned::to_owned));
+
+    Json(schema.execute(request.data(state).data(token)).await)
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The profile of a player, given their UUID or name.
+    async fn profile(&self, ctx: &Context<'_>, player: String) -> async_graphql::Result<Profile> {
+        let state = ctx.data::<NMSRState>()?;
+
+        let entry = RenderRequestEntry::try_from(player).map_err(NMSRaaSError::from)?;
+        let profile = ProfileInformation::resolve(state, &entry).await?;
+
+        Ok(Profile(profile))
+    }
+}
+
+pub struct Profile(ProfileInformation);
+
+#[Object]
+impl Profile {
+    async fn uuid(&self) -> String {
+        self.0.uuid.to_string()
+    }
+
+    /// Either "classic" or "slim".
+    async fn model(&self) -> String {
+        self.0.model.to_string()
+    }
+
+    async fn skin(&self) -> Option<Texture> {
+        self.0.skin.as_ref().map(Texture::from)
+    }
+
+    async fn cape(&self) -> Option<Texture> {
+        self.0.cape.as_ref().map(Texture::from)
+    }
+
+    /// The name of the skin server the profile was found on.
+    async fn server(&self) -> &str {
+        &self.0.server
+    }
+
+    /// The URLs to render this player in the given modes, with the same options as the HTTP API given as
+    /// a query string (e.g. "yaw=20&nolayers"). The options are validated the same way a render would be.
+    /// The URLs are signed if this instance requires render URLs to be signed, which requires the signing token.
+    async fn renders(
+        &self,
+        ctx: &Context<'_>,
+        modes: Vec<String>,
+        options: Option<String>,
+    ) -> async_graphql::Result<Vec<Render>> {
+        let state = ctx.data::<NMSRState>()?;
+        let options = options.unwrap_or_default();
+
+        // Otherwise, anyone could get signed URLs for anything from us.
+        let AuthorizationToken(token) = ctx.data::<AuthorizationToken>()?;
+        state.authorize_url_signing(token.as_deref())?;
+
+        modes
+            .into_iter()
+            .map(|name| {
+                let (mode, custom_mode) = state.modes().parse_name(&name)
+                    .filter(|(mode, _)| state.validate_mode(mode, None))
+                    .ok_or_else(|| RenderRequestError::InvalidRenderMode(name.clone()))
+                    .map_err(NMSRaaSError::from)?;
+
+                let mut query = RenderRequestQueryParams::from_query_string(&options)?;
+                query.custom_mode = custom_mode;
+
+                create_render_request(
+                    state,
+                    None,
+                    mode,
+                    RenderRequestEntry::MojangPlayerUuid(self.0.uuid),
+                    query,
+                )?;
+
+                let path = format!("/{name}/{uuid}", uuid = self.0.uuid.simple());
+                let url = state.sign_url(&path, Some(options.as_str()).filter(|o| !o.is_empty()));
+
+                Ok(Render { mode: name, url })
+            })
+            .collect()
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Texture {
+    hash: String,
+    url: String,
+}
+
+impl From<&ProfileTextureInformation> for Texture {
+    fn from(texture: &ProfileTextureInformation) -> Self {
+        Self {
+            hash: texture.hash.clone(),
+            url: texture.url.clone(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Render {
+    mode: String,
+    /// The path of the render, relative to this instance.
+    url: String,
+}
@@ -0,0 +1,237 @@
+use std::{net::SocketAddr, pin::Pin};
+
+use hyper::StatusCode;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{
+    service::{interceptor::InterceptedService, Interceptor},
+    Code, Request, Response, Status,
+};
+use tracing::{error, info, instrument};
+
+use super::{
+    extractors::create_render_request,
+    profile::{ProfileInformation, ProfileTextureInformation},
+    render::render_image,
+    NMSRState, RenderRequestValidator,
+};
+use crate::{
+    config::GrpcConfiguration,
+    error::{NMSRaaSError, RenderRequestError, Result},
+    model::request::{entry::RenderRequestEntry, RenderRequest},
+    routes::query::RenderRequestQueryParams,
+    utils::render_scheduler::RenderClass,
+};
+
+#[allow(clippy::pedantic, clippy::nursery)]
+pub mod proto {
+    tonic::include_proto!("nmsr");
+}
+
+use proto::{
+    batch_render_response,
+    renderer_server::{Renderer, RendererServer},
+    BatchRenderResponse, ProfileResponse, ProfileTexture, RenderPlayerRequest,
+    RenderPlayersRequest, RenderResponse, RenderSkinRequest, ResolveProfileRequest,
+};
+
+impl From<NMSRaaSError> for Status {
+    fn from(error: NMSRaaSError) -> Self {
+        let code = match error.status_code() {
+            StatusCode::BAD_REQUEST => Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::PAYLOAD_TOO_LARGE => {
+                Code::ResourceExhausted
+            }
+            StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            _ => Code::Internal,
+        };
+
+        Self::new(code, error.to_string())
+    }
+}
+
+/// Starts serving the gRPC rendering interface in the background, if it's configured.
+pub fn spawn_server(config: Option<&GrpcConfiguration>, state: &NMSRState) -> anyhow::Result<()> {
+    let Some(config) = config else {
+        return Ok(());
+    };
+
+    let addr: SocketAddr = format!("{}:{}", config.address, config.port).parse()?;
+    let server =
+        tonic::transport::Server::builder().add_service(GrpcRenderer::server(state.clone()));
+
+    info!("Listening for gRPC on {}", &addr);
+
+    tokio::spawn(async move {
+        if let Err(err) = server.serve(addr).await {
+            error!("gRPC server failed: {err}");
+        }
+    });
+
+    Ok(())
+}
+
+/// The gRPC rendering interface, for internal callers that would rather not deal with HTTP.
+#[derive(Clone)]
+pub struct GrpcRenderer {
+    state: NMSRState,
+}
+
+impl GrpcRenderer {
+    pub fn server(
+        state: NMSRState,
+    ) -> InterceptedService<RendererServer<Self>, SigningTokenInterceptor> {
+        let interceptor = SigningTokenInterceptor(state.clone());
+
+        RendererServer::with_interceptor(Self { state }, interceptor)
+    }
+
+    /// Creates a render request the same way the HTTP API would, with the options given as a query string.
+    fn create_request(
+        &self,
+        mode: &str,
+        entry: RenderRequestEntry,
+        options: &str,
+    ) -> Result<RenderRequest> {
+        // Exported models aren't images, so they're only available through the HTTP API.
+        let (mode, custom_mode) = self.state.modes().parse_name(mode)
+            .filter(|(mode, _)| !mode.is_blockbench_export())
+            .filter(|(mode, _)| self.state.validate_mode(mode, None))
+            .ok_or_else(|| RenderRequestError::InvalidRenderMode(mode.to_owned()))?;
+
+        let mut query = RenderRequestQueryParams::from_query_string(options)?;
+        query.custom_mode = custom_mode;
+
+        create_render_request(&self.state, None, mode, entry, query)
+    }
+
+    async fn render(&self, request: RenderRequest, class: RenderClass) -> Result<Vec<u8>> {
+        let resolved = self.state.resolver.resolve(&request).await?;
+
+        render_image(&request, &self.state, resolved, class).await
+    }
+
+    async fn render_player_request(
+        &self,
+        request: RenderPlayerRequest,
+        class: RenderClass,
+    ) -> Result<Vec<u8>> {
+        let entry = RenderRequestEntry::try_from(request.player)?;
+        let request = self.create_request(&request.mode, entry, &request.options)?;
+
+        self.render(request, class).await
+    }
+}
+
+/// Rejects calls without the URL signing token while render URLs need to be signed, since gRPC calls aren't signed.
+#[derive(Clone)]
+pub struct SigningTokenInterceptor(NMSRState);
+
+impl Interceptor for SigningTokenInterceptor {
+    fn call(&mut self, request: Request<()>) -> std::result::Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        self.0.authorize_url_signing(token)?;
+
+        Ok(request)
+    }
+}
+
+#[tonic::async_trait]
+impl Renderer for GrpcRenderer {
+    type RenderPlayersStream =
+        Pin<Box<dyn Stream<Item = std::result::Result<BatchRenderResponse, Status>> + Send>>;
+
+    #[instrument(skip(self))]
+    async fn render_player(
+        &self,
+        request: Request<RenderPlayerRequest>,
+    ) -> std::result::Result<Response<RenderResponse>, Status> {
+        let image = self
+            .render_player_request(request.into_inner(), RenderClass::Single)
+            .await?;
+
+        Ok(Response::new(RenderResponse { image }))
+    }
+
+    #[instrument(skip(self))]
+    async fn render_players(
+        &self,
+        request: Request<RenderPlayersRequest>,
+    ) -> std::result::Result<Response<Self::RenderPlayersStream>, Status> {
+        let requests = request.into_inner().requests;
+        let (sender, receiver) = tokio::sync::mpsc::channel(requests.len().max(1));
+
+        let renderer = self.clone();
+        tokio::spawn(async move {
+            for (index, request) in (0..).zip(requests) {
+                // Stop rendering as soon as the caller is gone, even if we're still waiting for a render slot.
+                let render = tokio::select! {
+                    render = renderer.render_player_request(request, RenderClass::Batch) => render,
+                    () = sender.closed() => break,
+                };
+
+                let result = match render {
+                    Ok(image) => batch_render_response::Result::Image(image),
+                    Err(err) => batch_render_response::Result::Error(err.to_string()),
+                };
+
+                let response = BatchRenderResponse {
+                    index,
+                    result: Some(result),
+                };
+
+                if sender.send(Ok(response)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn render_skin(
+        &self,
+        request: Request<RenderSkinRequest>,
+    ) -> std::result::Result<Response<RenderResponse>, Status> {
+        let request = request.into_inner();
+
+        let entry = RenderRequestEntry::try_from(request.skin).map_err(NMSRaaSError::from)?;
+        let request = self.create_request(&request.mode, entry, &request.options)?;
+
+        let image = self.render(request, RenderClass::Single).await?;
+
+        Ok(Response::new(RenderResponse { image }))
+    }
+
+    #[instrument(skip(self))]
+    async fn resolve_profile(
+        &self,
+        request: Request<ResolveProfileRequest>,
+    ) -> std::result::Result<Response<ProfileResponse>, Status> {
+        let entry = RenderRequestEntry::try_from(request.into_inner().player)
+            .map_err(NMSRaaSError::from)?;
+
+        let profile = ProfileInformation::resolve(&self.state, &entry).await?;
+
+        let texture = |texture: ProfileTextureInformation| ProfileTexture {
+            hash: texture.hash,
+            url: texture.url,
+        };
+
+        Ok(Response::new(ProfileResponse {
+            uuid: profile.uuid.to_string(),
+            model: profile.model.to_string(),
+            skin: profile.skin.map(texture),
+            cape: profile.cape.map(texture),
+            server: profile.server,
+        }))
+    }
+}
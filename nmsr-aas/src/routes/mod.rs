@@ -1,68 +1,227 @@
+pub mod admin;
 pub mod bbmodel_export;
+pub mod composite;
+pub mod embed;
 pub mod extractors;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "live_preview")]
+pub mod preview;
+pub mod profile;
 pub mod query;
 mod render;
+mod render_face_parallax;
 mod render_model;
 mod render_skin;
+pub mod version;
+mod warmup;
 use crate::{
-    config::{FeaturesConfiguration, ModelCacheConfiguration, NmsrConfiguration},
-    error::Result,
+    config::{
+        AdminConfiguration, AvifConfiguration, CameraLimitsConfiguration,
+        DeterminismConfiguration, FeaturesConfiguration, ModelCacheConfiguration,
+        NmsrConfiguration, ProfileConfiguration, RenderingConfiguration,
+    },
+    error::{NMSRaaSError, RenderRequestError, Result},
     model::{
         armor::manager::VanillaMinecraftArmorManager,
         request::{
-            cache::ModelCache, entry::RenderRequestEntry, RenderRequest, RenderRequestFeatures,
-            RenderRequestMode,
+            background::{BackgroundImages, Watermarks}, cache::ModelCache, entry::RenderRequestEntry, format::RenderRequestOutputFormat,
+            render_cache::RenderCache, CustomModes, ModeOverrides, RenderModes, RenderRequest, RenderRequestFeatures, RenderRequestMode,
+        },
+        resolver::{
+            access_list::AccessLists, fallback::FallbackSkin, mojang::client::MojangClient,
+            game_profile::GameProfileCache, player_name::PlayerNameCache, texture_hash::TextureHashIndex,
+            RenderRequestResolver,
         },
-        resolver::{mojang::client::MojangClient, RenderRequestResolver},
+    },
+    routes::query::RenderRequestQueryParams,
+    utils::{
+        access_log::{AccessLog, AccessLogDetails, AccessLogTiming},
+        client_ip::TrustedProxies,
+        coalescing::RequestCoalescer,
+        png::create_png_with_text,
+        rate_limit::ClientRateLimiter,
+        reloadable::Reloadable,
+        render_scheduler::{RenderClass, RenderPermit, RenderScheduler},
+        serving_mode::ServingMode,
+        stats::UsageStatistics,
+        url_signing::UrlSigner,
     },
 };
+use axum::body::Bytes;
 use deadpool::managed::Object;
 use enumset::EnumSet;
 use image::RgbaImage;
-#[cfg(feature = "ears")]
+use nmsr_rendering::errors::NMSRRenderingError;
 use nmsr_rendering::high_level::camera::Camera;
 use nmsr_rendering::high_level::pipeline::{
-    pools::SceneContextPoolManager, Backends, Features, GraphicsContext, GraphicsContextDescriptor,
-    GraphicsContextPools,
+    pools::SceneContextPoolManager, post_processing::PostProcessingEffect, scene::Scene, Backends,
+    Features, GraphicsContext, GraphicsContextDescriptor, GraphicsContextPools,
 };
+use nmsr_rendering::high_level::skin;
+use nmsr_rendering_blockbench_model_generator_experiment::generator::ModelGenerationLimits;
 pub use render::{render, render_post_warning, render_get_warning};
-use std::{borrow::Cow, hint::black_box, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow, future::Future, hint::black_box, sync::Arc, time::Duration,
+};
 use strum::IntoEnumIterator;
-use tracing::{debug_span, info, info_span, instrument, Instrument};
+use tokio::{sync::oneshot, time::Instant};
+use tracing::{debug_span, error, info, info_span, instrument, warn, Instrument};
 use uuid::uuid;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// A render shared between identical requests (or the error that failed it), along with the details recorded in
+/// the access log while rendering it.
+pub(crate) type CoalescedRender = (
+    std::result::Result<Bytes, Arc<NMSRaaSError>>,
+    Option<AccessLogDetails>,
+);
 
 pub trait RenderRequestValidator {
-    fn validate_mode(&self, mode: &RenderRequestMode) -> bool;
+    fn validate_mode(&self, mode: &RenderRequestMode, host: Option<&str>) -> bool;
+
+    #[allow(unused_variables)]
+    fn apply_defaults(&self, query: &mut RenderRequestQueryParams, host: Option<&str>) {}
+
+    /// The size constraints of a render in the given mode, as `[min_w, min_h, max_w, max_h]`.
+    fn size_constraints(&self, mode: RenderRequestMode) -> [u32; 4] {
+        mode.size_constraints()
+    }
+
+    /// The limits the camera settings of a request are clamped to.
+    fn camera_limits(&self) -> CameraLimitsConfiguration {
+        CameraLimitsConfiguration::default()
+    }
+
+    /// The modes as set in the configuration, which requests are rendered with.
+    fn modes(&self) -> Arc<RenderModes> {
+        Arc::default()
+    }
 
     #[allow(unused_variables)]
-    fn cleanup_request(&self, request: &mut RenderRequest) {}
+    fn cleanup_request(&self, request: &mut RenderRequest, host: Option<&str>) {}
 }
 
 #[derive(Clone)]
 pub struct NMSRState {
     pub resolver: Arc<RenderRequestResolver>,
     pub armor_manager: Arc<VanillaMinecraftArmorManager>,
-    pub graphics_context: Arc<GraphicsContext>,
-    pools: Arc<GraphicsContextPools>,
-    cache_config: ModelCacheConfiguration,
+    graphics_context: Option<Arc<GraphicsContext>>,
+    pools: Option<Arc<GraphicsContextPools>>,
+    pub(crate) serving_mode: ServingMode,
+    render_scheduler: Option<Arc<RenderScheduler>>,
+    render_timeout: Option<Duration>,
+    /// The post-processing effects applied to every render made on the graphics adapter.
+    post_processing_effects: Vec<PostProcessingEffect>,
+    determinism: Option<DeterminismConfiguration>,
+    render_cache: Option<Arc<RenderCache>>,
+    /// The renders in progress, which identical requests made in the meantime wait for instead of rendering again.
+    pub(crate) render_coalescer: Arc<RequestCoalescer<String, CoalescedRender>>,
+    cache_config: Reloadable<ModelCacheConfiguration>,
     features_config: FeaturesConfiguration,
+    export_limits: ModelGenerationLimits,
+    camera_limits: Reloadable<CameraLimitsConfiguration>,
+    max_render_size: (Option<u32>, Option<u32>),
+    admin_config: Option<AdminConfiguration>,
+    /// The profiles by name, in the order of their names.
+    profiles: Reloadable<Vec<(String, ProfileConfiguration)>>,
+    watermarks: Reloadable<Watermarks>,
+    modes: Reloadable<RenderModes>,
+    pub(crate) rate_limiter: Reloadable<Option<ClientRateLimiter>>,
+    pub(crate) url_signer: Reloadable<Option<UrlSigner>>,
+    pub(crate) trusted_proxies: Reloadable<TrustedProxies>,
+    pub(crate) access_log: Reloadable<bool>,
+    pub(crate) statistics: Arc<UsageStatistics>,
+    backgrounds: Arc<BackgroundImages>,
+    avif: AvifConfiguration,
 }
 
 impl RenderRequestValidator for NMSRState {
-    fn validate_mode(&self, mode: &RenderRequestMode) -> bool {
-        !self.features_config.disabled_modes.contains(mode)
+    fn validate_mode(&self, mode: &RenderRequestMode, host: Option<&str>) -> bool {
+        let disabled_for_profile = self
+            .get_profile(host)
+            .is_some_and(|(_, profile)| profile.disabled_modes.contains(mode));
+
+        !self.features_config.disabled_modes.contains(mode) && !disabled_for_profile
+    }
+
+    fn apply_defaults(&self, query: &mut RenderRequestQueryParams, host: Option<&str>) {
+        let Some((_, ProfileConfiguration { defaults, .. })) = self.get_profile(host) else {
+            return;
+        };
+
+        query.yaw = query.yaw.or(defaults.yaw);
+        query.pitch = query.pitch.or(defaults.pitch);
+        query.roll = query.roll.or(defaults.roll);
+
+        // Only apply the default size if the request didn't ask for one, they'd conflict otherwise.
+        if query.width.is_none() && query.height.is_none() {
+            query.width = defaults.width;
+            query.height = defaults.height;
+        }
+
+        query.arms = query.arms.or(defaults.arms);
+        query.distance = query.distance.or(defaults.distance);
+        query.background = query.background.take().or(defaults.background);
     }
 
-    fn cleanup_request(&self, request: &mut RenderRequest) {
+    fn size_constraints(&self, mode: RenderRequestMode) -> [u32; 4] {
+        let [min_w, min_h, max_w, max_h] = mode.size_constraints();
+        let (max_render_width, max_render_height) = self.max_render_size;
+
+        let max_w = max_render_width.unwrap_or(max_w);
+        let max_h = max_render_height.unwrap_or(max_h);
+
+        if mode.is_square() {
+            // Square modes have the same constraints for both sides.
+            let max = max_w.min(max_h);
+            return [min_w, min_h, max, max];
+        }
+
+        [min_w, min_h, max_w, max_h]
+    }
+
+    fn camera_limits(&self) -> CameraLimitsConfiguration {
+        *self.camera_limits.get()
+    }
+
+    fn modes(&self) -> Arc<RenderModes> {
+        self.modes.get()
+    }
+
+    fn cleanup_request(&self, request: &mut RenderRequest, host: Option<&str>) {
         let mut disabled_features: EnumSet<RenderRequestFeatures> = EnumSet::new();
         for feature in self.features_config.disabled_features.iter() {
             disabled_features.insert(*feature);
         }
 
+        let profile = self.get_profile(host);
+
+        if let Some((_, profile)) = &profile {
+            for feature in &profile.disabled_features {
+                disabled_features.insert(*feature);
+            }
+
+            if let Some(allowed_features) = &profile.allowed_features {
+                let allowed_features: EnumSet<_> = allowed_features.iter().copied().collect();
+                disabled_features |= allowed_features.complement();
+            }
+        }
+
         if disabled_features.contains(RenderRequestFeatures::ExtraSettings) {
             request.extra_settings = None;
         }
 
+        // The watermark is part of the request, that way renders with and without it are cached separately.
+        if let Some((name, _)) = profile.filter(|(_, profile)| profile.watermark.is_some()) {
+            request
+                .extra_settings
+                .get_or_insert_with(Default::default)
+                .watermark = Some(name);
+        }
+
         request.features.remove_all(disabled_features);
     }
 }
@@ -73,45 +232,513 @@ impl NMSRState {
     );
 
     pub async fn new(config: &NmsrConfiguration) -> Result<Self> {
-        let mojang_client = MojangClient::new(Arc::new(config.mojank.clone()))?;
+        let statistics = Arc::new(UsageStatistics::new());
+        let armor_manager =
+            VanillaMinecraftArmorManager::new("cache".into(), statistics.clone()).await?;
+
+        Self::with_armor_manager(config, armor_manager, statistics).await
+    }
+
+    /// Creates the state without downloading anything, see [`VanillaMinecraftArmorManager::new_offline`].
+    #[cfg(test)]
+    pub(crate) async fn new_offline(config: &NmsrConfiguration) -> Result<Self> {
+        let statistics = Arc::new(UsageStatistics::new());
+        let armor_manager =
+            VanillaMinecraftArmorManager::new_offline("cache".as_ref(), statistics.clone());
+
+        Self::with_armor_manager(config, armor_manager, statistics).await
+    }
+
+    /// Creates the state with the given armor manager, which tests create without downloading the armor textures.
+    async fn with_armor_manager(
+        config: &NmsrConfiguration,
+        armor_manager: VanillaMinecraftArmorManager,
+        statistics: Arc<UsageStatistics>,
+    ) -> Result<Self> {
+        let mojang_client = MojangClient::new(config.mojank.clone(), statistics.clone())?;
         let cache_config = config.caching.clone();
         let model_cache = ModelCache::new("cache".into(), cache_config).await?;
 
         let rendering_config = config.rendering.clone();
 
-        let resolver = RenderRequestResolver::new(model_cache, Arc::new(mojang_client));
+        let player_name_cache = PlayerNameCache::new(config.caching.player_name_cache_duration);
+        let game_profile_cache = GameProfileCache::new(config.caching.resolve_cache_duration);
+        let texture_hashes = TextureHashIndex::new(config.caching.resolve_cache_duration);
+
+        let fallback_skin = match config.fallback_skin.as_ref() {
+            Some(fallback_skin) => Some(FallbackSkin::load(fallback_skin).await?),
+            None => None,
+        };
+
+        let resolver = RenderRequestResolver::new(
+            model_cache,
+            player_name_cache,
+            game_profile_cache,
+            texture_hashes,
+            Arc::new(mojang_client),
+            fallback_skin,
+            rendering_config.as_ref().and_then(|c| c.shoulder_buddies),
+            AccessLists::new(&config.access_lists.clone().unwrap_or_default()),
+            statistics.clone(),
+        );
 
         let graphics_context = GraphicsContext::new(GraphicsContextDescriptor {
-            backends: Some(Backends::all()),
+            backends: Some(
+                rendering_config
+                    .as_ref()
+                    .map_or_else(Backends::all, |c| c.adapter.backends()),
+            ),
+            adapter: rendering_config
+                .as_ref()
+                .map(|c| c.adapter.selector())
+                .unwrap_or_default(),
             surface_provider: Box::new(|_| None),
             default_size: (0, 0), // can be zero since we don't provide any surface
             texture_format: None,
+            color_space: rendering_config
+                .as_ref()
+                .and_then(|c| c.color_space)
+                .map(Into::into),
             features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
             limits: None,
             blend_state: None,
             sample_count: rendering_config.as_ref().map(|c| c.sample_count),
             use_smaa: rendering_config.as_ref().map(|c| c.use_smaa),
         })
-        .await?;
+        .await;
+
+        let (graphics_context, serving_mode) =
+            Self::select_serving_mode(graphics_context, rendering_config.as_ref())?;
 
-        let graphics_context = Arc::new(graphics_context);
+        let pools = graphics_context
+            .clone()
+            .map(GraphicsContextPools::new)
+            .transpose()?;
 
-        let pools = GraphicsContextPools::new(graphics_context.clone())?;
+        let render_cache = config
+            .caching
+            .s3
+            .as_ref()
+            .map(|s3| RenderCache::new(config.caching.clone(), s3))
+            .transpose()?;
 
-        let armor_manager = VanillaMinecraftArmorManager::new("cache".into()).await?;
 
         Ok(Self {
             resolver: Arc::new(resolver),
             graphics_context,
-            pools: Arc::new(pools),
-            cache_config: config.caching.clone(),
+            pools: pools.map(Arc::new),
+            serving_mode,
+            render_scheduler: Self::create_render_scheduler(config),
+            render_timeout: rendering_config.as_ref().and_then(|c| c.render_timeout),
+            post_processing_effects: Self::create_post_processing_effects(
+                rendering_config.as_ref(),
+            ),
+            determinism: config.determinism,
+            render_cache: render_cache.map(Arc::new),
+            render_coalescer: Arc::default(),
+            cache_config: Reloadable::new(config.caching.clone()),
             armor_manager: Arc::new(armor_manager),
             features_config: config.features.clone().unwrap_or_default(),
+            export_limits: config
+                .export
+                .map(|export| ModelGenerationLimits {
+                    max_elements: export.max_elements,
+                    max_texture_bytes: export.max_texture_bytes,
+                    max_wall_time: export.max_wall_time,
+                })
+                .unwrap_or_default(),
+            camera_limits: Reloadable::new(Self::create_camera_limits(config)),
+            max_render_size: rendering_config
+                .map(|c| (c.max_render_width, c.max_render_height))
+                .unwrap_or_default(),
+            admin_config: config.admin.clone(),
+            profiles: Reloadable::new(Self::create_profiles(config)),
+            watermarks: Reloadable::new(Self::create_watermarks(config)?),
+            modes: Reloadable::new(Self::create_modes(config)?),
+            rate_limiter: Reloadable::new(Self::create_rate_limiter(config)?),
+            url_signer: Reloadable::new(Self::create_url_signer(config)),
+            trusted_proxies: Reloadable::new(Self::create_trusted_proxies(config)),
+            access_log: Reloadable::new(config.server.access_log),
+            statistics,
+            backgrounds: Arc::new(BackgroundImages::load(config.backgrounds.as_ref())?),
+            avif: config.avif.unwrap_or_default(),
         })
     }
 
+    /// Picks the serving mode depending on whether the renderer could be initialized, see [`ServingMode::select`].
+    fn select_serving_mode(
+        graphics_context: std::result::Result<GraphicsContext, NMSRRenderingError>,
+        rendering_config: Option<&RenderingConfiguration>,
+    ) -> Result<(Option<Arc<GraphicsContext>>, ServingMode)> {
+        let texture_only_fallback = rendering_config.is_some_and(|c| c.texture_only_fallback);
+        let software_fallback = rendering_config.is_some_and(|c| c.software_fallback);
+
+        let serving_mode = ServingMode::select(
+            graphics_context.is_ok(),
+            software_fallback,
+            texture_only_fallback,
+        );
+
+        match (graphics_context, serving_mode) {
+            (Ok(graphics_context), _) => Ok((Some(Arc::new(graphics_context)), ServingMode::Full)),
+            (Err(err), Some(ServingMode::Software)) => {
+                warn!("Unable to initialize the renderer, rendering on the CPU instead: {err}");
+                Ok((None, ServingMode::Software))
+            }
+            (Err(err), Some(serving_mode)) => {
+                warn!("Unable to initialize the renderer, only serving modes that don't need it: {err}");
+                Ok((None, serving_mode))
+            }
+            (Err(err), None) => {
+                error!("Unable to initialize the renderer. Enable rendering.software_fallback or rendering.texture_only_fallback to start without it.");
+                Err(err.into())
+            }
+        }
+    }
+
+    fn create_camera_limits(config: &NmsrConfiguration) -> CameraLimitsConfiguration {
+        config
+            .rendering
+            .as_ref()
+            .map(|c| c.camera)
+            .unwrap_or_default()
+    }
+
+    fn create_profiles(config: &NmsrConfiguration) -> Vec<(String, ProfileConfiguration)> {
+        config
+            .profiles
+            .clone()
+            .map(|profiles| profiles.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn create_watermarks(config: &NmsrConfiguration) -> Result<Watermarks> {
+        Watermarks::load(config.profiles.iter().flatten())
+    }
+
+    fn create_modes(config: &NmsrConfiguration) -> Result<RenderModes> {
+        Ok(RenderModes::new(
+            Self::create_mode_overrides(config)?,
+            Self::create_custom_modes(config),
+        ))
+    }
+
+    fn create_mode_overrides(config: &NmsrConfiguration) -> Result<ModeOverrides> {
+        config
+            .modes
+            .iter()
+            .flatten()
+            .map(|(mode, overrides)| {
+                let mode = RenderRequestMode::try_from(mode.as_str())
+                    .map_err(|_| RenderRequestError::InvalidRenderMode(mode.clone()))?;
+
+                Ok((mode, *overrides))
+            })
+            .collect()
+    }
+
+    fn create_custom_modes(config: &NmsrConfiguration) -> CustomModes {
+        config.custom_modes.clone().unwrap_or_default()
+    }
+
+    fn create_url_signer(config: &NmsrConfiguration) -> Option<UrlSigner> {
+        config.server.url_signing.as_ref().map(UrlSigner::new)
+    }
+
+    fn create_trusted_proxies(config: &NmsrConfiguration) -> TrustedProxies {
+        TrustedProxies::new(config.server.trusted_proxies.as_ref())
+    }
+
+    fn create_rate_limiter(config: &NmsrConfiguration) -> Result<Option<ClientRateLimiter>> {
+        let api_keys = config
+            .server
+            .api_keys
+            .as_ref()
+            .map(ClientRateLimiter::load_api_keys)
+            .transpose()?
+            .unwrap_or_default();
+
+        let rate_limiter = ClientRateLimiter::new(config.server.rate_limit, api_keys);
+
+        Ok(Some(rate_limiter).filter(ClientRateLimiter::is_enabled))
+    }
+
+    /// Applies the settings that can be changed without restarting, e.g. when the configuration file is reloaded.
+    ///
+    /// These are the cache durations, the rate limits and API keys, the URL signing secret, the trusted proxies, the
+    /// skin servers, the access lists, the camera limits, the profiles, the mode overrides and the custom modes. Everything else (e.g. the rendering settings) is only applied on startup.
+    pub fn reload(&self, config: &NmsrConfiguration) -> Result<()> {
+        // Load the API keys and the modes first, that way nothing is applied if they're invalid.
+        let mut rate_limiter = Self::create_rate_limiter(config)?;
+        let modes = Self::create_modes(config)?;
+        let watermarks = Self::create_watermarks(config)?;
+
+        self.resolver.reload(
+            &config.caching,
+            config.mojank.clone(),
+            &config.access_lists.clone().unwrap_or_default(),
+        );
+
+        if let Some(render_cache) = &self.render_cache {
+            render_cache.set_cache_config(config.caching.clone());
+        }
+
+        self.cache_config.set(config.caching.clone());
+        self.camera_limits.set(Self::create_camera_limits(config));
+        self.profiles.set(Self::create_profiles(config));
+        self.watermarks.set(watermarks);
+        self.modes.set(modes);
+
+        if let (Some(rate_limiter), Some(previous)) =
+            (&mut rate_limiter, self.rate_limiter.get().as_ref())
+        {
+            rate_limiter.keep_state_of(previous);
+        }
+
+        self.rate_limiter.set(rate_limiter);
+        self.url_signer.set(Self::create_url_signer(config));
+        self.trusted_proxies.set(Self::create_trusted_proxies(config));
+        self.access_log.set(config.server.access_log);
+
+        Ok(())
+    }
+
+    /// Checks that a client which can render anything (or get signed URLs for anything) may do so.
+    /// Everyone may while render URLs don't need to be signed, otherwise they need to send the signing token.
+    #[cfg_attr(not(any(feature = "graphql", feature = "grpc")), allow(dead_code))]
+    pub(crate) fn authorize_url_signing(&self, token: Option<&str>) -> Result<()> {
+        if let Some(url_signer) = self.url_signer.get().as_ref() {
+            return url_signer.authorize(token);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the URL made of the given path and query string, signed if render URLs need to be signed.
+    pub(crate) fn sign_url(&self, path: &str, query: Option<&str>) -> String {
+        if let Some(url_signer) = self.url_signer.get().as_ref() {
+            return url_signer.sign(path, query);
+        }
+
+        query.map_or_else(|| path.to_owned(), |query| format!("{path}?{query}"))
+    }
+
+    /// Composites a render on top of the background the request asked for, if any.
+    pub(crate) fn apply_background(
+        &self,
+        request: &RenderRequest,
+        size: (u32, u32),
+        pixels: &mut [u8],
+    ) -> Result<()> {
+        let Some(background) = request.get_background() else {
+            return Ok(());
+        };
+
+        self.backgrounds.composite(background, size, pixels)
+    }
+
+    /// Composites the watermark of the profile the request was made for over a render, if it has one.
+    pub(crate) fn apply_watermark(&self, request: &RenderRequest, size: (u32, u32), pixels: &mut [u8]) {
+        if let Some(profile) = request.get_watermark() {
+            self.watermarks.get().composite(profile, size, pixels);
+        }
+    }
+
+    /// Describes how a render was made (mode, skin and camera), for the metadata of PNG renders.
+    pub(crate) fn describe_render(
+        request: &RenderRequest,
+        skin: Option<&[u8]>,
+        camera: Option<&Camera>,
+    ) -> Vec<(&'static str, String)> {
+        let mut metadata = vec![
+            ("Software", format!("NMSRaaS {}", env!("CARGO_PKG_VERSION"))),
+            ("nmsr:mode", request.get_mode_name()),
+        ];
+
+        if let Some(skin) = skin {
+            metadata.push(("nmsr:skin_hash", format!("{:016x}", xxh3_64(skin))));
+        }
+
+        if let Some(camera) = camera {
+            metadata.push((
+                "nmsr:camera",
+                format!(
+                    "{:?} {:?} {:?}",
+                    camera.get_position_parameters(),
+                    camera.get_rotation(),
+                    camera.get_projection()
+                ),
+            ));
+        }
+
+        metadata
+    }
+
+    /// Encodes a render (as RGBA8 pixels) in the format the request asked for, along with the given metadata
+    /// for PNG renders. Slow formats are encoded on the blocking thread pool, that way they don't hold up
+    /// other requests.
+    pub(crate) async fn encode_render(
+        &self,
+        request: &RenderRequest,
+        size: (u32, u32),
+        pixels: Vec<u8>,
+        metadata: &[(&str, String)],
+    ) -> Result<Vec<u8>> {
+        let format = request.get_output_format();
+        let quality = request.get_quality();
+        let avif = self.avif;
+        let started = Instant::now();
+
+        let encoded = if format == RenderRequestOutputFormat::Png {
+            create_png_with_text(size, &pixels, metadata)
+        } else if format.is_slow() {
+            Self::spawn_blocking(move || format.encode(size, &pixels, quality, avif)).await?
+        } else {
+            format.encode(size, &pixels, quality, avif)
+        };
+
+        AccessLog::record_timing(AccessLogTiming::Encode, started.elapsed());
+
+        encoded
+    }
+
+    fn get_profile(&self, host: Option<&str>) -> Option<(String, ProfileConfiguration)> {
+        let host = host?;
+
+        self.profiles
+            .get()
+            .iter()
+            .find(|(_, profile)| profile.matches_host(host))
+            .cloned()
+    }
+
+    /// Returns the graphics context used for rendering, which is unavailable in software and texture-only mode.
+    pub(crate) fn graphics_context(&self) -> Result<&Arc<GraphicsContext>> {
+        self.graphics_context
+            .as_ref()
+            .ok_or(NMSRaaSError::RendererUnavailable(self.serving_mode))
+    }
+
     pub async fn create_scene_context(&self) -> Result<Object<SceneContextPoolManager>> {
-        Ok(self.pools.create_scene_context().await?)
+        let pools = self
+            .pools
+            .as_ref()
+            .ok_or(NMSRaaSError::RendererUnavailable(self.serving_mode))?;
+
+        let mut scene_context = pools.create_scene_context().await?;
+        scene_context.set_post_processing_effects(self.post_processing_effects.clone());
+
+        Ok(scene_context)
+    }
+
+    fn create_post_processing_effects(
+        rendering_config: Option<&RenderingConfiguration>,
+    ) -> Vec<PostProcessingEffect> {
+        // FXAA is only a fallback for when SMAA is off, there's no point in smoothing edges twice.
+        if rendering_config.is_some_and(|c| c.fxaa && !c.use_smaa) {
+            vec![PostProcessingEffect::Fxaa]
+        } else {
+            vec![]
+        }
+    }
+
+    fn create_render_scheduler(config: &NmsrConfiguration) -> Option<Arc<RenderScheduler>> {
+        let mut scheduler = config.scheduler;
+
+        // Only render one thing at a time when deterministic, that way renders can't affect each other.
+        if config.determinism.is_some() {
+            let mut deterministic = scheduler.unwrap_or_default();
+            deterministic.max_concurrent_renders = 1;
+
+            scheduler = Some(deterministic);
+        }
+
+        scheduler.map(|scheduler| Arc::new(RenderScheduler::new(scheduler)))
+    }
+
+    /// Waits for the render scheduler to allow a render of the given class, if renders are scheduled.
+    pub(crate) async fn acquire_render_permit(
+        &self,
+        class: RenderClass,
+    ) -> Result<Option<RenderPermit>> {
+        let Some(scheduler) = self.render_scheduler.as_ref() else {
+            return Ok(None);
+        };
+
+        Ok(Some(scheduler.acquire(class).await?))
+    }
+
+    /// Reads back a scene rendered at the given time, giving up on the GPU once the render timeout has passed.
+    pub(crate) async fn read_back_render(
+        &self,
+        scene: &Scene<Object<SceneContextPoolManager>>,
+        graphics_context: &GraphicsContext,
+        started: Instant,
+    ) -> Result<Vec<u8>> {
+        let Some(render_timeout) = self.render_timeout else {
+            return Ok(scene.copy_output_texture(graphics_context, true).await?);
+        };
+
+        let deadline = (started + render_timeout).into_std();
+
+        match scene
+            .copy_output_texture_until(graphics_context, true, deadline)
+            .await
+        {
+            Err(NMSRRenderingError::RenderTimedOut) => {
+                warn!("Render took longer than {render_timeout:?}, cancelling it");
+                Err(NMSRaaSError::RenderTimedOut)
+            }
+            render => Ok(render?),
+        }
+    }
+
+    /// Waits for a render started at the given time to finish, giving up once the render timeout has passed.
+    pub(crate) async fn with_render_timeout<T, E>(
+        &self,
+        started: Instant,
+        render: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T>
+    where
+        NMSRaaSError: From<E>,
+    {
+        let Some(render_timeout) = self.render_timeout else {
+            return Ok(render.await?);
+        };
+
+        let Ok(result) = tokio::time::timeout_at(started + render_timeout, render).await else {
+            warn!("Render took longer than {render_timeout:?}, cancelling it");
+            return Err(NMSRaaSError::RenderTimedOut);
+        };
+
+        Ok(result?)
+    }
+
+    /// Runs slow work for a request (e.g. rendering on the CPU) on the blocking thread pool.
+    ///
+    /// Blocking work can't be interrupted once it's started, so it's skipped instead if the request is dropped
+    /// (e.g. because the client disconnected) before a blocking thread is free to run it.
+    pub(crate) async fn spawn_blocking<T: Send + 'static>(
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T> {
+        let (sender, mut receiver) = oneshot::channel();
+
+        tokio::task::spawn_blocking(move || {
+            if !sender.is_closed() {
+                let _ = sender.send(work());
+            }
+        })
+        .await?;
+
+        Ok(receiver.try_recv().unwrap_or_else(|_| {
+            unreachable!("The work is only skipped once we stop waiting for it")
+        }))
+    }
+
+    /// Throws away a scene instead of giving its context back to the pool, e.g. because the GPU might still be
+    /// using it after its render timed out. The pool creates a fresh context in its place when needed.
+    pub(crate) fn discard_scene(scene: Scene<Object<SceneContextPoolManager>>) {
+        drop(Object::take(scene.into_scene_context()));
     }
 
     #[allow(unused_variables)]
@@ -120,7 +747,7 @@ impl NMSRState {
         skin_image: RgbaImage,
         features: EnumSet<RenderRequestFeatures>,
     ) -> Result<RgbaImage> {
-        let mut skin_image = ears_rs::utils::upgrade_skin_if_needed(skin_image);
+        let mut skin_image = skin::upgrade_legacy_skin(skin_image);
 
         #[cfg(feature = "ears")]
         {
@@ -171,30 +798,71 @@ impl NMSRState {
 
     #[instrument(skip(self))]
     pub(crate) async fn init(&self) -> Result<()> {
+        if let Some(graphics_context) = self.graphics_context.as_ref() {
+            let adapter = &graphics_context.adapter.get_info();
+            let samples = &graphics_context.multisampling_strategy;
+
+            info!(
+                "Initialized state with adapter {:?} and using {:?} multisampling strategy",
+                adapter, samples
+            );
+        }
+
+        info!("Serving in {} mode", self.serving_mode);
+
+        if let Some(determinism) = self.determinism {
+            info!(
+                "Running deterministically with seed {}, renders will run one at a time",
+                determinism.seed
+            );
+        }
+
         info!("Pre-loading our cache biases.");
         self.preload_cache_biases().await?;
 
-        info!("Pre-warming model renderer.");
-        self.prewarm_renderer().await?;
+        if self.serving_mode == ServingMode::Full {
+            info!("Pre-warming model renderer.");
+            self.prewarm_renderer().await?;
+        }
 
         info!("Starting cache clean-up task");
         self.start_cache_cleanup_task();
 
+        // The rate limiter can be enabled by reloading the configuration, so this task always runs.
+        info!("Starting rate limiter clean-up task");
+        self.start_rate_limiter_cleanup_task();
+
         Ok(())
     }
 
     fn start_cache_cleanup_task(&self) {
-        let mut interval = tokio::time::interval(self.cache_config.cleanup_interval);
-
+        let cache_config = self.cache_config.clone();
         let resolver = self.resolver.clone();
 
         tokio::task::spawn(async move {
             loop {
-                interval.tick().await;
-
                 if let Err(err) = Self::do_cache_clean_up(resolver.clone()).await {
                     tracing::error!("Error while cleaning up cache: {:?}", err);
                 }
+
+                // Read the interval every time, it may have changed since the configuration was reloaded.
+                tokio::time::sleep(cache_config.get().cleanup_interval).await;
+            }
+        });
+    }
+
+    fn start_rate_limiter_cleanup_task(&self) {
+        let rate_limiter = self.rate_limiter.clone();
+
+        let mut interval = tokio::time::interval(Duration::from_mins(1));
+
+        tokio::task::spawn(async move {
+            loop {
+                interval.tick().await;
+
+                if let Some(rate_limiter) = rate_limiter.get().as_ref() {
+                    rate_limiter.retain_recent();
+                }
             }
         });
     }
@@ -207,7 +875,7 @@ impl NMSRState {
 
     #[instrument(skip(self))]
     async fn preload_cache_biases(&self) -> Result<()> {
-        for entry in self.cache_config.cache_biases.keys() {
+        for entry in self.cache_config.get().cache_biases.keys() {
             let _guard = debug_span!("preload_cache_biases", entry = ?entry).entered();
 
             let request = RenderRequest::new_from_excluded_features(
@@ -236,7 +904,8 @@ impl NMSRState {
             None,
             EnumSet::EMPTY,
             None,
-        );
+        )
+        .with_modes(self.modes());
 
         let resolved = self.resolver.resolve(&request).await?;
 
@@ -249,7 +918,10 @@ impl NMSRState {
 
             let _ = black_box(
                 black_box(render_model::internal_render_model(
-                    &request, self, &resolved,
+                    &request,
+                    self,
+                    &resolved,
+                    RenderClass::Batch,
                 ))
                 .instrument(info_span!("prewarm_render", mode = ?mode))
                 .await?,
@@ -265,9 +937,21 @@ impl NMSRState {
             return "public, no-store".into();
         }
 
+        let cache_config = self.cache_config.get();
+        let request_duration =
+            cache_config.get_render_cache_duration(request, &cache_config.resolve_cache_duration);
+
+        Self::create_cache_control(request_duration)
+    }
+
+    pub fn get_cache_control_for_entry(&self, entry: &RenderRequestEntry) -> Cow<'_, str> {
         // Get the cache duration for this entry.
-        let entry_duration = self.cache_config.get_cache_duration(&request.entry);
+        let cache_config = self.cache_config.get();
+
+        Self::create_cache_control(cache_config.get_cache_duration(entry))
+    }
 
+    fn create_cache_control(entry_duration: &Duration) -> Cow<'static, str> {
         // Limit our max-age duration to 1 year if we have set this entry to be cached forever.
         let max_age_duration = entry_duration.min(&Self::ONE_YEAR_DURATION);
 
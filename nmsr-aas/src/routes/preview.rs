@@ -0,0 +1,359 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use deadpool::managed::Object;
+use nmsr_rendering::high_level::{
+    pipeline::{
+        pools::SceneContextPoolManager,
+        scene::{Scene, TextureRegion},
+    },
+    types::PlayerPartTextureType,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, instrument};
+
+use super::{
+    extractors::{create_render_request, RequestHost},
+    query::RenderRequestQueryParams,
+    render_model::{create_part_context, load_image, load_textures},
+    NMSRState, RenderRequestValidator,
+};
+#[cfg(feature = "ears")]
+use crate::model::request::RenderRequestFeatures;
+use crate::{
+    config::LivePreviewConfiguration,
+    error::{NMSRaaSError, RenderRequestError, Result},
+    model::{
+        request::{entry::RenderRequestEntry, RenderRequest, RenderRequestMode},
+        resolver::ResolvedRenderRequest,
+    },
+    utils::{metrics::Metrics, png::create_png_from_bytes, render_scheduler::RenderClass},
+};
+
+/// A message sent by a live preview client.
+///
+/// Besides these, clients can send a binary message with a skin (as a PNG) to render it.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PreviewMessage {
+    /// Changes the render mode and its options, which are the same as the query parameters of a render.
+    Settings {
+        mode: String,
+        #[serde(flatten)]
+        query: Box<RenderRequestQueryParams>,
+    },
+    /// Renders the skin of a player (or a texture hash).
+    Entry { entry: String },
+    /// Replaces a region of the skin, with the pixels encoded as base64 RGBA8.
+    /// Patches are kept until the skin or the settings are changed.
+    Patch {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: String,
+    },
+}
+
+/// The state of a single live preview connection.
+///
+/// The scene is kept around between frames, that way only what changed needs to be sent to the GPU.
+struct PreviewSession {
+    state: NMSRState,
+    /// The host the connection was made to, which selects the profile renders are made with.
+    host: Option<String>,
+    /// The number of scenes this connection can still take from the pool.
+    scenes_left: u32,
+    max_scenes: u32,
+    mode: RenderRequestMode,
+    query: Option<RenderRequestQueryParams>,
+    entry: Option<RenderRequestEntry>,
+    resolved: Option<ResolvedRenderRequest>,
+    scene: Option<Scene<Object<SceneContextPoolManager>>>,
+    needs_textures: bool,
+}
+
+pub(crate) async fn preview(
+    State(state): State<NMSRState>,
+    RequestHost(host): RequestHost,
+    ws: WebSocketUpgrade,
+    config: LivePreviewConfiguration,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_preview_socket(socket, state, host, config))
+}
+
+#[instrument(skip_all)]
+async fn handle_preview_socket(
+    mut socket: WebSocket,
+    state: NMSRState,
+    host: Option<String>,
+    config: LivePreviewConfiguration,
+) {
+    let frame_interval = Duration::from_secs(1) / config.max_fps.max(1);
+
+    let mut session = PreviewSession::new(state, host, config.max_scenes);
+    let mut next_frame = Instant::now();
+    let mut needs_frame = false;
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let message = match message {
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                    Some(Ok(message)) => message,
+                };
+
+                match session.handle_message(message) {
+                    Ok(changed) => needs_frame |= changed,
+                    Err(err) => {
+                        if send_error(&mut socket, &err).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+            () = sleep_until(next_frame), if needs_frame => {
+                needs_frame = false;
+                next_frame = Instant::now() + frame_interval;
+
+                let (sent, exhausted) = match session.render_frame().await {
+                    Ok(Some(frame)) => (socket.send(Message::Binary(frame)).await, false),
+                    Ok(None) => (Ok(()), false),
+                    Err(err) => (
+                        send_error(&mut socket, &err).await,
+                        matches!(err, NMSRaaSError::PreviewScenesExhausted(_)),
+                    ),
+                };
+
+                if sent.is_err() || exhausted {
+                    break;
+                }
+            }
+        }
+    }
+
+    debug!("Live preview connection closed");
+}
+
+async fn send_error(
+    socket: &mut WebSocket,
+    err: &crate::error::NMSRaaSError,
+) -> std::result::Result<(), axum::Error> {
+    let message = json!({ "error": err.to_string() }).to_string();
+
+    socket.send(Message::Text(message)).await
+}
+
+impl PreviewSession {
+    const fn new(state: NMSRState, host: Option<String>, max_scenes: u32) -> Self {
+        Self {
+            state,
+            host,
+            scenes_left: max_scenes,
+            max_scenes,
+            mode: RenderRequestMode::FullBody,
+            query: None,
+            entry: None,
+            resolved: None,
+            scene: None,
+            needs_textures: false,
+        }
+    }
+
+    /// Applies a message to the session, returning whether a new frame needs to be rendered.
+    fn handle_message(&mut self, message: Message) -> Result<bool> {
+        let message = match message {
+            Message::Binary(skin) => {
+                self.set_entry(RenderRequestEntry::PlayerSkin(skin));
+                return Ok(true);
+            }
+            Message::Text(text) => serde_json::from_str::<PreviewMessage>(&text)
+                .map_err(|err| RenderRequestError::InvalidPlayerRequest(err.to_string()))?,
+            _ => return Ok(false),
+        };
+
+        match message {
+            PreviewMessage::Settings { mode, query } => {
+                let (mode, custom_mode) = self.state.modes().parse_name(&mode)
+                    .filter(|(mode, _)| self.state.validate_mode(mode, self.host.as_deref()))
+                    .filter(|(mode, _)| mode.uses_rendering_pipeline())
+                    .ok_or(RenderRequestError::InvalidRenderMode(mode))?;
+
+                self.mode = mode;
+                self.query = Some(RenderRequestQueryParams {
+                    custom_mode,
+                    ..*query
+                });
+                // Settings can change the armor and how the skin is processed, so reload the textures.
+                self.needs_textures = true;
+            }
+            PreviewMessage::Entry { entry } => {
+                self.set_entry(RenderRequestEntry::try_from(entry)?);
+            }
+            PreviewMessage::Patch {
+                x,
+                y,
+                width,
+                height,
+                pixels,
+            } => {
+                let Some(scene) = self.scene.as_mut().filter(|_| !self.needs_textures) else {
+                    return Err(RenderRequestError::InvalidPlayerRequest(
+                        "A skin needs to be rendered before it can be patched".to_string(),
+                    )
+                    .into());
+                };
+
+                let pixels = STANDARD.decode(pixels).map_err(|err| {
+                    RenderRequestError::InvalidPlayerRequest(format!("Invalid patch pixels: {err}"))
+                })?;
+
+                let region = TextureRegion {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+
+                scene.update_texture_region(
+                    self.state.graphics_context()?,
+                    PlayerPartTextureType::Skin,
+                    region,
+                    &pixels,
+                )?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn set_entry(&mut self, entry: RenderRequestEntry) {
+        self.entry = Some(entry);
+        self.resolved = None;
+        self.needs_textures = true;
+    }
+
+    fn create_request(&self, entry: RenderRequestEntry) -> Result<RenderRequest> {
+        let query = self
+            .query
+            .clone()
+            .map_or_else(|| serde_json::from_value(json!({})), Ok);
+
+        let query =
+            query.map_err(|err| RenderRequestError::InvalidPlayerRequest(err.to_string()))?;
+
+        create_render_request(&self.state, self.host.as_deref(), self.mode, entry, query)
+    }
+
+    /// Renders a frame with the current settings, or nothing if there's nothing to render yet.
+    async fn render_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entry.clone() else {
+            return Ok(None);
+        };
+
+        let request = self.create_request(entry)?;
+
+        let resolved = match self.resolved.take() {
+            Some(resolved) => resolved,
+            None => self.state.resolver.resolve(&request).await?,
+        };
+        let resolved = self.resolved.insert(resolved);
+
+        let state = &self.state;
+        let graphics_context = state.graphics_context()?;
+        let _permit = state.acquire_render_permit(RenderClass::Single).await?;
+        let started = Instant::now();
+
+        #[allow(unused_mut)] // We use mut when we have ears feature enabled
+        let mut camera = request.get_camera();
+        let size = request.get_size();
+        let lighting = request.get_lighting();
+        let parts = request.get_body_parts();
+
+        let mut part_context = create_part_context(&request, resolved);
+
+        #[cfg(feature = "ears")]
+        if request.features.contains(RenderRequestFeatures::Ears) {
+            if let Some(features) = part_context.ears_features.as_ref() {
+                NMSRState::apply_ears_camera_settings(features, request.mode, &mut camera);
+            }
+        }
+
+        let scene = if let Some(scene) = self.scene.as_mut() {
+            *scene.camera_mut() = camera;
+            *scene.viewport_size_mut() = size;
+            *scene.sun_information_mut() = lighting;
+
+            scene.rebuild_parts(&part_context, parts);
+            scene.update(graphics_context);
+
+            scene
+        } else {
+            self.scenes_left = self
+                .scenes_left
+                .checked_sub(1)
+                .ok_or(NMSRaaSError::PreviewScenesExhausted(self.max_scenes))?;
+
+            let scene_context = state.create_scene_context().await?;
+
+            self.needs_textures = true;
+            self.scene.insert(Scene::new(
+                graphics_context,
+                scene_context,
+                camera,
+                lighting,
+                size,
+                &part_context,
+                &parts,
+            ))
+        };
+
+        // The shadow depends on the mode, so make sure the right one is uploaded.
+        if part_context.shadow_y_pos.is_some() {
+            let shadow_bytes = Scene::<Object<SceneContextPoolManager>>::get_shadow_bytes(
+                part_context.shadow_is_square,
+            );
+
+            scene.set_texture(
+                graphics_context,
+                PlayerPartTextureType::Shadow,
+                &load_image(shadow_bytes)?,
+            );
+        }
+
+        if self.needs_textures {
+            load_textures(resolved, state, &request, &mut part_context, scene).await?;
+            self.needs_textures = false;
+        }
+
+        scene.render(graphics_context)?;
+
+        let render = state.read_back_render(scene, graphics_context, started).await;
+
+        // The next frame starts over with a fresh scene, as this one might still be in use by the GPU.
+        if matches!(render, Err(NMSRaaSError::RenderTimedOut)) {
+            if let Some(scene) = self.scene.take() {
+                NMSRState::discard_scene(scene);
+            }
+        }
+
+        let mut render = render?;
+        Metrics::record_render_duration(&request.get_mode_name(), started.elapsed());
+
+        state.apply_background(&request, (size.width, size.height), &mut render)?;
+        state.apply_watermark(&request, (size.width, size.height), &mut render);
+
+        let frame = create_png_from_bytes((size.width, size.height), &render)?;
+
+        Ok(Some(frame))
+    }
+}
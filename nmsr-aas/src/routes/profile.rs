@@ -0,0 +1,94 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hyper::header::CACHE_CONTROL;
+use serde::Serialize;
+use strum::Display;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::NMSRState;
+use crate::{
+    error::{NMSRaaSError, Result},
+    model::{request::entry::RenderRequestEntry, resolver::mojang::model::GameProfileTexture},
+};
+
+/// The model of a player's skin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ProfileSkinModel {
+    Classic,
+    Slim,
+}
+
+/// A texture of a player's profile.
+#[derive(Debug, Serialize)]
+pub struct ProfileTextureInformation {
+    pub hash: String,
+    pub url: String,
+}
+
+impl TryFrom<&GameProfileTexture> for ProfileTextureInformation {
+    type Error = NMSRaaSError;
+
+    fn try_from(texture: &GameProfileTexture) -> Result<Self> {
+        Ok(Self {
+            hash: texture.hash()?.to_owned(),
+            url: texture.url().to_owned(),
+        })
+    }
+}
+
+/// Information about a player's profile, so that other sites don't need to talk to Mojang themselves.
+#[derive(Debug, Serialize)]
+pub struct ProfileInformation {
+    pub uuid: Uuid,
+    pub model: ProfileSkinModel,
+    pub skin: Option<ProfileTextureInformation>,
+    pub cape: Option<ProfileTextureInformation>,
+    /// The name of the skin server the profile was found on.
+    pub server: String,
+}
+
+impl ProfileInformation {
+    /// Resolves the profile of a player, given their UUID or name.
+    pub(crate) async fn resolve(state: &NMSRState, entry: &RenderRequestEntry) -> Result<Self> {
+        let (uuid, textures, server) = state.resolver.resolve_game_profile_textures(entry).await?;
+
+        let model = if textures.skin().is_some_and(GameProfileTexture::is_slim) {
+            ProfileSkinModel::Slim
+        } else {
+            ProfileSkinModel::Classic
+        };
+
+        Ok(Self {
+            uuid,
+            model,
+            skin: textures.skin().map(TryInto::try_into).transpose()?,
+            cape: textures.cape().map(TryInto::try_into).transpose()?,
+            server,
+        })
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn profile(
+    State(state): State<NMSRState>,
+    Path(player): Path<String>,
+) -> Result<Response> {
+    let entry = RenderRequestEntry::try_from(player)?;
+
+    let information = ProfileInformation::resolve(&state, &entry).await?;
+
+    let mut response = Json(information).into_response();
+
+    if let Ok(cache_ctrl) = HeaderValue::from_str(&state.get_cache_control_for_entry(&entry)) {
+        response.headers_mut().insert(CACHE_CONTROL, cache_ctrl);
+    }
+
+    Ok(response)
+}
@@ -2,10 +2,16 @@ use crate::{
     error::{RenderRequestError, Result},
     model::{
         armor::VanillaMinecraftArmorMaterialData,
-        request::{entry::RenderRequestEntryModel, RenderRequestFeatures, RenderRequestMode},
+        request::{
+            background::RenderRequestBackground, entry::RenderRequestEntryModel,
+            format::RenderRequestOutputFormat, RenderModes, RenderRequestExtraSettings,
+            RenderRequestFeatures, RenderRequestMode, RenderRequestProjection,
+        },
     },
 };
+use axum::{extract::Query, http::Uri};
 use enumset::EnumSet;
+use nmsr_rendering::high_level::model::PlayerBodyProportions;
 use serde::Deserialize;
 use serde_with::TryFromInto;
 use serde_with::{formats::CommaSeparator, serde_as, DisplayFromStr, StringWithSeparator};
@@ -21,14 +27,22 @@ use serde_with::{formats::CommaSeparator, serde_as, DisplayFromStr, StringWithSe
 ///  - `?r=<roll>` or `?roll=<roll>`: set the roll of the camera
 ///
 ///  - `?w=<width>` or `?width=<width>`: set the width of the image
-///  - `?h=<height>` or `?height=<height>`: set the height of the image
+///  - `?h=<height>` or `?height=<height>`: set the height of the image (when both are set, the aspect-ratio of the mode isn't kept)
 ///  - `?model=<steve|alex|wide|slim>`: set the model of the entry
 ///  - `?alex`: set the model of the entry to alex [compatibility with old URLs]
 ///  - `?steve`: set the model of the entry to steve [compatibility with old URLs]
-///  - `?process`: process the skin (upgrade skin to 1.8 format, strip alpha from the body regions, apply erase regions if Ears feature is enabled)
+///  - `?process`: process the skin (upgrade skin to 1.8 format and strip its opaque hat background, strip alpha from the body regions, apply erase regions if Ears feature is enabled)
 ///  
 ///  - `?arms=<rotation>` or `arm=<rotation>`: set the rotation of the arms
 ///  - `?dist=<distance>` or `distance=<distance>`: set the distance of the camera
+///  - `?dpr=<ratio>`: scale the image for high-DPI displays, used when the browser doesn't send the DPR client hint
+///  - `?fov=<degrees>`: set the field of view of the camera (requires a perspective projection)
+///  - `?projection=<perspective|orthographic>`: set the projection of the camera, overriding the one used by the mode
+///  - `?proportions=<adult|child>`: set the body proportions of the entry
+///  - `?mau5=<true|false>`: render deadmau5's ears on the entry (shown on deadmau5 himself unless disabled)
+///
+///  - `?parallax_offset=<offset>`: set the offset of the hat layer in skin pixels (requires using Face Parallax mode)
+///  - `?parallax_shadow=<strength>`: set the strength of the hat layer's drop shadow (requires using Face Parallax mode)
 ///
 ///  - `xpos=<x>` or `x_pos=<x>`: set the x position of the camera (requires using Custom mode)
 ///  - `ypos=<y>` or `y_pos=<y>`: set the y position of the camera (requires using Custom mode)
@@ -40,8 +54,13 @@ use serde_with::{formats::CommaSeparator, serde_as, DisplayFromStr, StringWithSe
 ///  - `?chestplate=<chestplate>`: set the chestplate of the entry
 ///  - `?leggings=<leggings>`: set the leggings of the entry
 ///  - `?boots=<boots>`: set the boots of the entry
+///
+///  - `?background=<color|name>` or `?bg=<color|name>`: composite the render on top of a hex color (`RRGGBB` or `RRGGBBAA`) or a configured background image
+///
+///  - `?format=<png|jpeg|avif>`: set the image format of the render (formats without transparency are flattened onto the background, or white if there's none, and AVIF requires the avif feature)
+///  - `?quality=<1-100>`: set the quality of lossy formats (defaults to 85 for JPEG, and to the configured quality for AVIF)
 #[serde_as]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct RenderRequestQueryParams {
     #[serde_as(as = "Option<StringWithSeparator::<CommaSeparator, RenderRequestFeatures>>")]
     #[serde(alias = "no")]
@@ -75,6 +94,19 @@ pub struct RenderRequestQueryParams {
     #[serde(alias = "d")]
     pub distance: Option<f32>,
 
+    pub fov: Option<f32>,
+
+    pub dpr: Option<f32>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub projection: Option<RenderRequestProjection>,
+
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub proportions: Option<PlayerBodyProportions>,
+    pub mau5: Option<bool>,
+
+    pub parallax_offset: Option<f32>,
+    pub parallax_shadow: Option<f32>,
+
     #[serde(alias = "xpos")]
     pub x_pos: Option<f32>,
     #[serde(alias = "ypos")]
@@ -93,6 +125,18 @@ pub struct RenderRequestQueryParams {
     pub leggings: Option<VanillaMinecraftArmorMaterialData>,
     #[serde_as(as = "Option<TryFromInto<String>>")]
     pub boots: Option<VanillaMinecraftArmorMaterialData>,
+
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(alias = "bg")]
+    pub background: Option<RenderRequestBackground>,
+
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub format: Option<RenderRequestOutputFormat>,
+    pub quality: Option<u8>,
+
+    /// The name of the mode defined in the configuration the render was requested for, set from the path.
+    #[serde(skip)]
+    pub custom_mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -104,6 +148,34 @@ pub struct RenderRequestMultipartParams {
 }
 
 impl RenderRequestQueryParams {
+    /// Parses the options of a render from a query string (e.g. `yaw=20&nolayers`), for APIs other than HTTP.
+    pub fn from_query_string(options: &str) -> Result<Self> {
+        let uri = format!("/?{options}").parse::<Uri>().map_err(|_| {
+            RenderRequestError::InvalidPlayerRequest(format!("Invalid options: {options}"))
+        })?;
+
+        let Query(query) = Query::<Self>::try_from_uri(&uri).map_err(RenderRequestError::from)?;
+
+        Ok(query)
+    }
+
+    /// Uses the size of the mode defined in the configuration the render was requested for, unless the request
+    /// asked for one.
+    pub(crate) fn apply_custom_mode_defaults(&mut self, modes: &RenderModes) {
+        if self.width.is_some() || self.height.is_some() {
+            return;
+        }
+
+        if let Some(custom_mode) = self
+            .custom_mode
+            .as_deref()
+            .and_then(|name| modes.get_custom_mode(name))
+        {
+            self.width = custom_mode.width;
+            self.height = custom_mode.height;
+        }
+    }
+
     pub fn get_excluded_features(&self) -> EnumSet<RenderRequestFeatures> {
         let mut excluded = self.exclude.unwrap_or(EnumSet::EMPTY);
 
@@ -137,7 +209,8 @@ impl RenderRequestQueryParams {
         alex.or(steve).or(model)
     }
 
-    pub fn validate(&mut self, mode: RenderRequestMode) -> Result<()> {
+    /// Validates the options for the given mode, with the size constraints given as `[min_w, min_h, max_w, max_h]`.
+    pub fn validate(&mut self, mode: RenderRequestMode, size_constraints: [u32; 4]) -> Result<()> {
         fn clamp(value: &mut Option<f32>, min: f32, max: f32) {
             let epsilon = 0.01;
             if let Some(value) = value {
@@ -145,28 +218,50 @@ impl RenderRequestQueryParams {
             }
         }
 
-        let [min_w, min_h, max_w, max_h] = mode.size_constraints();
+        let [min_w, min_h, max_w, max_h] = size_constraints;
 
         RenderRequestMode::validate_unit("width", self.width, &min_w, &max_w)?;
         RenderRequestMode::validate_unit("height", self.height, &min_h, &max_h)?;
 
+        RenderRequestMode::validate_unit(
+            "dpr",
+            self.dpr,
+            &RenderRequestMode::MIN_DPR,
+            &RenderRequestMode::MAX_DPR,
+        )?;
+
+        // The other side is worked out from the aspect ratio of the mode, and both are scaled by the DPR,
+        // so the final size needs to fit as well.
+        if self.width.is_some() || self.height.is_some() || self.dpr.is_some() {
+            let size = RenderRequestExtraSettings {
+                width: self.width,
+                height: self.height,
+                dpr: self.dpr,
+                ..Default::default()
+            }
+            .get_size_for_mode(mode);
+
+            RenderRequestMode::validate_unit("width", Some(size.width), &min_w, &max_w)?;
+            RenderRequestMode::validate_unit("height", Some(size.height), &min_h, &max_h)?;
+        }
+
         RenderRequestMode::validate_unit("yaw", self.yaw, &-180.0, &180.0)?;
         RenderRequestMode::validate_unit("pitch", self.pitch, &-90.0, &90.0)?;
         RenderRequestMode::validate_unit("roll", self.roll, &-180.0, &360.0)?;
 
         RenderRequestMode::validate_unit("arm", self.arms, &0.0, &180.0)?;
 
-        RenderRequestMode::validate_unit("distance", self.distance, &-5.0, &30.0)?;
+        // The distance and field of view were clamped to the configured camera limits already.
 
         // Clamp yaw, pitch, roll so that there is no weirdness with the camera
         clamp(&mut self.yaw, -180.0, 180.0);
         clamp(&mut self.pitch, -90.0, 90.0);
         clamp(&mut self.roll, -180.0, 360.0);
 
-        if !mode.is_custom() && self.width.is_some() && self.height.is_some() {
+        if mode.get_base_render_mode().is_some() && self.width.is_some() && self.height.is_some() {
             return Err(RenderRequestError::InvalidModeSettingSpecifiedError(
                 "both width and height settings",
-                "Pick one or the other to use as a constraint based on aspect-ratio or switch to custom mode.",
+                "Bust modes keep their aspect-ratio, so pick one or the other to use as a constraint.",
             ).into());
         }
 
@@ -195,10 +290,57 @@ impl RenderRequestQueryParams {
             self.z_pos.replace(pos[2]);
         }
 
+        if !mode.is_face_parallax()
+            && (self.parallax_offset.is_some() || self.parallax_shadow.is_some())
+        {
+            return Err(RenderRequestError::InvalidModeSettingSpecifiedError(
+                "parallax settings",
+                "Switch to face parallax mode to make use of these.",
+            )
+            .into());
+        }
+
+        RenderRequestMode::validate_unit("parallax_offset", self.parallax_offset, &0.0, &2.0)?;
+        RenderRequestMode::validate_unit("parallax_shadow", self.parallax_shadow, &0.0, &1.0)?;
+
+        if (mode.is_skin() || mode.is_blockbench_export()) && self.background.is_some() {
+            return Err(RenderRequestError::InvalidModeSettingSpecifiedError(
+                "a background",
+                "Backgrounds can only be used with modes that output a render.",
+            )
+            .into());
+        }
+
+        self.validate_format(mode)?;
+
         RenderRequestMode::validate_unit("xpos", self.x_pos, &-50.0, &50.0)?;
         RenderRequestMode::validate_unit("ypos", self.y_pos, &-50.0, &50.0)?;
         RenderRequestMode::validate_unit("zpos", self.z_pos, &-50.0, &50.0)?;
 
         Ok(())
     }
+
+    fn validate_format(&self, mode: RenderRequestMode) -> Result<()> {
+        let format = self.format.unwrap_or_default();
+
+        if (mode.is_skin() || mode.is_blockbench_export())
+            && format != RenderRequestOutputFormat::Png
+        {
+            return Err(RenderRequestError::InvalidModeSettingSpecifiedError(
+                "an output format",
+                "Only modes that output a render can be encoded in other formats.",
+            )
+            .into());
+        }
+
+        if !format.is_lossy() && self.quality.is_some() {
+            return Err(RenderRequestError::InvalidModeSettingSpecifiedError(
+                "a quality",
+                "Only lossy formats (e.g. ?format=jpeg) have a quality setting.",
+            )
+            .into());
+        }
+
+        RenderRequestMode::validate_unit("quality", self.quality, &1, &100)
+    }
 }
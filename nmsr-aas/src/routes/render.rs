@@ -1,23 +1,37 @@
 use super::{NMSRState, bbmodel_export::internal_bbmodel_export};
 use crate::{
-    error::{Result, RenderRequestError},
-    model::request::{RenderRequest, RenderRequestMode},
+    error::{NMSRaaSError, Result, RenderRequestError},
+    model::{
+        request::{entry::RenderRequestEntry, RenderRequest, RenderRequestMode},
+        resolver::{ResolvedRenderEntryTextureType, ResolvedRenderRequest},
+    },
+    routes::render_face_parallax::{can_render_face_flat, internal_render_face_parallax, load_skin_image},
     routes::render_model::internal_render_model,
     routes::render_skin::internal_render_skin,
+    utils::{
+        access_log::AccessLog,
+        render_scheduler::RenderClass,
+        stats::StatisticsCache,
+    },
 };
 use axum::{
+    body::Bytes,
     extract::State,
     http::HeaderValue,
     response::{IntoResponse, Response},
 };
 use hyper::{
-    header::{CACHE_CONTROL, CONTENT_TYPE},
+    header::{HeaderName, CACHE_CONTROL, CONTENT_TYPE, VARY},
     Method,
 };
-use tracing::instrument;
+use std::sync::Arc;
+use tracing::{instrument, warn};
 use xxhash_rust::xxh3::xxh3_64;
 
-const IMAGE_PNG_MIME: &str = "image/png";
+const CLIENT_HINTS: &str = "Sec-CH-DPR, Sec-CH-Width, DPR, Width";
+
+const ACCEPT_CH: HeaderName = HeaderName::from_static("accept-ch");
+const CONTENT_DPR: HeaderName = HeaderName::from_static("content-dpr");
 
 #[axum::debug_handler]
 pub async fn render_post_warning() -> Result<Response> {
@@ -36,20 +50,19 @@ pub async fn render(
     method: Method,
     request: RenderRequest,
 ) -> Result<Response> {
-    let resolved = state.resolver.resolve(&request).await?;
-
     if request.mode.is_blockbench_export() {
+        state.resolver.resolve(&request).await?;
         return internal_bbmodel_export(state, method, request).await;
     }
     
     if method == Method::HEAD {
-        return Ok(([(CONTENT_TYPE, HeaderValue::from_static(IMAGE_PNG_MIME))]).into_response());
+        state.resolver.resolve(&request).await?;
+
+        let mime_type = request.get_output_format().mime_type();
+        return Ok(([(CONTENT_TYPE, HeaderValue::from_static(mime_type))]).into_response());
     }
 
-    let result = match request.mode {
-        RenderRequestMode::Skin => internal_render_skin(&request, resolved).await,
-        _ => internal_render_model(&request, &state, &resolved).await,
-    }?;
+    let result = render_image_coalesced(&request, &state).await?;
 
     let mut res = create_image_response(result, &state, &request);
     let hash = xxh3_64(format!("{request:?}").as_bytes());
@@ -61,7 +74,148 @@ pub async fn render(
     Ok(res)
 }
 
-fn create_image_response<T>(
+/// Resolves and renders a request into an image, sharing the render with identical requests made in the meantime.
+async fn render_image_coalesced(request: &RenderRequest, state: &NMSRState) -> Result<Bytes> {
+    let render = || async {
+        if let Some(render) = get_cached_render_before_resolving(request, state).await {
+            return Ok(Bytes::from(render));
+        }
+
+        let resolved = state.resolver.resolve(request).await?;
+        let render = render_image(request, state, resolved, RenderClass::Single).await?;
+
+        Ok::<_, NMSRaaSError>(Bytes::from(render))
+    };
+
+    // Uploaded skins are left out of the request's debug representation, and are rarely uploaded twice at once.
+    if matches!(request.entry, RenderRequestEntry::PlayerSkin(_)) {
+        return render().await;
+    }
+
+    let (render, details) = state
+        .render_coalescer
+        .run(format!("{request:?}"), || async {
+            let (render, details) = AccessLog::capture(Box::pin(render())).await;
+            (render.map_err(Arc::new), details)
+        })
+        .await;
+
+    // Whoever did the render recorded its details, the requests that waited for it are logged with them too.
+    if let Some(details) = &details {
+        AccessLog::record_details(details);
+    }
+
+    render.map_err(NMSRaaSError::SharedError)
+}
+
+/// Looks up the render of a player we've resolved recently in the render cache, using the textures they were resolved
+/// to last time instead of resolving them again. Misses are looked up again once the player is resolved, which is
+/// cheap next to rendering.
+async fn get_cached_render_before_resolving(
+    request: &RenderRequest,
+    state: &NMSRState,
+) -> Option<Vec<u8>> {
+    // Only the renders that go through the pipeline are cached, and plain faces are usually drawn flat instead.
+    let render_cache = state.render_cache.as_ref().filter(|_| {
+        request.mode.uses_rendering_pipeline() && !request.mode.is_custom() && !request.is_plain_face()
+    })?;
+
+    let textures = state.resolver.get_indexed_textures(request).await?;
+
+    let render = match render_cache.get_cached_render(request, &textures).await {
+        Ok(render) => render?,
+        Err(err) => {
+            warn!("Unable to read render from cache: {err}");
+            return None;
+        }
+    };
+
+    let mode = request.get_mode_name();
+    state.statistics.record_render(&mode, None);
+    state.statistics.record_cache_lookup(StatisticsCache::Renders, true);
+    AccessLog::record_mode(&mode);
+    AccessLog::record_cache_hit(true);
+
+    Some(render)
+}
+
+/// Renders a request into an image, in the format it asked for. Exported models aren't images, so they have to be handled by the caller.
+pub(crate) async fn render_image(
+    request: &RenderRequest,
+    state: &NMSRState,
+    resolved: ResolvedRenderRequest,
+    class: RenderClass,
+) -> Result<Vec<u8>> {
+    let mode = request.get_mode_name();
+    let skin = resolved
+        .textures
+        .get(&ResolvedRenderEntryTextureType::Skin)
+        .map(Vec::as_slice);
+
+    state.statistics.record_render(&mode, skin);
+    AccessLog::record_mode(&mode);
+
+    match request.mode {
+        RenderRequestMode::Skin => internal_render_skin(request, resolved).await,
+        RenderRequestMode::FaceParallax => {
+            let skin = load_skin_image(&resolved)?;
+            internal_render_face_parallax(request, state, &resolved, skin).await
+        }
+        // Plain faces don't need the renderer, so skip it (and the render cache) entirely when we can.
+        RenderRequestMode::Face if request.is_plain_face() => {
+            let skin = load_skin_image(&resolved)?;
+
+            if can_render_face_flat(request, &skin) {
+                internal_render_face_parallax(request, state, &resolved, skin).await
+            } else {
+                render_model_with_cache(request, state, &resolved, class).await
+            }
+        }
+        _ => render_model_with_cache(request, state, &resolved, class).await,
+    }
+}
+
+pub(crate) async fn render_model_with_cache(
+    request: &RenderRequest,
+    state: &NMSRState,
+    resolved: &ResolvedRenderRequest,
+    class: RenderClass,
+) -> Result<Vec<u8>> {
+    // Custom renders are too unique to be worth storing.
+    let Some(render_cache) = state
+        .render_cache
+        .as_ref()
+        .filter(|_| !request.mode.is_custom())
+    else {
+        return internal_render_model(request, state, resolved, class).await;
+    };
+
+    let textures = resolved.key();
+
+    // The render cache is a nice-to-have, so we don't fail the request if it's unavailable.
+    match render_cache.get_cached_render(request, &textures).await {
+        Ok(Some(render)) => {
+            state.statistics.record_cache_lookup(StatisticsCache::Renders, true);
+            AccessLog::record_cache_hit(true);
+            return Ok(render);
+        }
+        Ok(None) => {
+            state.statistics.record_cache_lookup(StatisticsCache::Renders, false);
+            AccessLog::record_cache_hit(false);
+        }
+        Err(err) => warn!("Unable to read render from cache: {err}"),
+    }
+
+    let render = internal_render_model(request, state, resolved, class).await?;
+
+    if let Err(err) = render_cache.cache_render(request, &textures, &render).await {
+        warn!("Unable to write render to cache: {err}");
+    }
+
+    Ok(render)
+}
+
+pub(crate) fn create_image_response<T>(
     skin: T,
     State(state): &State<NMSRState>,
     request: &RenderRequest,
@@ -76,9 +230,24 @@ where
         response.headers_mut().insert(CACHE_CONTROL, cache_ctrl);
     }
 
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(request.get_output_format().mime_type()),
+    );
+
+    // Renders are scaled using the client hints of the browser, so let it know to send them.
+    response
+        .headers_mut()
+        .insert(ACCEPT_CH, HeaderValue::from_static(CLIENT_HINTS));
     response
         .headers_mut()
-        .insert(CONTENT_TYPE, HeaderValue::from_static(IMAGE_PNG_MIME));
+        .insert(VARY, HeaderValue::from_static(CLIENT_HINTS));
+
+    if let Some(dpr) = request.get_dpr() {
+        if let Ok(dpr) = HeaderValue::from_str(&dpr.to_string()) {
+            response.headers_mut().insert(CONTENT_DPR, dpr);
+        }
+    }
 
     response
 }
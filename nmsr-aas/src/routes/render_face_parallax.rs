@@ -0,0 +1,282 @@
+use image::{
+    imageops::{self, FilterType},
+    Pixel, Rgba, RgbaImage,
+};
+use nmsr_rendering::{
+    errors::NMSRRenderingError,
+    high_level::skin_regions::{self, SkinRegion},
+};
+use tokio::time::Instant;
+
+use super::NMSRState;
+use crate::{
+    error::{RenderRequestError, Result},
+    model::{
+        request::{RenderRequest, RenderRequestFeatures},
+        resolver::{ResolvedRenderEntryTextureType, ResolvedRenderRequest},
+    },
+    utils::{
+        access_log::{AccessLog, AccessLogTiming},
+        metrics::Metrics,
+    },
+};
+
+/// The default offset of the hat layer, in skin pixels.
+const DEFAULT_PARALLAX_OFFSET: f32 = 0.5;
+/// The default opacity of the hat layer's drop shadow.
+const DEFAULT_PARALLAX_SHADOW: f32 = 0.5;
+
+/// The size of the face, in skin pixels.
+const FACE_SIZE: f32 = 8.0;
+/// The size of the hat layer, in skin pixels. Like in-game, the hat layer is slightly bigger than the face.
+const HAT_SIZE: f32 = 9.0;
+
+/// Decodes the skin of a resolved request, for the renders that are drawn straight from it.
+pub(crate) fn load_skin_image(resolved: &ResolvedRenderRequest) -> Result<RgbaImage> {
+    let skin = resolved
+        .textures
+        .get(&ResolvedRenderEntryTextureType::Skin)
+        .ok_or(RenderRequestError::InvalidPlayerRequest(
+            "Missing skin texture".to_string(),
+        ))?;
+
+    Ok(image::load_from_memory(skin)
+        .map_err(NMSRRenderingError::ImageFromRawError)?
+        .into_rgba8())
+}
+
+/// Whether a plain face (see [`RenderRequest::is_plain_face`]) can be drawn flat from the given skin instead of going
+/// through the renderer, which is a lot faster and works without a graphics adapter.
+#[cfg_attr(not(feature = "ears"), allow(clippy::missing_const_for_fn))]
+pub(crate) fn can_render_face_flat(request: &RenderRequest, skin: &RgbaImage) -> bool {
+    // Ears features (like snouts) stick out of the face, so skins with any need to be rendered.
+    #[cfg(feature = "ears")]
+    if request.features.contains(RenderRequestFeatures::Ears) {
+        let has_ears_features = ears_rs::parser::EarsParser::parse(skin)
+            .ok()
+            .flatten()
+            .is_some();
+
+        return !has_ears_features;
+    }
+
+    #[cfg(not(feature = "ears"))]
+    let _ = (request, skin);
+
+    true
+}
+
+/// Renders a face flat or with parallax from the decoded skin, depending on the mode. Flat faces look the same as
+/// the rendered ones, with the hat layer slightly bigger than the face.
+pub(crate) async fn internal_render_face_parallax(
+    request: &RenderRequest,
+    state: &NMSRState,
+    resolved: &ResolvedRenderRequest,
+    skin_image: RgbaImage,
+) -> Result<Vec<u8>> {
+    let started = Instant::now();
+    let skin_image = NMSRState::process_skin(skin_image, request.features)?;
+
+    let settings = request.extra_settings.as_ref();
+    let (offset, shadow) = if request.mode.is_face_parallax() {
+        let offset = settings
+            .and_then(|s| s.parallax_offset)
+            .unwrap_or(DEFAULT_PARALLAX_OFFSET);
+        let shadow = settings
+            .and_then(|s| s.parallax_shadow)
+            .unwrap_or(DEFAULT_PARALLAX_SHADOW);
+
+        (offset, Some(shadow))
+    } else {
+        (0.0, None)
+    };
+
+    let mut render = render_face_parallax(
+        &skin_image,
+        request.get_size().width,
+        offset,
+        request.features.contains(RenderRequestFeatures::HatLayer),
+        shadow.filter(|_| request.features.contains(RenderRequestFeatures::Shadow)),
+    );
+
+    AccessLog::record_timing(AccessLogTiming::Render, started.elapsed());
+    Metrics::record_render_duration(&request.get_mode_name(), started.elapsed());
+
+    let size = render.dimensions();
+    state.apply_background(request, size, &mut render)?;
+    state.apply_watermark(request, size, &mut render);
+
+    let skin = resolved
+        .textures
+        .get(&ResolvedRenderEntryTextureType::Skin)
+        .map(Vec::as_slice);
+    let metadata = NMSRState::describe_render(request, skin, None);
+
+    let render_bytes = state
+        .encode_render(request, render.dimensions(), render.into_raw(), &metadata)
+        .await?;
+
+    Ok(render_bytes)
+}
+
+/// Renders the face of a skin flat, with the hat layer moved up and to the left by `offset` skin pixels.
+/// The hat layer casts a shadow on the face where it would've been without the offset, giving a 2.5D effect.
+fn render_face_parallax(
+    skin: &RgbaImage,
+    size: u32,
+    offset: f32,
+    hat_layer: bool,
+    shadow: Option<f32>,
+) -> RgbaImage {
+    // Skins missing a region (which shouldn't happen for the head) get a transparent face instead.
+    let crop = |region: SkinRegion| {
+        region
+            .extract(skin)
+            .unwrap_or_else(|| RgbaImage::new(region.width, region.height))
+    };
+
+    let unit = size as f32 / (HAT_SIZE + offset);
+    let to_pixels = |units: f32| (units * unit).round() as u32;
+
+    let face_position = to_pixels(offset + (HAT_SIZE - FACE_SIZE) / 2.0);
+    let face_size = to_pixels(FACE_SIZE);
+    let hat_size = to_pixels(HAT_SIZE);
+
+    let mut render = RgbaImage::new(size, size);
+
+    let face = crop(skin_regions::HEAD.front);
+    let face = imageops::resize(&face, face_size, face_size, FilterType::Nearest);
+    imageops::overlay(
+        &mut render,
+        &face,
+        i64::from(face_position),
+        i64::from(face_position),
+    );
+
+    if !hat_layer {
+        return render;
+    }
+
+    let hat = crop(skin_regions::HAT.front);
+    let hat = imageops::resize(&hat, hat_size, hat_size, FilterType::Nearest);
+
+    if let Some(strength) = shadow.filter(|s| *s > 0.0) {
+        let mut hat_shadow = RgbaImage::from_fn(hat_size, hat_size, |x, y| {
+            let alpha = f32::from(hat.get_pixel(x, y)[3]) * strength;
+            Rgba([0, 0, 0, alpha.round() as u8])
+        });
+
+        // Soften the shadow a bit so that it reads as depth instead of an outline.
+        hat_shadow = imageops::blur(&hat_shadow, unit / 4.0);
+
+        // The shadow is cast where the hat layer would be without the offset, but only on the face.
+        let shadow_position = to_pixels(offset);
+        for (x, y, pixel) in hat_shadow.enumerate_pixels() {
+            let (x, y) = (x + shadow_position, y + shadow_position);
+
+            let inside_face = (face_position..face_position + face_size).contains(&x)
+                && (face_position..face_position + face_size).contains(&y);
+
+            if inside_face && x < size && y < size {
+                render.get_pixel_mut(x, y).blend(pixel);
+            }
+        }
+    }
+
+    imageops::overlay(&mut render, &hat, 0, 0);
+
+    render
+}
+
+#[cfg(test)]
+mod test {
+    use enumset::EnumSet;
+    use image::{Rgba, RgbaImage};
+    use nmsr_rendering::high_level::{
+        model::{PlayerBodyProportions, PlayerModel},
+        parts::provider::PlayerPartProviderContext,
+        pipeline::software::SoftwareScene,
+        skin_regions::{self, SkinRegion},
+        types::PlayerPartTextureType,
+    };
+    use uuid::Uuid;
+
+    use super::render_face_parallax;
+    use crate::model::request::{entry::RenderRequestEntry, RenderRequest, RenderRequestMode};
+
+    /// The bounds of the opaque pixels of a render, as `[left, top, right, bottom]`.
+    fn opaque_bounds(render: &RgbaImage) -> [u32; 4] {
+        render
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel[3] > u8::MAX / 2)
+            .fold([u32::MAX, u32::MAX, 0, 0], |[left, top, right, bottom], (x, y, _)| {
+                [left.min(x), top.min(y), right.max(x), bottom.max(y)]
+            })
+    }
+
+    fn fill(skin: &mut RgbaImage, region: SkinRegion, color: Rgba<u8>) {
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                skin.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Flat faces skip the renderer, so they have to be framed exactly like rendered faces are.
+    /// Without a graphics adapter in tests, the face is rendered on the CPU, which uses the same camera and parts.
+    #[test]
+    fn flat_faces_are_framed_like_rendered_ones() {
+        let request = RenderRequest::new_from_excluded_features(
+            RenderRequestMode::Face,
+            RenderRequestEntry::MojangPlayerUuid(Uuid::nil()),
+            None,
+            EnumSet::empty(),
+            None,
+        );
+
+        let size = request.get_size();
+
+        for hat_layer in [false, true] {
+            let mut skin = RgbaImage::new(64, 64);
+            fill(&mut skin, skin_regions::HEAD.front, Rgba([255, 0, 0, 255]));
+            if hat_layer {
+                fill(&mut skin, skin_regions::HAT.front, Rgba([0, 0, 255, 255]));
+            }
+
+            let context = PlayerPartProviderContext::<()> {
+                model: PlayerModel::Steve,
+                has_hat_layer: true,
+                has_layers: true,
+                has_cape: false,
+                arm_rotation: 0.0,
+                shadow_y_pos: None,
+                shadow_is_square: false,
+                armor_slots: None,
+                proportions: PlayerBodyProportions::default(),
+                shoulder_buddies: None,
+                has_deadmau5_ears: false,
+                #[cfg(feature = "ears")]
+                ears_features: None,
+            };
+
+            let mut scene = SoftwareScene::new(
+                request.get_camera(),
+                request.get_lighting(),
+                size,
+                &context,
+                &request.get_body_parts(),
+            );
+            scene.set_texture(PlayerPartTextureType::Skin, &skin);
+
+            let rendered =
+                RgbaImage::from_raw(size.width, size.height, scene.render().unwrap()).unwrap();
+            let flat = render_face_parallax(&skin, size.width, 0.0, true, None);
+
+            let (rendered, flat) = (opaque_bounds(&rendered), opaque_bounds(&flat));
+
+            for (rendered, flat) in rendered.into_iter().zip(flat) {
+                assert!(rendered.abs_diff(flat) <= 1, "{rendered:?} {flat:?} ({hat_layer})");
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Deref;
+
 use deadpool::managed::Object;
 use image::{ImageFormat, RgbaImage};
 use nmsr_rendering::{
@@ -5,10 +7,16 @@ use nmsr_rendering::{
     high_level::{
         model::{PlayerArmorSlots, PlayerModel},
         parts::provider::PlayerPartProviderContext,
-        pipeline::{pools::SceneContextPoolManager, scene::Scene},
+        pipeline::{pools::SceneContextPoolManager, scene::Scene, software::SoftwareScene},
+        types::PlayerPartTextureType,
     },
 };
+use tokio::time::Instant;
 use tracing::instrument;
+#[cfg(feature = "gpu_profiling")]
+use nmsr_rendering::high_level::pipeline::GraphicsContext;
+#[cfg(feature = "gpu_profiling")]
+use tracing::{debug, warn};
 
 use super::NMSRState;
 use crate::{
@@ -18,56 +26,162 @@ use crate::{
         request::{RenderRequest, RenderRequestFeatures},
         resolver::{ResolvedRenderEntryTextureType, ResolvedRenderRequest},
     },
-    utils::png::create_png_from_bytes,
+    utils::{
+        access_log::{AccessLog, AccessLogTiming},
+        metrics::Metrics,
+        render_scheduler::RenderClass,
+        serving_mode::ServingMode,
+    },
 };
 
 pub(crate) async fn internal_render_model(
     request: &RenderRequest,
     state: &NMSRState,
     resolved: &ResolvedRenderRequest,
+    class: RenderClass,
 ) -> Result<Vec<u8>> {
-    let scene_context = state.create_scene_context().await?;
+    // Without a graphics context, we can only render if we're allowed to do so on the CPU.
+    let graphics_context = match state.graphics_context() {
+        Ok(graphics_context) => Some(graphics_context),
+        Err(_) if state.serving_mode == ServingMode::Software => None,
+        Err(err) => return Err(err),
+    };
+
+    let _permit = state.acquire_render_permit(class).await?;
+    let started = Instant::now();
 
-    let mode = request.mode;
     #[allow(unused_mut)] // We use mut when we have ears feature enabled
     let mut camera = request.get_camera();
 
     let size = request.get_size();
     let lighting = request.get_lighting();
 
-    let parts = mode.get_body_parts();
+    let parts = request.get_body_parts();
 
     let mut part_context = create_part_context(request, resolved);
 
     #[cfg(feature = "ears")]
     if request.features.contains(RenderRequestFeatures::Ears) {
         if let Some(features) = part_context.ears_features.as_ref() {
-            NMSRState::apply_ears_camera_settings(features, mode, &mut camera);
+            NMSRState::apply_ears_camera_settings(features, request.mode, &mut camera);
         }
     }
 
-    let mut scene = Scene::new(
-        &state.graphics_context,
-        scene_context,
-        camera,
-        lighting,
-        size,
-        &part_context,
-        &parts,
-    );
+    let mut render = if let Some(graphics_context) = graphics_context {
+        let scene_context = state.create_scene_context().await?;
+
+        let mut scene = Scene::new(
+            graphics_context,
+            scene_context,
+            camera,
+            lighting,
+            size,
+            &part_context,
+            &parts,
+        );
+
+        load_textures(resolved, state, request, &mut part_context, &mut scene).await?;
+
+        scene.render(graphics_context)?;
 
-    load_textures(resolved, state, request, &mut part_context, &mut scene).await?;
+        let scene = SubmittedScene(Some(scene));
+        #[cfg(feature = "gpu_profiling")]
+        let readback_started = Instant::now();
+        let render = state
+            .read_back_render(&scene, graphics_context, started)
+            .await?;
+
+        #[cfg(feature = "gpu_profiling")]
+        log_render_timings(&scene, graphics_context, readback_started.elapsed()).await;
+
+        scene.finish();
+
+        render
+    } else {
+        let mut scene = SoftwareScene::new(camera, lighting, size, &part_context, &parts);
+
+        for (texture_type, texture) in
+            create_textures(resolved, state, request, &part_context).await?
+        {
+            scene.set_texture(texture_type, &texture);
+        }
 
-    scene.render(&state.graphics_context)?;
+        // Rendering on the CPU takes a while, so don't hold up other requests in the meantime.
+        // A render that times out can't be interrupted, but the request doesn't wait for it anymore.
+        let render = NMSRState::spawn_blocking(move || scene.render());
 
-    let render = scene
-        .copy_output_texture(&state.graphics_context, true)
+        state.with_render_timeout(started, render).await??
+    };
+
+    AccessLog::record_timing(AccessLogTiming::Render, started.elapsed());
+    Metrics::record_render_duration(&request.get_mode_name(), started.elapsed());
+
+    state.apply_background(request, (size.width, size.height), &mut render)?;
+    state.apply_watermark(request, (size.width, size.height), &mut render);
+
+    let skin = resolved
+        .textures
+        .get(&ResolvedRenderEntryTextureType::Skin)
+        .map(Vec::as_slice);
+    let metadata = NMSRState::describe_render(request, skin, Some(&camera));
+
+    let render_bytes = state
+        .encode_render(request, (size.width, size.height), render, &metadata)
         .await?;
-    let render_bytes = create_png_from_bytes((size.width, size.height), &render)?;
 
     Ok(render_bytes)
 }
 
+/// A scene whose render was submitted to the GPU, but not read back yet.
+///
+/// If the render is dropped before then (e.g. because it timed out or the client disconnected), the GPU might still be
+/// using the scene, so it's thrown away instead of being given back to the pool.
+struct SubmittedScene(Option<Scene<Object<SceneContextPoolManager>>>);
+
+impl SubmittedScene {
+    /// Gives the scene back to the pool, now that the GPU is done with it.
+    fn finish(mut self) {
+        drop(self.0.take());
+    }
+}
+
+impl Deref for SubmittedScene {
+    type Target = Scene<Object<SceneContextPoolManager>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+            .as_ref()
+            .unwrap_or_else(|| unreachable!("The scene is only taken once it's finished"))
+    }
+}
+
+impl Drop for SubmittedScene {
+    fn drop(&mut self) {
+        if let Some(scene) = self.0.take() {
+            NMSRState::discard_scene(scene);
+        }
+    }
+}
+
+/// Logs where the time of a render went: encoding it, on the GPU, or reading it back.
+#[cfg(feature = "gpu_profiling")]
+async fn log_render_timings(
+    scene: &Scene<Object<SceneContextPoolManager>>,
+    graphics_context: &GraphicsContext,
+    readback: std::time::Duration,
+) {
+    match scene.render_timings(graphics_context).await {
+        Ok(timings) => debug!(
+            encoding = ?timings.encoding,
+            gpu = ?timings.gpu_time(),
+            ?readback,
+            passes = ?timings.passes,
+            "Render timings"
+        ),
+        Err(error) => warn!("Unable to read back the render timings: {error}"),
+    }
+}
+
 #[cfg(feature = "ears")]
 fn load_ears_features(
     part_context: &mut PlayerPartProviderContext<VanillaMinecraftArmorMaterialData>,
@@ -83,13 +197,31 @@ fn load_ears_features(
 }
 
 #[instrument(skip_all)]
-async fn load_textures(
+pub(crate) async fn load_textures(
     resolved: &ResolvedRenderRequest,
     state: &NMSRState,
     request: &RenderRequest,
     part_provider: &mut PlayerPartProviderContext<VanillaMinecraftArmorMaterialData>,
     scene: &mut Scene<Object<SceneContextPoolManager>>,
 ) -> Result<()> {
+    let graphics_context = state.graphics_context()?;
+
+    for (texture_type, texture) in create_textures(resolved, state, request, part_provider).await? {
+        scene.set_texture(graphics_context, texture_type, &texture);
+    }
+
+    Ok(())
+}
+
+/// Decodes the textures of a request, along with the armor textures it needs.
+async fn create_textures(
+    resolved: &ResolvedRenderRequest,
+    state: &NMSRState,
+    request: &RenderRequest,
+    part_provider: &PlayerPartProviderContext<VanillaMinecraftArmorMaterialData>,
+) -> Result<Vec<(PlayerPartTextureType, RgbaImage)>> {
+    let mut textures = Vec::with_capacity(resolved.textures.len());
+
     for (&texture_type, texture_bytes) in &resolved.textures {
         let mut image_buffer = load_image(texture_bytes)?;
 
@@ -97,7 +229,7 @@ async fn load_textures(
             image_buffer = NMSRState::process_skin(image_buffer, request.features)?;
         }
 
-        scene.set_texture(&state.graphics_context, texture_type.into(), &image_buffer);
+        textures.push((texture_type.into(), image_buffer));
     }
 
     if let Some(armor_slots) = part_provider.armor_slots.as_ref() {
@@ -106,22 +238,20 @@ async fn load_textures(
             .create_armor_texture(armor_slots)
             .await?;
 
-        scene.set_texture(
-            &state.graphics_context,
+        textures.push((
             VanillaMinecraftArmorMaterialData::ARMOR_TEXTURE_ONE,
-            &main_layer,
-        );
+            main_layer,
+        ));
 
         if let Some(second_armor_layer) = second_armor_layer {
-            scene.set_texture(
-                &state.graphics_context,
+            textures.push((
                 VanillaMinecraftArmorMaterialData::ARMOR_TEXTURE_TWO,
-                &second_armor_layer,
-            );
+                second_armor_layer,
+            ));
         }
     }
 
-    Ok(())
+    Ok(textures)
 }
 
 pub(crate) fn load_image(texture: &[u8]) -> Result<RgbaImage> {
@@ -195,6 +325,13 @@ pub(crate) fn create_part_context(
         shadow_y_pos,
         shadow_is_square: request.mode.is_head() || request.mode.is_head_iso(),
         armor_slots: Some(player_armor_slots),
+        proportions: request
+            .extra_settings
+            .as_ref()
+            .and_then(|x| x.proportions)
+            .unwrap_or_default(),
+        shoulder_buddies: resolved.shoulder_buddies,
+        has_deadmau5_ears: request.has_deadmau5_ears(),
         #[cfg(feature = "ears")]
         ears_features: None,
     };
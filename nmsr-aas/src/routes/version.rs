@@ -0,0 +1,118 @@
+use axum::{extract::State, Json};
+use enumset::EnumSet;
+use nmsr_rendering::high_level::pipeline::GraphicsContextCapabilities;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::{NMSRState, RenderRequestValidator};
+use crate::{
+    model::request::{RenderRequestFeatures, RenderRequestMode},
+    utils::serving_mode::ServingMode,
+};
+
+/// Information about this instance, used by clients to discover what it supports.
+#[derive(Debug, Serialize)]
+pub struct VersionInformation {
+    pub version: &'static str,
+    pub modes: Vec<String>,
+    pub features: Vec<String>,
+    pub serving_mode: ServingMode,
+    pub capabilities: Option<GraphicsContextCapabilities>,
+}
+
+impl VersionInformation {
+    pub fn new(state: &NMSRState) -> Self {
+        let render_modes = state.modes();
+
+        let modes = RenderRequestMode::iter()
+            .map(|mode| (mode.to_string(), mode))
+            .chain(render_modes.get_custom_modes())
+            .filter(|(_, mode)| state.validate_mode(mode, None))
+            .filter(|&(_, mode)| state.serving_mode.supports_mode(mode))
+            .map(|(name, _)| name)
+            .collect();
+
+        let features = EnumSet::<RenderRequestFeatures>::all()
+            .iter()
+            .filter(|feature| !state.features_config.disabled_features.contains(feature))
+            .map(|feature| feature.to_string())
+            .collect();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            modes,
+            features,
+            serving_mode: state.serving_mode,
+            capabilities: state
+                .graphics_context()
+                .ok()
+                .map(|graphics_context| graphics_context.capabilities()),
+        }
+    }
+}
+
+pub(crate) async fn version(State(state): State<NMSRState>) -> Json<VersionInformation> {
+    Json(VersionInformation::new(&state))
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use http_body_util::BodyExt;
+    use hyper::StatusCode;
+    use tower::ServiceExt;
+
+    use super::version;
+    use crate::{
+        config::{AdapterConfiguration, NmsrConfiguration, RenderingConfiguration},
+        routes::NMSRState,
+    };
+
+    #[tokio::test]
+    async fn texture_only_mode_is_reported() {
+        let config = NmsrConfiguration {
+            rendering: Some(RenderingConfiguration {
+                texture_only_fallback: true,
+                // Make sure no graphics adapter is found, whatever machine the tests run on.
+                adapter: AdapterConfiguration {
+                    name: Some("No such graphics adapter".to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let state = NMSRState::new_offline(&config)
+            .await
+            .expect("State should start without a renderer");
+
+        let router = Router::new()
+            .route("/version", get(version))
+            .with_state(state);
+
+        let request = Request::get("/version")
+            .body(Body::empty())
+            .expect("Request should be valid");
+        let response = router.oneshot(request).await.expect("Request should succeed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Body should be read")
+            .to_bytes();
+        let version: serde_json::Value =
+            serde_json::from_slice(&body).expect("Body should be JSON");
+
+        assert_eq!(version["serving_mode"], "texture_only");
+        assert_eq!(version["capabilities"], serde_json::Value::Null);
+
+        let modes = version["modes"].as_array().expect("Modes should be listed");
+        assert!(modes.contains(&"texture".into()));
+        assert!(modes.contains(&"face".into()));
+        assert!(!modes.contains(&"fullbody".into()));
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use tracing::{info, info_span, warn, Instrument};
+
+use super::{
+    extractors::create_render_request, render::render_model_with_cache, NMSRState,
+    RenderRequestValidator,
+};
+use crate::{
+    error::Result,
+    model::request::{entry::RenderRequestEntry, RenderRequestMode},
+    routes::query::RenderRequestQueryParams,
+    utils::{render_scheduler::RenderClass, serving_mode::ServingMode},
+};
+
+impl NMSRState {
+    /// Resolves and renders the given players in the background, that way their renders are already cached
+    /// by the time they're requested.
+    ///
+    /// Renders are scheduled as a batch, so they don't slow down the renders requested in the meantime.
+    pub(crate) fn spawn_warm_up(
+        &self,
+        players: Vec<RenderRequestEntry>,
+        modes: &[RenderRequestMode],
+    ) {
+        // Only the modes that would be rendered (and cached) by the pipeline are worth warming up.
+        // There's no pipeline in texture-only mode, the faces it serves are drawn flat.
+        let modes: Vec<_> = modes
+            .iter()
+            .copied()
+            .filter(|&mode| mode.uses_rendering_pipeline() && !mode.is_custom())
+            .filter(|_| self.serving_mode != ServingMode::TextureOnly)
+            .filter(|&mode| self.serving_mode.supports_mode(mode))
+            .filter(|mode| self.validate_mode(mode, None))
+            .collect();
+
+        let state = self.clone();
+
+        tokio::spawn(
+            async move {
+                let start = Instant::now();
+                let players_count = players.len();
+                let mut warmed_up = 0;
+
+                for entry in players {
+                    match state.warm_up_player(entry.clone(), &modes).await {
+                        Ok(()) => warmed_up += 1,
+                        Err(err) => warn!("Unable to warm up {entry:?}: {err}"),
+                    }
+                }
+
+                info!(
+                    "Warmed up {warmed_up} out of {players_count} players in {:?}",
+                    start.elapsed()
+                );
+            }
+            .instrument(info_span!("warm_up")),
+        );
+    }
+
+    async fn warm_up_player(
+        &self,
+        entry: RenderRequestEntry,
+        modes: &[RenderRequestMode],
+    ) -> Result<()> {
+        // The player's textures are resolved (and cached) even if no modes are rendered.
+        let mut request = create_render_request(
+            self,
+            None,
+            RenderRequestMode::Skin,
+            entry,
+            RenderRequestQueryParams::default(),
+        )?;
+
+        let resolved = self.resolver.resolve(&request).await?;
+
+        for &mode in modes {
+            // Create the request the same way a plain request for this mode would be, so that it's cached the same way.
+            request = create_render_request(
+                self,
+                None,
+                mode,
+                request.entry,
+                RenderRequestQueryParams::default(),
+            )?;
+
+            render_model_with_cache(&request, self, &resolved, RenderClass::Batch).await?;
+        }
+
+        Ok(())
+    }
+}
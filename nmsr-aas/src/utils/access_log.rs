@@ -0,0 +1,256 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use serde::Serialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{routes::NMSRState, utils::client_ip::ClientIp};
+
+/// The tracing target access log entries are emitted with, which only the access log layer writes out.
+pub(crate) const ACCESS_LOG_TARGET: &str = "access_log";
+
+tokio::task_local! {
+    /// The access log entry of the request handled by the current task, when access logs are enabled.
+    static CURRENT_ENTRY: RefCell<AccessLogEntry>;
+}
+
+/// The steps of a render whose duration is logged.
+#[derive(Debug, Clone, Copy)]
+pub enum AccessLogTiming {
+    Resolve,
+    Render,
+    Encode,
+}
+
+/// A request, as logged in the access logs.
+#[derive(Debug, Default, Serialize)]
+pub struct AccessLogEntry {
+    /// When the request was received, in RFC 3339 format.
+    timestamp: String,
+    method: String,
+    /// The path of the request, without the query string (which might contain a URL signature).
+    path: String,
+    status: u16,
+    client_ip: Option<String>,
+    request_id: Option<String>,
+    #[serde(flatten)]
+    details: AccessLogDetails,
+    total_ms: f64,
+}
+
+/// The details of a request recorded while handling it, which requests that shared its render are logged with too.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccessLogDetails {
+    /// The mode name of the render (built-in or custom), if the request was for a render.
+    mode: Option<String>,
+    /// The UUID of the player the request was for, once resolved from their name if needed.
+    uuid: Option<Uuid>,
+    /// Whether the render was served from the render cache, if it was looked up there.
+    cache_hit: Option<bool>,
+    resolve_ms: Option<f64>,
+    render_ms: Option<f64>,
+    encode_ms: Option<f64>,
+}
+
+impl AccessLogDetails {
+    fn add_timing(&mut self, timing: AccessLogTiming, milliseconds: f64) {
+        let duration = match timing {
+            AccessLogTiming::Resolve => &mut self.resolve_ms,
+            AccessLogTiming::Render => &mut self.render_ms,
+            AccessLogTiming::Encode => &mut self.encode_ms,
+        };
+
+        *duration = Some(duration.unwrap_or_default() + milliseconds);
+    }
+}
+
+/// Records the details of the request handled by the current task in its access log entry.
+///
+/// Nothing is recorded when access logs are disabled, or outside of HTTP requests (e.g. warm-up renders).
+pub struct AccessLog;
+
+impl AccessLog {
+    fn record(record: impl FnOnce(&mut AccessLogDetails)) {
+        let _ = CURRENT_ENTRY.try_with(|entry| record(&mut entry.borrow_mut().details));
+    }
+
+    pub fn record_mode(mode: &str) {
+        Self::record(|details| details.mode = Some(mode.to_string()));
+    }
+
+    pub fn record_uuid(uuid: Uuid) {
+        Self::record(|details| details.uuid = Some(uuid));
+    }
+
+    pub fn record_cache_hit(hit: bool) {
+        Self::record(|details| details.cache_hit = Some(hit));
+    }
+
+    /// Records how long a step took, adding to the previous ones if a request goes through it more than once.
+    pub fn record_timing(timing: AccessLogTiming, elapsed: Duration) {
+        Self::record(|details| details.add_timing(timing, elapsed.as_secs_f64() * 1000.0));
+    }
+
+    /// Records the details captured by [`AccessLog::capture`], e.g. of a render this request waited for.
+    pub fn record_details(captured: &AccessLogDetails) {
+        Self::record(|details| {
+            details.mode = details.mode.take().or_else(|| captured.mode.clone());
+            details.uuid = details.uuid.or(captured.uuid);
+            details.cache_hit = details.cache_hit.or(captured.cache_hit);
+
+            let timings = [
+                (AccessLogTiming::Resolve, captured.resolve_ms),
+                (AccessLogTiming::Render, captured.render_ms),
+                (AccessLogTiming::Encode, captured.encode_ms),
+            ];
+
+            for (timing, milliseconds) in timings {
+                if let Some(milliseconds) = milliseconds {
+                    details.add_timing(timing, milliseconds);
+                }
+            }
+        });
+    }
+
+    /// Runs the given work, returning the details it recorded along with its output instead of recording them,
+    /// that way they can be recorded by every request sharing the work. Nothing is captured outside of requests
+    /// that are being logged.
+    pub async fn capture<F: Future>(work: F) -> (F::Output, Option<AccessLogDetails>) {
+        if CURRENT_ENTRY.try_with(|_| ()).is_err() {
+            return (work.await, None);
+        }
+
+        CURRENT_ENTRY
+            .scope(RefCell::default(), async move {
+                let output = work.await;
+                (output, Some(CURRENT_ENTRY.with(RefCell::take).details))
+            })
+            .await
+    }
+}
+
+/// Logs every request as a line of JSON, for log collectors like Loki or Elasticsearch.
+///
+/// Entries are emitted with the [`ACCESS_LOG_TARGET`] tracing target, which is written to the standard output
+/// on its own, without the usual log formatting.
+pub(crate) async fn log_access(
+    State(state): State<NMSRState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !*state.access_log.get() {
+        return next.run(request).await;
+    }
+
+    let started = Instant::now();
+
+    let entry = AccessLogEntry {
+        timestamp: Utc::now().to_rfc3339(),
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+        client_ip: request
+            .extensions()
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| ip.to_string()),
+        request_id: request
+            .headers()
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string),
+        ..Default::default()
+    };
+
+    let (response, mut entry) = CURRENT_ENTRY
+        .scope(RefCell::new(entry), async move {
+            let response = next.run(request).await;
+            (response, CURRENT_ENTRY.with(RefCell::take))
+        })
+        .await;
+
+    entry.status = response.status().as_u16();
+    entry.total_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    if let Ok(line) = serde_json::to_string(&entry) {
+        info!(target: ACCESS_LOG_TARGET, "{line}");
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, time::Duration};
+
+    use super::{AccessLog, AccessLogDetails, AccessLogEntry, AccessLogTiming, CURRENT_ENTRY};
+
+    #[tokio::test]
+    async fn details_are_recorded_for_the_current_request() {
+        // Outside of a request, there's nothing to record to.
+        AccessLog::record_mode("fullbody");
+
+        let entry = CURRENT_ENTRY
+            .scope(RefCell::new(AccessLogEntry::default()), async {
+                AccessLog::record_mode("fullbody");
+                AccessLog::record_cache_hit(false);
+                AccessLog::record_timing(AccessLogTiming::Encode, Duration::from_millis(2));
+                AccessLog::record_timing(AccessLogTiming::Encode, Duration::from_millis(3));
+
+                CURRENT_ENTRY.with(RefCell::take).details
+            })
+            .await;
+
+        assert_eq!(entry.mode.as_deref(), Some("fullbody"));
+        assert_eq!(entry.cache_hit, Some(false));
+        assert!(entry
+            .encode_ms
+            .is_some_and(|encode_ms| (encode_ms - 5.0).abs() < 1e-9));
+        assert_eq!(entry.render_ms, None);
+    }
+
+    #[tokio::test]
+    async fn captured_details_are_recorded_by_other_requests() {
+        // Outside of a request, there's nothing to capture.
+        let ((), captured) = AccessLog::capture(async { AccessLog::record_mode("face") }).await;
+        assert!(captured.is_none());
+
+        let (leader, captured) = CURRENT_ENTRY
+            .scope(RefCell::new(AccessLogEntry::default()), async {
+                let ((), captured) = AccessLog::capture(async {
+                    AccessLog::record_mode("face");
+                    AccessLog::record_timing(AccessLogTiming::Render, Duration::from_millis(4));
+                })
+                .await;
+
+                let captured = captured.unwrap();
+                AccessLog::record_details(&captured);
+
+                (CURRENT_ENTRY.with(RefCell::take).details, captured)
+            })
+            .await;
+
+        let follower = CURRENT_ENTRY
+            .scope(RefCell::new(AccessLogEntry::default()), async {
+                AccessLog::record_details(&captured);
+
+                CURRENT_ENTRY.with(RefCell::take).details
+            })
+            .await;
+
+        for details in [leader, follower] {
+            let AccessLogDetails { mode, render_ms, .. } = details;
+
+            assert_eq!(mode.as_deref(), Some("face"));
+            assert!(render_ms.is_some_and(|render_ms| (render_ms - 4.0).abs() < 1e-9));
+        }
+    }
+}
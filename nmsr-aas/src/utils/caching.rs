@@ -1,17 +1,45 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
     fs::Metadata,
     marker::PhantomData,
     path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
 };
 
 use async_trait::async_trait;
 use derive_more::Debug;
+use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tokio_stream::{wrappers::ReadDirStream, StreamExt};
-use tracing::{instrument, trace};
+use tracing::{debug, instrument, trace, warn};
 
-use crate::error::{ExplainableExt, Result};
+use crate::{
+    error::{ExplainableExt, Result},
+    utils::reloadable::Reloadable,
+};
+
+/// The extension of files that are still being written, see [`write_atomically`].
+const TEMPORARY_FILE_EXTENSION: &str = "tmp";
+
+/// Writes a file in the cache without leaving it half-written if we're interrupted.
+///
+/// The data is written to a temporary file next to it first, which is then renamed to the final path.
+/// Temporary files left over from an interrupted write are removed when the cache is loaded.
+pub(crate) async fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".");
+    temporary_path.push(TEMPORARY_FILE_EXTENSION);
+
+    fs::write(&temporary_path, data).await?;
+    fs::rename(&temporary_path, path).await
+}
+
+fn is_temporary_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == TEMPORARY_FILE_EXTENSION)
+}
 
 pub struct CacheSystem<Key, ResultEntry, Config, Marker, Handler>
 where
@@ -19,11 +47,86 @@ where
     Handler: CacheHandler<Key, ResultEntry, Config, Marker> + Sync,
 {
     base_path: PathBuf,
-    config: Config,
+    config: Reloadable<Config>,
     handler: Handler,
+    index: Mutex<CacheIndex>,
     _phantom: PhantomData<(ResultEntry, Marker, Key)>,
 }
 
+/// The limits to enforce on the size of a cache.
+/// Once a limit is exceeded, the least recently used entries are evicted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheLimits {
+    /// The maximum size of the cache, in bytes.
+    pub max_size: Option<u64>,
+    /// The maximum number of entries in the cache.
+    pub max_entries: Option<usize>,
+}
+
+impl CacheLimits {
+    const fn is_unlimited(&self) -> bool {
+        self.max_size.is_none() && self.max_entries.is_none()
+    }
+}
+
+/// Keeps track of the size and last access time of every entry in a cache.
+///
+/// The index is persisted next to the cache directory, that way the eviction order survives restarts.
+#[derive(Default, Serialize, Deserialize)]
+struct CacheIndex {
+    entries: HashMap<String, CacheIndexEntry>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct CacheIndexEntry {
+    size: u64,
+    last_access: SystemTime,
+}
+
+impl CacheIndex {
+    fn total_size(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    /// Removes the least recently used entries until the limits are respected, returning their names.
+    fn evict(&mut self, limits: CacheLimits) -> Vec<String> {
+        let mut total_size = self.total_size();
+        let mut total_entries = self.entries.len();
+
+        let is_over_limits = |size: u64, entries: usize| {
+            limits.max_size.is_some_and(|max| size > max)
+                || limits.max_entries.is_some_and(|max| entries > max)
+        };
+
+        if !is_over_limits(total_size, total_entries) {
+            return vec![];
+        }
+
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(name, entry)| (name.clone(), *entry))
+            .collect();
+        entries.sort_by_key(|(_, entry)| entry.last_access);
+
+        let mut evicted = Vec::new();
+
+        for (name, entry) in entries {
+            if !is_over_limits(total_size, total_entries) {
+                break;
+            }
+
+            total_size -= entry.size;
+            total_entries -= 1;
+
+            self.entries.remove(&name);
+            evicted.push(name);
+        }
+
+        evicted
+    }
+}
+
 #[async_trait]
 #[allow(unused_variables)]
 pub trait CacheHandler<Key, Value, Config, Marker>
@@ -99,6 +202,11 @@ where
     fn always_overwrite(&self) -> bool {
         false
     }
+
+    /// The limits to enforce on the size of the cache.
+    fn get_cache_limits(&self, config: &Config) -> CacheLimits {
+        CacheLimits::default()
+    }
 }
 
 impl<Key, ResultEntry, Config, Marker, Handler>
@@ -112,16 +220,222 @@ where
             .await
             .explain(format!("Unable to create cache directory {:?}", &base_path))?;
 
+        let index = Self::load_index(&base_path).await?;
+
         Ok(Self {
             base_path,
-            config,
+            config: Reloadable::new(config),
             handler,
+            index: Mutex::new(index),
             _phantom: PhantomData,
         })
     }
 
+    /// Replaces the configuration of this cache, e.g. when the configuration file is reloaded.
+    ///
+    /// Entries that are already cached are kept, they'll expire according to the new configuration.
+    pub fn set_config(&self, config: Config) {
+        self.config.set(config);
+    }
+
+    fn lock_index(&self) -> MutexGuard<'_, CacheIndex> {
+        self.index.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_index_path(base_path: &Path) -> PathBuf {
+        base_path.with_extension("index.json")
+    }
+
+    /// Builds the index from the entries currently in the cache directory.
+    ///
+    /// The access times are taken from the persisted index if we have one,
+    /// otherwise we fall back to the last modification time of each entry.
+    async fn load_index(base_path: &Path) -> Result<CacheIndex> {
+        let index_path = Self::get_index_path(base_path);
+
+        let persisted = fs::read(&index_path)
+            .await
+            .ok()
+            .and_then(|data| serde_json::from_slice::<CacheIndex>(&data).ok())
+            .unwrap_or_default();
+
+        let entries = fs::read_dir(base_path).await.explain(format!(
+            "Unable to read cache directory {}",
+            base_path.display()
+        ))?;
+
+        let mut stream = ReadDirStream::new(entries);
+        let mut index = CacheIndex::default();
+
+        while let Some(file) = stream.next().await {
+            let file = file.explain(format!(
+                "Unable to read cache entry while indexing {}",
+                base_path.display()
+            ))?;
+
+            let Some(name) = file.file_name().to_str().map(ToOwned::to_owned) else {
+                continue;
+            };
+
+            let path = file.path();
+
+            // Anything still being written when we were stopped can't be trusted.
+            if is_temporary_file(&path) {
+                fs::remove_file(&path).await.explain(format!(
+                    "Unable to remove unfinished cache entry {}",
+                    path.display()
+                ))?;
+                continue;
+            }
+            let size = Self::get_entry_size(&path).await?;
+
+            let last_access = match persisted.entries.get(&name) {
+                Some(entry) => entry.last_access,
+                None => fs::symlink_metadata(&path)
+                    .await
+                    .and_then(|m| m.modified())
+                    .unwrap_or_else(|_| SystemTime::now()),
+            };
+
+            index
+                .entries
+                .insert(name, CacheIndexEntry { size, last_access });
+        }
+
+        Ok(index)
+    }
+
+    async fn save_index(&self) -> Result<()> {
+        let index_path = Self::get_index_path(&self.base_path);
+
+        let data = {
+            let index = self.lock_index();
+            serde_json::to_vec(&*index).unwrap_or_default()
+        };
+
+        write_atomically(&index_path, &data).await.explain(format!(
+            "Unable to write cache index {}",
+            index_path.display()
+        ))
+    }
+
+    /// Persists everything kept in memory, so that nothing is lost when shutting down.
+    pub async fn flush(&self) -> Result<()> {
+        self.save_index().await
+    }
+
+    /// Returns the size of a cache entry on disk, without following symlinks.
+    async fn get_entry_size(path: &Path) -> Result<u64> {
+        let metadata = fs::symlink_metadata(path).await.explain(format!(
+            "Unable to read metadata for cache entry {}",
+            path.display()
+        ))?;
+
+        if !metadata.is_dir() {
+            return Ok(metadata.len());
+        }
+
+        let entries = fs::read_dir(path).await.explain(format!(
+            "Unable to read cache entry directory {}",
+            path.display()
+        ))?;
+
+        let mut stream = ReadDirStream::new(entries);
+        let mut size = 0;
+
+        while let Some(Ok(file)) = stream.next().await {
+            if let Ok(metadata) = fs::symlink_metadata(file.path()).await {
+                size += metadata.len();
+            }
+        }
+
+        Ok(size)
+    }
+
+    fn get_entry_name(path: &Path) -> Option<String> {
+        path.file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .map(ToOwned::to_owned)
+    }
+
+    fn touch_entry(&self, path: &Path) {
+        let Some(name) = Self::get_entry_name(path) else {
+            return;
+        };
+
+        let mut index = self.lock_index();
+
+        if let Some(entry) = index.entries.get_mut(&name) {
+            entry.last_access = SystemTime::now();
+        }
+    }
+
+    async fn index_entry(&self, path: &Path) -> Result<()> {
+        let Some(name) = Self::get_entry_name(path) else {
+            return Ok(());
+        };
+
+        let size = Self::get_entry_size(path).await?;
+
+        self.lock_index().entries.insert(
+            name,
+            CacheIndexEntry {
+                size,
+                last_access: SystemTime::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn forget_entry(&self, path: &Path) {
+        if let Some(name) = Self::get_entry_name(path) {
+            self.lock_index().entries.remove(&name);
+        }
+    }
+
+    /// Evicts the least recently used entries until the cache is within its limits.
+    #[instrument(skip(self))]
+    async fn enforce_limits(&self) -> Result<()> {
+        let limits = self.handler.get_cache_limits(&self.config.get());
+
+        if limits.is_unlimited() {
+            return Ok(());
+        }
+
+        let evicted = {
+            let mut index = self.lock_index();
+            index.evict(limits)
+        };
+
+        if !evicted.is_empty() {
+            debug!(
+                "Evicting {} entries from cache {}",
+                evicted.len(),
+                self.base_path.display()
+            );
+        }
+
+        for name in evicted {
+            let path = self.base_path.join(&name);
+
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path).await
+            } else {
+                fs::remove_file(&path).await
+            };
+
+            if let Err(err) = result {
+                // The entry might have been invalidated in the meantime, so this isn't fatal.
+                warn!("Unable to evict cache entry {}: {err}", path.display());
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn get_cache_entry_path(&self, entry: &Key) -> Result<Option<PathBuf>> {
-        let key = self.handler.get_cache_key(entry, &self.config).await?;
+        let key = self.handler.get_cache_key(entry, &self.config.get()).await?;
 
         Ok(key.map(|k| self.base_path.join(k)))
     }
@@ -144,11 +458,12 @@ where
 
             let result = self
                 .handler
-                .read_cache(entry, &self.config, &path, &marker)
+                .read_cache(entry, &self.config.get(), &path, &marker)
                 .await?;
 
             if result.is_some() {
                 trace!("Cache entry found.");
+                self.touch_entry(&path);
             } else {
                 trace!("Cache entry missing at path {}.", path.display());
             }
@@ -170,7 +485,7 @@ where
             return Ok(None);
         }
 
-        let marker_path = self.handler.get_marker_path(entry, &self.config).await?;
+        let marker_path = self.handler.get_marker_path(entry, &self.config.get()).await?;
         let marker_path = if marker_path.is_empty() {
             path.to_owned()
         } else {
@@ -183,7 +498,7 @@ where
         }
         let marker = self
             .handler
-            .read_marker(entry, &self.config, &marker_path)
+            .read_marker(entry, &self.config.get(), &marker_path)
             .await?;
         let marker_metadata = marker_path.metadata().explain(format!(
             "Unable to read marker for entry {:?} ({})",
@@ -193,11 +508,12 @@ where
 
         let is_expired = self
             .handler
-            .is_expired(entry, &self.config, &marker, marker_metadata)?;
+            .is_expired(entry, &self.config.get(), &marker, marker_metadata)?;
 
         if is_expired {
             trace!("Entry is expired, discarding.");
             Self::invalidate_self(entry, path).await?;
+            self.forget_entry(path);
 
             return Ok(None);
         }
@@ -225,6 +541,57 @@ where
         Ok(())
     }
 
+    /// Removes an entry from the cache, regardless of whether it has expired.
+    /// Returns whether the entry was in the cache.
+    pub async fn invalidate_entry(&self, entry: &Key) -> Result<bool> {
+        let Some(path) = self.get_cache_entry_path(entry).await? else {
+            return Ok(false);
+        };
+
+        if path.symlink_metadata().is_err() {
+            return Ok(false);
+        }
+
+        Self::invalidate_self(entry, &path).await?;
+        self.forget_entry(&path);
+
+        Ok(true)
+    }
+
+    /// Removes every entry from the cache.
+    #[instrument(skip(self))]
+    pub async fn invalidate_all(&self) -> Result<()> {
+        let entries = fs::read_dir(&self.base_path).await.explain(format!(
+            "Unable to read cache directory {}",
+            &self.base_path.display()
+        ))?;
+
+        let mut stream = ReadDirStream::new(entries);
+
+        while let Some(file) = stream.next().await {
+            let path = file
+                .explain(format!(
+                    "Unable to read cache entry while invalidating {}",
+                    &self.base_path.display()
+                ))?
+                .path();
+
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path).await
+            } else {
+                fs::remove_file(&path).await
+            };
+
+            result.explain(format!(
+                "Unable to invalidate cache entry {}",
+                path.display()
+            ))?;
+        }
+
+        self.lock_index().entries.clear();
+        self.save_index().await
+    }
+
     pub async fn set_cache_entry(
         &self,
         entry: &Key,
@@ -234,10 +601,11 @@ where
 
         if let Some(path) = &path {
             if path.exists() && !self.handler.always_overwrite() {
+                self.touch_entry(path);
                 return Ok(Some(path.clone()));
             }
 
-            let marker_path = self.handler.get_marker_path(entry, &self.config).await?;
+            let marker_path = self.handler.get_marker_path(entry, &self.config.get()).await?;
             let marker_path = if marker_path.is_empty() {
                 path.to_owned()
             } else {
@@ -245,12 +613,15 @@ where
             };
 
             self.handler
-                .write_cache(entry, value, &self.config, path)
+                .write_cache(entry, value, &self.config.get(), path)
                 .await?;
 
             self.handler
-                .write_marker(entry, value, &self.config, &marker_path)
+                .write_marker(entry, value, &self.config.get(), &marker_path)
                 .await?;
+
+            self.index_entry(path).await?;
+            self.enforce_limits().await?;
         }
 
         Ok(path)
@@ -272,13 +643,40 @@ where
 
             let path = file.path();
 
-            if let Some(key) = self.handler.read_key_from_path(&self.config, &path).await? {
+            // Entries being written right now are none of our business.
+            if is_temporary_file(&path) {
+                continue;
+            }
+
+            if let Some(key) = self.handler.read_key_from_path(&self.config.get(), &path).await? {
                 let _ = self
                     .get_marker_and_clean_expired_if_needed(&key, &path)
                     .await?;
             }
         }
 
+        // Entries might have been invalidated without going through us, so forget about those.
+        // The index is only locked once we know which are gone, it's used by every cache lookup in the meantime.
+        let names: Vec<String> = self.lock_index().entries.keys().cloned().collect();
+        let mut missing = Vec::new();
+
+        for name in names {
+            if fs::symlink_metadata(self.base_path.join(&name)).await.is_err() {
+                missing.push(name);
+            }
+        }
+
+        if !missing.is_empty() {
+            let mut index = self.lock_index();
+
+            for name in &missing {
+                index.entries.remove(name);
+            }
+        }
+
+        self.enforce_limits().await?;
+        self.save_index().await?;
+
         Ok(())
     }
 }
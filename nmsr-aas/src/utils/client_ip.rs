@@ -0,0 +1,274 @@
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use hyper::{
+    header::{HeaderName, FORWARDED},
+    HeaderMap,
+};
+
+use crate::{config::TrustedProxiesConfiguration, routes::NMSRState};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const X_FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// A range of IP addresses in CIDR notation (e.g. `173.245.48.0/20`), or a single address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(address), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+
+                u32::from(address) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(address), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+
+                u128::from(address) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = value
+            .split_once('/')
+            .map_or((value, None), |(address, prefix_len)| {
+                (address, Some(prefix_len))
+            });
+
+        let address = IpAddr::from_str(address)
+            .map_err(|err| format!("Invalid address in IP range {value}: {err}"))?
+            .to_canonical();
+
+        let max_prefix_len = if address.is_ipv4() { 32 } else { 128 };
+
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_len| *prefix_len <= max_prefix_len)
+                .ok_or_else(|| format!("Invalid prefix length in IP range {value}"))?,
+            None => max_prefix_len,
+        };
+
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// The address of the client that made a request, as seen through the trusted proxies in front of us.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// The proxies whose forwarding headers (`Forwarded` and `X-Forwarded-For`) are trusted.
+#[derive(Default)]
+pub struct TrustedProxies {
+    ranges: Vec<IpRange>,
+}
+
+impl TrustedProxies {
+    #[must_use]
+    pub fn new(config: Option<&TrustedProxiesConfiguration>) -> Self {
+        Self {
+            ranges: config
+                .map(|config| config.addresses.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Finds the address of the client a request was made by.
+    ///
+    /// Each proxy appends the address it got the request from to the forwarding headers, so they're walked back for as
+    /// long as the addresses belong to trusted proxies. Anything before that could've been made up by the client.
+    #[must_use]
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer;
+
+        for ip in Self::forwarded_for(headers).into_iter().rev() {
+            if !self.is_trusted(client) {
+                break;
+            }
+
+            // Proxies can hide the addresses they forward for, in which case we can't go any further.
+            let Some(ip) = ip else {
+                break;
+            };
+
+            client = ip;
+        }
+
+        client
+    }
+
+    /// Returns the addresses of the forwarding headers, from the first proxy to the last one.
+    /// The standard `Forwarded` header is preferred over the `X-Forwarded-For` header when both are present.
+    fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+        let values = |header| {
+            headers
+                .get_all(header)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .collect::<Vec<_>>()
+        };
+
+        let forwarded = values(FORWARDED);
+
+        if forwarded.is_empty() {
+            return values(X_FORWARDED_FOR_HEADER)
+                .into_iter()
+                .map(Self::parse_node)
+                .collect();
+        }
+
+        forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| Self::parse_node(node.trim().trim_matches('"')))
+            })
+            .collect()
+    }
+
+    /// Parses the address of a node, which can have a port (e.g. `192.0.2.43:47011` or `[2001:db8:cafe::17]:4711`).
+    fn parse_node(node: &str) -> Option<IpAddr> {
+        if let Ok(ip) = IpAddr::from_str(node) {
+            return Some(ip);
+        }
+
+        SocketAddr::from_str(node)
+            .ok()
+            .map(|addr| addr.ip())
+            .or_else(|| {
+                // IPv6 addresses are enclosed in brackets in the Forwarded header, even without a port.
+                let ip = node.strip_prefix('[')?.strip_suffix(']')?;
+                IpAddr::from_str(ip).ok()
+            })
+    }
+}
+
+/// Finds out the address of the client behind the trusted proxies, for the rate limiter and the logs.
+pub(crate) async fn resolve_client_ip(
+    State(state): State<NMSRState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let client_ip = state
+        .trusted_proxies
+        .get()
+        .client_ip(addr.ip(), request.headers());
+
+    request.extensions_mut().insert(ClientIp(client_ip));
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use hyper::{header::FORWARDED, HeaderMap};
+
+    use super::{IpRange, TrustedProxies};
+
+    fn create_trusted_proxies(ranges: &[&str]) -> TrustedProxies {
+        TrustedProxies {
+            ranges: ranges.iter().map(|range| range.parse().unwrap()).collect(),
+        }
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ip_ranges_are_matched() {
+        let range: IpRange = "173.245.48.0/20".parse().unwrap();
+
+        assert!(range.contains(ip("173.245.63.255")));
+        assert!(range.contains(ip("::ffff:173.245.48.1")));
+        assert!(!range.contains(ip("173.245.64.0")));
+
+        assert!("2400:cb00::/32"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains(ip("2400:cb00:1::1")));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn client_ips_are_taken_from_trusted_proxies() {
+        let proxies = create_trusted_proxies(&["10.0.0.0/8"]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "1.1.1.1, 203.0.113.7, 10.0.0.2".parse().unwrap(),
+        );
+
+        // The first address that isn't a trusted proxy is the client, anything before it can't be trusted.
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), &headers),
+            ip("203.0.113.7")
+        );
+
+        // Clients that aren't trusted proxies can't pretend to be someone else.
+        assert_eq!(
+            proxies.client_ip(ip("192.0.2.1"), &headers),
+            ip("192.0.2.1")
+        );
+
+        headers.insert(
+            FORWARDED,
+            r#"for=192.0.2.60;proto=https, for="[2001:db8:cafe::17]:4711""#
+                .parse()
+                .unwrap(),
+        );
+
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), &headers),
+            ip("2001:db8:cafe::17")
+        );
+    }
+}
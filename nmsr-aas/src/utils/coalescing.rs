@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tokio::sync::OnceCell;
+
+/// Lets concurrent requests for the same thing share a single piece of work (e.g. a render), instead of each of
+/// them doing it on their own. Popular players can get dozens of identical requests at once.
+///
+/// If the request doing the work is cancelled (e.g. because its client disconnected), one of the requests waiting
+/// for it takes over.
+pub struct RequestCoalescer<K, V> {
+    in_flight: Mutex<HashMap<K, Arc<OnceCell<V>>>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> RequestCoalescer<K, V> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::default(),
+        }
+    }
+
+    /// Runs the work for the given key, unless it's already being run, in which case its result is shared with us.
+    pub async fn run<F: Future<Output = V>>(&self, key: K, work: impl FnOnce() -> F) -> V {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let guard = InFlightGuard {
+            coalescer: self,
+            key,
+            cell,
+        };
+
+        guard.cell.get_or_init(work).await.clone()
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for RequestCoalescer<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Forgets about the work once it's done, or once nobody is waiting for it anymore, that way later requests
+/// start over instead of getting a stale result.
+struct InFlightGuard<'a, K: Eq + Hash, V> {
+    coalescer: &'a RequestCoalescer<K, V>,
+    key: K,
+    cell: Arc<OnceCell<V>>,
+}
+
+impl<K: Eq + Hash, V> Drop for InFlightGuard<'_, K, V> {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .coalescer
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let Some(cell) = in_flight.get(&self.key) else {
+            return;
+        };
+
+        // The cell is held by the map and by us, anyone else waiting for it has their own reference to it.
+        if Arc::ptr_eq(cell, &self.cell)
+            && (self.cell.initialized() || Arc::strong_count(&self.cell) <= 2)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::RequestCoalescer;
+
+    #[tokio::test]
+    async fn concurrent_requests_share_the_work() {
+        let coalescer = Arc::new(RequestCoalescer::<&str, usize>::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let requests = (0..8)
+            .map(|_| {
+                let coalescer = coalescer.clone();
+                let runs = runs.clone();
+
+                tokio::spawn(async move {
+                    coalescer
+                        .run("Notch", || async {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            runs.fetch_add(1, Ordering::SeqCst) + 1
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for request in requests {
+            assert_eq!(request.await.unwrap(), 1);
+        }
+
+        // Once the work is done, the next request starts over.
+        assert_eq!(coalescer.run("Notch", || async { 2 }).await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_work_is_taken_over() {
+        let coalescer = Arc::new(RequestCoalescer::<&str, usize>::new());
+
+        let cancelled = tokio::spawn({
+            let coalescer = coalescer.clone();
+            async move { coalescer.run("Notch", std::future::pending).await }
+        });
+
+        tokio::task::yield_now().await;
+
+        let waiting = tokio::spawn({
+            let coalescer = coalescer.clone();
+            async move { coalescer.run("Notch", || async { 1 }).await }
+        });
+
+        tokio::task::yield_now().await;
+        cancelled.abort();
+
+        assert_eq!(waiting.await.unwrap(), 1);
+    }
+}
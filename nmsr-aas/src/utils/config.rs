@@ -1,21 +1,33 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::Metadata,
+    hash::{Hash, Hasher},
     path::PathBuf,
     time::{Duration, SystemTime},
 };
 
 use chrono::{DateTime, Local};
 use derive_more::Debug;
+use http::Method;
+use nmsr_rendering::high_level::{
+    parts::provider::shoulder_buddies::ShoulderBuddies,
+    pipeline::{AdapterSelector, Backend, Backends, ColorSpace, PowerPreference},
+    types::PlayerBodyPartType,
+};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr, TryFromInto};
 use tracing::trace;
 use twelf::config;
+use xxhash_rust::xxh3::Xxh3;
 
 use crate::{
-    error::ExplainableExt,
+    caching::CacheLimits,
+    routes::query::RenderRequestQueryParams,
+    utils::{client_ip::IpRange, listener::ListenAddress},
+    error::{ExplainableExt, NMSRaaSError, Result},
     model::request::{
-        cache::CacheBias, entry::RenderRequestEntry, RenderRequestFeatures, RenderRequestMode,
+        background::RenderRequestBackground, cache::CacheBias, entry::RenderRequestEntry,
+        RenderRequest, RenderRequestFeatures, RenderRequestMode, RenderRequestProjection,
     },
 };
 
@@ -28,6 +40,93 @@ pub struct NmsrConfiguration {
     pub mojank: MojankConfiguration,
     pub rendering: Option<RenderingConfiguration>,
     pub features: Option<FeaturesConfiguration>,
+    pub export: Option<ExportConfiguration>,
+    pub admin: Option<AdminConfiguration>,
+    pub live_preview: Option<LivePreviewConfiguration>,
+    pub fallback_skin: Option<FallbackSkinConfiguration>,
+    pub access_lists: Option<AccessListsConfiguration>,
+    pub scheduler: Option<RenderSchedulerConfiguration>,
+    /// The profiles selected by the host requests are made to, by name.
+    pub profiles: Option<BTreeMap<String, ProfileConfiguration>>,
+    /// The values each mode is rendered with instead of the built-in ones, by mode name.
+    pub modes: Option<HashMap<String, ModeOverridesConfiguration>>,
+    /// The modes defined in addition to the built-in ones, by name.
+    pub custom_modes: Option<HashMap<String, CustomModeConfiguration>>,
+    /// The background images renders can be composited on, by name.
+    pub backgrounds: Option<HashMap<String, PathBuf>>,
+    pub determinism: Option<DeterminismConfiguration>,
+    pub warmup: Option<WarmupConfiguration>,
+    pub grpc: Option<GrpcConfiguration>,
+    pub graphql: Option<GraphQlConfiguration>,
+    pub avif: Option<AvifConfiguration>,
+    pub embed: Option<EmbedConfiguration>,
+}
+
+impl NmsrConfiguration {
+    /// Checks the settings that can't be checked while they're deserialized, e.g. because they depend on each other.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(rendering) = &self.rendering {
+            rendering.camera.validate()?;
+        }
+
+        let jitter = self.caching.cache_duration_jitter;
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(NMSRaaSError::InvalidConfiguration(format!(
+                "the cache duration jitter ({jitter}) has to be between 0 and 1"
+            )));
+        }
+
+        let custom_modes = self.custom_modes.as_ref();
+        let is_custom_mode = |name: &str| custom_modes.is_some_and(|modes| modes.contains_key(name));
+
+        // Built-in modes always take precedence, so a custom mode with the same name would never be used.
+        if let Some(name) = custom_modes
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .find(|name| RenderRequestMode::try_from(name.as_str()).is_ok())
+        {
+            return Err(NMSRaaSError::InvalidConfiguration(format!(
+                "the custom mode {name} can't have the name of a built-in mode"
+            )));
+        }
+
+        // Cache durations can be given to custom modes too, so they have to be checked against both.
+        if let Some(name) = self.caching.mode_cache_durations.keys().find(|name| {
+            RenderRequestMode::try_from(name.as_str()).is_err() && !is_custom_mode(name)
+        }) {
+            return Err(NMSRaaSError::InvalidConfiguration(format!(
+                "the cache duration of the mode {name} is set, but there's no such mode"
+            )));
+        }
+
+        let mut profile_hosts = HashMap::new();
+
+        for (name, profile) in self.profiles.iter().flatten() {
+            // A host can only belong to a single profile, otherwise which one applies would be ambiguous.
+            for host in &profile.hosts {
+                if let Some(other) = profile_hosts.insert(host.to_ascii_lowercase(), name) {
+                    return Err(NMSRaaSError::InvalidConfiguration(format!(
+                        "the host {host} belongs to both the {other} and {name} profiles"
+                    )));
+                }
+            }
+
+            if let Some(RenderRequestBackground::Image(background)) = &profile.defaults.background {
+                let is_configured = self
+                    .backgrounds
+                    .as_ref()
+                    .is_some_and(|backgrounds| backgrounds.contains_key(background));
+
+                if !is_configured {
+                    return Err(NMSRaaSError::InvalidConfiguration(format!(
+                        "the default background of the {name} profile ({background}) isn't a configured background image"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[serde_as]
@@ -52,11 +151,40 @@ pub struct ModelCacheConfiguration {
     #[serde(with = "humantime_serde")]
     pub texture_cache_duration: Duration,
 
+    /// The duration of time to keep a player name -> UUID lookup in memory.
+    /// Players can change their names, so this should be kept short.
+    #[serde(with = "humantime_serde")]
+    pub player_name_cache_duration: Duration,
+
+    /// The fraction (between 0 and 1) of the cache durations that can be taken off each entry.
+    /// Entries cached at the same time (e.g. during a burst of requests) then expire at different times,
+    /// instead of all being fetched or rendered again at once.
+    pub cache_duration_jitter: f32,
+
     /// Cache biases for specific entries.
     /// A cache bias is a duration of time to keep a specific entry in the cache.
     /// This is useful for entries that are requested often, such as the models in the home page.
     #[serde_as(as = "HashMap<TryFromInto<String>, TryFromInto<String>>")]
     pub cache_biases: HashMap<RenderRequestEntry, CacheBias>,
+
+    /// The duration of time to keep the renders of a mode cached, by the name of the mode (built-in or custom).
+    /// This replaces the default duration, but the cache biases of specific entries still take precedence.
+    /// This is useful for modes that are requested far more often than others, such as faces used as avatars.
+    #[serde_as(as = "HashMap<_, TryFromInto<String>>")]
+    pub mode_cache_durations: HashMap<String, CacheBias>,
+
+    /// The maximum size in bytes of each of the caches on disk (textures and resolved models).
+    /// When exceeded, the least recently used entries are evicted.
+    pub max_cache_size: Option<u64>,
+
+    /// The maximum number of entries in each of the caches on disk (textures and resolved models).
+    /// When exceeded, the least recently used entries are evicted.
+    pub max_cache_entries: Option<usize>,
+
+    /// The S3-compatible object storage to use for caching rendered images.
+    /// When set, rendered images are shared between every instance using the same bucket
+    /// and survive restarts.
+    pub s3: Option<S3CacheConfiguration>,
 }
 
 impl Default for ModelCacheConfiguration {
@@ -65,7 +193,61 @@ impl Default for ModelCacheConfiguration {
             cleanup_interval: Duration::from_secs(60 * 60),
             resolve_cache_duration: Duration::from_secs(60 * 60 * 15),
             texture_cache_duration: Duration::from_secs(60 * 60 * 24 * 2),
+            player_name_cache_duration: Duration::from_mins(5),
+            cache_duration_jitter: 0.1,
             cache_biases: HashMap::new(),
+            mode_cache_durations: HashMap::new(),
+            max_cache_size: None,
+            max_cache_entries: None,
+            s3: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct S3CacheConfiguration {
+    /// The bucket to store the rendered images in.
+    pub bucket: String,
+
+    /// The prefix to prepend to the key of every stored render.
+    pub prefix: String,
+
+    /// The region the bucket is located in.
+    pub region: String,
+
+    /// The endpoint of the S3-compatible service.
+    /// Leave empty to use Amazon S3.
+    pub endpoint: Option<String>,
+
+    /// The access key id to authenticate with.
+    pub access_key_id: String,
+
+    /// The secret access key to authenticate with.
+    #[debug(skip)]
+    pub secret_access_key: String,
+
+    /// Whether to allow plain HTTP connections to the endpoint.
+    /// This is useful for local S3-compatible services, such as `MinIO`.
+    pub allow_http: bool,
+
+    /// The duration of time to keep a rendered image in the cache.
+    /// Cache biases are also applied to rendered images.
+    #[serde(with = "humantime_serde")]
+    pub render_cache_duration: Duration,
+}
+
+impl Default for S3CacheConfiguration {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            prefix: "renders".to_string(),
+            region: "us-east-1".to_string(),
+            endpoint: None,
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            allow_http: false,
+            render_cache_duration: Duration::from_secs(60 * 60 * 24),
         }
     }
 }
@@ -79,10 +261,32 @@ pub struct MojankConfiguration {
     /// The textures server to use for downloading player textures.
     pub textures_server: String,
 
+    /// The Mojang API server to use for resolving player names to UUIDs.
+    pub api_server: String,
+
     pub geysermc_api_server: String,
 
     /// The rate limit to use for requests to the session server in a 1 second window.
     pub session_server_rate_limit: u64,
+
+    /// Whether to resolve players using Mojang's servers.
+    /// Disable this to only use the skin servers configured below.
+    pub use_mojang: bool,
+
+    /// The priority of Mojang's servers amongst the skin servers.
+    pub mojang_priority: i32,
+
+    /// Additional skin servers to resolve players from, such as ones compatible with authlib-injector.
+    /// Skin servers are queried in order of priority (lowest first) until one of them knows the player.
+    pub skin_servers: Vec<SkinServerConfiguration>,
+
+    /// Whether to accept the (version 3) UUIDs of offline-mode players, which Mojang doesn't know about.
+    /// Offline players get the default skin the game gives them, unless the offline profile URL knows about them.
+    pub allow_offline_players: bool,
+
+    /// The URL template used to get the game profile of an offline-mode player, e.g. from the skin plugin of a server.
+    /// `{uuid}` is replaced with the player's UUID (without dashes).
+    pub offline_profile_url: Option<String>,
 }
 
 impl Default for MojankConfiguration {
@@ -90,27 +294,179 @@ impl Default for MojankConfiguration {
         Self {
             session_server: "https://sessionserver.mojang.com/".to_string(),
             textures_server: "https://textures.minecraft.net".to_string(),
+            api_server: "https://api.mojang.com".to_string(),
             geysermc_api_server: "https://api.geysermc.org/".to_string(),
             session_server_rate_limit: 10,
+            use_mojang: true,
+            mojang_priority: 0,
+            skin_servers: Vec::new(),
+            allow_offline_players: false,
+            offline_profile_url: None,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkinServerConfiguration {
+    /// The name of the skin server, used when logging.
+    pub name: String,
+
+    /// The priority of the skin server, lower priorities are queried first.
+    #[serde(default)]
+    pub priority: i32,
+
+    /// The URL template used to get the game profile of a player.
+    /// `{uuid}` is replaced with the player's UUID (without dashes).
+    pub profile_url: String,
+
+    /// The URL template used to resolve a player name into a UUID, if supported by the skin server.
+    /// `{name}` is replaced with the player's name.
+    pub name_url: Option<String>,
+}
+
+#[serde_as]
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ServerConfiguration {
     /// The address to bind the server to.
     pub address: String,
     /// The port to bind the server to.
     pub port: u16,
+    /// The addresses (TCP or Unix sockets) to listen on instead of the address and port above.
+    #[serde(default)]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub listen: Vec<ListenAddress>,
     /// The static files directory to serve.
     pub static_files_directory: Option<PathBuf>,
+    /// The rate limit to apply to each anonymous client.
+    pub rate_limit: Option<RateLimitConfiguration>,
+    /// The API keys clients can use to get their own rate limits.
+    pub api_keys: Option<ApiKeysConfiguration>,
+    /// The secret render URLs need to be signed with, unsigned render requests are rejected when set.
+    pub url_signing: Option<UrlSigningConfiguration>,
+    /// How long to wait for in-flight renders to finish when shutting down, before exiting anyway.
+    #[serde(
+        default = "ServerConfiguration::default_shutdown_timeout",
+        with = "humantime_serde"
+    )]
+    pub shutdown_timeout: Duration,
+    /// The proxies in front of this instance, which the address of clients is taken from.
+    pub trusted_proxies: Option<TrustedProxiesConfiguration>,
+    /// Whether to log every request as a line of JSON on the standard output.
+    #[serde(default)]
+    pub access_log: bool,
+    /// The origins browsers are allowed to make requests from, any origin is allowed when not set.
+    pub cors: Option<CorsConfiguration>,
 }
+
+impl ServerConfiguration {
+    const fn default_shutdown_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+}
+
 impl Default for ServerConfiguration {
     fn default() -> Self {
         Self {
             address: "0.0.0.0".to_string(),
             port: 8080,
+            listen: Vec::new(),
             static_files_directory: None,
+            rate_limit: None,
+            api_keys: None,
+            url_signing: None,
+            shutdown_timeout: Self::default_shutdown_timeout(),
+            trusted_proxies: None,
+            access_log: false,
+            cors: None,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CorsConfiguration {
+    /// The origins (e.g. `https://example.com`) browsers can make requests from, or `*` for any origin.
+    pub allowed_origins: Vec<String>,
+    /// The HTTP methods browsers can make requests with.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub allowed_methods: Vec<Method>,
+    /// How long browsers can cache the result of preflight requests for.
+    #[serde(with = "humantime_serde")]
+    pub max_age: Option<Duration>,
+}
+
+impl Default for CorsConfiguration {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_methods: vec![Method::GET, Method::HEAD, Method::POST],
+            max_age: None,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TrustedProxiesConfiguration {
+    /// The addresses (or CIDR ranges) of the proxies in front of this instance, e.g. Cloudflare's.
+    /// The address of clients is taken from the `Forwarded` or `X-Forwarded-For` headers of the requests they forward.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub addresses: Vec<IpRange>,
+    /// Whether connections start with a PROXY protocol (version 1 or 2) header, like the ones sent by HAProxy.
+    /// Every connection needs to have one, so this should only be enabled when the proxies are the only ones able to connect.
+    /// This can't be changed without a restart.
+    pub proxy_protocol: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UrlSigningConfiguration {
+    /// The secret shared with the sites allowed to embed renders, used to sign their URLs.
+    #[debug(skip)]
+    pub secret: String,
+    /// How long the URLs signed by this instance (e.g. the render URLs returned by GraphQL) stay valid.
+    /// When not set, they never expire. Renders of URLs that expire are only cached until they do.
+    #[serde(default, with = "humantime_serde")]
+    pub lifetime: Option<Duration>,
+    /// The token that GraphQL and gRPC clients need to send as a bearer token, since they can render (or get signed
+    /// URLs for) anything. When not set, they can't be used while URLs need to be signed.
+    #[debug(skip)]
+    pub token: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ApiKeysConfiguration {
+    /// The API keys, by name.
+    pub keys: HashMap<String, ApiKeyConfiguration>,
+    /// A JSON file to load more API keys from, in the same format as `keys`.
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKeyConfiguration {
+    /// The key clients send in the X-API-Key header.
+    #[debug(skip)]
+    pub key: String,
+    /// The rate limit to apply to this key. If not specified, the key isn't rate limited.
+    pub rate_limit: Option<RateLimitConfiguration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct RateLimitConfiguration {
+    /// The number of requests a client can make per second, once its burst is used up.
+    pub requests_per_second: u32,
+    /// The number of requests a client can make at once.
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfiguration {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 10,
+            burst: 20,
         }
     }
 }
@@ -122,14 +478,593 @@ pub struct TracingConfiguration {
     /// The service name to use for traces.
     #[serde(default = "default_service_name")]
     pub service_name: String,
+    /// Where to export metrics to, metrics aren't exported unless this is set.
+    pub metrics: Option<MetricsConfiguration>,
 }
 
-#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MetricsConfiguration {
+    /// The OpenTelemetry endpoint to send metrics to.
+    pub endpoint: String,
+    /// The interval of time between metric exports.
+    #[serde(with = "humantime_serde", default = "default_metrics_interval")]
+    pub interval: Duration,
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
 pub struct RenderingConfiguration {
     /// The number of MSAA samples to use when rendering.
     pub sample_count: u32,
     /// Whether to use SMAA.
     pub use_smaa: bool,
+    /// Whether to smooth out edges with FXAA, a cheaper post-processing pass, when SMAA is disabled.
+    #[serde(default)]
+    pub fxaa: bool,
+    /// Whether to render shoulder buddies drawn in the unused regions of a skin's hat layer.
+    /// Shoulder buddies are disabled unless this is set.
+    pub shoulder_buddies: Option<ShoulderBuddiesConfiguration>,
+    /// Whether to keep serving the modes that don't need the renderer (such as skin, or plain faces which are drawn flat)
+    /// when no graphics adapter is available.
+    /// When disabled, the server refuses to start without a renderer.
+    #[serde(default)]
+    pub texture_only_fallback: bool,
+    /// Whether to render on the CPU when no graphics adapter is available, instead of refusing to start.
+    /// This takes precedence over `texture_only_fallback`.
+    #[serde(default)]
+    pub software_fallback: bool,
+    /// The maximum width of a render, overriding the default maximum of each mode.
+    #[serde(default)]
+    pub max_render_width: Option<u32>,
+    /// The maximum height of a render, overriding the default maximum of each mode.
+    #[serde(default)]
+    pub max_render_height: Option<u32>,
+    /// The ranges the camera settings of a request are clamped to.
+    #[serde(default)]
+    pub camera: CameraLimitsConfiguration,
+    /// Which graphics adapter (GPU) to render with.
+    #[serde(default)]
+    pub adapter: AdapterConfiguration,
+    /// How long a render can take once it has started before it's cancelled, and a 503 is returned instead.
+    /// This stops a render that wedged the GPU from holding up everyone else. When not set, renders never time out.
+    #[serde(default, with = "humantime_serde")]
+    pub render_timeout: Option<Duration>,
+    /// The color space to light renders in, so they look the same whatever the adapter's preferred formats are.
+    #[serde(default)]
+    pub color_space: Option<RenderColorSpace>,
+}
+
+/// The color space renders are lit in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl From<RenderColorSpace> for ColorSpace {
+    fn from(color_space: RenderColorSpace) -> Self {
+        match color_space {
+            RenderColorSpace::Srgb => Self::Srgb,
+            RenderColorSpace::Linear => Self::Linear,
+        }
+    }
+}
+
+/// A graphics API that adapters can be used through.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl From<GraphicsBackend> for Backends {
+    fn from(backend: GraphicsBackend) -> Self {
+        Backend::from(backend).into()
+    }
+}
+
+impl From<GraphicsBackend> for Backend {
+    fn from(backend: GraphicsBackend) -> Self {
+        match backend {
+            GraphicsBackend::Vulkan => Self::Vulkan,
+            GraphicsBackend::Metal => Self::Metal,
+            GraphicsBackend::Dx12 => Self::Dx12,
+            GraphicsBackend::Gl => Self::Gl,
+        }
+    }
+}
+
+/// The kind of adapter to prefer when there's more than one.
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdapterPowerPreference {
+    #[default]
+    None,
+    LowPower,
+    HighPerformance,
+}
+
+impl From<AdapterPowerPreference> for PowerPreference {
+    fn from(preference: AdapterPowerPreference) -> Self {
+        match preference {
+            AdapterPowerPreference::None => Self::None,
+            AdapterPowerPreference::LowPower => Self::LowPower,
+            AdapterPowerPreference::HighPerformance => Self::HighPerformance,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AdapterConfiguration {
+    /// The backends to look for adapters in, in order of preference, all of them if empty.
+    /// When none of a backend's adapters fit, the next backend is tried.
+    pub backends: Vec<GraphicsBackend>,
+    /// Use the first adapter whose name contains this, case-insensitively.
+    pub name: Option<String>,
+    /// Use the adapter at this index, amongst the adapters found (that match the name, if given).
+    pub index: Option<usize>,
+    /// The kind of adapter to prefer when neither a name nor an index is given.
+    pub power_preference: AdapterPowerPreference,
+    /// Whether to use a fallback (software) adapter, even if a GPU is available.
+    pub force_fallback: bool,
+}
+
+impl AdapterConfiguration {
+    #[must_use]
+    pub fn backends(&self) -> Backends {
+        if self.backends.is_empty() {
+            return Backends::all();
+        }
+
+        self.backends
+            .iter()
+            .fold(Backends::empty(), |backends, &backend| {
+                backends | backend.into()
+            })
+    }
+
+    #[must_use]
+    pub fn selector(&self) -> AdapterSelector {
+        AdapterSelector {
+            backend_preference: self
+                .backends
+                .iter()
+                .map(|&backend| backend.into())
+                .collect(),
+            name: self.name.clone(),
+            index: self.index,
+            power_preference: self.power_preference.into(),
+            force_fallback_adapter: self.force_fallback,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct CameraLimitsConfiguration {
+    /// The minimum distance offset of the camera.
+    pub min_distance: f32,
+    /// The maximum distance offset of the camera.
+    pub max_distance: f32,
+    /// The minimum field of view of the camera, in degrees.
+    pub min_fov: f32,
+    /// The maximum field of view of the camera, in degrees.
+    pub max_fov: f32,
+}
+
+impl Default for CameraLimitsConfiguration {
+    fn default() -> Self {
+        Self {
+            min_distance: -5.0,
+            max_distance: 30.0,
+            min_fov: 10.0,
+            max_fov: 120.0,
+        }
+    }
+}
+
+impl CameraLimitsConfiguration {
+    /// The range the field of view can be limited to, past which the projection breaks down.
+    const FOV_RANGE: (f32, f32) = (1.0, 179.0);
+
+    /// Clamps the camera settings of a request to the limits, before the request is validated.
+    pub fn clamp(&self, query: &mut RenderRequestQueryParams) {
+        // Not using f32::clamp, since it panics if the configured minimum is greater than the maximum.
+        let clamp = |value: &mut Option<f32>, min: f32, max: f32| {
+            if let Some(value) = value {
+                *value = value.max(min).min(max);
+            }
+        };
+
+        clamp(&mut query.distance, self.min_distance, self.max_distance);
+        clamp(&mut query.fov, self.min_fov, self.max_fov);
+    }
+
+    fn validate(&self) -> Result<()> {
+        let (min_fov, max_fov) = Self::FOV_RANGE;
+
+        if self.min_distance > self.max_distance {
+            return Err(NMSRaaSError::InvalidConfiguration(
+                "the minimum distance of the camera is greater than its maximum".to_string(),
+            ));
+        }
+
+        if self.min_fov > self.max_fov || self.min_fov < min_fov || self.max_fov > max_fov {
+            return Err(NMSRaaSError::InvalidConfiguration(format!(
+                "the field of view of the camera has to be limited to a range between {min_fov} and {max_fov} degrees"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ShoulderBuddiesConfiguration {
+    /// The size of each shoulder buddy, in pixels.
+    pub size: u32,
+    /// How high above the shoulder each buddy sits, in pixels.
+    pub offset: i32,
+}
+
+impl Default for ShoulderBuddiesConfiguration {
+    fn default() -> Self {
+        Self {
+            size: ShoulderBuddies::DEFAULT_SIZE,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ExportConfiguration {
+    /// The maximum number of elements in an exported model.
+    pub max_elements: Option<usize>,
+    /// The maximum amount of decoded texture data (in bytes) in an exported model.
+    pub max_texture_bytes: Option<usize>,
+    /// The maximum amount of time spent generating an exported model.
+    #[serde(with = "humantime_serde")]
+    pub max_wall_time: Option<Duration>,
+}
+
+/// A set of settings applied to requests made to specific hosts.
+/// This allows a single instance to serve several sites, each with its own defaults.
+#[serde_as]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProfileConfiguration {
+    /// The hosts this profile applies to (as sent in the Host header, without the port).
+    pub hosts: Vec<String>,
+
+    /// The features disabled for this profile, in addition to the globally disabled ones.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub disabled_features: Vec<RenderRequestFeatures>,
+
+    /// The modes disabled for this profile, in addition to the globally disabled ones.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub disabled_modes: Vec<RenderRequestMode>,
+
+    /// The only features available for this profile, every other feature is disabled.
+    /// Every feature that isn't disabled is available when not set.
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    pub allowed_features: Option<Vec<RenderRequestFeatures>>,
+
+    /// An image composited over the bottom right corner of the renders made for this profile.
+    pub watermark: Option<PathBuf>,
+
+    /// The settings to use when a request doesn't specify them.
+    pub defaults: ProfileDefaultsConfiguration,
+}
+
+impl ProfileConfiguration {
+    pub(crate) fn matches_host(&self, host: &str) -> bool {
+        self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+    }
+}
+
+#[serde_as]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ProfileDefaultsConfiguration {
+    pub yaw: Option<f32>,
+    pub pitch: Option<f32>,
+    pub roll: Option<f32>,
+
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+
+    pub arms: Option<f32>,
+    pub distance: Option<f32>,
+
+    /// The hex color or the name of the configured background image renders are composited on.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub background: Option<RenderRequestBackground>,
+}
+
+/// The camera, lighting and arm rotation a mode is rendered with, replacing the built-in values.
+/// Requests can still change them (e.g. with ?yaw=), the same way they change the built-in ones.
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ModeOverridesConfiguration {
+    pub yaw: Option<f32>,
+    pub pitch: Option<f32>,
+    pub roll: Option<f32>,
+
+    /// The height of the point the camera looks at.
+    pub look_at_y: Option<f32>,
+    /// The distance between the camera and the point it looks at, for modes using a perspective projection.
+    pub distance: Option<f32>,
+    /// The vertical field of view of the camera, for modes using a perspective projection.
+    pub fov: Option<f32>,
+    /// Half the height of the view of the camera, for modes using an orthographic projection (e.g. isometric modes).
+    pub aspect: Option<f32>,
+
+    /// The rotation of the arms, in degrees.
+    pub arms: Option<f32>,
+
+    /// The direction the sun light comes from.
+    pub sun_direction: Option<[f32; 3]>,
+    /// The intensity of the sun light.
+    pub sun_intensity: Option<f32>,
+    /// The minimum amount of light a face gets, even when facing away from the sun.
+    pub ambient_light: Option<f32>,
+}
+
+/// A mode defined in the configuration, rendered like its base mode with the given settings.
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CustomModeConfiguration {
+    /// The built-in mode this mode is based on, its camera, lighting and body parts are used unless set here.
+    #[serde_as(as = "DisplayFromStr")]
+    pub base: RenderRequestMode,
+
+    /// The body parts to render, along with their layers.
+    #[serde_as(as = "Option<Vec<DisplayFromStr>>")]
+    #[serde(default)]
+    pub parts: Option<Vec<PlayerBodyPartType>>,
+
+    /// The size of a render when the request doesn't ask for one.
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+
+    /// The projection of the camera.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub projection: Option<RenderRequestProjection>,
+
+    /// The camera, lighting and arm rotation, which are the same settings as the mode overrides.
+    #[serde(flatten)]
+    pub settings: ModeOverridesConfiguration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GrpcConfiguration {
+    /// The address to bind the gRPC server to.
+    pub address: String,
+    /// The port to bind the gRPC server to.
+    pub port: u16,
+}
+
+impl Default for GrpcConfiguration {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1".to_string(),
+            port: 50051,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct GraphQlConfiguration {
+    /// The maximum depth of a query.
+    pub max_depth: usize,
+    /// The maximum complexity of a query, which is roughly the number of fields queried.
+    pub max_complexity: usize,
+}
+
+impl Default for GraphQlConfiguration {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            max_complexity: 256,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct AvifConfiguration {
+    /// The quality renders are encoded with, from 1 to 100, unless the request asks for another one.
+    pub quality: u8,
+    /// How fast the encoder is, from 1 (slowest, smallest images) to 10 (fastest, biggest images).
+    pub speed: u8,
+}
+
+impl Default for AvifConfiguration {
+    fn default() -> Self {
+        Self {
+            quality: 70,
+            speed: 8,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmbedConfiguration {
+    /// The URL this instance is publicly reachable at (e.g. `https://nmsr.example.com`).
+    /// Link previews need absolute URLs to the renders.
+    pub public_url: String,
+    /// The mode of the render shown in link previews, which can be a custom mode.
+    #[serde(default = "EmbedConfiguration::default_mode")]
+    pub mode: String,
+    /// The options of the render shown in link previews, as a query string (e.g. "yaw=20").
+    #[serde(default)]
+    pub options: Option<String>,
+    /// The name of the site shown in link previews.
+    #[serde(default)]
+    pub site_name: Option<String>,
+}
+
+impl EmbedConfiguration {
+    fn default_mode() -> String {
+        RenderRequestMode::FullBody.to_string()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct AdminConfiguration {
+    /// The token required to use the admin API, sent as a bearer token.
+    #[debug(skip)]
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct LivePreviewConfiguration {
+    /// The maximum number of frames rendered per second for each live preview connection.
+    /// Changes received in between frames are merged into the next frame.
+    pub max_fps: u32,
+    /// The maximum number of pooled scenes each live preview connection can use.
+    /// A connection keeps its scene between frames, but needs a new one whenever a frame times out.
+    /// The connection is closed once it has used up its scenes, that way it can't keep taking GPU memory.
+    pub max_scenes: u32,
+}
+
+impl Default for LivePreviewConfiguration {
+    fn default() -> Self {
+        Self {
+            max_fps: 30,
+            max_scenes: 4,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct DeterminismConfiguration {
+    /// The seed used for anything that would otherwise be random, such as the names of exported model elements.
+    pub seed: u64,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WarmupConfiguration {
+    /// The players (UUIDs or names) to resolve and render in the background on startup.
+    #[serde_as(as = "Vec<TryFromInto<String>>")]
+    pub players: Vec<RenderRequestEntry>,
+
+    /// The modes to render each player in.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub modes: Vec<RenderRequestMode>,
+}
+
+impl WarmupConfiguration {
+    /// The modes most sites use, rendered when no modes are given.
+    pub const DEFAULT_MODES: [RenderRequestMode; 5] = [
+        RenderRequestMode::FullBody,
+        RenderRequestMode::BodyBust,
+        RenderRequestMode::FrontBust,
+        RenderRequestMode::Face,
+        RenderRequestMode::Head,
+    ];
+}
+
+impl Default for WarmupConfiguration {
+    fn default() -> Self {
+        Self {
+            players: Vec::new(),
+            modes: Self::DEFAULT_MODES.to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct RenderSchedulerConfiguration {
+    /// The maximum number of renders running at the same time, across every class.
+    pub max_concurrent_renders: usize,
+    /// The maximum number of renders waiting for a slot, across every class.
+    /// Renders requested while the queue is full are rejected with a 503 instead of waiting.
+    pub max_queued_renders: Option<usize>,
+    /// How long clients are told to wait (with the Retry-After header) before retrying a rejected render.
+    #[serde(with = "humantime_serde")]
+    pub retry_after: Duration,
+    /// Single renders, requested interactively (e.g. an avatar on a web page).
+    pub single: RenderClassConfiguration,
+    /// Renders requested in bulk (e.g. priming the cache).
+    pub batch: RenderClassConfiguration,
+}
+
+impl Default for RenderSchedulerConfiguration {
+    fn default() -> Self {
+        Self {
+            max_concurrent_renders: 8,
+            max_queued_renders: None,
+            retry_after: Duration::from_secs(1),
+            single: RenderClassConfiguration {
+                weight: 8,
+                max_concurrent_renders: 8,
+            },
+            batch: RenderClassConfiguration {
+                weight: 1,
+                max_concurrent_renders: 2,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct RenderClassConfiguration {
+    /// The share of renders this class gets when classes compete, relative to the other classes.
+    pub weight: u32,
+    /// The maximum number of renders of this class running at the same time.
+    pub max_concurrent_renders: usize,
+}
+
+impl Default for RenderClassConfiguration {
+    fn default() -> Self {
+        Self {
+            weight: 1,
+            max_concurrent_renders: usize::MAX,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FallbackSkinConfiguration {
+    /// The path to a skin to render instead of the default skins.
+    /// When not set, Steve or Alex's skin is used depending on the player's UUID.
+    pub skin_path: Option<PathBuf>,
+
+    /// Whether the custom skin uses the slim (Alex) model.
+    pub slim: bool,
+}
+
+#[serde_as]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AccessListsConfiguration {
+    /// The players (by UUID or name) and texture hashes that are never rendered.
+    #[serde_as(as = "Vec<TryFromInto<String>>")]
+    pub blocked: Vec<RenderRequestEntry>,
+
+    /// The players (by UUID or name) and texture hashes that are rendered, everything else is rejected.
+    /// When empty, everything that isn't blocked is rendered.
+    #[serde_as(as = "Vec<TryFromInto<String>>")]
+    pub allowed: Vec<RenderRequestEntry>,
 }
 
 #[serde_as]
@@ -143,6 +1078,14 @@ pub struct FeaturesConfiguration {
 }
 
 impl ModelCacheConfiguration {
+    #[must_use]
+    pub const fn get_cache_limits(&self) -> CacheLimits {
+        CacheLimits {
+            max_size: self.max_cache_size,
+            max_entries: self.max_cache_entries,
+        }
+    }
+
     #[must_use]
     pub fn get_cache_duration(&self, entry: &RenderRequestEntry) -> &Duration {
         self.get_cache_duration_with_default(entry, &self.resolve_cache_duration)
@@ -159,13 +1102,55 @@ impl ModelCacheConfiguration {
         bias.map_or(default_duration, |bias| {
             trace!("Found cache bias for entry: {:?}", bias);
 
-            match bias {
-                CacheBias::KeepCachedFor(duration) => duration,
-                CacheBias::CacheIndefinitely => &Duration::MAX,
-            }
+            bias.get_duration()
         })
     }
 
+    /// The duration of time to keep a render cached, which is the cache bias of its entry if there's one,
+    /// then the cache duration of its mode if there's one, and the given default duration otherwise.
+    ///
+    /// Renders made for a custom mode use the cache duration of their base mode if the custom mode doesn't have one.
+    #[must_use]
+    pub fn get_render_cache_duration<'a>(
+        &'a self,
+        request: &RenderRequest,
+        default_duration: &'a Duration,
+    ) -> &'a Duration {
+        let custom_mode_bias = request
+            .get_custom_mode_name()
+            .and_then(|name| self.mode_cache_durations.get(name));
+
+        let mode_bias = custom_mode_bias.or_else(|| {
+            self.mode_cache_durations
+                .iter()
+                .find(|(name, _)| RenderRequestMode::try_from(name.as_str()) == Ok(request.mode))
+                .map(|(_, bias)| bias)
+        });
+
+        let default_duration = mode_bias.map_or(default_duration, CacheBias::get_duration);
+
+        self.get_cache_duration_with_default(&request.entry, default_duration)
+    }
+
+    /// Takes up to the configured jitter off a cache duration. How much is taken off is worked out from the
+    /// given key, that way it's the same every time the entry is looked up.
+    #[must_use]
+    pub fn apply_jitter(&self, duration: Duration, key: impl Hash) -> Duration {
+        let jitter = f64::from(self.cache_duration_jitter.clamp(0.0, 1.0));
+
+        if duration == Duration::MAX || jitter <= 0.0 {
+            return duration;
+        }
+
+        let mut hasher = Xxh3::new();
+        key.hash(&mut hasher);
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = hasher.finish() as f64 / u64::MAX as f64;
+
+        duration.mul_f64(1.0 - jitter * fraction)
+    }
+
     pub fn is_expired(
         &self,
         entry: &RenderRequestEntry,
@@ -190,7 +1175,7 @@ impl ModelCacheConfiguration {
         let expiry = marker_metadata.modified().explain(format!(
             "Unable to get marker modified date for entry {:?}",
             &entry
-        ))? + *duration;
+        ))? + self.apply_jitter(*duration, entry);
 
         trace!("Entry expires on {}", Into::<DateTime<Local>>::into(expiry));
 
@@ -215,3 +1200,53 @@ impl ModelCacheConfiguration {
 fn default_service_name() -> String {
     "nmsr-aas".to_string()
 }
+
+const fn default_metrics_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, time::Duration};
+
+    use super::NmsrConfiguration;
+    use crate::model::request::cache::CacheBias;
+
+    #[test]
+    fn cache_duration_jitter_is_validated() {
+        let mut config = NmsrConfiguration::default();
+
+        for jitter in [0.0, 0.5, 1.0] {
+            config.caching.cache_duration_jitter = jitter;
+            assert!(config.validate().is_ok(), "{jitter}");
+        }
+
+        for jitter in [-0.1, 1.5, f32::NAN, f32::INFINITY] {
+            config.caching.cache_duration_jitter = jitter;
+            assert!(config.validate().is_err(), "{jitter}");
+        }
+    }
+
+    #[test]
+    fn mode_cache_durations_are_validated() {
+        let mut config = NmsrConfiguration::default();
+        let duration = CacheBias::KeepCachedFor(Duration::from_secs(90));
+
+        config.caching.mode_cache_durations = HashMap::from([("face".to_string(), duration)]);
+        assert!(config.validate().is_ok());
+
+        config.caching.mode_cache_durations = HashMap::from([("avatar".to_string(), duration)]);
+        assert!(config.validate().is_err());
+
+        let avatar = serde_json::from_value(serde_json::json!({ "base": "face" }))
+            .expect("Custom mode should be deserialized");
+        config.custom_modes = Some(HashMap::from([("avatar".to_string(), avatar)]));
+        assert!(config.validate().is_ok());
+
+        // Custom modes can't hide a built-in mode.
+        let face = serde_json::from_value(serde_json::json!({ "base": "head" }))
+            .expect("Custom mode should be deserialized");
+        config.custom_modes = Some(HashMap::from([("face".to_string(), face)]));
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,7 +1,12 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use axum::response::IntoResponse;
-use hyper::StatusCode;
+use hyper::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    http::HeaderValue,
+    StatusCode,
+};
+use serde::Serialize;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -17,10 +22,62 @@ pub enum NMSRaaSError {
     RenderError(#[from] nmsr_rendering::errors::NMSRRenderingError),
     #[error("Armor manager error: {0}")]
     ArmorManagerError(#[from] ArmorManagerError),
+    #[error("Render cache error: {0}")]
+    RenderCacheError(#[from] object_store::Error),
     
     #[error("{0}")]
     ClonedError(String),
 
+    /// An error shared between requests that were coalesced into one.
+    #[error("{0}")]
+    SharedError(Arc<NMSRaaSError>),
+
+    #[error("Missing or invalid admin token")]
+    Unauthorized,
+
+    #[error("Invalid API key")]
+    InvalidApiKey,
+
+    #[error("Missing or invalid URL signature")]
+    InvalidSignature,
+
+    #[error("Missing or invalid URL signing token")]
+    InvalidSigningToken,
+
+    #[error("This URL has expired")]
+    ExpiredSignature,
+
+    #[error("This player ({0}) has been blocked from being rendered on this instance.")]
+    PlayerBlocked(String),
+
+    #[error("This player ({0}) isn't on the allowlist of this instance, so it can't be rendered.")]
+    PlayerNotAllowed(String),
+
+    #[error("Too many requests. Try again in {0} seconds.")]
+    RateLimited(u64),
+
+    #[error("Too many renders are queued. Try again in {0} seconds.")]
+    RenderQueueFull(u64),
+
+    #[error("This mode is unavailable, this instance is serving in {0} mode without a renderer.")]
+    RendererUnavailable(crate::utils::serving_mode::ServingMode),
+
+    #[error("The render took too long and was cancelled")]
+    RenderTimedOut,
+
+    #[error("This live preview connection has used up its {0} scenes, reconnect to keep previewing")]
+    PreviewScenesExhausted(u32),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfiguration(String),
+
+    #[error("Blocking task failed: {0}")]
+    BlockingTaskError(#[from] tokio::task::JoinError),
+
+    #[cfg(feature = "avif")]
+    #[error("Unable to encode AVIF image: {0}")]
+    AvifEncodingError(#[from] ravif::Error),
+
     #[cfg(feature = "ears")]
     #[error("Ears error: {0}")]
     EarsError(#[from] ears_rs::utils::errors::EarsError),
@@ -60,6 +117,14 @@ pub enum RenderRequestError {
     InvalidRenderSettingError(&'static str, String),
     #[error("You've specified {0} which is invalid for this mode. {1}")]
     InvalidModeSettingSpecifiedError(&'static str, &'static str),
+    #[error("The skin isn't a PNG image.")]
+    InvalidSkinFormat,
+    #[error("The skin is {0}x{1}, skins should be 64x64 or 64x32 (or a multiple of these for HD skins).")]
+    InvalidSkinDimensions(u32, u32),
+    #[error("The skin is too large, skins can't take more than {0} bytes once decoded.")]
+    SkinTooLarge(u64),
+    #[error("The skin is malformed: {0}")]
+    MalformedSkin(String),
     #[error("Missing render request texture. Did you forget to specify a texture?")]
     MissingRenderRequestEntry,
     #[error("Invalid HTTP Method. Did you mean to use \"{1}\" instead of \"{0}\"? This endpoint only supports \"{0}\".")]
@@ -67,6 +132,32 @@ pub enum RenderRequestError {
 }
 
 impl RenderRequestError {
+    /// A stable, machine-readable code for this error, sent along with it in error responses.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidUUID(_) => "invalid_uuid",
+            Self::InvalidPlayerUuidRequest(_, _) => "invalid_uuid_version",
+            Self::InvalidPlayerRequest(_) => "invalid_player",
+            Self::ExplainedIoError(_, _) => "io_error",
+            Self::PathRejection(_) => "invalid_path",
+            Self::QueryRejection(_) => "invalid_query",
+            Self::MultipartError(_)
+            | Self::MultipartRejection(_)
+            | Self::MultipartDecodeError(_, _) => "invalid_multipart",
+            Self::InvalidRenderMode(_) => "invalid_mode",
+            Self::LegacySkinUpgradeError => "legacy_skin_upgrade_failed",
+            Self::InvalidRenderSettingError(_, _) => "invalid_render_setting",
+            Self::InvalidModeSettingSpecifiedError(_, _) => "invalid_mode_setting",
+            Self::InvalidSkinFormat => "invalid_skin_format",
+            Self::InvalidSkinDimensions(_, _) => "invalid_skin_dimensions",
+            Self::SkinTooLarge(_) => "skin_too_large",
+            Self::MalformedSkin(_) => "malformed_skin",
+            Self::MissingRenderRequestEntry => "missing_texture",
+            Self::WrongHttpMethodError(_, _) => "wrong_http_method",
+        }
+    }
+
     #[must_use]
     pub const fn is_bad_request(&self) -> bool {
         matches!(
@@ -77,6 +168,10 @@ impl RenderRequestError {
                 | Self::InvalidRenderMode(_)
                 | Self::InvalidRenderSettingError(_, _)
                 | Self::InvalidModeSettingSpecifiedError(_, _)
+                | Self::InvalidSkinFormat
+                | Self::InvalidSkinDimensions(_, _)
+                | Self::SkinTooLarge(_)
+                | Self::MalformedSkin(_)
                 | Self::MissingRenderRequestEntry
                 | Self::WrongHttpMethodError(_, _)
         )
@@ -130,6 +225,46 @@ pub enum MojangRequestError {
     InvalidTextureHashError(String),
     #[error("Unable to find a player with the UUID {0}")]
     GameProfileNotFound(Uuid),
+    #[error("Unable to find a player named {0}. If the player has changed their name, use their new name or their UUID instead.")]
+    PlayerNameNotFound(String),
+    #[error("Unable to find a Bedrock player with the gamertag {0}. They need to have joined a server using Geyser before.")]
+    GamertagNotFound(String),
+}
+
+impl MojangRequestError {
+    /// A stable, machine-readable code for this error, sent along with it in error responses.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Base64Error(_)
+            | Self::Utf8Error(_)
+            | Self::JsonError(_)
+            | Self::MissingTexturesPropertyError
+            | Self::InvalidTexturesPropertyError(_) => "invalid_game_profile",
+            Self::UrlParseError(_)
+            | Self::HttpRequestError(_)
+            | Self::BoxedRequestError(_)
+            | Self::RequestError(_)
+            | Self::MojangFetchRequestError(_) => "upstream_request_failed",
+            Self::MissingSkinPropertyError(_) => "missing_skin",
+            Self::InvalidTextureUrlError(_) => "invalid_texture_url",
+            // The code of the underlying error says more about why the player couldn't be resolved.
+            Self::UnableToResolveRenderRequestEntity(err, _) => {
+                if let Some(err) = err.downcast_ref::<NMSRaaSError>() {
+                    err.code()
+                } else if let Some(err) = err.downcast_ref::<Self>() {
+                    err.code()
+                } else {
+                    "unable_to_resolve_player"
+                }
+            }
+            Self::UnableToParseUuidIntoXuid(_) => "invalid_xuid",
+            Self::InvalidTextureHashError(_) => "invalid_texture_hash",
+            Self::GameProfileNotFound(_) => "profile_not_found",
+            Self::PlayerNameNotFound(_) => "player_not_found",
+            Self::GamertagNotFound(_) => "gamertag_not_found",
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -173,6 +308,8 @@ impl<T> ExplainableExt<T> for std::result::Result<T, std::io::Error> {
     }
 }
 
+const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
 pub struct NmsrErrorExtension(pub NMSRaaSError);
 
 impl Clone for NmsrErrorExtension {
@@ -182,26 +319,182 @@ impl Clone for NmsrErrorExtension {
     }
 }
 
-impl IntoResponse for NMSRaaSError {
-    fn into_response(self) -> axum::response::Response {
-        let mut res = axum::response::IntoResponse::into_response(self.to_string());
+impl NMSRaaSError {
+    /// The HTTP status code that best describes this error.
+    #[must_use]
+    pub fn status_code(&self) -> StatusCode {
+        if let Self::SharedError(error) = self {
+            return error.status_code();
+        }
 
-        let is_bad_request = if let Self::RenderRequestError(error) = &self {
+        let is_bad_request = if let Self::RenderRequestError(error) = self {
             error.is_bad_request()
         } else {
             false
         };
 
-        let error = if is_bad_request {
+        let is_over_budget = if let Self::BlockbenchGeneratorError(error) = self {
+            error.is_budget_exceeded()
+        } else {
+            false
+        };
+
+        if is_bad_request {
             StatusCode::BAD_REQUEST
+        } else if matches!(
+            self,
+            Self::Unauthorized | Self::InvalidApiKey | Self::InvalidSigningToken
+        ) {
+            StatusCode::UNAUTHORIZED
+        } else if matches!(
+            self,
+            Self::InvalidSignature
+                | Self::ExpiredSignature
+                | Self::PlayerBlocked(_)
+                | Self::PlayerNotAllowed(_)
+        ) {
+            StatusCode::FORBIDDEN
+        } else if matches!(self, Self::RateLimited(_)) {
+            StatusCode::TOO_MANY_REQUESTS
+        } else if matches!(
+            self,
+            Self::RendererUnavailable(_)
+                | Self::RenderQueueFull(_)
+                | Self::RenderTimedOut
+                | Self::PreviewScenesExhausted(_)
+        ) {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if is_over_budget {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else if matches!(
+            self,
+            Self::MojangRequestError(
+                MojangRequestError::PlayerNameNotFound(_) | MojangRequestError::GamertagNotFound(_)
+            )
+        ) {
+            StatusCode::NOT_FOUND
         } else {
             StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+impl NMSRaaSError {
+    /// A stable, machine-readable code for this error, sent along with it in error responses.
+    /// Unlike the message, it doesn't change between versions, so clients can tell errors apart with it.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::RenderRequestError(err) => err.code(),
+            Self::MojangRequestError(err) => err.code(),
+            Self::SharedError(err) => err.code(),
+            Self::ModelCacheError(_) => "model_cache_error",
+            Self::RenderError(_) => "render_failed",
+            Self::ArmorManagerError(_) => "armor_error",
+            Self::RenderCacheError(_) => "render_cache_error",
+            Self::ClonedError(_) | Self::BlockingTaskError(_) | Self::InvalidConfiguration(_) => {
+                "internal_error"
+            }
+            Self::Unauthorized => "unauthorized",
+            Self::InvalidApiKey => "invalid_api_key",
+            Self::InvalidSignature => "invalid_signature",
+            Self::InvalidSigningToken => "invalid_signing_token",
+            Self::ExpiredSignature => "expired_signature",
+            Self::PlayerBlocked(_) => "player_blocked",
+            Self::PlayerNotAllowed(_) => "player_not_allowed",
+            Self::RateLimited(_) => "rate_limited",
+            Self::RenderQueueFull(_) => "render_queue_full",
+            Self::RendererUnavailable(_) => "renderer_unavailable",
+            Self::RenderTimedOut => "render_timed_out",
+            Self::PreviewScenesExhausted(_) => "preview_scenes_exhausted",
+            #[cfg(feature = "avif")]
+            Self::AvifEncodingError(_) => "encoding_failed",
+            #[cfg(feature = "ears")]
+            Self::EarsError(_) => "ears_error",
+            Self::BlockbenchGeneratorError(err) if err.is_budget_exceeded() => "export_too_large",
+            Self::BlockbenchGeneratorError(_) => "export_failed",
+        }
+    }
+}
+
+/// An error response, as described by [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807).
+#[derive(Serialize)]
+struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    /// The machine-readable code of the error, see [`NMSRaaSError::code`].
+    code: &'static str,
+}
+
+impl IntoResponse for NMSRaaSError {
+    fn into_response(self) -> axum::response::Response {
+        let status = self.status_code();
+
+        let problem = ProblemDetails {
+            // The status code says all there is to say about the type of the problem, the code is more specific.
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Unknown Error"),
+            status: status.as_u16(),
+            detail: self.to_string(),
+            code: self.code(),
+        };
+
+        let mut res = serde_json::to_vec(&problem).map_or_else(
+            |_| self.to_string().into_response(),
+            |body| ([(CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)], body).into_response(),
+        );
+
+        *res.status_mut() = status;
+
+        let original = match &self {
+            Self::SharedError(error) => error.as_ref(),
+            error => error,
         };
 
-        *res.status_mut() = error;
+        if let Self::RateLimited(retry_after) | Self::RenderQueueFull(retry_after) = original {
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(*retry_after));
+        }
 
         res.extensions_mut().insert(NmsrErrorExtension(self));
 
         res
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use axum::response::IntoResponse;
+    use hyper::{header::CONTENT_TYPE, StatusCode};
+
+    use super::{MojangRequestError, NMSRaaSError};
+    use crate::model::request::entry::RenderRequestEntry;
+
+    #[test]
+    fn errors_are_described_by_their_code() {
+        let not_found =
+            NMSRaaSError::from(MojangRequestError::PlayerNameNotFound("Notch".to_string()));
+
+        // Players that couldn't be resolved are described by the reason they couldn't be.
+        let unresolved = MojangRequestError::UnableToResolveRenderRequestEntity(
+            Box::new(not_found),
+            RenderRequestEntry::MojangPlayerName("Notch".to_string()),
+        );
+        assert_eq!(unresolved.code(), "player_not_found");
+
+        let shared = NMSRaaSError::SharedError(Arc::new(NMSRaaSError::RateLimited(1)));
+        assert_eq!(shared.code(), "rate_limited");
+
+        let response = shared.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+    }
+}
@@ -3,10 +3,10 @@ use axum::{
     http::{HeaderName, HeaderValue},
 };
 use http_body_util::BodyExt;
-use hyper::{body::{Bytes, Incoming}, Method, Request, Response};
+use hyper::{body::{Bytes, Incoming}, Method, Request, Response, StatusCode};
 use hyper_tls::HttpsConnector;
 use hyper_util::{client::legacy::Client, rt::TokioExecutor};
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use sync_wrapper::SyncWrapper;
 use tokio::sync::RwLock;
 use tower::{util::BoxService, Service, ServiceBuilder, ServiceExt};
@@ -17,7 +17,10 @@ use tower_http::{
 };
 use tracing::{instrument, Span};
 
-use crate::error::{MojangRequestError, MojangRequestResult};
+use crate::{
+    error::{MojangRequestError, MojangRequestResult},
+    utils::{metrics::Metrics, stats::UsageStatistics, tracing::inject_trace_context},
+};
 
 const USER_AGENT: &str = concat!(
     "NMSR-as-a-Service/",
@@ -31,11 +34,12 @@ type BoxedTracedResponse = BoxService<Request<Body>, Response<TraceResponseBody>
 
 pub struct NmsrHttpClient {
     inner: RwLock<SyncWrapper<BoxedTracedResponse>>,
+    statistics: Arc<UsageStatistics>,
 }
 
 impl NmsrHttpClient {
-    pub fn new(rate_limit_per_second: u64) -> Self {
-        create_http_client(rate_limit_per_second)
+    pub fn new(rate_limit_per_second: u64, statistics: Arc<UsageStatistics>) -> Self {
+        create_http_client(rate_limit_per_second, statistics)
     }
 
     #[allow(clippy::significant_drop_tightening)] // Not worth making the code less readable
@@ -47,19 +51,29 @@ impl NmsrHttpClient {
         parent_span: &Span,
         on_error: impl FnOnce() -> Option<MojangRequestError>,
     ) -> MojangRequestResult<Bytes> {
-        let request = Request::builder()
+        let mut request = Request::builder()
             .method(method)
             .uri(url)
             .body(Body::empty())?;
 
+        inject_trace_context(&Span::current(), request.headers_mut());
+
+        self.statistics.record_upstream_request();
+
         let response = {
             let mut client = self.inner.write().await;
             let service = client.get_mut().ready().await?;
 
-            service.call(request).await?
+            service.call(request).await.inspect_err(|_| Metrics::record_upstream_error())?
         };
 
-        if !response.status().is_success() {
+        // Players that don't exist aren't the upstream service's fault, but being rate limited or it being down are.
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Metrics::record_upstream_error();
+        }
+
+        if !status.is_success() {
             if let Some(err) = on_error() {
                 return Err(err);
             }
@@ -74,7 +88,7 @@ impl NmsrHttpClient {
     }
 }
 
-fn create_http_client(rate_limit_per_second: u64) -> NmsrHttpClient {
+fn create_http_client(rate_limit_per_second: u64, statistics: Arc<UsageStatistics>) -> NmsrHttpClient {
     let https = HttpsConnector::new();
     
     // A new higher level client from hyper is in the works, so we gotta use the legacy one
@@ -93,5 +107,6 @@ fn create_http_client(rate_limit_per_second: u64) -> NmsrHttpClient {
 
     NmsrHttpClient {
         inner: RwLock::new(SyncWrapper::new(service)),
+        statistics,
     }
 }
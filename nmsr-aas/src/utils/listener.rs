@@ -0,0 +1,235 @@
+use std::{
+    fmt::{self, Display},
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+};
+
+/// An address to listen on, either a TCP address (e.g. `0.0.0.0:8080` or `[::]:8080`) or the path of a Unix
+/// socket prefixed with `unix:` (e.g. `unix:/run/nmsr/nmsr.sock`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = value.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(format!("Missing Unix socket path in address {value}"));
+            }
+
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        SocketAddr::from_str(value)
+            .map(Self::Tcp)
+            .map_err(|err| format!("Invalid address {value}: {err}"))
+    }
+}
+
+impl Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "{address}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A socket we accept connections on.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Binds to the given address. Unix sockets left behind by a previous run are replaced, but sockets another
+    /// instance is still listening on (or anything else already at the socket's path) are left alone and fail the bind.
+    pub async fn bind(address: &ListenAddress) -> io::Result<Self> {
+        match address {
+            ListenAddress::Tcp(address) => Ok(Self::Tcp(TcpListener::bind(address).await?)),
+            #[cfg(unix)]
+            ListenAddress::Unix(path) => {
+                use std::os::unix::fs::FileTypeExt;
+
+                match tokio::fs::symlink_metadata(path).await {
+                    Ok(metadata) if metadata.file_type().is_socket() => {
+                        match tokio::net::UnixStream::connect(path).await {
+                            Ok(_) => {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::AddrInUse,
+                                    format!("{} is already being listened on", path.display()),
+                                ));
+                            }
+                            // Nothing is listening on it anymore, so it's stale.
+                            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                                tokio::fs::remove_file(path).await?;
+                            }
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    Ok(_) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            format!("{} already exists and isn't a Unix socket", path.display()),
+                        ));
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+
+                Ok(Self::Unix(
+                    tokio::net::UnixListener::bind(path)?,
+                    path.clone(),
+                ))
+            }
+            #[cfg(not(unix))]
+            ListenAddress::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix sockets aren't supported on this platform",
+            )),
+        }
+    }
+
+    /// Accepts a connection, along with the address of its peer.
+    ///
+    /// Connections made through Unix sockets come from the same host (usually a reverse proxy like nginx), so they
+    /// appear to come from `127.0.0.1`. Add it to the trusted proxies to take the client's address from the proxy.
+    pub async fn accept(&self) -> io::Result<(Connection, SocketAddr)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((Connection::Tcp(stream), peer))
+            }
+            #[cfg(unix)]
+            Self::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((
+                    Connection::Unix(stream),
+                    SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        // Unix sockets aren't removed when closed, so clean up after ourselves.
+        if let Self::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// A connection accepted by a [`Listener`].
+pub enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, path::PathBuf};
+
+    use super::{ListenAddress, Listener};
+
+    #[test]
+    fn listen_addresses_are_parsed() {
+        assert_eq!(
+            "[::]:8080".parse::<ListenAddress>(),
+            Ok(ListenAddress::Tcp("[::]:8080".parse().unwrap()))
+        );
+        assert_eq!(
+            "unix:/run/nmsr/nmsr.sock".parse::<ListenAddress>(),
+            Ok(ListenAddress::Unix(PathBuf::from("/run/nmsr/nmsr.sock")))
+        );
+
+        assert!("unix:".parse::<ListenAddress>().is_err());
+        assert!("0.0.0.0".parse::<ListenAddress>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn only_stale_sockets_are_replaced() {
+        let path = std::env::temp_dir().join(format!("nmsr-listener-test-{}", std::process::id()));
+        let address = ListenAddress::Unix(path.clone());
+
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(Listener::bind(&address).await.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        std::fs::remove_file(&path).unwrap();
+
+        // Leave a socket behind like a previous run that didn't shut down cleanly would.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let listener = Listener::bind(&address).await.unwrap();
+
+        // But don't take over the socket of an instance that's still running.
+        let Err(err) = Listener::bind(&address).await else {
+            panic!("Bound to a socket that's still being listened on");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+}
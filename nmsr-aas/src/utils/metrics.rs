@@ -0,0 +1,105 @@
+use std::{sync::LazyLock, time::Duration};
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Unit, UpDownCounter},
+    KeyValue,
+};
+
+use crate::utils::{render_scheduler::RenderClass, stats::StatisticsCache};
+
+/// The instruments our metrics are recorded with.
+///
+/// These are created on first use, which has to be after the meter provider is set up, otherwise they would
+/// record nothing.
+static INSTRUMENTS: LazyLock<Instruments> = LazyLock::new(Instruments::new);
+
+struct Instruments {
+    render_duration: Histogram<f64>,
+    cache_lookups: Counter<u64>,
+    upstream_errors: Counter<u64>,
+    queued_renders: UpDownCounter<i64>,
+    render_queue_wait: Histogram<f64>,
+}
+
+impl Instruments {
+    fn new() -> Self {
+        let meter = global::meter("nmsr-aas");
+
+        Self {
+            render_duration: meter
+                .f64_histogram("nmsr.render.duration")
+                .with_description("How long renders took, without encoding them")
+                .with_unit(Unit::new("s"))
+                .init(),
+            cache_lookups: meter
+                .u64_counter("nmsr.cache.lookups")
+                .with_description("Lookups in our caches, by cache and whether they were hits")
+                .init(),
+            upstream_errors: meter
+                .u64_counter("nmsr.upstream.errors")
+                .with_description(
+                    "Requests to skin servers (or other upstream services) that failed",
+                )
+                .init(),
+            queued_renders: meter
+                .i64_up_down_counter("nmsr.scheduler.queued")
+                .with_description("Renders waiting for a render slot, by render class")
+                .init(),
+            render_queue_wait: meter
+                .f64_histogram("nmsr.scheduler.wait")
+                .with_description("How long renders waited for a render slot, by render class")
+                .with_unit(Unit::new("s"))
+                .init(),
+        }
+    }
+}
+
+/// Records metrics for OpenTelemetry. Unlike the usage statistics, these are only kept until they're exported.
+///
+/// Nothing is recorded unless metrics are exported.
+pub struct Metrics;
+
+impl Metrics {
+    /// Records how long a render in the given mode (or custom mode) took.
+    pub fn record_render_duration(mode: &str, duration: Duration) {
+        INSTRUMENTS.render_duration.record(
+            duration.as_secs_f64(),
+            &[KeyValue::new("mode", mode.to_string())],
+        );
+    }
+
+    /// Records whether a lookup in one of our caches found what it was looking for.
+    pub fn record_cache_lookup(cache: StatisticsCache, hit: bool) {
+        let cache = match cache {
+            StatisticsCache::Resolved => "resolved",
+            StatisticsCache::Textures => "textures",
+            StatisticsCache::Renders => "renders",
+        };
+
+        INSTRUMENTS.cache_lookups.add(
+            1,
+            &[KeyValue::new("cache", cache), KeyValue::new("hit", hit)],
+        );
+    }
+
+    /// Records a failed request to a skin server (or any other upstream service).
+    pub fn record_upstream_error() {
+        INSTRUMENTS.upstream_errors.add(1, &[]);
+    }
+
+    /// Records renders of the given class starting (or, with a negative delta, no longer) waiting for a render slot.
+    pub fn add_queued_renders(class: RenderClass, delta: i64) {
+        INSTRUMENTS
+            .queued_renders
+            .add(delta, &[KeyValue::new("class", class.to_string())]);
+    }
+
+    /// Records how long a render of the given class waited for a render slot.
+    pub fn record_render_queue_wait(class: RenderClass, duration: Duration) {
+        INSTRUMENTS.render_queue_wait.record(
+            duration.as_secs_f64(),
+            &[KeyValue::new("class", class.to_string())],
+        );
+    }
+}
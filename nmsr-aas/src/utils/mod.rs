@@ -1,6 +1,19 @@
+pub mod access_log;
 pub mod caching;
+pub mod client_ip;
+pub mod coalescing;
 pub mod config;
 pub mod error;
 pub mod http_client;
+pub mod listener;
+pub mod metrics;
 pub mod png;
+pub mod proxy_protocol;
+pub mod rate_limit;
+pub mod reloadable;
+pub mod render_scheduler;
+pub mod server;
+pub mod serving_mode;
+pub mod stats;
 pub mod tracing;
+pub mod url_signing;
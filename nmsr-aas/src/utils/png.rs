@@ -7,6 +7,18 @@ use tracing::trace_span;
 use crate::error::{ExplainableExt, Result};
 
 pub(crate) fn create_png_from_bytes(size: (u32, u32), bytes: &[u8]) -> Result<Vec<u8>> {
+    create_png_with_text(size, bytes, &[])
+}
+
+/// Creates a PNG with the given text metadata, written right after its header.
+///
+/// ASCII text is written as `tEXt` chunks and anything else as UTF-8 `iTXt` chunks.
+/// Keywords have to be Latin-1 and can't be longer than 79 bytes.
+pub(crate) fn create_png_with_text(
+    size: (u32, u32),
+    bytes: &[u8],
+    entries: &[(&str, String)],
+) -> Result<Vec<u8>> {
     let render_bytes = Vec::new();
 
     let _guard = trace_span!("write_image_bytes").entered();
@@ -26,6 +38,15 @@ pub(crate) fn create_png_from_bytes(size: (u32, u32), bytes: &[u8]) -> Result<Ve
     encoder
         .write_header(&header)
         .explain_closure(|| "Unable to write header for output PNG".to_string())?;
+
+    for (keyword, text) in entries {
+        let (tag, data) = create_text_chunk(keyword, text);
+
+        encoder
+            .write_chunk(tag, &data)
+            .explain_closure(|| format!("Unable to write {keyword} text for output PNG"))?;
+    }
+
     encoder
         .write_image_rows(bytes)
         .explain_closure(|| "Unable to write image rows for output PNG".to_string())?;
@@ -34,3 +55,51 @@ pub(crate) fn create_png_from_bytes(size: (u32, u32), bytes: &[u8]) -> Result<Ve
         .finish()
         .explain_closure(|| "Unable to finish writing output PNG".to_string())
 }
+
+/// Returns the tag and data of the chunk holding the given text.
+fn create_text_chunk(keyword: &str, text: &str) -> (&'static [u8], Vec<u8>) {
+    let mut data = Vec::with_capacity(keyword.len() + 5 + text.len());
+    data.extend_from_slice(keyword.as_bytes());
+    data.push(0);
+
+    if text.is_ascii() {
+        data.extend_from_slice(text.as_bytes());
+
+        return (b"tEXt", data);
+    }
+
+    // Uncompressed, without a language tag or translated keyword.
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+
+    (b"iTXt", data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::create_png_with_text;
+
+    #[test]
+    fn text_chunks_are_added_after_the_header() {
+        let png = create_png_with_text(
+            (2, 2),
+            &[u8::MAX; 2 * 2 * 4],
+            &[
+                ("nmsr:mode", "fullbody".to_string()),
+                ("nmsr:player", "Jérôme".to_string()),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(&png[37..41], b"tEXt");
+        assert_eq!(&png[41..59], b"nmsr:mode\0fullbody");
+
+        assert_eq!(&png[67..71], b"iTXt");
+        assert_eq!(&png[71..83], b"nmsr:player\0");
+        assert_eq!(&png[83..87], &[0, 0, 0, 0]);
+        assert_eq!(&png[87..95], "Jérôme".as_bytes());
+
+        let image = image::load_from_memory(&png).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tracing::debug;
+
+/// The signature version 2 (binary) headers start with.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of a version 1 (text) header, including the trailing CRLF.
+const V1_MAX_LENGTH: u64 = 107;
+
+/// How long proxies have to send the header once connected, that way idle connections don't linger.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads the PROXY protocol header at the start of a connection, returning the address of the client the proxy
+/// accepted the connection from. Connections the proxy made on its own (e.g. health checks) don't have one.
+async fn read_header<R: AsyncRead + Unpin>(
+    stream: &mut BufReader<R>,
+) -> io::Result<Option<SocketAddr>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    // Both versions of the header are longer than the signature of the binary one.
+    let mut signature = [0; 12];
+    stream.read_exact(&mut signature).await?;
+
+    if signature.starts_with(b"PROXY ") {
+        let mut header = signature.to_vec();
+        (&mut *stream)
+            .take(V1_MAX_LENGTH - signature.len() as u64)
+            .read_until(b'\n', &mut header)
+            .await?;
+
+        let header = std::str::from_utf8(&header)
+            .ok()
+            .and_then(|header| header.strip_suffix("\r\n"))
+            .ok_or_else(|| invalid("Invalid PROXY protocol v1 header"))?;
+
+        return parse_v1_header(header).ok_or_else(|| invalid("Invalid PROXY protocol v1 header"));
+    }
+
+    if &signature != V2_SIGNATURE {
+        return Err(invalid("Missing PROXY protocol header"));
+    }
+
+    let mut header = [0; 4];
+    stream.read_exact(&mut header).await?;
+
+    let [version_command, family, length @ ..] = header;
+    let mut addresses = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut addresses).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("Unsupported PROXY protocol version"));
+    }
+
+    // Local connections are made by the proxy itself, so they don't have a client.
+    if version_command & 0x0F == 0 {
+        return Ok(None);
+    }
+
+    // The address family is in the high nibble, the transport protocol (TCP or UDP) is in the low one.
+    let address = match family >> 4 {
+        0x1 if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[0..4].try_into().unwrap_or_default();
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+
+            Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ip)), port))
+        }
+        0x2 if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[0..16].try_into().unwrap_or_default();
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+
+            Some(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port))
+        }
+        0x1 | 0x2 => return Err(invalid("Truncated PROXY protocol v2 addresses")),
+        // Unix sockets and unspecified families don't have an address we can use.
+        _ => None,
+    };
+
+    Ok(address)
+}
+
+/// Parses a version 1 header without its trailing CRLF, e.g. `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443`.
+fn parse_v1_header(header: &str) -> Option<Option<SocketAddr>> {
+    let mut parts = header.split(' ').skip(1);
+
+    match parts.next()? {
+        "TCP4" | "TCP6" => {
+            let source: IpAddr = parts.next()?.parse().ok()?;
+            let _destination: IpAddr = parts.next()?.parse().ok()?;
+            let source_port: u16 = parts.next()?.parse().ok()?;
+
+            Some(Some(SocketAddr::new(source, source_port)))
+        }
+        "UNKNOWN" => Some(None),
+        _ => None,
+    }
+}
+
+/// Reads the PROXY protocol header at the start of a connection made by a proxy, returning the address of the client
+/// it was made on behalf of (or the proxy's, if it made the connection on its own).
+///
+/// Connections without a valid header are rejected, since anyone able to connect could otherwise pretend to be
+/// any client.
+pub async fn read_client<R: AsyncRead + Unpin>(
+    stream: &mut BufReader<R>,
+    peer: SocketAddr,
+) -> Option<SocketAddr> {
+    match tokio::time::timeout(HEADER_TIMEOUT, read_header(stream)).await {
+        Ok(Ok(client)) => Some(client.unwrap_or(peer)),
+        Ok(Err(err)) => {
+            debug!("Rejected connection from {peer}: {err}");
+            None
+        }
+        Err(_) => {
+            debug!("Rejected connection from {peer}: no PROXY protocol header in time");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use tokio::io::BufReader;
+
+    use super::read_header;
+
+    async fn read(header: &[u8]) -> Option<SocketAddr> {
+        read_header(&mut BufReader::new(header)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn headers_are_parsed() {
+        let client: SocketAddr = "192.0.2.1:56324".parse().unwrap();
+
+        assert_eq!(
+            read(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n").await,
+            Some(client)
+        );
+        assert_eq!(read(b"PROXY UNKNOWN\r\n").await, None);
+
+        let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c".to_vec();
+        header.extend([192, 0, 2, 1, 198, 51, 100, 1]);
+        header.extend(56324_u16.to_be_bytes());
+        header.extend(443_u16.to_be_bytes());
+
+        assert_eq!(read(&header).await, Some(client));
+
+        assert!(read_header(&mut BufReader::new(&b"GET / HTTP/1.1\r\n"[..]))
+            .await
+            .is_err());
+    }
+}
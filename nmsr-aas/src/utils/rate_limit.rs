@@ -0,0 +1,256 @@
+use std::{collections::HashMap, net::IpAddr, num::NonZeroU32, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    DefaultDirectRateLimiter, DefaultKeyedRateLimiter, Quota, RateLimiter,
+};
+use hyper::header::HeaderName;
+use tracing::{trace, Span};
+
+use crate::{
+    config::{ApiKeyConfiguration, ApiKeysConfiguration, RateLimitConfiguration},
+    error::{ExplainableExt, NMSRaaSError, Result},
+    routes::NMSRState,
+    utils::client_ip::ClientIp,
+};
+
+pub(crate) const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// A rate limiter along with the quota it was created with, so that it can be kept when the quota doesn't change.
+struct QuotaLimiter<L> {
+    quota: Quota,
+    limiter: Arc<L>,
+}
+
+impl<L> QuotaLimiter<L> {
+    fn new(quota: Quota, create: impl FnOnce(Quota) -> L) -> Self {
+        Self {
+            quota,
+            limiter: Arc::new(create(quota)),
+        }
+    }
+
+    /// Keeps using the previous limiter (and what clients have used of their quota) if the quota is unchanged.
+    fn keep_state_of(&mut self, previous: &Self) {
+        if self.quota == previous.quota {
+            self.limiter = previous.limiter.clone();
+        }
+    }
+}
+
+struct ApiKey {
+    name: Arc<str>,
+    limiter: Option<QuotaLimiter<DefaultDirectRateLimiter>>,
+}
+
+/// Rate limits clients based on the API key they've sent or, for anonymous clients, their IP address.
+///
+/// Each API key has its own quota, which is usually higher than the default quota anonymous clients get.
+pub struct ClientRateLimiter {
+    anonymous: Option<QuotaLimiter<DefaultKeyedRateLimiter<IpAddr>>>,
+    api_keys: HashMap<String, ApiKey>,
+}
+
+impl ClientRateLimiter {
+    #[must_use]
+    pub fn new(
+        rate_limit: Option<RateLimitConfiguration>,
+        api_keys: HashMap<String, ApiKeyConfiguration>,
+    ) -> Self {
+        let api_keys = api_keys
+            .into_iter()
+            .map(|(name, config)| {
+                let key = ApiKey {
+                    name: name.into(),
+                    limiter: config.rate_limit.map(|rate_limit| {
+                        QuotaLimiter::new(Self::create_quota(rate_limit), RateLimiter::direct)
+                    }),
+                };
+
+                (config.key, key)
+            })
+            .collect();
+
+        Self {
+            anonymous: rate_limit.map(|rate_limit| {
+                QuotaLimiter::new(Self::create_quota(rate_limit), RateLimiter::keyed)
+            }),
+            api_keys,
+        }
+    }
+
+    /// Carries over the state of the previous rate limiter, e.g. when the configuration is reloaded.
+    ///
+    /// Clients keep what they've used of their quota unless it has changed, in which case they start over with a full
+    /// one. API keys are matched by their key, so renaming one doesn't reset it.
+    pub fn keep_state_of(&mut self, previous: &Self) {
+        if let (Some(anonymous), Some(previous)) = (&mut self.anonymous, &previous.anonymous) {
+            anonymous.keep_state_of(previous);
+        }
+
+        for (key, api_key) in &mut self.api_keys {
+            let previous = previous
+                .api_keys
+                .get(key)
+                .and_then(|previous| previous.limiter.as_ref());
+
+            if let (Some(limiter), Some(previous)) = (&mut api_key.limiter, previous) {
+                limiter.keep_state_of(previous);
+            }
+        }
+    }
+
+    pub fn load_api_keys(
+        config: &ApiKeysConfiguration,
+    ) -> Result<HashMap<String, ApiKeyConfiguration>> {
+        let mut keys = config.keys.clone();
+
+        if let Some(file) = &config.file {
+            let data = std::fs::read(file)
+                .explain(format!("Unable to read API keys file {}", file.display()))?;
+
+            let file_keys: HashMap<String, ApiKeyConfiguration> = serde_json::from_slice(&data)
+                .map_err(std::io::Error::from)
+                .explain(format!("Unable to parse API keys file {}", file.display()))?;
+
+            keys.extend(file_keys);
+        }
+
+        Ok(keys)
+    }
+
+    fn create_quota(config: RateLimitConfiguration) -> Quota {
+        let requests_per_second =
+            NonZeroU32::new(config.requests_per_second).unwrap_or(NonZeroU32::MIN);
+        let burst = NonZeroU32::new(config.burst).unwrap_or(requests_per_second);
+
+        Quota::per_second(requests_per_second).allow_burst(burst)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.anonymous.is_some() || !self.api_keys.is_empty()
+    }
+
+    /// Checks whether a client is allowed to make a request, returning the name of its API key if it sent one.
+    pub fn check(&self, ip: IpAddr, api_key: Option<&str>) -> Result<Option<Arc<str>>> {
+        let clock = DefaultClock::default();
+
+        let into_error = |not_until: governor::NotUntil<_>| {
+            let wait_time = not_until.wait_time_from(clock.now());
+
+            // Round up, clients shouldn't retry before they're allowed to.
+            let retry_after = wait_time.as_secs() + u64::from(wait_time.subsec_nanos() > 0);
+
+            NMSRaaSError::RateLimited(retry_after)
+        };
+
+        if let Some(api_key) = api_key {
+            let api_key = self
+                .api_keys
+                .get(api_key)
+                .ok_or(NMSRaaSError::InvalidApiKey)?;
+
+            if let Some(QuotaLimiter { limiter, .. }) = &api_key.limiter {
+                limiter.check().map_err(into_error)?;
+            }
+
+            return Ok(Some(api_key.name.clone()));
+        }
+
+        if let Some(QuotaLimiter { limiter, .. }) = &self.anonymous {
+            limiter.check_key(&ip).map_err(into_error)?;
+        }
+
+        Ok(None)
+    }
+
+    /// Forgets about anonymous clients that haven't made a request recently, that way we don't keep growing forever.
+    pub fn retain_recent(&self) {
+        if let Some(QuotaLimiter { limiter, .. }) = &self.anonymous {
+            limiter.retain_recent();
+        }
+    }
+}
+
+pub(crate) async fn rate_limit(
+    State(state): State<NMSRState>,
+    Extension(ClientIp(ip)): Extension<ClientIp>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    if let Some(rate_limiter) = state.rate_limiter.get().as_ref() {
+        let api_key = request
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        let api_key_name = rate_limiter.check(ip, api_key).map_err(|err| {
+            trace!("Rejected request from client {ip}: {err}");
+            err
+        })?;
+
+        // Only the name of the key is recorded, the key itself is a secret.
+        if let Some(name) = api_key_name {
+            Span::current().record("api_key", &*name);
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        net::{IpAddr, Ipv4Addr},
+    };
+
+    use super::ClientRateLimiter;
+    use crate::config::{ApiKeyConfiguration, RateLimitConfiguration};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn create_limiter(burst: u32) -> ClientRateLimiter {
+        let rate_limit = RateLimitConfiguration {
+            requests_per_second: 1,
+            burst,
+        };
+
+        let api_keys = HashMap::from([(
+            "website".to_string(),
+            ApiKeyConfiguration {
+                key: "hunter2".to_string(),
+                rate_limit: Some(rate_limit),
+            },
+        )]);
+
+        ClientRateLimiter::new(Some(rate_limit), api_keys)
+    }
+
+    #[test]
+    fn reloading_keeps_the_state_of_unchanged_quotas() {
+        let previous = create_limiter(1);
+
+        assert!(previous.check(CLIENT, None).is_ok());
+        assert!(previous.check(CLIENT, Some("hunter2")).is_ok());
+
+        let mut reloaded = create_limiter(1);
+        reloaded.keep_state_of(&previous);
+
+        assert!(reloaded.check(CLIENT, None).is_err());
+        assert!(reloaded.check(CLIENT, Some("hunter2")).is_err());
+
+        // Clients start over once their quota changes.
+        let mut changed = create_limiter(2);
+        changed.keep_state_of(&reloaded);
+
+        assert!(changed.check(CLIENT, None).is_ok());
+        assert!(changed.check(CLIENT, Some("hunter2")).is_ok());
+    }
+}
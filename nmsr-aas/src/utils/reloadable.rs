@@ -0,0 +1,37 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A value that can be replaced while the server is running, e.g. when the configuration is reloaded.
+///
+/// Readers get a snapshot of the current value, that way a reload never changes a value halfway through a request.
+pub struct Reloadable<T>(Arc<RwLock<Arc<T>>>);
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(value))))
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> Arc<T> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the current value, readers holding on to the previous one keep using it.
+    pub fn set(&self, value: T) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(value);
+    }
+}
+
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for Reloadable<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use strum::{Display, EnumCount, EnumIter, IntoEnumIterator};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::{
+    config::{RenderClassConfiguration, RenderSchedulerConfiguration},
+    error::{NMSRaaSError, Result},
+    utils::metrics::Metrics,
+};
+
+/// The kind of request a render was made for, used to share the renderer fairly between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumCount, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum RenderClass {
+    Single,
+    Batch,
+}
+
+struct RenderClassState {
+    config: RenderClassConfiguration,
+    running: usize,
+    /// The virtual time of this class, which grows slower the higher its weight is.
+    pass: u64,
+    waiting: VecDeque<oneshot::Sender<()>>,
+}
+
+struct RenderSchedulerState {
+    max_concurrent_renders: usize,
+    max_queued_renders: Option<usize>,
+    running: usize,
+    classes: [RenderClassState; RenderClass::COUNT],
+}
+
+impl RenderSchedulerState {
+    /// The pass added to a class with a weight of 1 for each render it's given.
+    const STRIDE: u64 = 1 << 20;
+
+    /// Queues a render, returning how many renders of its class are waiting, or [`None`] if the queue is full.
+    fn enqueue(&mut self, class: RenderClass, sender: oneshot::Sender<()>) -> Option<usize> {
+        let class_state = &self.classes[class as usize];
+
+        // A class that was idle doesn't get to catch up on the service it missed while idle.
+        if class_state.waiting.is_empty() && class_state.running == 0 {
+            let min_pass = self
+                .classes
+                .iter()
+                .filter(|class| !class.waiting.is_empty() || class.running > 0)
+                .map(|class| class.pass)
+                .min()
+                .unwrap_or(0);
+
+            let class_state = &mut self.classes[class as usize];
+            class_state.pass = class_state.pass.max(min_pass);
+        }
+
+        let class_state = &mut self.classes[class as usize];
+        class_state.waiting.push_back(sender);
+        let queued = class_state.waiting.len();
+
+        self.dispatch();
+
+        // Renders that got a slot right away never count towards the queue. Otherwise, ours is still the last
+        // render of its class, since renders are dispatched from the front of the queue.
+        if !self.classes[class as usize].waiting.is_empty() && self.is_over_capacity() {
+            self.classes[class as usize].waiting.pop_back();
+
+            return None;
+        }
+
+        Some(queued)
+    }
+
+    fn is_over_capacity(&mut self) -> bool {
+        let Some(max_queued_renders) = self.max_queued_renders else {
+            return false;
+        };
+
+        // Renders cancelled while waiting are still in the queue until they'd be given a slot.
+        let queued: usize = self
+            .classes
+            .iter_mut()
+            .map(|class| {
+                class.waiting.retain(|sender| !sender.is_closed());
+                class.waiting.len()
+            })
+            .sum();
+
+        queued > max_queued_renders
+    }
+
+    fn release(&mut self, class: RenderClass) {
+        self.classes[class as usize].running -= 1;
+        self.running -= 1;
+
+        self.dispatch();
+    }
+
+    /// Gives free render slots to the waiting renders, picking the class with the lowest pass first.
+    fn dispatch(&mut self) {
+        while self.running < self.max_concurrent_renders {
+            let next = self
+                .classes
+                .iter_mut()
+                .filter(|class| {
+                    !class.waiting.is_empty() && class.running < class.config.max_concurrent_renders
+                })
+                .min_by_key(|class| class.pass);
+
+            let Some(class) = next else {
+                return;
+            };
+
+            let Some(sender) = class.waiting.pop_front() else {
+                return;
+            };
+
+            // The render might have been cancelled while waiting, in which case the slot is given to the next one.
+            if sender.send(()).is_ok() {
+                class.running += 1;
+                class.pass += Self::STRIDE / u64::from(class.config.weight.max(1));
+                self.running += 1;
+            }
+        }
+    }
+}
+
+/// Shares the renderer between the different classes of renders using weighted fair queueing.
+///
+/// Every class has its own queue and concurrency limit. Whenever a render slot frees up, it's given to
+/// the class that has had the least service relative to its weight, that way a large batch can't
+/// starve interactive renders.
+pub struct RenderScheduler {
+    state: Mutex<RenderSchedulerState>,
+    retry_after: Duration,
+}
+
+/// A render slot, which is given back to the scheduler when dropped.
+pub struct RenderPermit {
+    scheduler: Arc<RenderScheduler>,
+    class: RenderClass,
+}
+
+impl RenderScheduler {
+    #[must_use]
+    pub fn new(config: RenderSchedulerConfiguration) -> Self {
+        let classes = RenderClass::iter()
+            .map(|class| RenderClassState {
+                config: match class {
+                    RenderClass::Single => config.single,
+                    RenderClass::Batch => config.batch,
+                },
+                running: 0,
+                pass: 0,
+                waiting: VecDeque::new(),
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("There's a state for each render class"));
+
+        Self {
+            state: Mutex::new(RenderSchedulerState {
+                max_concurrent_renders: config.max_concurrent_renders.max(1),
+                max_queued_renders: config.max_queued_renders,
+                running: 0,
+                classes,
+            }),
+            retry_after: config.retry_after,
+        }
+    }
+
+    /// Waits for a render slot for the given class.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NMSRaaSError::RenderQueueFull`] if too many renders are already waiting for a slot.
+    pub async fn acquire(self: &Arc<Self>, class: RenderClass) -> Result<RenderPermit> {
+        let start = Instant::now();
+        let (sender, receiver) = oneshot::channel();
+
+        let Some(queued) = self.lock().enqueue(class, sender) else {
+            debug!(%class, "Rejected render, the queue is full");
+
+            return Err(NMSRaaSError::RenderQueueFull(
+                self.retry_after.as_secs().max(1),
+            ));
+        };
+
+        Metrics::add_queued_renders(class, 1);
+
+        let mut waiter = RenderWaiter {
+            scheduler: self.as_ref(),
+            class,
+            receiver: Some(receiver),
+        };
+
+        if let Some(receiver) = waiter.receiver.as_mut() {
+            // The sender is only dropped without sending when the scheduler itself is dropped.
+            let _ = receiver.await;
+        }
+
+        waiter.receiver = None;
+
+        Metrics::record_render_queue_wait(class, start.elapsed());
+
+        debug!(
+            %class,
+            queued,
+            waited_ms = start.elapsed().as_millis(),
+            "Acquired render slot"
+        );
+
+        Ok(RenderPermit {
+            scheduler: self.clone(),
+            class,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RenderSchedulerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn release(&self, class: RenderClass) {
+        self.lock().release(class);
+    }
+}
+
+/// Gives the render slot back if the render is cancelled after being given one, but before it started.
+struct RenderWaiter<'a> {
+    scheduler: &'a RenderScheduler,
+    class: RenderClass,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for RenderWaiter<'_> {
+    fn drop(&mut self) {
+        Metrics::add_queued_renders(self.class, -1);
+
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+
+            if receiver.try_recv().is_ok() {
+                self.scheduler.release(self.class);
+            }
+        }
+    }
+}
+
+impl Drop for RenderPermit {
+    fn drop(&mut self) {
+        self.scheduler.release(self.class);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{RenderClass, RenderScheduler};
+    use crate::{
+        config::{RenderClassConfiguration, RenderSchedulerConfiguration},
+        error::NMSRaaSError,
+    };
+
+    #[tokio::test]
+    async fn batch_renders_do_not_starve_single_renders() {
+        let scheduler = Arc::new(RenderScheduler::new(RenderSchedulerConfiguration {
+            max_concurrent_renders: 1,
+            single: RenderClassConfiguration {
+                weight: 4,
+                max_concurrent_renders: 1,
+            },
+            batch: RenderClassConfiguration {
+                weight: 1,
+                max_concurrent_renders: 1,
+            },
+            ..Default::default()
+        }));
+
+        let permit = scheduler.acquire(RenderClass::Batch).await.unwrap();
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        // Queue a large batch first, then a few single renders behind it.
+        for class in [RenderClass::Batch; 8]
+            .into_iter()
+            .chain([RenderClass::Single; 4])
+        {
+            let scheduler = scheduler.clone();
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                let _permit = scheduler.acquire(class).await.unwrap();
+                sender.send(class).unwrap();
+            });
+
+            tokio::task::yield_now().await;
+        }
+
+        drop(sender);
+        drop(permit);
+
+        let mut order = Vec::new();
+        while let Some(class) = receiver.recv().await {
+            order.push(class);
+        }
+
+        // The single renders are weighted 4 times higher, so they get ahead of most of the batch.
+        let last_single = order
+            .iter()
+            .rposition(|class| *class == RenderClass::Single)
+            .unwrap();
+
+        assert_eq!(order.len(), 12);
+        assert!(last_single < 6, "Single renders were starved: {order:?}");
+    }
+
+    #[tokio::test]
+    async fn full_queue_rejects_renders() {
+        let scheduler = Arc::new(RenderScheduler::new(RenderSchedulerConfiguration {
+            max_concurrent_renders: 1,
+            max_queued_renders: Some(1),
+            ..Default::default()
+        }));
+
+        let permit = scheduler.acquire(RenderClass::Single).await.unwrap();
+
+        let waiting = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire(RenderClass::Single).await.is_ok() }
+        });
+
+        tokio::task::yield_now().await;
+
+        assert!(matches!(
+            scheduler.acquire(RenderClass::Batch).await,
+            Err(NMSRaaSError::RenderQueueFull(1))
+        ));
+
+        // The rejected render doesn't take the place of the one that was already waiting.
+        drop(permit);
+        assert!(waiting.await.unwrap());
+
+        assert!(scheduler.acquire(RenderClass::Batch).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn cancelled_renders_give_up_their_place() {
+        let scheduler = Arc::new(RenderScheduler::new(RenderSchedulerConfiguration {
+            max_concurrent_renders: 1,
+            max_queued_renders: Some(1),
+            ..Default::default()
+        }));
+
+        let permit = scheduler.acquire(RenderClass::Single).await.unwrap();
+
+        // A client that disconnects while its render is queued drops the request, and the render along with it.
+        let cancelled = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire(RenderClass::Single).await.is_ok() }
+        });
+
+        tokio::task::yield_now().await;
+        cancelled.abort();
+        assert!(cancelled.await.is_err());
+
+        // The cancelled render neither counts towards the queue nor gets the slot once it's free.
+        let waiting = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire(RenderClass::Single).await.is_ok() }
+        });
+
+        tokio::task::yield_now().await;
+
+        drop(permit);
+        assert!(waiting.await.unwrap());
+
+        assert!(scheduler.acquire(RenderClass::Single).await.is_ok());
+    }
+}
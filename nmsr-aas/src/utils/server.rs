@@ -0,0 +1,144 @@
+use std::{future::Future, net::SocketAddr, time::Duration};
+
+use axum::{body::Body, extract::ConnectInfo, Extension, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, BufReader},
+    sync::watch,
+};
+use tower::ServiceExt;
+use tracing::{debug, error};
+
+use crate::utils::{
+    listener::{Connection, Listener},
+    proxy_protocol,
+};
+
+/// Serves requests on every listener, with the address of the client each connection was made by as its
+/// connection info. When the proxies in front of us use the PROXY protocol, every connection is expected to start
+/// with its header, and the client's address is taken from it instead.
+///
+/// Once `shutdown` completes, we stop accepting connections and wait for the open ones to finish.
+pub async fn serve(
+    listeners: Vec<Listener>,
+    router: Router,
+    proxy_protocol: bool,
+    shutdown: impl Future<Output = ()>,
+) {
+    let (shutdown_sender, shutdown_receiver) = watch::channel(());
+    let (close_sender, close_receiver) = watch::channel(());
+
+    let accept_loops = listeners
+        .into_iter()
+        .map(|listener| {
+            tokio::spawn(accept_connections(
+                listener,
+                router.clone(),
+                proxy_protocol,
+                shutdown_receiver.clone(),
+                close_receiver.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    drop(close_receiver);
+
+    shutdown.await;
+    let _ = shutdown_sender.send(());
+
+    // Stop accepting connections (and close the listeners) before waiting for the open ones.
+    for accept_loop in accept_loops {
+        let _ = accept_loop.await;
+    }
+
+    close_sender.closed().await;
+}
+
+async fn accept_connections(
+    listener: Listener,
+    router: Router,
+    proxy_protocol: bool,
+    mut shutdown_receiver: watch::Receiver<()>,
+    close_receiver: watch::Receiver<()>,
+) {
+    loop {
+        let (connection, peer) = tokio::select! {
+            result = listener.accept() => match result {
+                Ok(connection) => connection,
+                Err(err) => {
+                    // Usually caused by running out of file descriptors, so give some of them time to close.
+                    error!("Unable to accept connection: {err}");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            _ = shutdown_receiver.changed() => break,
+        };
+
+        let router = router.clone();
+        let mut shutdown_receiver = shutdown_receiver.clone();
+        let close_receiver = close_receiver.clone();
+
+        tokio::spawn(async move {
+            serve_connection(
+                connection,
+                peer,
+                router,
+                proxy_protocol,
+                &mut shutdown_receiver,
+            )
+            .await;
+            drop(close_receiver);
+        });
+    }
+}
+
+async fn serve_connection(
+    connection: Connection,
+    peer: SocketAddr,
+    router: Router,
+    proxy_protocol: bool,
+    shutdown_receiver: &mut watch::Receiver<()>,
+) {
+    if !proxy_protocol {
+        return serve_io(connection, peer, router, shutdown_receiver).await;
+    }
+
+    let mut stream = BufReader::new(connection);
+
+    if let Some(client) = proxy_protocol::read_client(&mut stream, peer).await {
+        serve_io(stream, client, router, shutdown_receiver).await;
+    }
+}
+
+async fn serve_io<I: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    io: I,
+    client: SocketAddr,
+    router: Router,
+    shutdown_receiver: &mut watch::Receiver<()>,
+) {
+    let router = router.layer(Extension(ConnectInfo(client)));
+    let service = hyper::service::service_fn(move |request: hyper::Request<Incoming>| {
+        router.clone().oneshot(request.map(Body::new))
+    });
+
+    let builder = Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::pin!(connection);
+
+    let result = tokio::select! {
+        result = connection.as_mut() => result,
+        _ = shutdown_receiver.changed() => {
+            connection.as_mut().graceful_shutdown();
+            connection.as_mut().await
+        }
+    };
+
+    if let Err(err) = result {
+        debug!("Unable to serve connection from {client}: {err}");
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use strum::Display;
+
+use crate::model::request::RenderRequestMode;
+
+/// What an instance is able to serve, depending on what was available when it started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ServingMode {
+    /// The renderer is available, so every mode can be served.
+    Full,
+    /// No graphics adapter was available, so renders are done on the CPU instead.
+    /// Every mode can be served, but renders are a lot slower and the live preview is unavailable.
+    Software,
+    /// No graphics adapter was available, so only the modes that don't use the renderer are served.
+    /// Faces are served too, as long as they can be drawn flat from the skin.
+    TextureOnly,
+}
+
+impl ServingMode {
+    /// Picks the serving mode to start with, or `None` if the instance shouldn't start at all.
+    ///
+    /// | Renderer      | `software_fallback` | `texture_only_fallback` | Result                |
+    /// |---------------|---------------------|-------------------------|-----------------------|
+    /// | available     | any                 | any                     | [`Self::Full`]        |
+    /// | unavailable   | `true`              | any                     | [`Self::Software`]    |
+    /// | unavailable   | `false`             | `true`                  | [`Self::TextureOnly`] |
+    /// | unavailable   | `false`             | `false`                 | refuse to start       |
+    #[must_use]
+    pub const fn select(
+        renderer_available: bool,
+        software_fallback: bool,
+        texture_only_fallback: bool,
+    ) -> Option<Self> {
+        match (renderer_available, software_fallback, texture_only_fallback) {
+            (true, _, _) => Some(Self::Full),
+            (false, true, _) => Some(Self::Software),
+            (false, false, true) => Some(Self::TextureOnly),
+            (false, false, false) => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn supports_mode(self, mode: RenderRequestMode) -> bool {
+        match self {
+            Self::Full => true,
+            Self::Software => Self::supports_mode_in_software(mode),
+            // Plain faces are drawn flat from the skin, the others fail without the renderer.
+            Self::TextureOnly => !mode.uses_rendering_pipeline() || mode.is_face(),
+        }
+    }
+
+    /// Whether the given mode can be served without a graphics adapter, listed one by one so that new modes have to
+    /// be checked against the software renderer before they're served in software mode.
+    const fn supports_mode_in_software(mode: RenderRequestMode) -> bool {
+        match mode {
+            // The first ones don't use the renderer at all, the rest are drawn by the software renderer,
+            // which supports every camera and body part the renderer does.
+            RenderRequestMode::Skin
+            | RenderRequestMode::BlockbenchExport
+            | RenderRequestMode::FaceParallax
+            | RenderRequestMode::FullBody
+            | RenderRequestMode::BodyBust
+            | RenderRequestMode::FrontFull
+            | RenderRequestMode::FrontBust
+            | RenderRequestMode::Face
+            | RenderRequestMode::Head
+            | RenderRequestMode::FullBodyIso
+            | RenderRequestMode::HeadIso
+            | RenderRequestMode::HeadBlock
+            | RenderRequestMode::Custom => true,
+        }
+    }
+
+    /// Whether the live preview can be served, which keeps scenes on the graphics adapter between frames.
+    #[must_use]
+    pub const fn supports_live_preview(self) -> bool {
+        matches!(self, Self::Full)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use super::ServingMode;
+    use crate::model::request::RenderRequestMode;
+
+    #[test]
+    fn select_serving_mode() {
+        assert_eq!(
+            ServingMode::select(true, false, false),
+            Some(ServingMode::Full)
+        );
+        assert_eq!(
+            ServingMode::select(true, false, true),
+            Some(ServingMode::Full)
+        );
+        assert_eq!(
+            ServingMode::select(false, false, true),
+            Some(ServingMode::TextureOnly)
+        );
+        assert_eq!(ServingMode::select(false, false, false), None);
+    }
+
+    #[test]
+    fn software_fallback_takes_precedence() {
+        assert_eq!(
+            ServingMode::select(true, true, true),
+            Some(ServingMode::Full)
+        );
+        assert_eq!(
+            ServingMode::select(false, true, false),
+            Some(ServingMode::Software)
+        );
+        assert_eq!(
+            ServingMode::select(false, true, true),
+            Some(ServingMode::Software)
+        );
+
+        for mode in RenderRequestMode::iter() {
+            assert!(ServingMode::Software.supports_mode(mode));
+        }
+
+        assert!(ServingMode::Full.supports_live_preview());
+        assert!(!ServingMode::Software.supports_live_preview());
+        assert!(!ServingMode::TextureOnly.supports_live_preview());
+    }
+
+    #[test]
+    fn texture_only_mode_skips_rendered_modes() {
+        for mode in RenderRequestMode::iter() {
+            assert!(ServingMode::Full.supports_mode(mode));
+            assert_eq!(
+                ServingMode::TextureOnly.supports_mode(mode),
+                !mode.uses_rendering_pipeline() || mode == RenderRequestMode::Face
+            );
+        }
+
+        assert!(ServingMode::TextureOnly.supports_mode(RenderRequestMode::Skin));
+        assert!(ServingMode::TextureOnly.supports_mode(RenderRequestMode::Face));
+        assert!(!ServingMode::TextureOnly.supports_mode(RenderRequestMode::FullBody));
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use hyperloglogplus::{HyperLogLog, HyperLogLogPlus};
+use serde::Serialize;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::utils::metrics::Metrics;
+
+/// The precision of the unique skins estimate, which keeps it within about 1% of the actual count
+/// while taking at most 16 KiB of memory no matter how many skins are rendered.
+const UNIQUE_SKINS_PRECISION: u8 = 14;
+
+/// The caches whose hits and misses are counted.
+#[derive(Debug, Clone, Copy)]
+pub enum StatisticsCache {
+    /// The resolved textures of players.
+    Resolved,
+    /// The textures downloaded from skin servers.
+    Textures,
+    /// The rendered images stored in object storage.
+    Renders,
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    fn snapshot(&self) -> CacheStatistics {
+        CacheStatistics {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters for what this instance has been up to, for lightweight dashboards.
+///
+/// Unlike traces, these are kept in memory and only cover the time since the state was created.
+pub struct UsageStatistics {
+    started_at: DateTime<Utc>,
+    renders: Mutex<BTreeMap<String, u64>>,
+    upstream_requests: AtomicU64,
+    resolved_cache: CacheCounters,
+    texture_cache: CacheCounters,
+    render_cache: CacheCounters,
+    /// An estimate of the number of different skins we've rendered, keeping every one of them would take too much memory.
+    unique_skins: Mutex<HyperLogLogPlus<u64, RandomState>>,
+}
+
+impl UsageStatistics {
+    /// Starts counting from now.
+    #[must_use]
+    pub fn new() -> Self {
+        let unique_skins = HyperLogLogPlus::new(UNIQUE_SKINS_PRECISION, RandomState::new())
+            .expect("Expected the unique skins precision to be valid");
+
+        Self {
+            started_at: Utc::now(),
+            renders: Mutex::default(),
+            upstream_requests: AtomicU64::default(),
+            resolved_cache: CacheCounters::default(),
+            texture_cache: CacheCounters::default(),
+            render_cache: CacheCounters::default(),
+            unique_skins: Mutex::new(unique_skins),
+        }
+    }
+
+    /// Records a render in the given mode (or custom mode), along with the skin it was made with.
+    pub fn record_render(&self, mode: &str, skin: Option<&[u8]>) {
+        *self
+            .renders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(mode.to_string())
+            .or_default() += 1;
+
+        if let Some(skin) = skin {
+            self.unique_skins
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(&xxh3_64(skin));
+        }
+    }
+
+    /// Records a request made to a skin server (or any other upstream service, like Geyser's).
+    pub fn record_upstream_request(&self) {
+        self.upstream_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records whether a lookup in one of our caches found what it was looking for.
+    pub fn record_cache_lookup(&self, cache: StatisticsCache, hit: bool) {
+        let counters = match cache {
+            StatisticsCache::Resolved => &self.resolved_cache,
+            StatisticsCache::Textures => &self.texture_cache,
+            StatisticsCache::Renders => &self.render_cache,
+        };
+
+        let counter = if hit {
+            &counters.hits
+        } else {
+            &counters.misses
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        Metrics::record_cache_lookup(cache, hit);
+    }
+
+    /// Returns the current value of every counter.
+    pub fn snapshot(&self) -> StatisticsSnapshot {
+        let renders = self
+            .renders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // It's a non-negative estimate
+        let unique_skins = self
+            .unique_skins
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .count()
+            .round() as u64;
+
+        StatisticsSnapshot {
+            started_at: self.started_at.to_rfc3339(),
+            uptime_seconds: (Utc::now() - self.started_at).num_seconds(),
+            total_renders: renders.values().sum(),
+            renders,
+            unique_skins,
+            upstream_requests: self.upstream_requests.load(Ordering::Relaxed),
+            resolved_cache: self.resolved_cache.snapshot(),
+            texture_cache: self.texture_cache.snapshot(),
+            render_cache: self.render_cache.snapshot(),
+        }
+    }
+}
+
+impl Default for UsageStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// The usage statistics at a point in time, as served by the statistics endpoint.
+#[derive(Debug, Serialize)]
+pub struct StatisticsSnapshot {
+    /// When this instance was started, in RFC 3339 format.
+    pub started_at: String,
+    pub uptime_seconds: i64,
+    pub total_renders: u64,
+    /// The number of renders by mode name (built-in or custom).
+    pub renders: BTreeMap<String, u64>,
+    /// An estimate of the number of different skins rendered, within about 1% of the actual count.
+    pub unique_skins: u64,
+    pub upstream_requests: u64,
+    pub resolved_cache: CacheStatistics,
+    pub texture_cache: CacheStatistics,
+    pub render_cache: CacheStatistics,
+}
+
+#[cfg(test)]
+mod test {
+    use super::UsageStatistics;
+
+    #[test]
+    fn unique_skins_are_estimated() {
+        let statistics = UsageStatistics::new();
+
+        for skin in 0..10_000u32 {
+            // Every skin is rendered twice, which shouldn't count twice.
+            statistics.record_render("fullbody", Some(&skin.to_le_bytes()));
+            statistics.record_render("face", Some(&skin.to_le_bytes()));
+        }
+
+        let snapshot = statistics.snapshot();
+
+        assert_eq!(snapshot.total_renders, 20_000);
+        assert!(snapshot.unique_skins.abs_diff(10_000) < 200, "{}", snapshot.unique_skins);
+    }
+}
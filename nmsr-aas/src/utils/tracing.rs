@@ -20,10 +20,8 @@ use tracing::{
 };
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::error::NmsrErrorExtension;
+use crate::{error::NmsrErrorExtension, utils::client_ip::ClientIp};
 
-#[allow(clippy::declare_interior_mutable_const)]
-const X_FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
 #[allow(clippy::declare_interior_mutable_const)]
 const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
 #[allow(clippy::declare_interior_mutable_const)]
@@ -91,13 +89,26 @@ impl Extractor for HeaderMapCarrier<'_> {
 
 impl<'a> Injector for MutableHeaderMapCarrier<'a> {
     fn set(&mut self, key: &str, value: String) {
-        self.0.insert(
-            HeaderName::from_lowercase(key.as_bytes()).expect("Invalid header name"),
-            HeaderValue::from_str(&value).expect("Invalid header value"),
-        );
+        // The tracestate header is copied from our callers, so don't trust it to be valid.
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_lowercase(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
     }
 }
 
+/// Adds the trace context of the given span to the headers of an outgoing request (as `traceparent` and
+/// `tracestate`), that way the services we call can continue our traces.
+pub(crate) fn inject_trace_context(span: &tracing::Span, headers: &mut HeaderMap) {
+    let context = span.context();
+
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MutableHeaderMapCarrier(headers));
+    });
+}
+
 impl<B> MakeSpan<B> for NmsrTracing<B> {
     fn make_span(&mut self, request: &Request<B>) -> tracing::Span {
         let user_agent = Self::extract_header_as_str(request.headers(), USER_AGENT)
@@ -118,13 +129,17 @@ impl<B> MakeSpan<B> for NmsrTracing<B> {
             exception.message = Empty,
 
             request_id = Empty,
+            api_key = Empty,
         );
 
         let context = global::get_text_map_propagator(|propagator| {
             propagator.extract(&HeaderMapCarrier(request.headers()))
         });
 
-        if context.has_active_span() {
+        // Continue the trace of whoever called us (as given by their traceparent and tracestate headers), if any.
+        let remote = context.span().span_context().clone();
+        if remote.is_valid() {
+            span.record("trace_id", remote.trace_id().to_string());
             span.set_parent(context);
         }
 
@@ -139,7 +154,11 @@ impl<B> OnRequest<B> for NmsrTracing<B> {
             .get::<MatchedPath>()
             .map_or(request.uri().path(), |p| p.as_str());
 
-        let client_ip = Self::extract_header_as_str(request.headers(), X_FORWARDED_FOR_HEADER)
+        // The address of the client behind our trusted proxies, if any, otherwise the address of whoever connected.
+        let client_ip = request
+            .extensions()
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| ip.to_string())
             .or_else(|| {
                 request
                     .extensions()
@@ -0,0 +1,293 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Request, State},
+    http::{header::CACHE_CONTROL, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use ring::hmac;
+use tracing::trace;
+
+use crate::{
+    config::UrlSigningConfiguration,
+    error::{NMSRaaSError, Result},
+    routes::{admin::constant_time_eq, NMSRState},
+};
+
+const SIGNATURE_PARAMETER: &str = "sig";
+const EXPIRY_PARAMETER: &str = "expires";
+
+/// Signs and verifies render URLs with a shared secret, that way only the sites knowing the secret can embed renders.
+///
+/// The signature (`?sig=`) is the hex-encoded HMAC-SHA256 of the path and query string of the URL, without the
+/// signature itself. URLs with an expiry (`?expires=`, a UNIX timestamp in seconds) are rejected once it has passed.
+pub struct UrlSigner {
+    key: hmac::Key,
+    lifetime: Option<Duration>,
+    token: Option<String>,
+}
+
+impl UrlSigner {
+    #[must_use]
+    pub fn new(config: &UrlSigningConfiguration) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, config.secret.as_bytes()),
+            lifetime: config.lifetime,
+            token: config.token.clone().filter(|token| !token.is_empty()),
+        }
+    }
+
+    /// Checks the bearer token of clients that aren't bound to signed URLs, i.e. GraphQL and gRPC.
+    pub fn authorize(&self, token: Option<&str>) -> Result<()> {
+        match (token, self.token.as_deref()) {
+            (Some(token), Some(expected)) if constant_time_eq(token, expected) => Ok(()),
+            _ => Err(NMSRaaSError::InvalidSigningToken),
+        }
+    }
+
+    /// Signs the URL made of the given path and query string, which expires if signed URLs are given a lifetime.
+    pub fn sign(&self, path: &str, query: Option<&str>) -> String {
+        let mut params: Vec<String> = Self::split_query(query).map(ToOwned::to_owned).collect();
+
+        if let Some(lifetime) = self.lifetime {
+            let expires = Self::unix_time() + lifetime.as_secs();
+            params.push(format!("{EXPIRY_PARAMETER}={expires}"));
+        }
+
+        let message = Self::create_message(path, &params.join("&"));
+        let signature = hmac::sign(&self.key, message.as_bytes());
+
+        params.push(format!(
+            "{SIGNATURE_PARAMETER}={}",
+            hex::encode(signature.as_ref())
+        ));
+
+        format!("{path}?{}", params.join("&"))
+    }
+
+    /// Checks that the URL made of the given path and query string was signed with our secret and hasn't expired.
+    ///
+    /// Returns how long the URL stays valid for, if it expires.
+    pub fn verify(&self, path: &str, query: Option<&str>) -> Result<Option<Duration>> {
+        let mut signature = None;
+        let mut expires = None;
+
+        let params: Vec<&str> = Self::split_query(query)
+            .filter(|param| match param.split_once('=') {
+                Some((SIGNATURE_PARAMETER, value)) => {
+                    signature = Some(value);
+                    false
+                }
+                Some((EXPIRY_PARAMETER, value)) => {
+                    expires = Some(value);
+                    true
+                }
+                _ => true,
+            })
+            .collect();
+
+        let signature = signature
+            .and_then(|signature| hex::decode(signature).ok())
+            .ok_or(NMSRaaSError::InvalidSignature)?;
+
+        let message = Self::create_message(path, &params.join("&"));
+        hmac::verify(&self.key, message.as_bytes(), &signature)
+            .map_err(|_| NMSRaaSError::InvalidSignature)?;
+
+        // The expiry is part of the signed message, so it can only be trusted once the signature is.
+        let Some(expires) = expires else {
+            return Ok(None);
+        };
+
+        let expires: u64 = expires
+            .parse()
+            .map_err(|_| NMSRaaSError::InvalidSignature)?;
+
+        let now = Self::unix_time();
+
+        if now > expires {
+            return Err(NMSRaaSError::ExpiredSignature);
+        }
+
+        Ok(Some(Duration::from_secs(expires - now)))
+    }
+
+    fn split_query(query: Option<&str>) -> impl Iterator<Item = &str> {
+        query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| !param.is_empty())
+    }
+
+    fn create_message(path: &str, query: &str) -> String {
+        if query.is_empty() {
+            path.to_owned()
+        } else {
+            format!("{path}?{query}")
+        }
+    }
+
+    fn unix_time() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+/// Rejects render requests without a valid signature, before anything is resolved or rendered.
+pub(crate) async fn verify_signature(
+    State(state): State<NMSRState>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let remaining = if let Some(url_signer) = state.url_signer.get().as_ref() {
+        let uri = request.uri();
+
+        url_signer.verify(uri.path(), uri.query()).map_err(|err| {
+            trace!("Rejected request to {uri}: {err}");
+            err
+        })?
+    } else {
+        None
+    };
+
+    let mut response = next.run(request).await;
+
+    // Caches must not keep serving a render once the URL it was requested with has expired.
+    if let Some(remaining) = remaining {
+        let capped = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| cap_max_age(value, remaining))
+            .and_then(|value| HeaderValue::from_str(&value).ok());
+
+        if let Some(capped) = capped {
+            response.headers_mut().insert(CACHE_CONTROL, capped);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Caps the `max-age` of the given Cache-Control header at the given duration, returns [`None`] if it has none.
+///
+/// Responses that expire can't be `immutable`, so that directive is dropped.
+fn cap_max_age(cache_control: &str, max_age: Duration) -> Option<String> {
+    let mut capped = false;
+
+    let directives: Vec<String> = cache_control
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.eq_ignore_ascii_case("immutable"))
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) if name.eq_ignore_ascii_case("max-age") => {
+                capped = true;
+                let value = value.parse().unwrap_or(0).min(max_age.as_secs());
+
+                format!("max-age={value}")
+            }
+            _ => directive.to_owned(),
+        })
+        .collect();
+
+    capped.then(|| directives.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{cap_max_age, UrlSigner};
+    use crate::{config::UrlSigningConfiguration, error::NMSRaaSError};
+
+    fn create_signer(secret: &str, lifetime: Option<Duration>) -> UrlSigner {
+        UrlSigner::new(&UrlSigningConfiguration {
+            secret: secret.to_string(),
+            lifetime,
+            token: Some("hunter4".to_string()),
+        })
+    }
+
+    fn split_url(url: &str) -> (&str, Option<&str>) {
+        url.split_once('?')
+            .map_or((url, None), |(path, query)| (path, Some(query)))
+    }
+
+    #[test]
+    fn signed_urls_are_verified() {
+        let signer = create_signer("hunter2", Some(Duration::from_secs(90)));
+
+        let url = signer.sign("/fullbody/Notch", Some("yaw=20&nolayers"));
+        let (path, query) = split_url(&url);
+
+        assert!(matches!(
+            signer.verify(path, query),
+            Ok(Some(remaining)) if remaining <= Duration::from_secs(90)
+        ));
+
+        // URLs signed with another secret aren't valid.
+        assert!(matches!(
+            create_signer("hunter3", None).verify(path, query),
+            Err(NMSRaaSError::InvalidSignature)
+        ));
+
+        // Changing anything that was signed invalidates the signature.
+        let tampered = url.replace("yaw=20", "yaw=30");
+        let (path, query) = split_url(&tampered);
+
+        assert!(matches!(
+            signer.verify(path, query),
+            Err(NMSRaaSError::InvalidSignature)
+        ));
+
+        assert!(matches!(
+            signer.verify("/fullbody/Notch", Some("yaw=20&nolayers")),
+            Err(NMSRaaSError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn only_the_token_is_authorized() {
+        let signer = create_signer("hunter2", None);
+
+        assert!(signer.authorize(Some("hunter4")).is_ok());
+
+        for token in [None, Some(""), Some("hunter2"), Some("hunter44")] {
+            assert!(matches!(
+                signer.authorize(token),
+                Err(NMSRaaSError::InvalidSigningToken)
+            ));
+        }
+    }
+
+    #[test]
+    fn expired_urls_are_rejected() {
+        let signer = create_signer("hunter2", None);
+
+        let url = signer.sign("/head/Notch", Some("expires=1"));
+        let (path, query) = split_url(&url);
+
+        assert!(matches!(
+            signer.verify(path, query),
+            Err(NMSRaaSError::ExpiredSignature)
+        ));
+    }
+
+    #[test]
+    fn max_age_is_capped_at_the_remaining_lifetime() {
+        let remaining = Duration::from_secs(90);
+
+        assert_eq!(
+            cap_max_age("public, max-age=31536000, immutable", remaining).as_deref(),
+            Some("public, max-age=90")
+        );
+        assert_eq!(
+            cap_max_age("public, max-age=30", remaining).as_deref(),
+            Some("public, max-age=30")
+        );
+        assert_eq!(cap_max_age("public, no-store", remaining), None);
+    }
+}
@@ -28,6 +28,8 @@ pub fn generate_project<M: ArmorMaterial, I: ModelProjectImageIO>(
     mut project: ModelGenerationProject<M, I>,
 ) -> ProjectOutputResult {
     let parts = project.generate_parts();
+    project.check_element_budget(parts.len())?;
+    project.check_time_budget()?;
     
     let texture_grouped_parts = group_by_texture(parts);
     project.filter_textures(&texture_grouped_parts.keys().copied().collect_vec());
@@ -35,6 +37,8 @@ pub fn generate_project<M: ArmorMaterial, I: ModelProjectImageIO>(
     let outliner_groups = vec![];
     let (resolution, raw_textures) =
         convert_to_raw_project_textures(&project, &texture_grouped_parts);
+    project.check_time_budget()?;
+
     let elements = convert_to_raw_elements(&project, texture_grouped_parts)?;
 
     let project = RawProject::new(resolution, elements, raw_textures, outliner_groups);
@@ -56,8 +60,10 @@ fn convert_to_raw_elements<M: ArmorMaterial, I: ModelProjectImageIO>(
     project: &ModelGenerationProject<M, I>,
     grouped_parts: HashMap<PlayerPartTextureType, Vec<Part>>,
 ) -> Result<Vec<RawProjectElement>> {
+    // Keep the elements in a stable order, so that the same project is generated every time.
     let parts = grouped_parts
         .into_iter()
+        .sorted_by_key(|(texture, _)| *texture)
         .flat_map(|(_, parts)| parts)
         .enumerate()
         .map(|(index, part)| -> Result<_> {
@@ -134,6 +140,9 @@ fn convert_to_raw_elements<M: ArmorMaterial, I: ModelProjectImageIO>(
 
     for part in parts {
         result.push(part?);
+
+        project.check_element_budget(result.len())?;
+        project.check_time_budget()?;
     }
 
     Ok(result)
@@ -79,11 +79,11 @@ impl RawProjectElement {
         texture: PlayerPartTextureType,
         project: &ModelGenerationProject<M, I>,
     ) -> Result<Self> {
-        fn random_names(a: &str, b: &str) -> (String, String) {
-            let (a_new, b_new) = Uuid::new_v4().as_u64_pair();
+        let random_names = |a: &str, b: &str| -> (String, String) {
+            let (a_new, b_new) = project.next_unique_name_suffix();
 
             (format!("{a}{a_new:x}"), format!("{b}{b_new:x}"))
-        }
+        };
 
         let converted = primitive_convert(&part);
 
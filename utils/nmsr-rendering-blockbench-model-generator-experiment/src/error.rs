@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use image::ImageError;
 use thiserror::Error;
 
@@ -18,7 +20,23 @@ pub enum BlockbenchGeneratorError {
     SerdeJsonError(#[from] serde_json::Error),
     #[error("{0}")]
     ExplainedError(String),
-    
+    #[error("Model has too many elements ({0}, the maximum is {1})")]
+    ElementBudgetExceeded(usize, usize),
+    #[error("Model textures are too big ({0} bytes, the maximum is {1} bytes)")]
+    TextureBudgetExceeded(usize, usize),
+    #[error("Model generation took too long (more than {0:?})")]
+    TimeBudgetExceeded(Duration),
+}
+
+impl BlockbenchGeneratorError {
+    pub const fn is_budget_exceeded(&self) -> bool {
+        matches!(
+            self,
+            Self::ElementBudgetExceeded(_, _)
+                | Self::TextureBudgetExceeded(_, _)
+                | Self::TimeBudgetExceeded(_)
+        )
+    }
 }
 
 pub trait Contextualizable<O> {
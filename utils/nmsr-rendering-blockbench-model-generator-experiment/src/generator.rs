@@ -1,13 +1,15 @@
 use std::{
     collections::HashMap,
     io::{BufWriter, Cursor},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
 };
 
 use glam::{Vec2, Vec3};
 use image::RgbaImage;
 use itertools::Itertools;
 use nmsr_rendering::high_level::{
-    model::{ArmorMaterial, PlayerModel},
+    model::{ArmorMaterial, PlayerBodyProportions, PlayerModel},
     parts::{
         part::{Part, PartAnchorInfo},
         provider::{PartsProvider, PlayerPartProviderContext, PlayerPartsProvider},
@@ -16,6 +18,8 @@ use nmsr_rendering::high_level::{
     types::{PlayerBodyPartType, PlayerPartTextureType},
     IntoEnumIterator,
 };
+use uuid::Uuid;
+use xxhash_rust::xxh3::xxh3_128;
 
 use crate::{
     blockbench::model::ModelFaceUv,
@@ -52,12 +56,29 @@ impl ModelProjectImageIO for DefaultImageIO {
     }
 }
 
+/// Limits enforced while generating a project, so that a single export can't exhaust the host.
+/// Every limit is optional, and unset limits aren't enforced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ModelGenerationLimits {
+    /// The maximum number of elements in the generated project.
+    pub max_elements: Option<usize>,
+    /// The maximum amount of decoded texture data (in bytes) the project can hold.
+    pub max_texture_bytes: Option<usize>,
+    /// The maximum amount of time spent generating the project.
+    pub max_wall_time: Option<Duration>,
+}
+
 pub struct ModelGenerationProject<M: ArmorMaterial, I: ModelProjectImageIO> {
     providers: Vec<PlayerPartsProvider>,
     part_context: PlayerPartProviderContext<M>,
     textures: HashMap<PlayerPartTextureType, RgbaImage>,
     max_resolution: Vec2,
     image_io: I,
+    limits: ModelGenerationLimits,
+    started_at: Option<Instant>,
+    /// The seed used for the generated names, which are random when not set.
+    seed: Option<u64>,
+    names_generated: AtomicU64,
 }
 
 pub fn new_model_generator_without_part_context<I: ModelProjectImageIO>(
@@ -74,6 +95,9 @@ pub fn new_model_generator_without_part_context<I: ModelProjectImageIO>(
         shadow_y_pos: None,
         shadow_is_square: false,
         armor_slots: None,
+        proportions: PlayerBodyProportions::Adult,
+        shoulder_buddies: None,
+        has_deadmau5_ears: false,
         #[cfg(feature = "ears")]
         ears_features: None,
     };
@@ -86,6 +110,8 @@ impl<M: ArmorMaterial, I: ModelProjectImageIO> ModelGenerationProject<M, I> {
         Self {
             providers: [
                 PlayerPartsProvider::Minecraft,
+                PlayerPartsProvider::ShoulderBuddies,
+                PlayerPartsProvider::Deadmau5Ears,
                 #[cfg(feature = "ears")]
                 PlayerPartsProvider::Ears,
             ]
@@ -94,9 +120,45 @@ impl<M: ArmorMaterial, I: ModelProjectImageIO> ModelGenerationProject<M, I> {
             textures: HashMap::new(),
             max_resolution: Vec2::ZERO,
             image_io,
+            limits: ModelGenerationLimits::default(),
+            started_at: None,
+            seed: None,
+            names_generated: AtomicU64::new(0),
         }
     }
 
+    /// Generates names from the given seed instead of randomly, making the generated project the same every time.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+
+        self
+    }
+
+    /// Returns a pair of values to make generated names unique, derived from the seed if there's one.
+    pub(crate) fn next_unique_name_suffix(&self) -> (u64, u64) {
+        let Some(seed) = self.seed else {
+            return Uuid::new_v4().as_u64_pair();
+        };
+
+        let index = self.names_generated.fetch_add(1, Ordering::Relaxed);
+
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&seed.to_be_bytes());
+        bytes[8..].copy_from_slice(&index.to_be_bytes());
+
+        let hash = xxh3_128(&bytes);
+
+        ((hash >> 64) as u64, hash as u64)
+    }
+
+    pub fn with_limits(mut self, limits: ModelGenerationLimits) -> Self {
+        // Only keep track of time if we have to, Instant isn't available everywhere (e.g. wasm).
+        self.started_at = limits.max_wall_time.map(|_| Instant::now());
+        self.limits = limits;
+
+        self
+    }
+
     pub fn load_texture(
         &mut self,
         texture_type: PlayerPartTextureType,
@@ -161,6 +223,47 @@ impl<M: ArmorMaterial, I: ModelProjectImageIO> ModelGenerationProject<M, I> {
         self.textures.insert(texture_type, texture);
         self.recompute_max_resolution();
 
+        self.check_texture_budget()?;
+        self.check_time_budget()
+    }
+
+    fn check_texture_budget(&self) -> Result<()> {
+        if let Some(max_texture_bytes) = self.limits.max_texture_bytes {
+            let texture_bytes = self.textures.values().map(|t| t.as_raw().len()).sum();
+
+            if texture_bytes > max_texture_bytes {
+                return Err(BlockbenchGeneratorError::TextureBudgetExceeded(
+                    texture_bytes,
+                    max_texture_bytes,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_element_budget(&self, elements: usize) -> Result<()> {
+        if let Some(max_elements) = self.limits.max_elements {
+            if elements > max_elements {
+                return Err(BlockbenchGeneratorError::ElementBudgetExceeded(
+                    elements,
+                    max_elements,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_time_budget(&self) -> Result<()> {
+        if let (Some(max_wall_time), Some(started_at)) =
+            (self.limits.max_wall_time, self.started_at)
+        {
+            if started_at.elapsed() > max_wall_time {
+                return Err(BlockbenchGeneratorError::TimeBudgetExceeded(max_wall_time));
+            }
+        }
+
         Ok(())
     }
 
@@ -9,12 +9,12 @@ use image::{GenericImage, ImageBuffer, Rgba, RgbaImage};
 use itertools::Itertools;
 use nmsr_rendering::high_level::{
     camera::{Camera, ProjectionParameters},
-    model::PlayerModel,
+    model::{PlayerBodyProportions, PlayerModel},
     parts::provider::PlayerPartProviderContext,
     pipeline::{
         scene::{Scene, Size, SunInformation},
-        Backends, BlendState, Features, GraphicsContext, GraphicsContextDescriptor, SceneContext,
-        SceneContextWrapper, ShaderSource,
+        AdapterSelector, Backends, BlendState, Features, GraphicsContext,
+        GraphicsContextDescriptor, SceneContext, SceneContextWrapper, ShaderSource,
     },
     types::{PlayerBodyPartType, PlayerPartTextureType},
 };
@@ -412,6 +412,9 @@ async fn process_group_logic(
         shadow_y_pos,
         shadow_is_square: false,
         armor_slots: None,
+        proportions: PlayerBodyProportions::Adult,
+        shoulder_buddies: None,
+        has_deadmau5_ears: false,
         #[cfg(feature = "ears")]
         ears_features: None,
     };
@@ -429,9 +432,11 @@ async fn process_group_logic(
     
     let descriptor = GraphicsContextDescriptor {
         backends: Some(Backends::all()),
+        adapter: AdapterSelector::default(),
         surface_provider: Box::new(|_| None),
         default_size: (0, 0),
         texture_format: None,
+        color_space: None,
         features: Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
         limits: None,
         blend_state: Some(BlendState::REPLACE),